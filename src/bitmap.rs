@@ -0,0 +1,134 @@
+use image;
+use primitives::*;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Decoded pixels, four bytes of RGBA with straight alpha each, top row
+/// first. The renderer keeps a texture for each bitmap it draws, found by
+/// the bitmap's id, until the bitmap is dropped.
+pub struct Bitmap {
+    id: usize,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Result<Bitmap, String> {
+        if pixels.len() != width as usize * height as usize * 4 {
+            return Err(format!("{} bytes of pixels for a {}x{} bitmap", pixels.len(), width, height));
+        }
+        Ok(Bitmap {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    /// Decodes a PNG or JPEG image, or any other format the `image` crate
+    /// recognizes.
+    pub fn decode(bytes: &[u8]) -> Result<Bitmap, String> {
+        let decoded = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+        let rgba = decoded.to_rgba8();
+        let (width, height) = rgba.dimensions();
+        Bitmap::from_rgba(width, height, rgba.into_raw())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Bitmap, String> {
+        let path = path.as_ref();
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Bitmap::decode(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The size in pixels.
+    pub fn size(&self) -> Size {
+        Size::new(self.width as f32, self.height as f32)
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+/// Where an image is decoded from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageSource {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+impl ImageSource {
+    pub fn decode(&self) -> Result<Bitmap, String> {
+        match *self {
+            ImageSource::Path(ref path) => Bitmap::load(path),
+            ImageSource::Bytes(ref bytes) => Bitmap::decode(bytes),
+        }
+    }
+}
+
+/// Decodes `source` on a thread of its own; the result arrives on the
+/// returned receiver, after which the thread calls `done`.
+pub fn decode_async<F: FnOnce() + Send + 'static>(source: ImageSource, done: F) -> Receiver<Result<Bitmap, String>> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        // The receiver is gone if the image was dropped in the meantime.
+        if sender.send(source.decode()).is_ok() {
+            done();
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2x1 PNG: an opaque red pixel and a half transparent blue one.
+    const TINY_PNG: &'static [u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0xf4, 0x22, 0x7f,
+        0x8a, 0x00, 0x00, 0x00, 0x0e, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xf8, 0xcf, 0xc0, 0x00,
+        0x42, 0x0d, 0x00, 0x0f, 0x7a, 0x03, 0x7e, 0x6a, 0x81, 0x31, 0xe1, 0x00, 0x00, 0x00, 0x00, 0x49,
+        0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    #[test]
+    fn decodes_png_to_rgba() {
+        let bitmap = Bitmap::decode(TINY_PNG).unwrap();
+
+        assert_eq!((bitmap.width(), bitmap.height()), (2, 1));
+        assert_eq!(bitmap.pixels(), &[255, 0, 0, 255, 0, 0, 255, 128][..]);
+        assert!(Bitmap::decode(&TINY_PNG[..20]).is_err());
+    }
+
+    #[test]
+    fn decodes_on_another_thread() {
+        let result = decode_async(ImageSource::Bytes(TINY_PNG.to_vec()), || {}).recv().unwrap();
+
+        assert_eq!(result.unwrap().size(), Size::new(2.0, 1.0));
+        assert!(decode_async(ImageSource::Path(PathBuf::from("missing.png")), || {}).recv().unwrap().is_err());
+        assert!(Bitmap::from_rgba(2, 2, vec![0; 12]).is_err());
+    }
+}
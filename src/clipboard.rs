@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Where cut and copied text goes. Applications can plug in the system
+/// clipboard with `set_shared`.
+pub trait Clipboard {
+    fn text(&self) -> Option<String>;
+    fn set_text(&mut self, text: &str);
+}
+
+pub type SharedClipboard = Rc<RefCell<Box<Clipboard>>>;
+
+/// A clipboard private to the application.
+pub struct LocalClipboard {
+    text: Option<String>,
+}
+
+impl LocalClipboard {
+    pub fn new() -> LocalClipboard {
+        LocalClipboard { text: None }
+    }
+}
+
+impl Clipboard for LocalClipboard {
+    fn text(&self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.text = Some(text.to_string());
+    }
+}
+
+thread_local! {
+    static SHARED: SharedClipboard = Rc::new(RefCell::new(Box::new(LocalClipboard::new())));
+}
+
+/// The clipboard used by text elements of the current thread.
+pub fn shared() -> SharedClipboard {
+    SHARED.with(|clipboard| clipboard.clone())
+}
+
+/// Replaces the clipboard used by text elements of the current thread.
+pub fn set_shared(clipboard: Box<Clipboard>) {
+    SHARED.with(|shared| *shared.borrow_mut() = clipboard);
+}
@@ -0,0 +1,114 @@
+use primitives::*;
+use render::*;
+use tree::*;
+
+/// Gives its single child the largest size with a fixed width to height
+/// ratio that fits the available space, centered in it.
+pub struct AspectRatio {
+    node: NodeWeakRef,
+    ratio: f32,
+}
+
+impl AspectRatio {
+    /// `ratio` is the width divided by the height.
+    pub fn new(ratio: f32) -> AspectRatio {
+        AspectRatio {
+            node: NodeWeakRef::empty(),
+            ratio: ratio,
+        }
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio;
+        self.node().invalidate_layout();
+    }
+
+    /// Sets the element kept at the aspect ratio, replacing the previous one.
+    pub fn set_content(&mut self, content: Box<Element>) -> NodeRef {
+        self.node().set_single_child(content)
+    }
+
+    pub fn content(&self) -> Option<NodeRef> {
+        self.node().children().iter().next().cloned()
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+}
+
+/// The largest size with the given width to height ratio inside `available`.
+pub fn fit_ratio(ratio: f32, available: Size) -> Size {
+    if ratio <= 0.0 {
+        return Size::zero();
+    }
+    if available.w / ratio <= available.h {
+        Size::new(available.w, available.w / ratio)
+    } else {
+        Size::new(available.h * ratio, available.h)
+    }
+}
+
+impl Element for AspectRatio {
+    fn init(&mut self, node: NodeRef) {
+        info!("[AspectRatio] Init");
+        self.node = node.downgrade();
+    }
+
+    /// Grows the content's desired size along one axis to reach the ratio.
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[AspectRatio] Measure");
+        let desired = self.content().map_or(Size::zero(), |c| c.measure());
+        if self.ratio <= 0.0 {
+            return desired;
+        }
+        if desired.w / self.ratio >= desired.h {
+            Size::new(desired.w, desired.w / self.ratio)
+        } else {
+            Size::new(desired.h * self.ratio, desired.h)
+        }
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[AspectRatio] Layout");
+        if let Some(content) = self.content() {
+            let size = fit_ratio(self.ratio, container);
+            let pos = Point::new((container.w - size.w) / 2.0, (container.h - size.h) / 2.0);
+            content.arrange(Rect::pos_size(pos, size));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree::testing::SizedElement;
+
+    #[test]
+    fn fit_ratio_uses_the_limiting_axis() {
+        assert_eq!(fit_ratio(2.0, Size::new(100.0, 100.0)).to_tuple(), (100.0, 50.0));
+        assert_eq!(fit_ratio(0.5, Size::new(100.0, 100.0)).to_tuple(), (50.0, 100.0));
+        assert_eq!(fit_ratio(0.0, Size::new(100.0, 100.0)).to_tuple(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn content_is_centered_with_the_ratio() {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(AspectRatio::new(16.0 / 9.0)))).unwrap();
+        let content = root.cast_element_mut::<AspectRatio>().unwrap().set_content(Box::new(SizedElement::new(Size::new(32.0, 9.0))));
+
+        assert_eq!(root.measure().to_tuple(), (32.0, 18.0));
+
+        root.layout(Size::new(320.0, 300.0));
+
+        assert_eq!(content.rect().to_pos_size_tuple(), ((0.0, 60.0), (320.0, 180.0)));
+    }
+}
@@ -0,0 +1,171 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+
+/// Draws a background and a stroke around a single content child.
+/// The content is placed inside the stroke and the node's padding, and is
+/// clipped to the rounded interior of the border.
+pub struct Border {
+    node: NodeWeakRef,
+    style: BorderStyle,
+}
+
+impl Border {
+    pub fn new() -> Border {
+        Border {
+            node: NodeWeakRef::empty(),
+            style: BorderStyle::new(Color::transparent()),
+        }
+    }
+
+    pub fn with_background(mut self, background: Color) -> Border {
+        self.style.background = background;
+        self
+    }
+
+    pub fn with_border(mut self, thickness: Thickness, color: Color) -> Border {
+        self.style = self.style.with_border(thickness, color);
+        self
+    }
+
+    /// Sets the stroke colors of the left, top, right and bottom sides.
+    pub fn with_side_colors(mut self, left: Color, top: Color, right: Color, bottom: Color) -> Border {
+        self.style.left_color = left;
+        self.style.top_color = top;
+        self.style.right_color = right;
+        self.style.bottom_color = bottom;
+        self
+    }
+
+    pub fn with_corner_radius(mut self, radius: CornerRadius) -> Border {
+        self.style.corner_radius = radius;
+        self
+    }
+
+    pub fn style(&self) -> BorderStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: BorderStyle) {
+        let thickness_changed = style.thickness != self.style.thickness;
+        self.style = style;
+        if let Some(node) = self.node.upgrade() {
+            if thickness_changed {
+                node.invalidate_layout();
+            } else {
+                node.invalidate_render();
+            }
+        }
+    }
+
+    /// Sets the space between the stroke and the content.
+    pub fn set_padding(&mut self, padding: Thickness) {
+        let mut node = self.node();
+        let mut props = node.layout_properties();
+        props.padding = padding;
+        node.set_layout_properties(props);
+    }
+
+    /// Puts `content` inside the border, replacing the previous content.
+    pub fn set_content(&mut self, content: Box<Element>) -> NodeRef {
+        self.node().set_single_child(content)
+    }
+
+    pub fn content(&self) -> Option<NodeRef> {
+        self.node().children().iter().next().cloned()
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+}
+
+impl Element for Border {
+    fn init(&mut self, node: NodeRef) {
+        info!("[Border] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[Border] Measure");
+        let thickness = self.style.thickness;
+        let content = self.content().map(|c| c.measure()).unwrap_or(Size::zero());
+        Size::new(content.w + thickness.horizontal(), content.h + thickness.vertical())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[Border] Layout");
+        if let Some(content) = self.content() {
+            content.arrange(Rect::from_size(container).inset(self.style.thickness));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[Border] Render");
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Border(Rect::from_size(node.rect().size), self.style));
+        cmd
+    }
+
+    fn children_clip(&self, node: NodeRef) -> Option<(Rect, CornerRadius)> {
+        let thickness = self.style.thickness;
+        let interior = Rect::from_size(node.rect().size).inset(thickness);
+        Some((interior, self.style.corner_radius.inset(thickness)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree::testing::SizedElement;
+
+    fn bordered(size: Size) -> (Tree, NodeRef) {
+        let border = Border::new()
+            .with_border(Thickness::uniform(2.0), Color::argb(1.0, 0.0, 0.0, 0.0))
+            .with_corner_radius(CornerRadius::uniform(20.0));
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(border))).unwrap();
+        root.set_layout_properties(LayoutProperties::new().with_padding(Thickness::uniform(3.0)));
+        let content = root.cast_element_mut::<Border>().unwrap().set_content(Box::new(SizedElement::new(size)));
+        (tree, content)
+    }
+
+    #[test]
+    fn measure_adds_stroke_and_padding() {
+        let (tree, _) = bordered(Size::new(20.0, 10.0));
+
+        assert_eq!(tree.root().unwrap().measure().to_tuple(), (30.0, 20.0));
+    }
+
+    #[test]
+    fn content_is_placed_inside_stroke_and_padding() {
+        let (tree, content) = bordered(Size::new(20.0, 10.0));
+
+        tree.root().unwrap().layout(Size::new(100.0, 50.0));
+
+        assert_eq!(content.window_rect().to_pos_size_tuple(), ((5.0, 5.0), (90.0, 40.0)));
+    }
+
+    #[test]
+    fn set_content_replaces_previous_content() {
+        let (tree, _) = bordered(Size::new(20.0, 10.0));
+        let mut root = tree.root().unwrap();
+
+        let content = root.cast_element_mut::<Border>().unwrap().set_content(Box::new(SizedElement::new(Size::zero())));
+
+        assert_eq!(root.children().len(), 1);
+        assert!(root.cast_element::<Border>().unwrap().content().unwrap() == content);
+    }
+
+    #[test]
+    fn hit_test_clips_content_to_rounded_interior() {
+        let (tree, content) = bordered(Size::new(20.0, 10.0));
+        let root = tree.root().unwrap();
+        root.layout(Size::new(100.0, 50.0));
+
+        assert!(root.hit_test(Point::new(50.0, 25.0)).unwrap() == content);
+        assert!(root.hit_test(Point::new(6.0, 6.0)).unwrap() == root);
+        assert!(root.hit_test(Point::new(12.0, 6.0)).unwrap() == content);
+    }
+}
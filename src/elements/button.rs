@@ -0,0 +1,403 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use msg::*;
+use elements::text_block::TextBlock;
+
+/// Raised by a `Button` when it is clicked; the button is the message's source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Click;
+
+impl Message for Click {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ButtonState {
+    Normal,
+    Hover,
+    Pressed,
+    Disabled,
+}
+
+/// The look of a button in each state. Focus is shown with a ring inside
+/// the border.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ButtonStyle {
+    pub normal: BorderStyle,
+    pub hover: BorderStyle,
+    pub pressed: BorderStyle,
+    pub disabled: BorderStyle,
+    pub focus: Color,
+    /// Text color for disabled buttons with text content.
+    pub disabled_text: Color,
+}
+
+impl ButtonStyle {
+    pub fn new() -> ButtonStyle {
+        let stroke = Thickness::uniform(1.0);
+        let radius = CornerRadius::uniform(3.0);
+        let style = |background, border| BorderStyle::new(background).with_border(stroke, border).with_corner_radius(radius);
+        ButtonStyle {
+            normal: style(Color::argb(1.0, 0.9, 0.9, 0.9), Color::argb(1.0, 0.6, 0.6, 0.6)),
+            hover: style(Color::argb(1.0, 0.85, 0.9, 0.97), Color::argb(1.0, 0.3, 0.5, 0.8)),
+            pressed: style(Color::argb(1.0, 0.75, 0.82, 0.93), Color::argb(1.0, 0.2, 0.4, 0.7)),
+            disabled: style(Color::argb(1.0, 0.95, 0.95, 0.95), Color::argb(1.0, 0.8, 0.8, 0.8)),
+            focus: Color::argb(1.0, 0.2, 0.4, 0.8),
+            disabled_text: Color::argb(1.0, 0.6, 0.6, 0.6),
+        }
+    }
+
+    pub fn for_state(&self, state: ButtonState) -> BorderStyle {
+        match state {
+            ButtonState::Normal => self.normal,
+            ButtonState::Hover => self.hover,
+            ButtonState::Pressed => self.pressed,
+            ButtonState::Disabled => self.disabled,
+        }
+    }
+}
+
+/// Tracks the hover and press state of clickable elements such as buttons
+/// and toggles. A click is a left press and release inside the element,
+/// or Space pressed and released while it has focus.
+pub struct PressTracker {
+    hovered: bool,
+    mouse_pressed: bool,
+    key_pressed: bool,
+}
+
+impl PressTracker {
+    pub fn new() -> PressTracker {
+        PressTracker {
+            hovered: false,
+            mouse_pressed: false,
+            key_pressed: false,
+        }
+    }
+
+    pub fn state(&self, enabled: bool) -> ButtonState {
+        if !enabled {
+            ButtonState::Disabled
+        } else if self.key_pressed || (self.mouse_pressed && self.hovered) {
+            ButtonState::Pressed
+        } else if self.hovered {
+            ButtonState::Hover
+        } else {
+            ButtonState::Normal
+        }
+    }
+
+    /// Drops any press in progress, e.g. when the element is disabled.
+    pub fn cancel(&mut self) {
+        self.mouse_pressed = false;
+        self.key_pressed = false;
+    }
+
+    /// Updates the state for an event sent to `node`. Returns whether the
+    /// event was handled and whether it completed a click. Hover is tracked
+    /// even while disabled.
+    pub fn handle_event(&mut self, node: &NodeRef, event: &InputEvent, enabled: bool) -> (bool, bool) {
+        match *event {
+            InputEvent::MouseEnter => {
+                self.hovered = true;
+                (false, false)
+            }
+            InputEvent::MouseLeave => {
+                self.hovered = false;
+                (false, false)
+            }
+            _ if !enabled => (false, false),
+            InputEvent::MouseDown(MouseButton::Left, _) => {
+                self.mouse_pressed = true;
+                (true, false)
+            }
+            InputEvent::MouseUp(MouseButton::Left, pos) if self.mouse_pressed => {
+                self.mouse_pressed = false;
+                (true, Rect::from_size(node.rect().size).contains(pos))
+            }
+            InputEvent::MouseMove(_) => (self.mouse_pressed, false),
+            InputEvent::KeyDown(Key::Space, modifiers) if modifiers.is_empty() => {
+                self.key_pressed = true;
+                (true, false)
+            }
+            InputEvent::KeyUp(Key::Space, _) if self.key_pressed => {
+                self.key_pressed = false;
+                (true, true)
+            }
+            _ => (false, false),
+        }
+    }
+}
+
+/// Greys out `content` if it is a `TextBlock` and `enabled` is false,
+/// keeping its color in `saved` to restore it when enabled again.
+pub fn update_label_color(content: &NodeRef, enabled: bool, saved: &mut Option<Color>, disabled: Color) {
+    let mut text = match content.cast_element_mut::<TextBlock>() {
+        Some(text) => text,
+        None => return,
+    };
+    if enabled {
+        if let Some(color) = saved.take() {
+            text.set_color(color);
+        }
+    } else if saved.is_none() {
+        *saved = Some(text.format().color);
+        text.set_color(disabled);
+    }
+}
+
+/// A push button holding text or any other element. It raises `Click`
+/// when pressed and released with the left mouse button inside its bounds,
+/// when Enter is pressed, or when Space is released while it has focus.
+pub struct Button {
+    node: NodeWeakRef,
+    content: Option<Box<Element>>,
+    style: ButtonStyle,
+    enabled: bool,
+    press: PressTracker,
+    /// The color text content had before the button was disabled.
+    text_color: Option<Color>,
+}
+
+impl Button {
+    pub fn new<T: Into<String>>(text: T) -> Button {
+        Button::with_content(Box::new(TextBlock::new(text)))
+    }
+
+    pub fn with_content(content: Box<Element>) -> Button {
+        Button {
+            node: NodeWeakRef::empty(),
+            content: Some(content),
+            style: ButtonStyle::new(),
+            enabled: true,
+            press: PressTracker::new(),
+            text_color: None,
+        }
+    }
+
+    pub fn with_style(mut self, style: ButtonStyle) -> Button {
+        self.style = style;
+        self
+    }
+
+    pub fn content(&self) -> Option<NodeRef> {
+        self.node().children().iter().next().cloned()
+    }
+
+    pub fn state(&self) -> ButtonState {
+        self.press.state(self.enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Disabled buttons ignore input and can't be focused.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.press.cancel();
+        if let Some(content) = self.content() {
+            update_label_color(&content, enabled, &mut self.text_color, self.style.disabled_text);
+        }
+        self.node().invalidate_render();
+    }
+
+    /// Raises `Click` as if the button had been clicked.
+    pub fn click(&mut self) {
+        self.node().raise(Box::new(Click));
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+}
+
+impl Element for Button {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[Button] Init");
+        self.node = node.downgrade();
+        node.set_layout_properties(LayoutProperties::new().with_padding(Thickness::hv(8.0, 3.0)));
+        if let Some(content) = self.content.take() {
+            let content = node.add_child(content);
+            content.set_layout_properties(LayoutProperties::new().with_alignment(Alignment::Center, Alignment::Center));
+        }
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[Button] Measure");
+        let thickness = self.style.normal.thickness;
+        let content = self.content().map_or(Size::zero(), |c| c.measure());
+        Size::new(content.w + thickness.horizontal(), content.h + thickness.vertical())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[Button] Layout");
+        if let Some(content) = self.content() {
+            content.arrange(Rect::from_size(container).inset(self.style.normal.thickness));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[Button] Render");
+        let mut cmd = RenderCommandList::new();
+        let rect = Rect::from_size(node.rect().size);
+        let style = self.style.for_state(self.state());
+        cmd.add(RenderCommand::Border(rect, style));
+        if self.enabled && node.has_focus() {
+            let ring = rect.inset(style.thickness).inset(Thickness::uniform(1.0));
+            cmd.add(RenderCommand::Border(ring, BorderStyle::new(Color::transparent())
+                .with_border(Thickness::uniform(1.0), self.style.focus)
+                .with_corner_radius(style.corner_radius.inset(style.thickness))));
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let state = self.state();
+        let handled = match *event {
+            InputEvent::KeyDown(Key::Enter, modifiers) if self.enabled && modifiers.is_empty() => {
+                self.click();
+                true
+            }
+            _ => {
+                let (handled, clicked) = self.press.handle_event(&node, event, self.enabled);
+                if clicked {
+                    self.click();
+                }
+                handled
+            }
+        };
+        if self.state() != state {
+            node.invalidate_render();
+        }
+        handled
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Panel {
+        clicks: usize,
+    }
+
+    impl Element for Panel {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+            for child in node.children().iter() {
+                child.arrange(Rect::pos_size(Point::new(10.0, 10.0), Size::new(80.0, 30.0)));
+            }
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+            if message.cast_ref::<Click>().is_some() && source.cast_element::<Button>().is_some() {
+                self.clicks += 1;
+                true
+            } else {
+                false
+            }
+        }
+    }
+
+    fn panel_with_button() -> (Tree, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Panel { clicks: 0 }))).unwrap();
+        let button = root.add_child(Box::new(Button::new("OK")));
+        root.layout(Size::new(100.0, 100.0));
+        (tree, root, button)
+    }
+
+    fn clicks(root: &NodeRef) -> usize {
+        root.cast_element::<Panel>().unwrap().clicks
+    }
+
+    fn state(button: &NodeRef) -> ButtonState {
+        button.cast_element::<Button>().unwrap().state()
+    }
+
+    #[test]
+    fn press_and_release_inside_clicks() {
+        let (mut tree, root, button) = panel_with_button();
+        let inside = Point::new(20.0, 20.0);
+
+        tree.dispatch_event(&InputEvent::MouseMove(inside));
+        assert_eq!(state(&button), ButtonState::Hover);
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, inside));
+        assert_eq!(state(&button), ButtonState::Pressed);
+        assert!(button.has_focus());
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, inside));
+
+        assert_eq!(clicks(&root), 1);
+        assert_eq!(state(&button), ButtonState::Hover);
+    }
+
+    #[test]
+    fn release_outside_cancels_the_click() {
+        let (mut tree, root, button) = panel_with_button();
+        let inside = Point::new(20.0, 20.0);
+        let outside = Point::new(5.0, 80.0);
+
+        tree.dispatch_event(&InputEvent::MouseMove(inside));
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, inside));
+        tree.dispatch_event(&InputEvent::MouseMove(outside));
+        assert_eq!(state(&button), ButtonState::Normal);
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, outside));
+
+        assert_eq!(clicks(&root), 0);
+    }
+
+    #[test]
+    fn space_and_enter_activate_the_focused_button() {
+        let (mut tree, root, button) = panel_with_button();
+        button.focus();
+
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Enter, Modifiers::none()));
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Space, Modifiers::none()));
+        assert_eq!(state(&button), ButtonState::Pressed);
+        assert_eq!(clicks(&root), 1);
+        tree.dispatch_event(&InputEvent::KeyUp(Key::Space, Modifiers::none()));
+
+        assert_eq!(clicks(&root), 2);
+    }
+
+    #[test]
+    fn disabled_buttons_ignore_input() {
+        let (mut tree, root, button) = panel_with_button();
+        button.cast_element_mut::<Button>().unwrap().set_enabled(false);
+        let inside = Point::new(20.0, 20.0);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, inside));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, inside));
+
+        assert_eq!(clicks(&root), 0);
+        assert!(!button.has_focus());
+        assert_eq!(state(&button), ButtonState::Disabled);
+        let content = button.cast_element::<Button>().unwrap().content().unwrap();
+        assert_eq!(content.cast_element::<TextBlock>().unwrap().format().color, ButtonStyle::new().disabled_text);
+    }
+
+    #[test]
+    fn measures_content_with_padding_and_stroke() {
+        let (_tree, _root, button) = panel_with_button();
+        let content = button.cast_element::<Button>().unwrap().content().unwrap();
+        let text = content.measure();
+
+        assert_eq!(button.measure().to_tuple(), (text.w + 18.0, text.h + 8.0));
+        assert!(content.rect().pos.x > 1.0);
+    }
+}
@@ -0,0 +1,784 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use msg::*;
+use property::*;
+use text::*;
+use text::layout::*;
+use elements::button::{ButtonState, ButtonStyle};
+use elements::scroll::{ScrollViewer, ScrollBarVisibility};
+use elements::text_block::{TextBlock, DEFAULT_FONT_SIZE};
+use elements::text_box::{TextBox, TextChanged};
+use elements::toggle::render_mark;
+use elements::virtualizing::{ItemSource, VirtualizingList, template};
+use std::cell::{Cell, RefCell};
+use std::f32;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const ARROW_WIDTH: f32 = 20.0;
+const ITEM_PADDING: (f32, f32) = (6.0, 3.0);
+const MAX_VISIBLE_ITEMS: usize = 8;
+const TYPE_AHEAD_MS: u64 = 1000;
+
+/// Raised by a `ComboBox` when the user changes the selected item.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SelectionChanged(pub Option<usize>);
+
+impl Message for SelectionChanged {}
+
+/// Raised by an item of the popup list when it is clicked.
+struct ItemChosen(usize);
+
+impl Message for ItemChosen {}
+
+/// The items and the highlighted one, shared by a combo box and its popup.
+struct ComboItems {
+    items: Vec<String>,
+    highlighted: Option<usize>,
+}
+
+impl ItemSource for ComboItems {
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+type SharedItems = Rc<RefCell<ComboItems>>;
+
+fn item_format() -> TextFormat {
+    TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE).with_wrapping(TextWrapping::NoWrap)
+}
+
+fn item_height() -> f32 {
+    item_format().line_height() + 2.0 * ITEM_PADDING.1
+}
+
+/// The item that typing `prefix` selects, ignoring case. Typing one letter
+/// repeatedly cycles through the items starting with it; otherwise the
+/// search starts at the current item, so that typing on refines the match.
+fn type_ahead_match(items: &[String], prefix: &str, current: Option<usize>) -> Option<usize> {
+    let prefix = prefix.to_lowercase();
+    let first = match prefix.chars().next() {
+        Some(first) => first,
+        None => return None,
+    };
+    let (prefix, start) = if prefix.chars().all(|c| c == first) {
+        (first.to_string(), current.map_or(0, |i| i + 1))
+    } else {
+        (prefix, current.unwrap_or(0))
+    };
+    (0..items.len())
+        .map(|i| (start + i) % items.len())
+        .find(|&i| items[i].to_lowercase().starts_with(&prefix))
+}
+
+/// A row of the popup list.
+struct ComboItem {
+    items: SharedItems,
+    index: usize,
+    pressed: bool,
+}
+
+impl Element for ComboItem {
+    fn init(&mut self, mut node: NodeRef) {
+        node.set_layout_properties(LayoutProperties::new().with_padding(Thickness::hv(ITEM_PADDING.0, ITEM_PADDING.1)));
+        let text = node.add_child(Box::new(TextBlock::with_format("", item_format())));
+        text.set_layout_properties(LayoutProperties::new().with_alignment(Alignment::Start, Alignment::Center));
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(0.0, item_format().line_height())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        for child in node.children().iter() {
+            child.arrange(Rect::from_size(container));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        let mut cmd = RenderCommandList::new();
+        if self.items.borrow().highlighted == Some(self.index) {
+            cmd.add(RenderCommand::Rect(Rect::from_size(node.rect().size), Color::argb(1.0, 0.8, 0.87, 0.97)));
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseEnter => {
+                self.items.borrow_mut().highlighted = Some(self.index);
+                node.invalidate_render();
+                false
+            }
+            InputEvent::MouseDown(MouseButton::Left, _) => {
+                self.pressed = true;
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, pos) if self.pressed => {
+                self.pressed = false;
+                if Rect::from_size(node.rect().size).contains(pos) {
+                    node.raise(Box::new(ItemChosen(self.index)));
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The popup of a combo box: a bordered, scrolling list of the items, at
+/// least as wide as the combo box and at most `MAX_VISIBLE_ITEMS` tall.
+struct ComboPopup {
+    node: NodeWeakRef,
+    items: SharedItems,
+    min_width: f32,
+    widest: f32,
+    reveal_pending: bool,
+}
+
+impl ComboPopup {
+    fn new(items: SharedItems, min_width: f32) -> ComboPopup {
+        let widest = items.borrow().items.iter()
+            .map(|item| TextLayout::new(item, item_format(), f32::INFINITY).size().w)
+            .fold(0.0, f32::max);
+        ComboPopup {
+            node: NodeWeakRef::empty(),
+            items: items,
+            min_width: min_width,
+            widest: widest + 2.0 * ITEM_PADDING.0,
+            reveal_pending: true,
+        }
+    }
+
+    fn viewer(&self) -> Option<NodeRef> {
+        self.node.upgrade().and_then(|node| node.children().iter().next().cloned())
+    }
+
+    /// Scrolls the highlighted item into view on the next layout.
+    fn reveal_highlighted(&mut self) {
+        self.reveal_pending = true;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+}
+
+impl Element for ComboPopup {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[ComboPopup] Init");
+        self.node = node.downgrade();
+        node.set_layout_properties(LayoutProperties::new().with_padding(Thickness::uniform(1.0)));
+        let viewer = node.add_child(Box::new(ScrollViewer::with_visibility(ScrollBarVisibility::Disabled, ScrollBarVisibility::Auto).with_focusable(false)));
+        let items = self.items.clone();
+        let list_items = self.items.clone();
+        let template = template(
+            move || Box::new(ComboItem { items: items.clone(), index: 0, pressed: false }) as Box<Element>,
+            |node, index| {
+                let text = {
+                    let mut item = node.cast_element_mut::<ComboItem>().unwrap();
+                    item.index = index;
+                    let text = item.items.borrow().items[index].clone();
+                    text
+                };
+                let label = node.children()[0].clone();
+                label.cast_element_mut::<TextBlock>().unwrap().set_text(text);
+            });
+        viewer.cast_element_mut::<ScrollViewer>().unwrap()
+            .set_content(Box::new(VirtualizingList::new(Box::new(list_items), template)));
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[ComboPopup] Measure");
+        let count = self.items.borrow().items.len().min(MAX_VISIBLE_ITEMS);
+        Size::new(self.widest.max(self.min_width - 2.0), count as f32 * item_height())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[ComboPopup] Layout");
+        let viewer = match self.viewer() {
+            Some(viewer) => viewer,
+            None => return,
+        };
+        viewer.arrange(Rect::from_size(container));
+        let highlighted = self.items.borrow().highlighted;
+        if let (true, Some(index)) = (self.reveal_pending, highlighted) {
+            self.reveal_pending = false;
+            let mut viewer = viewer.cast_element_mut::<ScrollViewer>().unwrap();
+            let (top, height) = (index as f32 * item_height(), item_height());
+            let offset = viewer.offset();
+            let view = viewer.viewport_size().h;
+            if top < offset.y {
+                viewer.scroll_to(Point::new(offset.x, top));
+            } else if top + height > offset.y + view {
+                viewer.scroll_to(Point::new(offset.x, top + height - view));
+            }
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[ComboPopup] Render");
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Border(Rect::from_size(node.rect().size), BorderStyle::new(Color::argb(1.0, 1.0, 1.0, 1.0))
+            .with_border(Thickness::uniform(1.0), Color::argb(1.0, 0.6, 0.6, 0.6))));
+        cmd
+    }
+}
+
+/// Shows the selected item of a list and opens the list in a popup to pick
+/// another one. The selection is the `SelectedIndex` property.
+///
+/// Up and Down change the selection, or the highlighted item while the
+/// popup is open; Alt+Down or F4 open the popup, Enter takes the
+/// highlighted item and Escape closes it. Typing selects the item that
+/// starts with the typed letters. An editable combo box shows a text box
+/// instead, and selects the item matching the text typed into it.
+pub struct ComboBox {
+    node: NodeWeakRef,
+    items: SharedItems,
+    selected: Property<Option<usize>>,
+    editable: bool,
+    enabled: bool,
+    hovered: bool,
+    style: ButtonStyle,
+    popup: NodeWeakRef,
+    typed: String,
+    typed_at: Instant,
+    /// Set while the text typed into an editable combo box picks the
+    /// selection, which then leaves the text as typed.
+    editing: Rc<Cell<bool>>,
+}
+
+impl ComboBox {
+    pub fn new<T: Into<String>>(items: Vec<T>) -> ComboBox {
+        let items = Rc::new(RefCell::new(ComboItems {
+            items: items.into_iter().map(|item| item.into()).collect(),
+            highlighted: None,
+        }));
+        let selected = Property::new("SelectedIndex", AccessType::ReadWrite, Box::new(source::Variable::new(None))).unwrap();
+        let valid = items.clone();
+        selected.coerce(move |index: Option<usize>| index.and_then(|i| if i < valid.borrow().items.len() { Some(i) } else { None }));
+        ComboBox {
+            node: NodeWeakRef::empty(),
+            items: items,
+            selected: selected,
+            editable: false,
+            enabled: true,
+            hovered: false,
+            style: ButtonStyle::new(),
+            popup: NodeWeakRef::empty(),
+            typed: String::new(),
+            typed_at: Instant::now(),
+            editing: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn with_editable(mut self, editable: bool) -> ComboBox {
+        self.editable = editable;
+        self
+    }
+
+    pub fn with_selected(self, index: Option<usize>) -> ComboBox {
+        self.selected.set(index);
+        self
+    }
+
+    pub fn with_style(mut self, style: ButtonStyle) -> ComboBox {
+        self.style = style;
+        self
+    }
+
+    pub fn items(&self) -> Vec<String> {
+        self.items.borrow().items.clone()
+    }
+
+    /// Replaces the items, closing the popup and clearing the selection.
+    pub fn set_items<T: Into<String>>(&mut self, items: Vec<T>) {
+        self.close();
+        self.items.borrow_mut().items = items.into_iter().map(|item| item.into()).collect();
+        self.select(None);
+    }
+
+    /// The index of the selected item. Indices past the items are taken as
+    /// no selection, and setting it shows the selected item.
+    pub fn selected(&self) -> &Property<Option<usize>> {
+        &self.selected
+    }
+
+    pub fn selected_item(&self) -> Option<String> {
+        self.selected.get().and_then(|i| self.items.borrow().items.get(i).cloned())
+    }
+
+    /// Selects an item without raising `SelectionChanged`.
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected.set(index);
+    }
+
+    /// Shows the selected item.
+    pub fn update(&mut self) {
+        if let Some(node) = self.node.upgrade() {
+            show_item(&node, &self.items, self.selected.get());
+        }
+    }
+
+    /// The shown text: the selected item, or what was typed into an editable combo box.
+    pub fn text(&self) -> String {
+        match self.content().and_then(|c| c.cast_element::<TextBox>().map(|t| t.text().get())) {
+            Some(text) => text,
+            None => self.selected_item().unwrap_or_default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.close();
+        }
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.popup_node().is_some()
+    }
+
+    /// Opens the popup with the selected item highlighted.
+    pub fn open(&mut self) {
+        let node = match self.node.upgrade() {
+            Some(node) => node,
+            None => return,
+        };
+        if self.is_open() || !self.enabled {
+            return;
+        }
+        self.items.borrow_mut().highlighted = self.selected.get();
+        let anchor = node.window_rect();
+        let popup = node.open_popup(Box::new(ComboPopup::new(self.items.clone(), anchor.size.w)), anchor, Placement::Below);
+        self.popup = popup.downgrade();
+        node.invalidate_render();
+    }
+
+    pub fn close(&mut self) {
+        if let Some(popup) = self.popup_node() {
+            popup.close_popup();
+        }
+        self.popup = NodeWeakRef::empty();
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    fn popup_node(&self) -> Option<NodeRef> {
+        self.popup.upgrade().and_then(|popup| if popup.is_popup() { Some(popup) } else { None })
+    }
+
+    fn content(&self) -> Option<NodeRef> {
+        self.node.upgrade().and_then(|node| node.children().iter().next().cloned())
+    }
+
+    fn state(&self) -> ButtonState {
+        if !self.enabled {
+            ButtonState::Disabled
+        } else if self.is_open() {
+            ButtonState::Pressed
+        } else if self.hovered {
+            ButtonState::Hover
+        } else {
+            ButtonState::Normal
+        }
+    }
+
+    /// Selects an item on behalf of the user, raising `SelectionChanged` if it changed.
+    fn choose(&mut self, node: &NodeRef, index: Option<usize>) {
+        let changed = index != self.selected.get();
+        self.select(index);
+        if changed {
+            node.raise(Box::new(SelectionChanged(self.selected.get())));
+        }
+    }
+
+    /// Moves the highlight while open, or else the selection, to `index`.
+    fn move_to(&mut self, node: &NodeRef, index: Option<usize>) {
+        if index.is_none() {
+            return;
+        }
+        match self.popup_node() {
+            Some(popup) => {
+                self.items.borrow_mut().highlighted = index;
+                popup.cast_element_mut::<ComboPopup>().unwrap().reveal_highlighted();
+                popup.invalidate_render();
+            }
+            None => self.choose(node, index),
+        }
+    }
+
+    /// The highlighted item while open, or else the selected one.
+    fn current(&self) -> Option<usize> {
+        if self.is_open() {
+            self.items.borrow().highlighted
+        } else {
+            self.selected.get()
+        }
+    }
+
+    fn handle_key(&mut self, node: &NodeRef, key: Key, modifiers: Modifiers) -> bool {
+        let len = self.items.borrow().items.len();
+        let last = if len > 0 { Some(len - 1) } else { None };
+        let current = self.current();
+        let step = |delta: isize| {
+            last.map(|last| match current {
+                Some(i) => (i as isize + delta).max(0).min(last as isize) as usize,
+                None if delta > 0 => 0,
+                None => last,
+            })
+        };
+        let page = MAX_VISIBLE_ITEMS as isize - 1;
+        match key {
+            Key::Down | Key::Up if modifiers.alt => {
+                if self.is_open() { self.close() } else { self.open() }
+            }
+            Key::F(4) => {
+                if self.is_open() { self.close() } else { self.open() }
+            }
+            Key::Down => self.move_to(node, step(1)),
+            Key::Up => self.move_to(node, step(-1)),
+            Key::PageDown => self.move_to(node, step(page)),
+            Key::PageUp => self.move_to(node, step(-page)),
+            Key::Home if !self.editable => self.move_to(node, step(-(len as isize))),
+            Key::End if !self.editable => self.move_to(node, step(len as isize)),
+            Key::Enter if self.is_open() => {
+                let highlighted = self.items.borrow().highlighted;
+                self.close();
+                if highlighted.is_some() {
+                    self.choose(node, highlighted);
+                }
+            }
+            Key::Escape if self.is_open() => self.close(),
+            Key::Space if !self.editable && !self.is_open() => self.open(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn type_ahead(&mut self, node: &NodeRef, c: char) {
+        let now = Instant::now();
+        if now > self.typed_at + Duration::from_millis(TYPE_AHEAD_MS) {
+            self.typed.clear();
+        }
+        self.typed_at = now;
+        self.typed.push(c);
+        let index = type_ahead_match(&self.items.borrow().items, &self.typed, self.current());
+        self.move_to(node, index);
+    }
+
+    /// Selects the item matching the text typed into an editable combo box,
+    /// and highlights the first item starting with it.
+    fn text_edited(&mut self, node: &NodeRef) {
+        let text = self.text().to_lowercase();
+        let (exact, prefix) = {
+            let items = &self.items.borrow().items;
+            (items.iter().position(|item| item.to_lowercase() == text),
+             items.iter().position(|item| !text.is_empty() && item.to_lowercase().starts_with(&text)))
+        };
+        if exact != self.selected.get() {
+            self.editing.set(true);
+            self.selected.set(exact);
+            self.editing.set(false);
+            node.raise(Box::new(SelectionChanged(exact)));
+        }
+        if let Some(popup) = self.popup_node() {
+            if prefix.is_some() {
+                self.items.borrow_mut().highlighted = prefix;
+                popup.cast_element_mut::<ComboPopup>().unwrap().reveal_highlighted();
+            }
+        }
+    }
+}
+
+/// Shows item `selected` of `items` in the content of `node`, a combo box.
+fn show_item(node: &NodeRef, items: &SharedItems, selected: Option<usize>) {
+    let text = selected.and_then(|i| items.borrow().items.get(i).cloned()).unwrap_or_default();
+    if let Some(content) = node.children().iter().next() {
+        if let Some(mut text_box) = content.cast_element_mut::<TextBox>() {
+            if text_box.text().get() != text {
+                text_box.set_text(text);
+            }
+        } else if let Some(mut text_block) = content.cast_element_mut::<TextBlock>() {
+            text_block.set_text(text);
+        }
+    }
+}
+
+impl Element for ComboBox {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[ComboBox] Init");
+        self.node = node.downgrade();
+        if self.editable {
+            node.add_child(Box::new(TextBox::new()));
+        } else {
+            let text = node.add_child(Box::new(TextBlock::with_format("", item_format())));
+            text.set_layout_properties(LayoutProperties::new()
+                .with_margin(Thickness::hv(ITEM_PADDING.0, ITEM_PADDING.1))
+                .with_alignment(Alignment::Stretch, Alignment::Center));
+        }
+        self.update();
+
+        let combo = node.downgrade();
+        let (items, editing) = (self.items.clone(), self.editing.clone());
+        self.selected.observe(move |&selected| if let Some(combo) = combo.upgrade() {
+            if !editing.get() {
+                show_item(&combo, &items, selected);
+            }
+        });
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[ComboBox] Measure");
+        let content = self.content().map_or(Size::zero(), |c| c.measure());
+        let widest = self.items.borrow().items.iter()
+            .map(|item| TextLayout::new(item, item_format(), f32::INFINITY).size().w + 2.0 * ITEM_PADDING.0)
+            .fold(0.0, f32::max);
+        Size::new(content.w.max(widest) + ARROW_WIDTH, content.h)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[ComboBox] Layout");
+        if let Some(content) = self.content() {
+            content.arrange(Rect::from_size(Size::new((container.w - ARROW_WIDTH).max(0.0), container.h)));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[ComboBox] Render");
+        let mut cmd = RenderCommandList::new();
+        let rect = Rect::from_size(node.rect().size);
+        let state = self.state();
+        let style = self.style.for_state(state);
+        let arrow = Rect::pos_size(Point::new(rect.size.w - ARROW_WIDTH, 0.0), Size::new(ARROW_WIDTH, rect.size.h));
+        cmd.add(RenderCommand::Border(if self.editable { arrow } else { rect }, style));
+        if !self.editable && self.enabled && node.has_focus() {
+            let ring = rect.inset(style.thickness).inset(Thickness::uniform(1.0));
+            cmd.add(RenderCommand::Border(ring, BorderStyle::new(Color::transparent())
+                .with_border(Thickness::uniform(1.0), self.style.focus)
+                .with_corner_radius(style.corner_radius.inset(style.thickness))));
+        }
+        let color = if state == ButtonState::Disabled { self.style.disabled_text } else { Color::argb(1.0, 0.3, 0.3, 0.3) };
+        render_mark(&mut cmd, arrow, "▾", color);
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseEnter | InputEvent::MouseLeave => {
+                self.hovered = *event == InputEvent::MouseEnter;
+                node.invalidate_render();
+                false
+            }
+            _ if !self.enabled => false,
+            InputEvent::MouseDown(MouseButton::Left, _) => {
+                if self.is_open() { self.close() } else { self.open() }
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) => self.handle_key(&node, key, modifiers),
+            InputEvent::Char(c) if !self.editable && !c.is_whitespace() => {
+                self.type_ahead(&node, c);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+        if let Some(&ItemChosen(index)) = message.cast_ref::<ItemChosen>() {
+            self.close();
+            self.choose(&node, Some(index));
+            true
+        } else if message.cast_ref::<TextChanged>().is_some() && self.editable {
+            self.text_edited(&node);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled && !self.editable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Panel {
+        changes: Vec<Option<usize>>,
+    }
+
+    impl Element for Panel {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+            for child in node.children().iter() {
+                child.arrange(Rect::pos_size(Point::new(10.0, 10.0), Size::new(100.0, 24.0)));
+            }
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+            match message.cast_ref::<SelectionChanged>() {
+                Some(&SelectionChanged(index)) => {
+                    self.changes.push(index);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    fn panel_with(combo: ComboBox) -> (Tree, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Panel { changes: Vec::new() }))).unwrap();
+        let combo = root.add_child(Box::new(combo));
+        tree.layout(Size::new(300.0, 300.0));
+        (tree, root, combo)
+    }
+
+    fn fruits() -> Vec<&'static str> {
+        vec!["Apple", "Banana", "Blueberry", "Cherry", "Date", "Elderberry", "Fig", "Grape", "Kiwi", "Lemon"]
+    }
+
+    fn key(tree: &mut Tree, key: Key, modifiers: Modifiers) {
+        tree.dispatch_event(&InputEvent::KeyDown(key, modifiers));
+    }
+
+    fn changes(root: &NodeRef) -> Vec<Option<usize>> {
+        root.cast_element::<Panel>().unwrap().changes.clone()
+    }
+
+    #[test]
+    fn clicking_opens_a_popup_below_and_an_item_selects() {
+        let (mut tree, root, combo) = panel_with(ComboBox::new(fruits()));
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(20.0, 20.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, Point::new(20.0, 20.0)));
+        tree.layout(Size::new(300.0, 300.0));
+
+        let popup = tree.popups()[0].clone();
+        let rect = popup.window_rect();
+        assert_eq!((rect.pos.x, rect.pos.y), (10.0, 34.0));
+        assert_eq!(rect.size.h, MAX_VISIBLE_ITEMS as f32 * item_height() + 2.0);
+        assert!(combo.has_focus());
+
+        let third = Point::new(20.0, 34.0 + 1.0 + 2.5 * item_height());
+        tree.dispatch_event(&InputEvent::MouseMove(third));
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, third));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, third));
+
+        assert!(tree.popups().is_empty());
+        assert_eq!(combo.cast_element::<ComboBox>().unwrap().selected_item(), Some("Blueberry".to_string()));
+        assert_eq!(changes(&root), vec![Some(2)]);
+    }
+
+    #[test]
+    fn keys_move_the_highlight_and_escape_cancels() {
+        let (mut tree, root, combo) = panel_with(ComboBox::new(fruits()).with_selected(Some(1)));
+        combo.focus();
+
+        key(&mut tree, Key::Down, Modifiers::none());
+        assert_eq!(changes(&root), vec![Some(2)]);
+        key(&mut tree, Key::Down, Modifiers { alt: true, ..Modifiers::none() });
+        assert!(combo.cast_element::<ComboBox>().unwrap().is_open());
+        key(&mut tree, Key::End, Modifiers::none());
+        tree.layout(Size::new(300.0, 300.0));
+        tree.layout(Size::new(300.0, 300.0));
+        let popup = tree.popups()[0].clone();
+        let viewer = popup.children()[0].clone();
+        assert!(viewer.cast_element::<ScrollViewer>().unwrap().offset().y > 0.0);
+        key(&mut tree, Key::Escape, Modifiers::none());
+
+        assert!(tree.popups().is_empty());
+        assert_eq!(combo.cast_element::<ComboBox>().unwrap().selected().get(), Some(2));
+        key(&mut tree, Key::F(4), Modifiers::none());
+        key(&mut tree, Key::Up, Modifiers::none());
+        key(&mut tree, Key::Enter, Modifiers::none());
+        assert_eq!(changes(&root), vec![Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn outside_click_closes_without_selecting() {
+        let (mut tree, root, combo) = panel_with(ComboBox::new(fruits()));
+        combo.cast_element_mut::<ComboBox>().unwrap().open();
+        tree.layout(Size::new(300.0, 300.0));
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(250.0, 20.0)));
+
+        assert!(tree.popups().is_empty());
+        assert!(!combo.cast_element::<ComboBox>().unwrap().is_open());
+        assert!(changes(&root).is_empty());
+    }
+
+    #[test]
+    fn typing_selects_by_prefix() {
+        let items: Vec<String> = fruits().into_iter().map(String::from).collect();
+        assert_eq!(type_ahead_match(&items, "b", None), Some(1));
+        assert_eq!(type_ahead_match(&items, "b", Some(1)), Some(2));
+        assert_eq!(type_ahead_match(&items, "bb", Some(2)), Some(1));
+        assert_eq!(type_ahead_match(&items, "blu", Some(1)), Some(2));
+        assert_eq!(type_ahead_match(&items, "x", Some(1)), None);
+
+        let (mut tree, root, combo) = panel_with(ComboBox::new(fruits()));
+        combo.focus();
+        tree.dispatch_event(&InputEvent::Char('c'));
+        tree.dispatch_event(&InputEvent::Char('h'));
+
+        assert_eq!(changes(&root), vec![Some(3)]);
+        assert_eq!(combo.cast_element::<ComboBox>().unwrap().text(), "Cherry");
+    }
+
+    #[test]
+    fn editable_selects_the_item_matching_the_text() {
+        let (mut tree, root, combo) = panel_with(ComboBox::new(fruits()).with_editable(true));
+        let text_box = combo.children()[0].clone();
+        text_box.focus();
+
+        for c in "fig".chars() {
+            tree.dispatch_event(&InputEvent::Char(c));
+        }
+        assert_eq!(changes(&root), vec![Some(6)]);
+        tree.dispatch_event(&InputEvent::Char('s'));
+        assert_eq!(changes(&root), vec![Some(6), None]);
+        assert_eq!(combo.cast_element::<ComboBox>().unwrap().text(), "figs");
+
+        key(&mut tree, Key::Down, Modifiers::none());
+        assert_eq!(text_box.cast_element::<TextBox>().unwrap().text().get(), "Apple");
+    }
+
+    #[test]
+    fn setting_the_selected_property_validates_and_shows_it() {
+        let (_tree, _root, combo) = panel_with(ComboBox::new(fruits()));
+
+        combo.cast_element::<ComboBox>().unwrap().selected().set(Some(3));
+        assert_eq!(combo.cast_element::<ComboBox>().unwrap().text(), "Cherry");
+        let label = combo.children()[0].clone();
+        assert_eq!(label.cast_element::<TextBlock>().unwrap().text(), "Cherry");
+
+        combo.cast_element::<ComboBox>().unwrap().selected().set(Some(42));
+        assert_eq!(combo.cast_element::<ComboBox>().unwrap().selected().get(), None);
+        assert_eq!(label.cast_element::<TextBlock>().unwrap().text(), "");
+    }
+}
@@ -0,0 +1,351 @@
+use primitives::*;
+use render::*;
+use tree::*;
+
+use cassowary::Solver;
+use std::cell::RefCell;
+use cassowary::strength::{REQUIRED, STRONG, WEAK};
+
+pub use cassowary::{Constraint, Expression, Variable};
+pub use cassowary::WeightedRelation::{EQ, LE, GE};
+pub use cassowary::strength;
+
+/// The solver variables for the edges of a node, in its parent's content coordinates.
+/// Constraints are written with them, e.g.
+/// `button.left |EQ(REQUIRED)| label.right() + 8.0`.
+#[derive(Copy, Clone)]
+pub struct Anchors {
+    pub left: Variable,
+    pub top: Variable,
+    pub width: Variable,
+    pub height: Variable,
+}
+
+impl Anchors {
+    fn new() -> Anchors {
+        Anchors {
+            left: Variable::new(),
+            top: Variable::new(),
+            width: Variable::new(),
+            height: Variable::new(),
+        }
+    }
+
+    pub fn right(&self) -> Expression {
+        self.left + self.width
+    }
+
+    pub fn bottom(&self) -> Expression {
+        self.top + self.height
+    }
+
+    pub fn center_x(&self) -> Expression {
+        self.left + self.width * 0.5
+    }
+
+    pub fn center_y(&self) -> Expression {
+        self.top + self.height * 0.5
+    }
+}
+
+struct ChildEntry {
+    node: NodeWeakRef,
+    anchors: Anchors,
+    constraints: Vec<Constraint>,
+}
+
+/// Places children by solving linear constraints over their edges.
+/// Every child also weakly prefers its measured size, so only the
+/// constraints that matter to a screen need to be given. The solver is
+/// kept between layouts, so adding or removing a constraint or resizing
+/// the container only updates the previous solution.
+pub struct ConstraintLayout {
+    node: NodeWeakRef,
+    solver: RefCell<Solver>,
+    container: Anchors,
+    children: Vec<ChildEntry>,
+}
+
+impl ConstraintLayout {
+    pub fn new() -> ConstraintLayout {
+        let container = Anchors::new();
+        let mut solver = Solver::new();
+        solver.add_constraints(&[
+            container.left |EQ(REQUIRED)| 0.0,
+            container.top |EQ(REQUIRED)| 0.0,
+        ]).unwrap();
+        solver.add_edit_variable(container.width, STRONG).unwrap();
+        solver.add_edit_variable(container.height, STRONG).unwrap();
+        ConstraintLayout {
+            node: NodeWeakRef::empty(),
+            solver: RefCell::new(solver),
+            container: container,
+            children: Vec::new(),
+        }
+    }
+
+    /// The anchors of the area children are laid out in. Its left and top are zero.
+    pub fn container(&self) -> Anchors {
+        self.container
+    }
+
+    /// Adds a child, returning its node. Its anchors are attached to the node.
+    pub fn add_child(&mut self, element: Box<Element>) -> NodeRef {
+        let child = self.node().add_child(element);
+        let anchors = Anchors::new();
+        let constraints = vec![
+            anchors.width |GE(REQUIRED)| 0.0,
+            anchors.height |GE(REQUIRED)| 0.0,
+        ];
+        {
+            let mut solver = self.solver.borrow_mut();
+            solver.add_constraints(&constraints).unwrap();
+            solver.add_edit_variable(anchors.width, WEAK).unwrap();
+            solver.add_edit_variable(anchors.height, WEAK).unwrap();
+        }
+        child.set_attached(anchors);
+        self.children.push(ChildEntry {
+            node: child.downgrade(),
+            anchors: anchors,
+            constraints: constraints,
+        });
+        self.node().invalidate_layout();
+        child
+    }
+
+    /// Removes a child. Constraints added with `add_constraint` that refer to
+    /// its anchors stay in place until removed.
+    pub fn remove_child(&mut self, child: &NodeRef) {
+        if let Some(index) = self.children.iter().position(|c| c.node.upgrade().as_ref() == Some(child)) {
+            let entry = self.children.remove(index);
+            {
+                let mut solver = self.solver.borrow_mut();
+                for constraint in &entry.constraints {
+                    solver.remove_constraint(constraint).unwrap();
+                }
+                solver.remove_edit_variable(entry.anchors.width).unwrap();
+                solver.remove_edit_variable(entry.anchors.height).unwrap();
+            }
+            let mut node = self.node();
+            node.remove_child(child);
+            node.invalidate_layout();
+        }
+    }
+
+    pub fn anchors(&self, child: &NodeRef) -> Anchors {
+        child.attached::<Anchors>().expect("Node is not a child of a ConstraintLayout")
+    }
+
+    pub fn add_constraint(&mut self, constraint: Constraint) -> Result<(), String> {
+        self.solver.borrow_mut().add_constraint(constraint).map_err(|e| format!("{:?}", e))?;
+        self.node().invalidate_layout();
+        Ok(())
+    }
+
+    pub fn add_constraints(&mut self, constraints: &[Constraint]) -> Result<(), String> {
+        for constraint in constraints {
+            self.add_constraint(constraint.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_constraint(&mut self, constraint: &Constraint) -> Result<(), String> {
+        self.solver.borrow_mut().remove_constraint(constraint).map_err(|e| format!("{:?}", e))?;
+        self.node().invalidate_layout();
+        Ok(())
+    }
+
+    /// The current value of a variable, as of the last layout or measure.
+    pub fn value(&self, variable: Variable) -> f32 {
+        self.solver.borrow().get_value(variable) as f32
+    }
+
+    /// Suggests the measured sizes of the children to the solver and
+    /// returns the rects it puts them in. Children whose nodes are gone
+    /// are skipped.
+    fn solve(&self) -> Vec<(NodeRef, Rect)> {
+        let children: Vec<(NodeRef, Anchors, Size)> = self.children.iter()
+            .filter_map(|entry| entry.node.upgrade().map(|node| {
+                let size = node.measure();
+                (node, entry.anchors, size)
+            }))
+            .collect();
+        {
+            let mut solver = self.solver.borrow_mut();
+            for &(_, anchors, size) in &children {
+                solver.suggest_value(anchors.width, size.w as f64).unwrap();
+                solver.suggest_value(anchors.height, size.h as f64).unwrap();
+            }
+        }
+        children.into_iter()
+            .map(|(node, anchors, _)| {
+                let rect = Rect::pos_size(Point::new(self.value(anchors.left), self.value(anchors.top)),
+                                          Size::new(self.value(anchors.width), self.value(anchors.height)));
+                (node, rect)
+            })
+            .collect()
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+}
+
+impl Element for ConstraintLayout {
+    fn init(&mut self, node: NodeRef) {
+        info!("[ConstraintLayout] Init");
+        self.node = node.downgrade();
+    }
+
+    /// Returns the area the children cover, against the container size of
+    /// the last layout.
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[ConstraintLayout] Measure");
+        self.solve().iter().fold(Size::zero(), |extent, &(_, rect)| Size::new(extent.w.max(rect.right()), extent.h.max(rect.bottom())))
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[ConstraintLayout] Layout");
+        {
+            let mut solver = self.solver.borrow_mut();
+            solver.suggest_value(self.container.width, container.w as f64).unwrap();
+            solver.suggest_value(self.container.height, container.h as f64).unwrap();
+        }
+        for (child, rect) in self.solve() {
+            child.place(rect);
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree::testing::SizedElement;
+
+    fn setup() -> (Tree, NodeRef, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(ConstraintLayout::new()))).unwrap();
+        let (label, button) = {
+            let mut layout = root.cast_element_mut::<ConstraintLayout>().unwrap();
+            (layout.add_child(Box::new(SizedElement::new(Size::new(40.0, 20.0)))),
+             layout.add_child(Box::new(SizedElement::new(Size::new(60.0, 30.0)))))
+        };
+        (tree, root, label, button)
+    }
+
+    fn rect(node: &NodeRef) -> ((f32, f32), (f32, f32)) {
+        node.rect().to_pos_size_tuple()
+    }
+
+    #[test]
+    fn children_take_measured_size_and_constrained_position() {
+        let (_tree, root, label, button) = setup();
+        {
+            let mut layout = root.cast_element_mut::<ConstraintLayout>().unwrap();
+            let (l, b) = (layout.anchors(&label), layout.anchors(&button));
+            layout.add_constraints(&[
+                l.left |EQ(REQUIRED)| 10.0,
+                l.top |EQ(REQUIRED)| 5.0,
+                b.left |EQ(REQUIRED)| l.right() + 8.0,
+                b.center_y() |EQ(REQUIRED)| l.center_y(),
+            ]).unwrap();
+        }
+
+        assert_eq!(root.measure(), Size::new(118.0, 30.0));
+        root.layout(Size::new(200.0, 100.0));
+
+        assert_eq!(rect(&label), ((10.0, 5.0), (40.0, 20.0)));
+        assert_eq!(rect(&button), ((58.0, 0.0), (60.0, 30.0)));
+    }
+
+    #[test]
+    fn constraints_against_container_follow_resizes() {
+        let (_tree, root, _, button) = setup();
+        {
+            let mut layout = root.cast_element_mut::<ConstraintLayout>().unwrap();
+            let (c, b) = (layout.container(), layout.anchors(&button));
+            layout.add_constraints(&[
+                b.right() |EQ(REQUIRED)| c.right() - 10.0,
+                b.bottom() |EQ(REQUIRED)| c.bottom(),
+            ]).unwrap();
+        }
+
+        root.layout(Size::new(200.0, 100.0));
+        assert_eq!(rect(&button), ((130.0, 70.0), (60.0, 30.0)));
+
+        root.layout(Size::new(300.0, 50.0));
+        assert_eq!(rect(&button), ((230.0, 20.0), (60.0, 30.0)));
+    }
+
+    #[test]
+    fn stronger_constraints_win_until_removed() {
+        let (_tree, root, label, button) = setup();
+        let strong = {
+            let mut layout = root.cast_element_mut::<ConstraintLayout>().unwrap();
+            let (l, b) = (layout.anchors(&label), layout.anchors(&button));
+            let strong: Constraint = l.width |EQ(STRONG)| b.width + 60.0;
+            layout.add_constraint(b.width |EQ(REQUIRED)| 60.0).unwrap();
+            layout.add_constraints(&[
+                strong.clone(),
+                l.width |EQ(strength::MEDIUM)| 80.0,
+            ]).unwrap();
+            strong
+        };
+
+        root.layout(Size::new(200.0, 100.0));
+        assert_eq!(rect(&label).1, (120.0, 20.0));
+
+        root.cast_element_mut::<ConstraintLayout>().unwrap().remove_constraint(&strong).unwrap();
+        root.layout(Size::new(200.0, 100.0));
+        assert_eq!(rect(&label).1, (80.0, 20.0));
+    }
+
+    #[test]
+    fn conflicting_required_constraint_is_rejected_and_removal_restores_layout() {
+        let (_tree, root, label, _) = setup();
+        let fixed = {
+            let mut layout = root.cast_element_mut::<ConstraintLayout>().unwrap();
+            let l = layout.anchors(&label);
+            let fixed: Constraint = l.width |EQ(REQUIRED)| 10.0;
+            layout.add_constraint(fixed.clone()).unwrap();
+            assert!(layout.add_constraint(l.width |EQ(REQUIRED)| 20.0).is_err());
+            fixed
+        };
+        root.layout(Size::new(200.0, 100.0));
+        assert_eq!(rect(&label).1, (10.0, 20.0));
+
+        {
+            let mut layout = root.cast_element_mut::<ConstraintLayout>().unwrap();
+            layout.remove_constraint(&fixed).unwrap();
+            assert!(layout.remove_constraint(&fixed).is_err());
+        }
+        root.layout(Size::new(200.0, 100.0));
+        assert_eq!(rect(&label).1, (40.0, 20.0));
+    }
+
+    #[test]
+    fn removed_child_is_detached() {
+        let (_tree, root, label, _) = setup();
+
+        root.cast_element_mut::<ConstraintLayout>().unwrap().remove_child(&label);
+        root.layout(Size::new(200.0, 100.0));
+
+        assert_eq!(root.children().len(), 1);
+        assert!(label.parrent().is_none());
+    }
+
+    #[test]
+    fn children_dropped_without_remove_child_are_skipped() {
+        let (_tree, mut root, label, button) = setup();
+
+        root.remove_child(&label);
+        drop(label);
+        root.layout(Size::new(200.0, 100.0));
+
+        assert_eq!(rect(&button).1, (60.0, 30.0));
+    }
+}
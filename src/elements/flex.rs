@@ -0,0 +1,679 @@
+use primitives::*;
+use render::*;
+use tree::*;
+
+use std::f32;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FlexDirection {
+    Row,
+    RowReverse,
+    Column,
+    ColumnReverse,
+}
+
+impl FlexDirection {
+    fn is_row(&self) -> bool {
+        match *self {
+            FlexDirection::Row | FlexDirection::RowReverse => true,
+            FlexDirection::Column | FlexDirection::ColumnReverse => false,
+        }
+    }
+
+    fn is_reverse(&self) -> bool {
+        match *self {
+            FlexDirection::RowReverse | FlexDirection::ColumnReverse => true,
+            FlexDirection::Row | FlexDirection::Column => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FlexWrap {
+    NoWrap,
+    Wrap,
+    WrapReverse,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum JustifyContent {
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlignItems {
+    FlexStart,
+    FlexEnd,
+    Center,
+    Stretch,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FlexBasis {
+    Auto,
+    Points(f32),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FlexStyle {
+    pub direction: FlexDirection,
+    pub wrap: FlexWrap,
+    pub justify_content: JustifyContent,
+    pub align_items: AlignItems,
+    pub row_gap: f32,
+    pub column_gap: f32,
+}
+
+impl Default for FlexStyle {
+    fn default() -> FlexStyle {
+        FlexStyle {
+            direction: FlexDirection::Row,
+            wrap: FlexWrap::NoWrap,
+            justify_content: JustifyContent::FlexStart,
+            align_items: AlignItems::Stretch,
+            row_gap: 0.0,
+            column_gap: 0.0,
+        }
+    }
+}
+
+/// Per-child flex settings, attached to the child node with `NodeRef::set_attached`.
+#[derive(Copy, Clone, Debug)]
+pub struct FlexItem {
+    pub grow: f32,
+    pub shrink: f32,
+    pub basis: FlexBasis,
+    pub align_self: Option<AlignItems>,
+}
+
+impl Default for FlexItem {
+    fn default() -> FlexItem {
+        FlexItem {
+            grow: 0.0,
+            shrink: 1.0,
+            basis: FlexBasis::Auto,
+            align_self: None,
+        }
+    }
+}
+
+impl FlexItem {
+    pub fn new() -> FlexItem {
+        FlexItem::default()
+    }
+
+    pub fn with_grow(mut self, grow: f32) -> FlexItem {
+        self.grow = grow;
+        self
+    }
+
+    pub fn with_shrink(mut self, shrink: f32) -> FlexItem {
+        self.shrink = shrink;
+        self
+    }
+
+    pub fn with_basis(mut self, basis: FlexBasis) -> FlexItem {
+        self.basis = basis;
+        self
+    }
+
+    pub fn with_align_self(mut self, align: AlignItems) -> FlexItem {
+        self.align_self = Some(align);
+        self
+    }
+}
+
+/// A child as seen by the flex algorithm: its settings, measured outer size
+/// and outer size limits.
+#[derive(Copy, Clone, Debug)]
+pub struct FlexChild {
+    pub item: FlexItem,
+    pub size: Size,
+    pub min_size: Size,
+    pub max_size: Size,
+}
+
+impl FlexChild {
+    pub fn new(item: FlexItem, size: Size) -> FlexChild {
+        FlexChild {
+            item: item,
+            size: size,
+            min_size: Size::zero(),
+            max_size: Size::new(f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    pub fn with_limits(mut self, min_size: Size, max_size: Size) -> FlexChild {
+        self.min_size = min_size;
+        self.max_size = max_size;
+        self
+    }
+}
+
+pub struct FlexContainer {
+    style: FlexStyle,
+    node: NodeWeakRef,
+}
+
+impl FlexContainer {
+    pub fn new() -> FlexContainer {
+        FlexContainer::with_style(FlexStyle::default())
+    }
+
+    pub fn with_style(style: FlexStyle) -> FlexContainer {
+        FlexContainer {
+            style: style,
+            node: NodeWeakRef::empty(),
+        }
+    }
+
+    pub fn style(&self) -> FlexStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: FlexStyle) {
+        self.style = style;
+    }
+
+    pub fn add_item(&mut self, element: Box<Element>, item: FlexItem) -> NodeRef {
+        let child = self.node.upgrade().unwrap().add_child(element);
+        child.set_attached(item);
+        child
+    }
+}
+
+impl Element for FlexContainer {
+    fn init(&mut self, node: NodeRef) {
+        info!("[FlexContainer] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[FlexContainer] Measure");
+        let items = collect_items(&node);
+        let row = self.style.direction.is_row();
+        let main_gap = if row { self.style.column_gap } else { self.style.row_gap };
+        let mut main = 0.0;
+        let mut cross: f32 = 0.0;
+        for (i, child) in items.iter().enumerate() {
+            if i > 0 {
+                main += main_gap;
+            }
+            main += basis_of(child.item, main_of(child.size, row));
+            cross = cross.max(cross_of(child.size, row));
+        }
+        size_from_axes(main, cross, row)
+    }
+
+    fn layout(&mut self, mut node: NodeRef, container: Size) {
+        info!("[FlexContainer] Layout");
+        let children: Vec<NodeRef> = node.children().iter().cloned().collect();
+        let items = collect_items(&node);
+        let rects = compute_layout(&self.style, &items, container);
+        for (child, rect) in children.iter().zip(rects.into_iter()) {
+            child.arrange(rect);
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+}
+
+fn collect_items(node: &NodeRef) -> Vec<FlexChild> {
+    node.children().iter()
+        .map(|c| {
+            let properties = c.layout_properties();
+            let margin = Size::new(properties.margin.horizontal(), properties.margin.vertical());
+            let min = properties.min_size();
+            let max = properties.max_size();
+            FlexChild::new(c.attached::<FlexItem>().unwrap_or_default(), c.measure())
+                .with_limits(Size::new(min.w + margin.w, min.h + margin.h),
+                             Size::new(max.w + margin.w, max.h + margin.h))
+        })
+        .collect()
+}
+
+fn main_of(size: Size, row: bool) -> f32 {
+    if row { size.w } else { size.h }
+}
+
+fn cross_of(size: Size, row: bool) -> f32 {
+    if row { size.h } else { size.w }
+}
+
+fn size_from_axes(main: f32, cross: f32, row: bool) -> Size {
+    if row { Size::new(main, cross) } else { Size::new(cross, main) }
+}
+
+fn basis_of(item: FlexItem, measured_main: f32) -> f32 {
+    match item.basis {
+        FlexBasis::Auto => measured_main,
+        FlexBasis::Points(p) => p,
+    }.max(0.0)
+}
+
+struct LineItem {
+    index: usize,
+    basis: f32,
+    min: f32,
+    max: f32,
+    target: f32,
+    frozen: bool,
+}
+
+fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    value.max(min).min(max)
+}
+
+/// Resolves the main sizes of one line's items, following the
+/// "resolve flexible lengths" steps of the CSS flexbox spec.
+fn resolve_flexible_lengths(line: &mut [LineItem], items: &[FlexChild], available: f32, main_gap: f32) {
+    let gaps = main_gap * (line.len() as f32 - 1.0).max(0.0);
+    let used: f32 = line.iter().map(|i| i.basis).sum();
+    let growing = used + gaps < available;
+
+    let factor = |li: &LineItem| {
+        let item = items[li.index].item;
+        if growing { item.grow } else { item.shrink * li.basis }
+    };
+
+    for li in line.iter_mut() {
+        li.target = li.basis;
+        let item = items[li.index].item;
+        if (growing && item.grow == 0.0) || (!growing && item.shrink == 0.0) {
+            li.target = clamp(li.basis, li.min, li.max);
+            li.frozen = true;
+        }
+    }
+
+    let initial_free = {
+        let occupied: f32 = line.iter().map(|li| if li.frozen { li.target } else { li.basis }).sum();
+        available - gaps - occupied
+    };
+
+    loop {
+        if line.iter().all(|li| li.frozen) {
+            break;
+        }
+
+        let occupied: f32 = line.iter().map(|li| if li.frozen { li.target } else { li.basis }).sum();
+        let mut free = available - gaps - occupied;
+
+        let factor_sum: f32 = line.iter().filter(|li| !li.frozen).map(|li| factor(li)).sum();
+        if growing {
+            let grow_sum: f32 = line.iter().filter(|li| !li.frozen).map(|li| items[li.index].item.grow).sum();
+            if grow_sum < 1.0 {
+                let scaled = initial_free * grow_sum;
+                if scaled.abs() < free.abs() {
+                    free = scaled;
+                }
+            }
+        }
+
+        let mut violation = 0.0;
+        for li in line.iter_mut().filter(|li| !li.frozen) {
+            let share = if factor_sum > 0.0 { factor(li) / factor_sum } else { 0.0 };
+            let unclamped = li.basis + free * share;
+            li.target = clamp(unclamped, li.min, li.max);
+            violation += li.target - unclamped;
+        }
+
+        for li in line.iter_mut().filter(|li| !li.frozen) {
+            let at_min = li.target == li.min;
+            let at_max = li.target == li.max;
+            li.frozen = violation == 0.0
+                || (violation > 0.0 && at_min)
+                || (violation < 0.0 && at_max);
+        }
+    }
+}
+
+/// Computes the child rects of a flex container of size `container`.
+/// `items` are given in child order.
+pub fn compute_layout(style: &FlexStyle, items: &[FlexChild], container: Size) -> Vec<Rect> {
+    let row = style.direction.is_row();
+    let (main_gap, cross_gap) = if row {
+        (style.column_gap, style.row_gap)
+    } else {
+        (style.row_gap, style.column_gap)
+    };
+    let container_main = main_of(container, row);
+    let container_cross = cross_of(container, row);
+
+    let mut lines: Vec<Vec<LineItem>> = Vec::new();
+    {
+        let mut current: Vec<LineItem> = Vec::new();
+        let mut line_main = 0.0;
+        for (index, child) in items.iter().enumerate() {
+            let basis = basis_of(child.item, main_of(child.size, row));
+            let advance = if current.is_empty() { basis } else { main_gap + basis };
+            if style.wrap != FlexWrap::NoWrap && !current.is_empty() && line_main + advance > container_main {
+                lines.push(current);
+                current = Vec::new();
+                line_main = basis;
+            } else {
+                line_main += advance;
+            }
+            current.push(LineItem {
+                index: index,
+                basis: basis,
+                min: main_of(child.min_size, row),
+                max: main_of(child.max_size, row),
+                target: basis,
+                frozen: false,
+            });
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+    }
+
+    let mut rects = vec![Rect::zero(); items.len()];
+    let single_line = style.wrap == FlexWrap::NoWrap;
+    let mut cross_pos = 0.0;
+
+    for line in lines.iter_mut() {
+        resolve_flexible_lengths(line, items, container_main, main_gap);
+
+        let line_cross = if single_line {
+            container_cross
+        } else {
+            line.iter().map(|li| cross_of(items[li.index].size, row)).fold(0.0, f32::max)
+        };
+
+        let count = line.len() as f32;
+        let used: f32 = line.iter().map(|li| li.target).sum::<f32>() + main_gap * (count - 1.0);
+        let free = container_main - used;
+        let (mut main_pos, between) = match style.justify_content {
+            JustifyContent::FlexStart => (0.0, 0.0),
+            JustifyContent::FlexEnd => (free, 0.0),
+            JustifyContent::Center => (free / 2.0, 0.0),
+            JustifyContent::SpaceBetween => {
+                if free > 0.0 && line.len() > 1 {
+                    (0.0, free / (count - 1.0))
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+            JustifyContent::SpaceAround => {
+                if free > 0.0 {
+                    (free / count / 2.0, free / count)
+                } else {
+                    (free / 2.0, 0.0)
+                }
+            }
+            JustifyContent::SpaceEvenly => {
+                if free > 0.0 {
+                    (free / (count + 1.0), free / (count + 1.0))
+                } else {
+                    (free / 2.0, 0.0)
+                }
+            }
+        };
+
+        for li in line.iter() {
+            let child = items[li.index];
+            let measured_cross = cross_of(child.size, row);
+            let (offset, cross_size) = match child.item.align_self.unwrap_or(style.align_items) {
+                AlignItems::FlexStart => (0.0, measured_cross),
+                AlignItems::FlexEnd => (line_cross - measured_cross, measured_cross),
+                AlignItems::Center => ((line_cross - measured_cross) / 2.0, measured_cross),
+                AlignItems::Stretch => (0.0, line_cross),
+            };
+
+            let mut main = main_pos;
+            if style.direction.is_reverse() {
+                main = container_main - main - li.target;
+            }
+            let mut cross = cross_pos + offset;
+            if style.wrap == FlexWrap::WrapReverse {
+                cross = container_cross - cross - cross_size;
+            }
+
+            rects[li.index] = if row {
+                Rect::pos_size(Point::new(main, cross), Size::new(li.target, cross_size))
+            } else {
+                Rect::pos_size(Point::new(cross, main), Size::new(cross_size, li.target))
+            };
+
+            main_pos += li.target + main_gap + between;
+        }
+
+        cross_pos += line_cross + cross_gap;
+    }
+
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Case {
+        name: &'static str,
+        style: FlexStyle,
+        container: (f32, f32),
+        items: Vec<(FlexItem, (f32, f32))>,
+        expected: Vec<(f32, f32, f32, f32)>,
+    }
+
+    fn item() -> FlexItem {
+        FlexItem::new()
+    }
+
+    fn style() -> FlexStyle {
+        FlexStyle::default()
+    }
+
+    fn cases() -> Vec<Case> {
+        vec![
+            Case {
+                name: "row stretches items to container cross size",
+                style: style(),
+                container: (300.0, 100.0),
+                items: vec![(item(), (50.0, 20.0)), (item(), (50.0, 20.0)), (item(), (50.0, 20.0))],
+                expected: vec![(0.0, 0.0, 50.0, 100.0), (50.0, 0.0, 50.0, 100.0), (100.0, 0.0, 50.0, 100.0)],
+            },
+            Case {
+                name: "grow distributes free space by factor",
+                style: style(),
+                container: (400.0, 50.0),
+                items: vec![
+                    (item().with_grow(1.0), (50.0, 10.0)),
+                    (item().with_grow(2.0), (50.0, 10.0)),
+                    (item().with_grow(1.0), (50.0, 10.0)),
+                ],
+                expected: vec![(0.0, 0.0, 112.5, 50.0), (112.5, 0.0, 175.0, 50.0), (287.5, 0.0, 112.5, 50.0)],
+            },
+            Case {
+                name: "grow factors below one distribute a fraction of free space",
+                style: style(),
+                container: (300.0, 50.0),
+                items: vec![(item().with_grow(0.5), (100.0, 10.0))],
+                expected: vec![(0.0, 0.0, 200.0, 50.0)],
+            },
+            Case {
+                name: "shrink is weighted by basis",
+                style: style(),
+                container: (150.0, 50.0),
+                items: vec![(item(), (100.0, 10.0)), (item(), (200.0, 10.0))],
+                expected: vec![(0.0, 0.0, 50.0, 50.0), (50.0, 0.0, 100.0, 50.0)],
+            },
+            Case {
+                name: "zero shrink keeps basis",
+                style: style(),
+                container: (150.0, 50.0),
+                items: vec![(item().with_shrink(0.0), (100.0, 10.0)), (item(), (100.0, 10.0))],
+                expected: vec![(0.0, 0.0, 100.0, 50.0), (100.0, 0.0, 50.0, 50.0)],
+            },
+            Case {
+                name: "explicit basis overrides measured size",
+                style: style(),
+                container: (300.0, 50.0),
+                items: vec![(item().with_basis(FlexBasis::Points(100.0)), (20.0, 10.0)), (item(), (30.0, 10.0))],
+                expected: vec![(0.0, 0.0, 100.0, 50.0), (100.0, 0.0, 30.0, 50.0)],
+            },
+            Case {
+                name: "justify flex-end",
+                style: FlexStyle { justify_content: JustifyContent::FlexEnd, ..style() },
+                container: (300.0, 50.0),
+                items: vec![(item(), (50.0, 10.0)), (item(), (50.0, 10.0))],
+                expected: vec![(200.0, 0.0, 50.0, 50.0), (250.0, 0.0, 50.0, 50.0)],
+            },
+            Case {
+                name: "justify center",
+                style: FlexStyle { justify_content: JustifyContent::Center, ..style() },
+                container: (300.0, 50.0),
+                items: vec![(item(), (50.0, 10.0)), (item(), (50.0, 10.0))],
+                expected: vec![(100.0, 0.0, 50.0, 50.0), (150.0, 0.0, 50.0, 50.0)],
+            },
+            Case {
+                name: "justify space-between",
+                style: FlexStyle { justify_content: JustifyContent::SpaceBetween, ..style() },
+                container: (300.0, 50.0),
+                items: vec![(item(), (50.0, 10.0)), (item(), (50.0, 10.0)), (item(), (50.0, 10.0))],
+                expected: vec![(0.0, 0.0, 50.0, 50.0), (125.0, 0.0, 50.0, 50.0), (250.0, 0.0, 50.0, 50.0)],
+            },
+            Case {
+                name: "justify space-around",
+                style: FlexStyle { justify_content: JustifyContent::SpaceAround, ..style() },
+                container: (300.0, 50.0),
+                items: vec![(item(), (50.0, 10.0)), (item(), (50.0, 10.0))],
+                expected: vec![(50.0, 0.0, 50.0, 50.0), (200.0, 0.0, 50.0, 50.0)],
+            },
+            Case {
+                name: "justify space-evenly",
+                style: FlexStyle { justify_content: JustifyContent::SpaceEvenly, ..style() },
+                container: (350.0, 50.0),
+                items: vec![(item(), (50.0, 10.0)), (item(), (50.0, 10.0)), (item(), (50.0, 10.0))],
+                expected: vec![(50.0, 0.0, 50.0, 50.0), (150.0, 0.0, 50.0, 50.0), (250.0, 0.0, 50.0, 50.0)],
+            },
+            Case {
+                name: "gap between items counts against free space",
+                style: FlexStyle { column_gap: 10.0, ..style() },
+                container: (200.0, 50.0),
+                items: vec![(item().with_grow(1.0), (50.0, 10.0)), (item().with_grow(1.0), (50.0, 10.0))],
+                expected: vec![(0.0, 0.0, 95.0, 50.0), (105.0, 0.0, 95.0, 50.0)],
+            },
+            Case {
+                name: "column with centered items",
+                style: FlexStyle { direction: FlexDirection::Column, align_items: AlignItems::Center, ..style() },
+                container: (100.0, 200.0),
+                items: vec![(item(), (40.0, 20.0)), (item(), (60.0, 30.0))],
+                expected: vec![(30.0, 0.0, 40.0, 20.0), (20.0, 20.0, 60.0, 30.0)],
+            },
+            Case {
+                name: "align-self overrides align-items",
+                style: style(),
+                container: (200.0, 100.0),
+                items: vec![(item(), (50.0, 20.0)), (item().with_align_self(AlignItems::FlexEnd), (50.0, 40.0))],
+                expected: vec![(0.0, 0.0, 50.0, 100.0), (50.0, 60.0, 50.0, 40.0)],
+            },
+            Case {
+                name: "row-reverse mirrors the main axis",
+                style: FlexStyle { direction: FlexDirection::RowReverse, align_items: AlignItems::FlexStart, ..style() },
+                container: (300.0, 100.0),
+                items: vec![(item(), (50.0, 20.0)), (item(), (50.0, 20.0))],
+                expected: vec![(250.0, 0.0, 50.0, 20.0), (200.0, 0.0, 50.0, 20.0)],
+            },
+            Case {
+                name: "column-reverse with justify flex-end",
+                style: FlexStyle {
+                    direction: FlexDirection::ColumnReverse,
+                    justify_content: JustifyContent::FlexEnd,
+                    align_items: AlignItems::FlexStart,
+                    ..style()
+                },
+                container: (100.0, 100.0),
+                items: vec![(item(), (10.0, 20.0)), (item(), (10.0, 30.0))],
+                expected: vec![(0.0, 30.0, 10.0, 20.0), (0.0, 0.0, 10.0, 30.0)],
+            },
+            Case {
+                name: "wrap breaks lines and applies both gaps",
+                style: FlexStyle { wrap: FlexWrap::Wrap, column_gap: 10.0, row_gap: 5.0, ..style() },
+                container: (150.0, 100.0),
+                items: vec![(item(), (60.0, 20.0)), (item(), (60.0, 30.0)), (item(), (60.0, 10.0))],
+                expected: vec![(0.0, 0.0, 60.0, 30.0), (70.0, 0.0, 60.0, 30.0), (0.0, 35.0, 60.0, 10.0)],
+            },
+            Case {
+                name: "wrap grows items within their own line",
+                style: FlexStyle { wrap: FlexWrap::Wrap, align_items: AlignItems::FlexStart, ..style() },
+                container: (100.0, 100.0),
+                items: vec![
+                    (item().with_grow(1.0), (40.0, 10.0)),
+                    (item().with_grow(1.0), (40.0, 10.0)),
+                    (item().with_grow(1.0), (40.0, 10.0)),
+                ],
+                expected: vec![(0.0, 0.0, 50.0, 10.0), (50.0, 0.0, 50.0, 10.0), (0.0, 10.0, 100.0, 10.0)],
+            },
+            Case {
+                name: "wrap-reverse stacks lines from the cross end",
+                style: FlexStyle {
+                    wrap: FlexWrap::WrapReverse,
+                    align_items: AlignItems::FlexStart,
+                    column_gap: 10.0,
+                    row_gap: 5.0,
+                    ..style()
+                },
+                container: (150.0, 100.0),
+                items: vec![(item(), (60.0, 20.0)), (item(), (60.0, 30.0)), (item(), (60.0, 10.0))],
+                expected: vec![(0.0, 80.0, 60.0, 20.0), (70.0, 70.0, 60.0, 30.0), (0.0, 55.0, 60.0, 10.0)],
+            },
+        ]
+    }
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.001
+    }
+
+    #[test]
+    fn reference_layouts() {
+        for case in cases() {
+            let items: Vec<FlexChild> = case.items.iter()
+                .map(|&(item, size)| FlexChild::new(item, Size::from_tuple(size)))
+                .collect();
+            let rects = compute_layout(&case.style, &items, Size::from_tuple(case.container));
+
+            assert_eq!(rects.len(), case.expected.len(), "{}", case.name);
+            for (i, (rect, &(x, y, w, h))) in rects.iter().zip(case.expected.iter()).enumerate() {
+                let ok = approx(rect.pos.x, x) && approx(rect.pos.y, y)
+                    && approx(rect.size.w, w) && approx(rect.size.h, h);
+                assert!(ok, "{}: item {} was {:?}, expected ({}, {}, {}, {})", case.name, i, rect, x, y, w, h);
+            }
+        }
+    }
+
+    #[test]
+    fn max_size_freezes_item_and_redistributes() {
+        let items = vec![
+            FlexChild::new(item().with_grow(1.0), Size::new(50.0, 10.0))
+                .with_limits(Size::zero(), Size::new(100.0, f32::INFINITY)),
+            FlexChild::new(item().with_grow(1.0), Size::new(50.0, 10.0)),
+        ];
+
+        let rects = compute_layout(&style(), &items, Size::new(400.0, 10.0));
+
+        assert_eq!(rects[0].to_pos_size_tuple(), ((0.0, 0.0), (100.0, 10.0)));
+        assert_eq!(rects[1].to_pos_size_tuple(), ((100.0, 0.0), (300.0, 10.0)));
+    }
+
+    #[test]
+    fn min_size_stops_shrinking() {
+        let items = vec![
+            FlexChild::new(item(), Size::new(100.0, 10.0))
+                .with_limits(Size::new(80.0, 0.0), Size::new(f32::INFINITY, f32::INFINITY)),
+            FlexChild::new(item(), Size::new(100.0, 10.0)),
+        ];
+
+        let rects = compute_layout(&style(), &items, Size::new(120.0, 10.0));
+
+        assert_eq!(rects[0].size.w, 80.0);
+        assert_eq!(rects[1].size.w, 40.0);
+    }
+}
@@ -0,0 +1,257 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use bitmap::{self, Bitmap, ImageSource};
+use std::cell::RefCell;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+enum ImageState {
+    Empty,
+    /// To be decoded once the image is in a tree, which the decoding
+    /// thread wakes when it is done.
+    Waiting(ImageSource),
+    Decoding(Receiver<Result<Bitmap, String>>),
+    Ready(Arc<Bitmap>),
+    Failed(String),
+}
+
+/// Shows a bitmap, scaled by its `Stretch` and centered in the node. Images
+/// from a path or bytes are decoded on another thread, taking no room until
+/// they are ready.
+pub struct Image {
+    node: NodeWeakRef,
+    state: RefCell<ImageState>,
+    stretch: Stretch,
+    quality: SamplingQuality,
+}
+
+impl Image {
+    pub fn new() -> Image {
+        Image {
+            node: NodeWeakRef::empty(),
+            state: RefCell::new(ImageState::Empty),
+            stretch: Stretch::Uniform,
+            quality: SamplingQuality::Mipmapped,
+        }
+    }
+
+    pub fn from_path<P: Into<PathBuf>>(path: P) -> Image {
+        let mut image = Image::new();
+        image.set_source(ImageSource::Path(path.into()));
+        image
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Image {
+        let mut image = Image::new();
+        image.set_source(ImageSource::Bytes(bytes));
+        image
+    }
+
+    /// Shows an already decoded bitmap, which other images can share.
+    pub fn from_bitmap(bitmap: Arc<Bitmap>) -> Image {
+        let mut image = Image::new();
+        image.set_bitmap(Some(bitmap));
+        image
+    }
+
+    pub fn with_stretch(mut self, stretch: Stretch) -> Image {
+        self.stretch = stretch;
+        self
+    }
+
+    pub fn with_quality(mut self, quality: SamplingQuality) -> Image {
+        self.quality = quality;
+        self
+    }
+
+    /// Starts decoding `source`, replacing the current bitmap once it is done.
+    pub fn set_source(&mut self, source: ImageSource) {
+        *self.state.borrow_mut() = match self.node.upgrade().and_then(|node| node.waker()) {
+            Some(waker) => ImageState::Decoding(bitmap::decode_async(source, move || waker.wake())),
+            None => ImageState::Waiting(source),
+        };
+        self.invalidate();
+    }
+
+    pub fn set_bitmap(&mut self, bitmap: Option<Arc<Bitmap>>) {
+        *self.state.borrow_mut() = match bitmap {
+            Some(bitmap) => ImageState::Ready(bitmap),
+            None => ImageState::Empty,
+        };
+        self.invalidate();
+    }
+
+    pub fn bitmap(&self) -> Option<Arc<Bitmap>> {
+        self.poll();
+        match *self.state.borrow() {
+            ImageState::Ready(ref bitmap) => Some(bitmap.clone()),
+            _ => None,
+        }
+    }
+
+    /// Whether the source is yet to be decoded.
+    pub fn is_decoding(&self) -> bool {
+        self.poll();
+        match *self.state.borrow() {
+            ImageState::Waiting(_) | ImageState::Decoding(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Why the last source couldn't be decoded.
+    pub fn error(&self) -> Option<String> {
+        self.poll();
+        match *self.state.borrow() {
+            ImageState::Failed(ref error) => Some(error.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn stretch(&self) -> Stretch {
+        self.stretch
+    }
+
+    pub fn set_stretch(&mut self, stretch: Stretch) {
+        self.stretch = stretch;
+        self.invalidate();
+    }
+
+    pub fn quality(&self) -> SamplingQuality {
+        self.quality
+    }
+
+    pub fn set_quality(&mut self, quality: SamplingQuality) {
+        self.quality = quality;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    /// Where the bitmap is drawn in a content rect of `size`.
+    pub fn image_rect(&self, size: Size) -> Option<Rect> {
+        self.bitmap().map(|bitmap| {
+            let natural = bitmap.size();
+            let (sx, sy) = self.stretch.scale(natural, size);
+            let scaled = Size::new(natural.w * sx, natural.h * sy);
+            Rect::pos_size(Point::new((size.w - scaled.w) / 2.0, (size.h - scaled.h) / 2.0), scaled)
+        })
+    }
+
+    /// Takes the result of a finished decode, which the decoding thread
+    /// wakes the tree for. The layout is invalidated when one arrives, since
+    /// the image takes room from then on.
+    fn poll(&self) {
+        let result = match *self.state.borrow() {
+            ImageState::Decoding(ref receiver) => match receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err("the decoding thread panicked".to_string()),
+            },
+            _ => return,
+        };
+        *self.state.borrow_mut() = match result {
+            Ok(bitmap) => ImageState::Ready(Arc::new(bitmap)),
+            Err(error) => {
+                info!("[Image] Decoding failed: {}", error);
+                ImageState::Failed(error)
+            }
+        };
+        self.invalidate();
+    }
+
+    fn invalidate(&self) {
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+}
+
+impl Element for Image {
+    fn init(&mut self, node: NodeRef) {
+        info!("[Image] Init");
+        self.node = node.downgrade();
+        let waiting = match mem::replace(&mut *self.state.borrow_mut(), ImageState::Empty) {
+            ImageState::Waiting(source) => Some(source),
+            state => {
+                *self.state.borrow_mut() = state;
+                None
+            }
+        };
+        if let Some(source) = waiting {
+            self.set_source(source);
+        }
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[Image] Measure");
+        self.bitmap().map_or(Size::zero(), |bitmap| bitmap.size())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[Image] Layout");
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[Image] Render");
+        let mut cmd = RenderCommandList::new();
+        let content = node.content_rect();
+        if let (Some(bitmap), Some(rect)) = (self.bitmap(), self.image_rect(content.size)) {
+            let rect = Rect::pos_size(content.pos + rect.pos, rect.size);
+            // Uniform-to-fill and unscaled images can be larger than the node.
+            let clip = rect.size.w > content.size.w || rect.size.h > content.size.h;
+            if clip {
+                cmd.add(RenderCommand::PushClip(content));
+            }
+            cmd.add(RenderCommand::Image(rect, bitmap, self.quality));
+            if clip {
+                cmd.add(RenderCommand::PopClip);
+            }
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn bitmap(width: u32, height: u32) -> Arc<Bitmap> {
+        Arc::new(Bitmap::from_rgba(width, height, vec![255; (width * height * 4) as usize]).unwrap())
+    }
+
+    #[test]
+    fn stretch_modes_place_the_bitmap() {
+        let size = Size::new(100.0, 100.0);
+        let rect = |stretch| Image::from_bitmap(bitmap(40, 20)).with_stretch(stretch).image_rect(size).unwrap().to_pos_size_tuple();
+
+        assert_eq!(rect(Stretch::None), ((30.0, 40.0), (40.0, 20.0)));
+        assert_eq!(rect(Stretch::Fill), ((0.0, 0.0), (100.0, 100.0)));
+        assert_eq!(rect(Stretch::Uniform), ((0.0, 25.0), (100.0, 50.0)));
+        assert_eq!(rect(Stretch::UniformToFill), ((-50.0, 0.0), (200.0, 100.0)));
+    }
+
+    #[test]
+    fn decoding_wakes_the_tree_and_measures_zero_until_done() {
+        let mut tree = Tree::new();
+        let (sender, woken) = channel();
+        tree.set_wakeup(move || sender.send(()).unwrap());
+        let root = tree.set_root(Some(Box::new(Image::from_bytes(vec![1, 2, 3])))).unwrap();
+        assert_eq!(root.measure(), Size::zero());
+
+        woken.recv().unwrap();
+        assert_eq!(tree.take_invalidation(), (true, true));
+        assert!(!root.cast_element::<Image>().unwrap().is_decoding());
+        assert_eq!(root.measure(), Size::zero());
+        assert!(root.cast_element::<Image>().unwrap().error().is_some());
+
+        root.cast_element_mut::<Image>().unwrap().set_bitmap(Some(bitmap(3, 2)));
+
+        assert_eq!(root.measure(), Size::new(3.0, 2.0));
+        assert!(tree.take_invalidation().0);
+    }
+}
@@ -0,0 +1,1238 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use msg::*;
+use text::*;
+use text::layout::*;
+use elements::text_block::DEFAULT_FONT_SIZE;
+use elements::toggle::render_mark;
+use std::cell::RefCell;
+use std::f32;
+use std::rc::Rc;
+
+const ITEM_PADDING: (f32, f32) = (6.0, 3.0);
+const TITLE_PADDING: (f32, f32) = (8.0, 3.0);
+/// The room left of item labels for check marks.
+const CHECK_WIDTH: f32 = 22.0;
+/// The room right of item labels for submenu arrows.
+const ARROW_WIDTH: f32 = 18.0;
+/// The least room between a label and its accelerator.
+const ACCELERATOR_GAP: f32 = 24.0;
+const SEPARATOR_HEIGHT: f32 = 7.0;
+
+/// Raised by a `MenuBar` or `ContextMenu` when one of its command items is
+/// activated. `checked` is the new state of a checkable item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MenuCommand {
+    pub command: String,
+    pub checked: Option<bool>,
+}
+
+impl Message for MenuCommand {}
+
+/// Raised by a menu popup when the item at the path, given from the top of
+/// the menu's items, is activated.
+struct ItemActivated(Vec<usize>);
+
+impl Message for ItemActivated {}
+
+/// A key combination that activates a menu item without opening the menu.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Accelerator {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl Accelerator {
+    pub fn new(key: Key, modifiers: Modifiers) -> Accelerator {
+        Accelerator {
+            key: key,
+            modifiers: modifiers,
+        }
+    }
+
+    pub fn ctrl(key: Key) -> Accelerator {
+        Accelerator::new(key, Modifiers { ctrl: true, ..Modifiers::none() })
+    }
+
+    pub fn matches(&self, key: Key, modifiers: Modifiers) -> bool {
+        self.key == key && self.modifiers == modifiers
+    }
+
+    /// The text shown next to the item, such as "Ctrl+Shift+S".
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        for &(held, name) in &[(self.modifiers.ctrl, "Ctrl+"), (self.modifiers.alt, "Alt+"),
+                               (self.modifiers.shift, "Shift+"), (self.modifiers.logo, "Super+")] {
+            if held {
+                label.push_str(name);
+            }
+        }
+        let key = match self.key {
+            Key::Letter(c) => c.to_uppercase().collect(),
+            Key::Digit(d) => d.to_string(),
+            Key::F(n) => format!("F{}", n),
+            Key::PageUp => "PgUp".to_string(),
+            Key::PageDown => "PgDn".to_string(),
+            Key::Escape => "Esc".to_string(),
+            Key::Back => "Backspace".to_string(),
+            Key::Delete => "Del".to_string(),
+            Key::Insert => "Ins".to_string(),
+            Key::Other => String::new(),
+            key => format!("{:?}", key),
+        };
+        label.push_str(&key);
+        label
+    }
+}
+
+/// An entry of a menu: a command, a submenu or a separator.
+///
+/// In labels, an '&' marks the next character as the item's mnemonic: it is
+/// underlined, and typing it while the menu is open activates the item.
+/// "&&" shows an '&'.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuItem {
+    label: String,
+    command: Option<String>,
+    accelerator: Option<Accelerator>,
+    checked: Option<bool>,
+    enabled: bool,
+    items: Vec<MenuItem>,
+    separator: bool,
+}
+
+impl MenuItem {
+    /// An item raising a `MenuCommand` with `command` when activated.
+    pub fn new<L: Into<String>, C: Into<String>>(label: L, command: C) -> MenuItem {
+        MenuItem {
+            label: label.into(),
+            command: Some(command.into()),
+            accelerator: None,
+            checked: None,
+            enabled: true,
+            items: Vec::new(),
+            separator: false,
+        }
+    }
+
+    pub fn submenu<L: Into<String>>(label: L, items: Vec<MenuItem>) -> MenuItem {
+        MenuItem {
+            command: None,
+            items: items,
+            ..MenuItem::new(label, "")
+        }
+    }
+
+    pub fn separator() -> MenuItem {
+        MenuItem {
+            command: None,
+            separator: true,
+            ..MenuItem::new("", "")
+        }
+    }
+
+    pub fn with_accelerator(mut self, accelerator: Accelerator) -> MenuItem {
+        self.accelerator = Some(accelerator);
+        self
+    }
+
+    /// Makes the item checkable: activating it toggles its check mark.
+    pub fn with_checked(mut self, checked: bool) -> MenuItem {
+        self.checked = Some(checked);
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> MenuItem {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The label as shown, without mnemonic markers.
+    pub fn text(&self) -> String {
+        parse_label(&self.label).0
+    }
+
+    pub fn mnemonic(&self) -> Option<char> {
+        let (text, index) = parse_label(&self.label);
+        index.and_then(|i| text[i..].chars().next()).and_then(|c| c.to_lowercase().next())
+    }
+
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_ref().map(|c| c.as_str())
+    }
+
+    pub fn accelerator(&self) -> Option<Accelerator> {
+        self.accelerator
+    }
+
+    /// Whether a checkable item is checked; `None` for other items.
+    pub fn checked(&self) -> Option<bool> {
+        self.checked
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_separator(&self) -> bool {
+        self.separator
+    }
+
+    /// The items of a submenu.
+    pub fn items(&self) -> &[MenuItem] {
+        &self.items
+    }
+
+    fn is_selectable(&self) -> bool {
+        self.enabled && !self.separator
+    }
+}
+
+/// Splits a label into the text shown and the byte index in it of the
+/// mnemonic, the character after the first single '&'.
+fn parse_label(label: &str) -> (String, Option<usize>) {
+    let mut text = String::new();
+    let mut mnemonic = None;
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        if c != '&' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('&') => text.push('&'),
+            Some(next) => {
+                if mnemonic.is_none() {
+                    mnemonic = Some(text.len());
+                }
+                text.push(next);
+            }
+            None => {}
+        }
+    }
+    (text, mnemonic)
+}
+
+fn item_format() -> TextFormat {
+    TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE).with_wrapping(TextWrapping::NoWrap)
+}
+
+/// Lays out a label with its mnemonic underlined.
+fn label_layout(label: &str) -> TextLayout {
+    let (text, mnemonic) = parse_label(label);
+    let style = SpanStyle::new(Font::default_font(), DEFAULT_FONT_SIZE, Color::argb(1.0, 0.0, 0.0, 0.0));
+    let mut spans = Vec::new();
+    match mnemonic {
+        Some(start) => {
+            let end = start + text[start..].chars().next().map_or(0, |c| c.len_utf8());
+            let mut underlined = style.clone();
+            underlined.underline = true;
+            if start > 0 {
+                spans.push(TextSpan::Text(text[..start].to_string(), style.clone()));
+            }
+            spans.push(TextSpan::Text(text[start..end].to_string(), underlined));
+            if end < text.len() {
+                spans.push(TextSpan::Text(text[end..].to_string(), style));
+            }
+        }
+        None => spans.push(TextSpan::Text(text, style)),
+    }
+    TextLayout::with_spans(&spans, item_format(), f32::INFINITY)
+}
+
+fn render_text(cmd: &mut RenderCommandList, layout: &TextLayout, origin: Point, color: Color) {
+    for mut run in layout.runs() {
+        run.color = color;
+        cmd.add(RenderCommand::Text(origin, run));
+    }
+    for (rect, _) in layout.decorations() {
+        cmd.add(RenderCommand::Rect(Rect::pos_size(origin + rect.pos, rect.size), color));
+    }
+}
+
+fn text_color(enabled: bool) -> Color {
+    if enabled { Color::argb(1.0, 0.1, 0.1, 0.1) } else { Color::argb(1.0, 0.6, 0.6, 0.6) }
+}
+
+fn highlight_color() -> Color {
+    Color::argb(1.0, 0.8, 0.87, 0.97)
+}
+
+fn row_height(item: &MenuItem) -> f32 {
+    if item.separator {
+        SEPARATOR_HEIGHT
+    } else {
+        item_format().line_height() + 2.0 * ITEM_PADDING.1
+    }
+}
+
+fn items_at<'a>(items: &'a [MenuItem], path: &[usize]) -> &'a [MenuItem] {
+    match path.split_first() {
+        Some((&first, rest)) => items.get(first).map_or(&[][..], |item| items_at(&item.items, rest)),
+        None => items,
+    }
+}
+
+fn item_at_mut<'a>(items: &'a mut [MenuItem], path: &[usize]) -> Option<&'a mut MenuItem> {
+    match path.split_first() {
+        Some((&first, rest)) => items.get_mut(first).and_then(|item| {
+            if rest.is_empty() { Some(item) } else { item_at_mut(&mut item.items, rest) }
+        }),
+        None => None,
+    }
+}
+
+fn find_command<'a>(items: &'a mut [MenuItem], command: &str) -> Option<&'a mut MenuItem> {
+    for item in items.iter_mut() {
+        if item.command.as_ref().map_or(false, |c| c == command) {
+            return Some(item);
+        }
+        if let Some(found) = find_command(&mut item.items, command) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// The path to the enabled command item with a matching accelerator, in
+/// enabled submenus.
+fn find_accelerator(items: &[MenuItem], key: Key, modifiers: Modifiers) -> Option<Vec<usize>> {
+    for (index, item) in items.iter().enumerate().filter(|&(_, item)| item.is_selectable()) {
+        if item.items.is_empty() && item.accelerator.map_or(false, |a| a.matches(key, modifiers)) {
+            return Some(vec![index]);
+        }
+        if let Some(mut path) = find_accelerator(&item.items, key, modifiers) {
+            path.insert(0, index);
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn mnemonic_match(items: &[MenuItem], c: char) -> Option<usize> {
+    let c = c.to_lowercase().next();
+    items.iter().position(|item| item.is_selectable() && item.mnemonic() == c)
+}
+
+/// The next selectable item after `from` in the direction of `delta`,
+/// wrapping around; from `None` it is the first or the last one.
+fn next_selectable(items: &[MenuItem], from: Option<usize>, delta: isize) -> Option<usize> {
+    let len = items.len() as isize;
+    let mut index = match from {
+        Some(index) => index as isize,
+        None if delta > 0 => -1,
+        None => len,
+    };
+    for _ in 0..len {
+        index = (index + delta + len) % len;
+        if items[index as usize].is_selectable() {
+            return Some(index as usize);
+        }
+    }
+    None
+}
+
+type SharedItems = Rc<RefCell<Vec<MenuItem>>>;
+
+/// The list of items of a menu, shown in a popup. Submenus open in popups
+/// of their own, to the right of their item.
+struct MenuPopup {
+    node: NodeWeakRef,
+    items: SharedItems,
+    path: Vec<usize>,
+    /// Whether the popup was opened from another one.
+    nested: bool,
+    highlighted: Option<usize>,
+    /// The rows of the items, in content coordinates.
+    rows: Vec<Rect>,
+    submenu: NodeWeakRef,
+    submenu_index: Option<usize>,
+}
+
+impl MenuPopup {
+    fn new(items: SharedItems, path: Vec<usize>, nested: bool) -> MenuPopup {
+        MenuPopup {
+            node: NodeWeakRef::empty(),
+            items: items,
+            path: path,
+            nested: nested,
+            highlighted: None,
+            rows: Vec::new(),
+            submenu: NodeWeakRef::empty(),
+            submenu_index: None,
+        }
+    }
+
+    fn entries(&self) -> Vec<MenuItem> {
+        items_at(&self.items.borrow(), &self.path).to_vec()
+    }
+
+    fn highlight(&mut self, index: Option<usize>) {
+        self.highlighted = index;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    fn highlight_first(&mut self) {
+        let first = next_selectable(&self.entries(), None, 1);
+        self.highlight(first);
+    }
+
+    fn submenu_node(&self) -> Option<NodeRef> {
+        self.submenu.upgrade().and_then(|popup| if popup.is_popup() { Some(popup) } else { None })
+    }
+
+    /// Opens the submenu of the item at `index`, moving the focus into it if
+    /// `focus` is set.
+    fn open_submenu(&mut self, node: &NodeRef, index: usize, focus: bool) {
+        let popup = match self.submenu_node() {
+            Some(ref popup) if self.submenu_index == Some(index) => popup.clone(),
+            _ => {
+                self.close_submenu();
+                let row = self.rows.get(index).cloned().unwrap_or(Rect::zero());
+                let anchor = Rect::pos_size(node.window_rect().pos + node.content_rect().pos + row.pos, row.size);
+                let mut path = self.path.clone();
+                path.push(index);
+                let popup = node.open_popup(Box::new(MenuPopup::new(self.items.clone(), path, true)), anchor, Placement::Right);
+                self.submenu = popup.downgrade();
+                self.submenu_index = Some(index);
+                popup
+            }
+        };
+        if focus {
+            popup.focus();
+            popup.cast_element_mut::<MenuPopup>().unwrap().highlight_first();
+        }
+    }
+
+    fn close_submenu(&mut self) {
+        if let Some(popup) = self.submenu_node() {
+            popup.close_popup();
+        }
+        self.submenu = NodeWeakRef::empty();
+        self.submenu_index = None;
+    }
+
+    /// Opens the item's submenu, or raises `ItemActivated` for a command.
+    fn activate(&mut self, node: &NodeRef, index: usize) {
+        let item = match self.entries().get(index) {
+            Some(item) if item.is_selectable() => item.clone(),
+            _ => return,
+        };
+        self.highlight(Some(index));
+        if item.items.is_empty() {
+            let mut path = self.path.clone();
+            path.push(index);
+            node.raise(Box::new(ItemActivated(path)));
+        } else {
+            self.open_submenu(node, index, true);
+        }
+    }
+
+    fn row_at(&self, node: &NodeRef, pos: Point) -> Option<usize> {
+        let pos = pos - node.content_rect().pos;
+        self.rows.iter().position(|row| row.contains(pos))
+    }
+
+    fn handle_key(&mut self, node: &NodeRef, key: Key) -> bool {
+        let entries = self.entries();
+        match key {
+            Key::Down => self.highlight(next_selectable(&entries, self.highlighted, 1)),
+            Key::Up => self.highlight(next_selectable(&entries, self.highlighted, -1)),
+            Key::Home => self.highlight(next_selectable(&entries, None, 1)),
+            Key::End => self.highlight(next_selectable(&entries, None, -1)),
+            // Unhandled, Left and Right go on to the menu bar, which moves to
+            // the next menu, and Escape to the menu's owner, which closes it.
+            Key::Right => match self.highlighted {
+                Some(index) if !entries[index].items.is_empty() && node.has_focus() => self.activate(node, index),
+                _ => return false,
+            },
+            Key::Left | Key::Escape if self.nested => node.close_popup(),
+            Key::Enter | Key::Space => {
+                if let Some(index) = self.highlighted {
+                    self.activate(node, index);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Element for MenuPopup {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[MenuPopup] Init");
+        self.node = node.downgrade();
+        node.set_layout_properties(LayoutProperties::new().with_padding(Thickness::uniform(1.0)));
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[MenuPopup] Measure");
+        let entries = self.entries();
+        let labels = entries.iter()
+            .filter(|item| !item.separator)
+            .map(|item| label_layout(&item.label).size().w)
+            .fold(0.0, f32::max);
+        let accelerators = entries.iter()
+            .filter_map(|item| item.accelerator)
+            .map(|a| TextLayout::new(&a.label(), item_format(), f32::INFINITY).size().w + ACCELERATOR_GAP)
+            .fold(0.0, f32::max);
+        let height = entries.iter().fold(0.0, |height, item| height + row_height(item));
+        Size::new(CHECK_WIDTH + labels + accelerators + ARROW_WIDTH, height)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[MenuPopup] Layout");
+        let mut top = 0.0;
+        self.rows = self.entries().iter().map(|item| {
+            let row = Rect::pos_size(Point::new(0.0, top), Size::new(container.w, row_height(item)));
+            top += row.size.h;
+            row
+        }).collect();
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[MenuPopup] Render");
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Border(Rect::from_size(node.rect().size), BorderStyle::new(Color::argb(1.0, 1.0, 1.0, 1.0))
+            .with_border(Thickness::uniform(1.0), Color::argb(1.0, 0.6, 0.6, 0.6))));
+        let origin = node.content_rect().pos;
+        for (index, (item, row)) in self.entries().iter().zip(self.rows.iter()).enumerate() {
+            let row = Rect::pos_size(origin + row.pos, row.size);
+            if item.separator {
+                let line = Rect::pos_size(Point::new(row.pos.x + CHECK_WIDTH, row.pos.y + (row.size.h / 2.0).floor()),
+                                          Size::new((row.size.w - CHECK_WIDTH - ITEM_PADDING.0).max(0.0), 1.0));
+                cmd.add(RenderCommand::Rect(line, Color::argb(1.0, 0.85, 0.85, 0.85)));
+                continue;
+            }
+            if self.highlighted == Some(index) {
+                cmd.add(RenderCommand::Rect(row, highlight_color()));
+            }
+            let color = text_color(item.enabled);
+            if item.checked == Some(true) {
+                render_mark(&mut cmd, Rect::pos_size(row.pos, Size::new(CHECK_WIDTH, row.size.h)), "\u{2713}", color);
+            }
+            let top = row.pos.y + ITEM_PADDING.1;
+            render_text(&mut cmd, &label_layout(&item.label), Point::new(row.pos.x + CHECK_WIDTH, top), color);
+            if let Some(accelerator) = item.accelerator {
+                let layout = TextLayout::new(&accelerator.label(), item_format(), f32::INFINITY);
+                render_text(&mut cmd, &layout, Point::new(row.right() - ARROW_WIDTH - layout.size().w, top), color);
+            }
+            if !item.items.is_empty() {
+                let arrow = Rect::pos_size(Point::new(row.right() - ARROW_WIDTH, row.pos.y), Size::new(ARROW_WIDTH, row.size.h));
+                render_mark(&mut cmd, arrow, "\u{25b8}", color);
+            }
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseMove(pos) => {
+                let entries = self.entries();
+                match self.row_at(&node, pos) {
+                    Some(index) if entries[index].is_selectable() => {
+                        if self.highlighted != Some(index) {
+                            self.highlight(Some(index));
+                        }
+                        if entries[index].items.is_empty() {
+                            self.close_submenu();
+                        } else {
+                            self.open_submenu(&node, index, false);
+                        }
+                    }
+                    Some(_) => {
+                        self.close_submenu();
+                        self.highlight(None);
+                    }
+                    None => {}
+                }
+                true
+            }
+            InputEvent::MouseLeave => {
+                if self.submenu_node().is_none() {
+                    self.highlight(None);
+                }
+                false
+            }
+            InputEvent::MouseDown(..) => true,
+            InputEvent::MouseUp(_, pos) => {
+                if let Some(index) = self.row_at(&node, pos) {
+                    if self.entries()[index].items.is_empty() {
+                        self.activate(&node, index);
+                    }
+                }
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) if modifiers.is_empty() => self.handle_key(&node, key),
+            InputEvent::Char(c) => match mnemonic_match(&self.entries(), c) {
+                Some(index) => {
+                    self.activate(&node, index);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+}
+
+/// The items of a menu bar or a context menu and the popup showing them.
+struct MenuRoot {
+    items: SharedItems,
+    popup: NodeWeakRef,
+    /// Focused before the popup opened, and again once it is closed.
+    restore_focus: NodeWeakRef,
+}
+
+impl MenuRoot {
+    fn new(items: Vec<MenuItem>) -> MenuRoot {
+        MenuRoot {
+            items: Rc::new(RefCell::new(items)),
+            popup: NodeWeakRef::empty(),
+            restore_focus: NodeWeakRef::empty(),
+        }
+    }
+
+    fn popup(&self) -> Option<NodeRef> {
+        self.popup.upgrade().and_then(|popup| if popup.is_popup() { Some(popup) } else { None })
+    }
+
+    /// Opens the items at `path` in a popup and focuses it.
+    fn open(&mut self, node: &NodeRef, path: Vec<usize>, anchor: Rect, placement: Placement, highlight_first: bool) {
+        self.close(node);
+        self.restore_focus = node.focused().map_or(NodeWeakRef::empty(), |f| f.downgrade());
+        let popup = node.open_popup(Box::new(MenuPopup::new(self.items.clone(), path, false)), anchor, placement);
+        popup.focus();
+        if highlight_first {
+            popup.cast_element_mut::<MenuPopup>().unwrap().highlight_first();
+        }
+        self.popup = popup.downgrade();
+        node.invalidate_render();
+    }
+
+    fn close(&mut self, node: &NodeRef) {
+        if let Some(popup) = self.popup() {
+            popup.close_popup();
+            if node.has_focus() {
+                if let Some(focus) = self.restore_focus.upgrade() {
+                    focus.focus();
+                }
+            }
+        }
+        self.popup = NodeWeakRef::empty();
+        node.invalidate_render();
+    }
+
+    /// Closes the menu, toggles the item at `path` if it is checkable, and
+    /// raises its `MenuCommand`.
+    fn activate(&mut self, node: &NodeRef, path: &[usize]) {
+        self.close(node);
+        let (command, checked) = {
+            let mut items = self.items.borrow_mut();
+            let item = match item_at_mut(&mut items, path) {
+                Some(item) => item,
+                None => return,
+            };
+            item.checked = item.checked.map(|checked| !checked);
+            (item.command.clone(), item.checked)
+        };
+        if let Some(command) = command {
+            node.raise(Box::new(MenuCommand { command: command, checked: checked }));
+        }
+    }
+
+    fn activate_accelerator(&mut self, node: &NodeRef, key: Key, modifiers: Modifiers) -> bool {
+        let path = find_accelerator(&self.items.borrow(), key, modifiers);
+        match path {
+            Some(path) => {
+                self.activate(node, &path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update<F: FnOnce(&mut MenuItem)>(&mut self, node: &NodeRef, command: &str, f: F) {
+        if let Some(item) = find_command(&mut self.items.borrow_mut(), command) {
+            f(item);
+        }
+        node.invalidate_render();
+    }
+}
+
+/// A row of menu titles, each opening its submenu below it. Items of the
+/// bar without a submenu are left out.
+///
+/// While a menu is open, hovering another title opens its menu instead, and
+/// Left and Right move to the previous and next menu. Key presses bubbling
+/// through the bar activate accelerators, and Alt with a title's mnemonic
+/// opens its menu; call `handle_shortcut` from an ancestor of the rest of
+/// the window to get the same from everywhere.
+pub struct MenuBar {
+    node: NodeWeakRef,
+    root: MenuRoot,
+    titles: Vec<Rect>,
+    hovered: Option<usize>,
+    open: Option<usize>,
+}
+
+impl MenuBar {
+    pub fn new(items: Vec<MenuItem>) -> MenuBar {
+        let items = items.into_iter().filter(|item| !item.items.is_empty()).collect();
+        MenuBar {
+            node: NodeWeakRef::empty(),
+            root: MenuRoot::new(items),
+            titles: Vec::new(),
+            hovered: None,
+            open: None,
+        }
+    }
+
+    pub fn items(&self) -> Vec<MenuItem> {
+        self.root.items.borrow().clone()
+    }
+
+    pub fn set_items(&mut self, items: Vec<MenuItem>) {
+        self.close();
+        *self.root.items.borrow_mut() = items.into_iter().filter(|item| !item.items.is_empty()).collect();
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+
+    /// Enables or disables the item raising `command`.
+    pub fn set_enabled(&mut self, command: &str, enabled: bool) {
+        if let Some(node) = self.node.upgrade() {
+            self.root.update(&node, command, |item| item.enabled = enabled);
+        }
+    }
+
+    /// Checks or unchecks the checkable item raising `command`.
+    pub fn set_checked(&mut self, command: &str, checked: bool) {
+        if let Some(node) = self.node.upgrade() {
+            self.root.update(&node, command, |item| if item.checked.is_some() { item.checked = Some(checked) });
+        }
+    }
+
+    pub fn is_checked(&self, command: &str) -> Option<bool> {
+        find_command(&mut self.root.items.borrow_mut(), command).and_then(|item| item.checked)
+    }
+
+    /// The index of the title whose menu is open.
+    pub fn open_menu_index(&self) -> Option<usize> {
+        self.root.popup().and(self.open)
+    }
+
+    /// Opens the menu of the title at `index`, with its first item
+    /// highlighted if `highlight_first` is set.
+    pub fn open_menu(&mut self, index: usize, highlight_first: bool) {
+        let node = match self.node.upgrade() {
+            Some(node) => node,
+            None => return,
+        };
+        if !self.root.items.borrow().get(index).map_or(false, |item| item.enabled) {
+            return;
+        }
+        let title = self.titles.get(index).cloned().unwrap_or(Rect::zero());
+        let anchor = Rect::pos_size(node.window_rect().pos + node.content_rect().pos + title.pos, title.size);
+        self.root.open(&node, vec![index], anchor, Placement::Below, highlight_first);
+        self.open = Some(index);
+    }
+
+    pub fn close(&mut self) {
+        if let Some(node) = self.node.upgrade() {
+            self.root.close(&node);
+        }
+        self.open = None;
+    }
+
+    /// Activates the item with a matching accelerator, or opens the menu of
+    /// the title whose mnemonic is typed with Alt. Returns whether the key
+    /// was used.
+    pub fn handle_shortcut(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        let node = match self.node.upgrade() {
+            Some(node) => node,
+            None => return false,
+        };
+        if let Key::Letter(c) = key {
+            if modifiers == (Modifiers { alt: true, ..Modifiers::none() }) {
+                let index = mnemonic_match(&self.root.items.borrow(), c);
+                if let Some(index) = index {
+                    self.open_menu(index, true);
+                    return true;
+                }
+            }
+        }
+        self.root.activate_accelerator(&node, key, modifiers)
+    }
+
+    fn title_at(&self, node: &NodeRef, pos: Point) -> Option<usize> {
+        let pos = pos - node.content_rect().pos;
+        self.titles.iter().position(|title| title.contains(pos))
+    }
+}
+
+impl Element for MenuBar {
+    fn init(&mut self, node: NodeRef) {
+        info!("[MenuBar] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[MenuBar] Measure");
+        let width = self.root.items.borrow().iter()
+            .fold(0.0, |width, item| width + label_layout(&item.label).size().w + 2.0 * TITLE_PADDING.0);
+        Size::new(width, item_format().line_height() + 2.0 * TITLE_PADDING.1)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[MenuBar] Layout");
+        let mut left = 0.0;
+        self.titles = self.root.items.borrow().iter().map(|item| {
+            let width = label_layout(&item.label).size().w + 2.0 * TITLE_PADDING.0;
+            let title = Rect::pos_size(Point::new(left, 0.0), Size::new(width, container.h));
+            left += width;
+            title
+        }).collect();
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[MenuBar] Render");
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Rect(Rect::from_size(node.rect().size), Color::argb(1.0, 0.96, 0.96, 0.96)));
+        let origin = node.content_rect().pos;
+        let open = self.open_menu_index();
+        for (index, (item, title)) in self.root.items.borrow().iter().zip(self.titles.iter()).enumerate() {
+            let title = Rect::pos_size(origin + title.pos, title.size);
+            if open == Some(index) {
+                cmd.add(RenderCommand::Rect(title, highlight_color()));
+            } else if open.is_none() && self.hovered == Some(index) && item.enabled {
+                cmd.add(RenderCommand::Rect(title, Color::argb(1.0, 0.9, 0.93, 0.98)));
+            }
+            let layout = label_layout(&item.label);
+            let top = title.pos.y + (title.size.h - layout.size().h) / 2.0;
+            render_text(&mut cmd, &layout, Point::new(title.pos.x + TITLE_PADDING.0, top), text_color(item.enabled));
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseMove(pos) => {
+                let hovered = self.title_at(&node, pos);
+                if hovered != self.hovered {
+                    self.hovered = hovered;
+                    node.invalidate_render();
+                }
+                if let (Some(open), Some(index)) = (self.open_menu_index(), hovered) {
+                    if open != index {
+                        self.open_menu(index, false);
+                    }
+                }
+                false
+            }
+            InputEvent::MouseLeave => {
+                self.hovered = None;
+                node.invalidate_render();
+                false
+            }
+            InputEvent::MouseDown(MouseButton::Left, pos) => match self.title_at(&node, pos) {
+                Some(index) => {
+                    if self.open_menu_index() == Some(index) {
+                        self.close();
+                    } else {
+                        self.open_menu(index, false);
+                    }
+                    true
+                }
+                None => false,
+            },
+            InputEvent::KeyDown(key @ Key::Left, modifiers) | InputEvent::KeyDown(key @ Key::Right, modifiers)
+                if modifiers.is_empty() && self.open_menu_index().is_some() => {
+                let delta = if key == Key::Left { -1 } else { 1 };
+                let next = next_selectable(&self.root.items.borrow(), self.open_menu_index(), delta);
+                if let Some(next) = next {
+                    self.open_menu(next, true);
+                }
+                true
+            }
+            InputEvent::KeyDown(Key::Escape, _) if self.open_menu_index().is_some() => {
+                self.close();
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) => self.handle_shortcut(key, modifiers),
+            _ => false,
+        }
+    }
+
+    fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+        match message.cast_ref::<ItemActivated>() {
+            Some(&ItemActivated(ref path)) => {
+                self.open = None;
+                self.root.activate(&node, path);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Wraps content with a menu that opens at the cursor when the content is
+/// right-clicked, or at its top left on Shift+F10. Key presses bubbling out
+/// of the content activate the menu's accelerators.
+pub struct ContextMenu {
+    node: NodeWeakRef,
+    content: Option<Box<Element>>,
+    root: MenuRoot,
+}
+
+impl ContextMenu {
+    pub fn new(content: Box<Element>, items: Vec<MenuItem>) -> ContextMenu {
+        ContextMenu {
+            node: NodeWeakRef::empty(),
+            content: Some(content),
+            root: MenuRoot::new(items),
+        }
+    }
+
+    pub fn content(&self) -> Option<NodeRef> {
+        self.node.upgrade().and_then(|node| node.children().iter().next().cloned())
+    }
+
+    pub fn items(&self) -> Vec<MenuItem> {
+        self.root.items.borrow().clone()
+    }
+
+    pub fn set_items(&mut self, items: Vec<MenuItem>) {
+        self.close();
+        *self.root.items.borrow_mut() = items;
+    }
+
+    /// Enables or disables the item raising `command`.
+    pub fn set_enabled(&mut self, command: &str, enabled: bool) {
+        if let Some(node) = self.node.upgrade() {
+            self.root.update(&node, command, |item| item.enabled = enabled);
+        }
+    }
+
+    /// Checks or unchecks the checkable item raising `command`.
+    pub fn set_checked(&mut self, command: &str, checked: bool) {
+        if let Some(node) = self.node.upgrade() {
+            self.root.update(&node, command, |item| if item.checked.is_some() { item.checked = Some(checked) });
+        }
+    }
+
+    pub fn is_checked(&self, command: &str) -> Option<bool> {
+        find_command(&mut self.root.items.borrow_mut(), command).and_then(|item| item.checked)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.root.popup().is_some()
+    }
+
+    /// Opens the menu at `pos`, in window coordinates.
+    pub fn open_at(&mut self, pos: Point) {
+        if let Some(node) = self.node.upgrade() {
+            self.root.open(&node, Vec::new(), Rect::pos_size(pos, Size::zero()), Placement::Below, false);
+        }
+    }
+
+    pub fn close(&mut self) {
+        if let Some(node) = self.node.upgrade() {
+            self.root.close(&node);
+        }
+    }
+}
+
+impl Element for ContextMenu {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[ContextMenu] Init");
+        self.node = node.downgrade();
+        if let Some(content) = self.content.take() {
+            node.add_child(content);
+        }
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[ContextMenu] Measure");
+        self.content().map_or(Size::zero(), |content| content.measure())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[ContextMenu] Layout");
+        if let Some(content) = self.content() {
+            content.arrange(Rect::from_size(container));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[ContextMenu] Render");
+        RenderCommandList::new()
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseDown(MouseButton::Right, pos) => {
+                self.open_at(node.window_rect().pos + pos);
+                true
+            }
+            InputEvent::KeyDown(Key::F(10), modifiers) if modifiers == (Modifiers { shift: true, ..Modifiers::none() }) => {
+                let pos = node.window_rect().pos;
+                self.root.open(&node, Vec::new(), Rect::pos_size(pos, Size::zero()), Placement::Below, true);
+                true
+            }
+            InputEvent::KeyDown(Key::Escape, _) if self.is_open() => {
+                self.close();
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) => self.root.activate_accelerator(&node, key, modifiers),
+            _ => false,
+        }
+    }
+
+    fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+        match message.cast_ref::<ItemActivated>() {
+            Some(&ItemActivated(ref path)) => {
+                self.root.activate(&node, path);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Panel {
+        commands: Vec<MenuCommand>,
+    }
+
+    impl Element for Panel {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+            let mut top = 0.0;
+            for child in node.children().iter() {
+                let height = child.measure().h;
+                child.arrange(Rect::pos_size(Point::new(0.0, top), Size::new(container.w, height)));
+                top += height;
+            }
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+            match message.cast_ref::<MenuCommand>() {
+                Some(command) => {
+                    self.commands.push(command.clone());
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    struct Block;
+
+    impl Element for Block {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::new(0.0, 100.0)
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {}
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn focusable(&self) -> bool {
+            true
+        }
+    }
+
+    const WINDOW: Size = Size { w: 400.0, h: 300.0 };
+
+    fn items() -> Vec<MenuItem> {
+        vec![
+            MenuItem::submenu("&File", vec![
+                MenuItem::new("&Open...", "open").with_accelerator(Accelerator::ctrl(Key::Letter('o'))),
+                MenuItem::new("&Save", "save").with_enabled(false),
+                MenuItem::separator(),
+                MenuItem::submenu("&Recent", vec![MenuItem::new("a.txt", "recent-a"), MenuItem::new("b.txt", "recent-b")]),
+                MenuItem::new("E&xit", "exit"),
+            ]),
+            MenuItem::submenu("&View", vec![
+                MenuItem::new("&Status Bar", "status-bar").with_checked(true),
+            ]),
+        ]
+    }
+
+    fn window_with(element: Box<Element>) -> (Tree, NodeRef, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Panel { commands: Vec::new() }))).unwrap();
+        let element = root.add_child(element);
+        let block = root.add_child(Box::new(Block));
+        tree.layout(WINDOW);
+        (tree, root, element, block)
+    }
+
+    fn commands(root: &NodeRef) -> Vec<(String, Option<bool>)> {
+        root.cast_element::<Panel>().unwrap().commands.iter().map(|c| (c.command.clone(), c.checked)).collect()
+    }
+
+    fn click(tree: &mut Tree, pos: Point) {
+        tree.dispatch_event(&InputEvent::MouseMove(pos));
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, pos));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, pos));
+        tree.layout(WINDOW);
+    }
+
+    fn key(tree: &mut Tree, key: Key) {
+        tree.dispatch_event(&InputEvent::KeyDown(key, Modifiers::none()));
+        tree.layout(WINDOW);
+    }
+
+    /// The center of the row of item `index` in `popup`.
+    fn row(popup: &NodeRef, index: usize) -> Point {
+        let rect = popup.window_rect();
+        let row = popup.cast_element::<MenuPopup>().unwrap().rows[index];
+        Point::new(rect.pos.x + 1.0 + row.pos.x + row.size.w / 2.0, rect.pos.y + 1.0 + row.pos.y + row.size.h / 2.0)
+    }
+
+    fn highlighted(popup: &NodeRef) -> Option<usize> {
+        popup.cast_element::<MenuPopup>().unwrap().highlighted
+    }
+
+    #[test]
+    fn labels_have_mnemonics_and_accelerators() {
+        assert_eq!(parse_label("E&xit"), ("Exit".to_string(), Some(1)));
+        assert_eq!(parse_label("Fish && &Chips"), ("Fish & Chips".to_string(), Some(7)));
+        assert_eq!(MenuItem::new("&Open", "open").mnemonic(), Some('o'));
+        assert_eq!(MenuItem::new("Open", "open").mnemonic(), None);
+
+        let ctrl_shift = Modifiers { ctrl: true, shift: true, ..Modifiers::none() };
+        assert_eq!(Accelerator::new(Key::Letter('s'), ctrl_shift).label(), "Ctrl+Shift+S");
+        assert_eq!(Accelerator::new(Key::F(5), Modifiers::none()).label(), "F5");
+        assert_eq!(Accelerator::ctrl(Key::Delete).label(), "Ctrl+Del");
+    }
+
+    #[test]
+    fn clicking_opens_menus_and_submenus_and_items_raise_commands() {
+        let (mut tree, root, bar, _) = window_with(Box::new(MenuBar::new(items())));
+        let title = bar.cast_element::<MenuBar>().unwrap().titles[0];
+
+        click(&mut tree, Point::new(title.pos.x + 5.0, title.pos.y + 5.0));
+        let file = tree.popups()[0].clone();
+        assert_eq!(file.window_rect().pos, Point::new(0.0, bar.rect().size.h));
+        assert!(file.has_focus());
+
+        click(&mut tree, row(&file, 1));
+        assert_eq!(tree.popups().len(), 1);
+
+        tree.dispatch_event(&InputEvent::MouseMove(row(&file, 3)));
+        tree.layout(WINDOW);
+        let recent = tree.popups()[1].clone();
+        let anchor = file.window_rect().inset(Thickness::uniform(1.0));
+        assert_eq!(recent.window_rect().pos.x, anchor.right());
+        assert_eq!(recent.window_rect().pos.y, anchor.pos.y + file.cast_element::<MenuPopup>().unwrap().rows[3].pos.y);
+        click(&mut tree, row(&recent, 1));
+        assert!(tree.popups().is_empty());
+        assert_eq!(commands(&root), vec![("recent-b".to_string(), None)]);
+
+        let view = bar.cast_element::<MenuBar>().unwrap().titles[1];
+        click(&mut tree, Point::new(view.pos.x + 5.0, view.pos.y + 5.0));
+        let popup = tree.popups()[0].clone();
+        click(&mut tree, row(&popup, 0));
+        assert_eq!(commands(&root)[1], ("status-bar".to_string(), Some(false)));
+        assert_eq!(bar.cast_element::<MenuBar>().unwrap().is_checked("status-bar"), Some(false));
+    }
+
+    #[test]
+    fn keyboard_navigates_menus() {
+        let (mut tree, root, bar, block) = window_with(Box::new(MenuBar::new(items())));
+        block.focus();
+
+        bar.cast_element_mut::<MenuBar>().unwrap().handle_shortcut(Key::Letter('f'), Modifiers { alt: true, ..Modifiers::none() });
+        tree.layout(WINDOW);
+        let file = tree.popups()[0].clone();
+        assert_eq!(highlighted(&file), Some(0));
+        key(&mut tree, Key::Down);
+        assert_eq!(highlighted(&file), Some(3));
+        key(&mut tree, Key::Right);
+        let recent = tree.popups()[1].clone();
+        assert!(recent.has_focus());
+        assert_eq!(highlighted(&recent), Some(0));
+        key(&mut tree, Key::Left);
+        assert_eq!(tree.popups().len(), 1);
+        assert!(file.has_focus());
+
+        key(&mut tree, Key::Right);
+        key(&mut tree, Key::Right);
+        assert_eq!(bar.cast_element::<MenuBar>().unwrap().open_menu_index(), Some(1));
+        key(&mut tree, Key::Left);
+        tree.dispatch_event(&InputEvent::Char('X'));
+        assert_eq!(commands(&root), vec![("exit".to_string(), None)]);
+        assert!(tree.popups().is_empty());
+        assert!(block.has_focus());
+
+        key(&mut tree, Key::Escape);
+        bar.cast_element_mut::<MenuBar>().unwrap().open_menu(0, false);
+        key(&mut tree, Key::Escape);
+        assert!(tree.popups().is_empty());
+        assert!(block.has_focus());
+    }
+
+    #[test]
+    fn context_menu_opens_at_the_cursor_and_handles_accelerators() {
+        let menu = ContextMenu::new(Box::new(Block), items()[0].items().to_vec());
+        let (mut tree, root, menu, _) = window_with(Box::new(menu));
+        let content = menu.cast_element::<ContextMenu>().unwrap().content().unwrap();
+        content.focus();
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Right, Point::new(30.0, 40.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Right, Point::new(30.0, 40.0)));
+        tree.layout(WINDOW);
+        let popup = tree.popups()[0].clone();
+        assert_eq!(popup.window_rect().pos, Point::new(30.0, 40.0));
+        assert_eq!(highlighted(&popup), None);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(5.0, 5.0)));
+        assert!(tree.popups().is_empty());
+
+        menu.cast_element_mut::<ContextMenu>().unwrap().open_at(Point::new(390.0, 290.0));
+        tree.layout(WINDOW);
+        let rect = tree.popups()[0].window_rect();
+        assert_eq!((rect.right(), rect.bottom()), (WINDOW.w, 290.0));
+        menu.cast_element_mut::<ContextMenu>().unwrap().close();
+
+        content.focus();
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Letter('o'), Modifiers { ctrl: true, ..Modifiers::none() }));
+        menu.cast_element_mut::<ContextMenu>().unwrap().set_enabled("open", false);
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Letter('o'), Modifiers { ctrl: true, ..Modifiers::none() }));
+        assert_eq!(commands(&root), vec![("open".to_string(), None)]);
+        assert!(tree.popups().is_empty());
+    }
+}
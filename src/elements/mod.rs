@@ -0,0 +1,21 @@
+pub mod aspect_ratio;
+pub mod border;
+pub mod button;
+pub mod combo_box;
+pub mod constraint;
+pub mod flex;
+pub mod image;
+pub mod menu;
+pub mod progress_bar;
+pub mod rich_text;
+pub mod scroll;
+pub mod slider;
+pub mod split;
+pub mod tabs;
+pub mod text_block;
+pub mod text_box;
+pub mod text_editor;
+pub mod toggle;
+pub mod tree_view;
+pub mod viewbox;
+pub mod virtualizing;
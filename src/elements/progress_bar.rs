@@ -0,0 +1,204 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use property::*;
+use std::time::{Duration, Instant};
+
+const THICKNESS: f32 = 6.0;
+const DEFAULT_LENGTH: f32 = 120.0;
+const FRAME_MS: u64 = 16;
+/// How long the indeterminate bar takes to sweep across the track.
+const SWEEP_MS: u64 = 1500;
+/// The length of the indeterminate bar, as a fraction of the track.
+const SWEEP_LENGTH: f32 = 0.3;
+
+/// Shows how far an operation has come. A determinate bar fills up to its
+/// value between `min` and `max`; an indeterminate bar sweeps across the
+/// track until the operation's length becomes known.
+pub struct ProgressBar {
+    node: NodeWeakRef,
+    orientation: Orientation,
+    min: f32,
+    max: f32,
+    value: Property<f32>,
+    indeterminate: Option<Instant>,
+    background: Color,
+    foreground: Color,
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar {
+            node: NodeWeakRef::empty(),
+            orientation: Orientation::Horizontal,
+            min: 0.0,
+            max: 100.0,
+            value: Property::new("Value", AccessType::ReadWrite, Box::new(source::Variable::new(0.0))).unwrap(),
+            indeterminate: None,
+            background: Color::argb(1.0, 0.88, 0.88, 0.88),
+            foreground: Color::argb(1.0, 0.2, 0.45, 0.8),
+        }
+    }
+
+    pub fn with_orientation(mut self, orientation: Orientation) -> ProgressBar {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn with_range(mut self, min: f32, max: f32) -> ProgressBar {
+        self.min = min;
+        self.max = max.max(min);
+        self
+    }
+
+    pub fn with_value(self, value: f32) -> ProgressBar {
+        self.value.set(value);
+        self
+    }
+
+    pub fn with_indeterminate(mut self, indeterminate: bool) -> ProgressBar {
+        self.set_indeterminate(indeterminate);
+        self
+    }
+
+    pub fn with_colors(mut self, background: Color, foreground: Color) -> ProgressBar {
+        self.background = background;
+        self.foreground = foreground;
+        self
+    }
+
+    pub fn value(&self) -> &Property<f32> {
+        &self.value
+    }
+
+    pub fn set_value(&mut self, value: f32) {
+        self.value.set(value);
+    }
+
+    pub fn is_indeterminate(&self) -> bool {
+        self.indeterminate.is_some()
+    }
+
+    pub fn set_indeterminate(&mut self, indeterminate: bool) {
+        if indeterminate != self.is_indeterminate() {
+            self.indeterminate = if indeterminate { Some(Instant::now()) } else { None };
+            if let Some(node) = self.node.upgrade() {
+                node.invalidate_render();
+            }
+        }
+    }
+
+    /// How much of the track is filled, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max > self.min {
+            ((self.value.get() - self.min) / (self.max - self.min)).max(0.0).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The part of the track the bar covers at `now`, as fractions from the
+    /// start of the track. The indeterminate bar enters at the start and
+    /// leaves at the end, so it is cut short on both.
+    fn filled(&self, now: Instant) -> (f32, f32) {
+        match self.indeterminate {
+            Some(start) => {
+                let elapsed = now - start;
+                let ms = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1000000) as u64;
+                let t = (ms % SWEEP_MS) as f32 / SWEEP_MS as f32;
+                let head = t * (1.0 + SWEEP_LENGTH);
+                ((head - SWEEP_LENGTH).max(0.0), head.min(1.0))
+            }
+            None => (0.0, self.fraction()),
+        }
+    }
+}
+
+impl Element for ProgressBar {
+    fn init(&mut self, node: NodeRef) {
+        info!("[ProgressBar] Init");
+        self.node = node.downgrade();
+        let bar = node.downgrade();
+        self.value.observe(move |_| if let Some(bar) = bar.upgrade() {
+            bar.invalidate_render();
+        });
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[ProgressBar] Measure");
+        match self.orientation {
+            Orientation::Horizontal => Size::new(DEFAULT_LENGTH, THICKNESS),
+            Orientation::Vertical => Size::new(THICKNESS, DEFAULT_LENGTH),
+        }
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[ProgressBar] Layout");
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[ProgressBar] Render");
+        let mut cmd = RenderCommandList::new();
+        let now = Instant::now();
+        let track = node.content_rect();
+        let round = CornerRadius::uniform(track.size.w.min(track.size.h) / 2.0);
+        cmd.add(RenderCommand::Border(track, BorderStyle::new(self.background).with_corner_radius(round)));
+
+        let (from, to) = self.filled(now);
+        if to > from {
+            let length = self.orientation.main(track.size);
+            // Vertical bars fill from the bottom up.
+            let bar = match self.orientation {
+                Orientation::Horizontal => Rect::pos_size(Point::new(track.pos.x + from * length, track.pos.y),
+                                                          Size::new((to - from) * length, track.size.h)),
+                Orientation::Vertical => Rect::pos_size(Point::new(track.pos.x, track.bottom() - to * length),
+                                                        Size::new(track.size.w, (to - from) * length)),
+            };
+            cmd.add(RenderCommand::Border(bar, BorderStyle::new(self.foreground).with_corner_radius(round)));
+        }
+        if self.is_indeterminate() {
+            node.invalidate_render_at(now + Duration::from_millis(FRAME_MS));
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn determinate_fills_up_to_the_value() {
+        let bar = ProgressBar::new().with_range(10.0, 20.0).with_value(12.5);
+
+        assert_eq!(bar.fraction(), 0.25);
+        assert_eq!(bar.filled(Instant::now()), (0.0, 0.25));
+        assert_eq!(ProgressBar::new().with_value(150.0).fraction(), 1.0);
+    }
+
+    #[test]
+    fn indeterminate_sweeps_across_the_track() {
+        let bar = ProgressBar::new().with_value(50.0).with_indeterminate(true);
+        let start = bar.indeterminate.unwrap();
+        let at = |ms: u64| bar.filled(start + Duration::from_millis(ms));
+
+        assert_eq!(at(0), (0.0, 0.0));
+        let (from, to) = at(SWEEP_MS / 2);
+        assert!(from > 0.0 && to < 1.0 && (to - from - SWEEP_LENGTH).abs() < 0.001);
+        assert_eq!(at(SWEEP_MS - 1).1, 1.0);
+        assert_eq!(at(SWEEP_MS), (0.0, 0.0));
+    }
+
+    #[test]
+    fn setting_the_value_repaints() {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(ProgressBar::new()))).unwrap();
+        tree.take_invalidation();
+
+        root.cast_element::<ProgressBar>().unwrap().value().set(40.0);
+
+        assert!(tree.take_invalidation().1);
+        assert_eq!(root.cast_element::<ProgressBar>().unwrap().fraction(), 0.4);
+    }
+}
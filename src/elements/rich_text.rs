@@ -0,0 +1,292 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use text::*;
+use text::layout::*;
+use elements::text_block::DEFAULT_FONT_SIZE;
+use std::f32;
+
+/// A piece of text in a `RichText`. Unset size and color come from the element.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub size: Option<f32>,
+    pub color: Option<Color>,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl Span {
+    pub fn new<T: Into<String>>(text: T) -> Span {
+        Span {
+            text: text.into(),
+            bold: false,
+            italic: false,
+            size: None,
+            color: None,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    pub fn with_bold(mut self) -> Span {
+        self.bold = true;
+        self
+    }
+
+    pub fn with_italic(mut self) -> Span {
+        self.italic = true;
+        self
+    }
+
+    pub fn with_size(mut self, size: f32) -> Span {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Span {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_underline(mut self) -> Span {
+        self.underline = true;
+        self
+    }
+
+    pub fn with_strikethrough(mut self) -> Span {
+        self.strikethrough = true;
+        self
+    }
+}
+
+enum Inline {
+    Span(Span),
+    Element(NodeWeakRef),
+}
+
+/// Text made of differently styled spans, with elements such as icons
+/// placed inline, standing on the baseline and wrapping with the words.
+/// Wraps like `TextBlock`.
+pub struct RichText {
+    node: NodeWeakRef,
+    family: FontFamily,
+    format: TextFormat,
+    inlines: Vec<Inline>,
+    layout: TextLayout,
+}
+
+impl RichText {
+    pub fn new() -> RichText {
+        RichText::with_family(FontFamily::default_family())
+    }
+
+    pub fn with_family(family: FontFamily) -> RichText {
+        let format = TextFormat::new(family.regular.clone(), DEFAULT_FONT_SIZE);
+        RichText {
+            node: NodeWeakRef::empty(),
+            layout: TextLayout::new("", format.clone(), f32::INFINITY),
+            family: family,
+            format: format,
+            inlines: Vec::new(),
+        }
+    }
+
+    /// Sets the paragraph settings, and the size and color of spans that don't set their own.
+    pub fn with_format(mut self, format: TextFormat) -> RichText {
+        self.format = format;
+        self
+    }
+
+    pub fn add_span(&mut self, span: Span) {
+        self.inlines.push(Inline::Span(span));
+        self.relayout();
+    }
+
+    /// Adds an element after the last span, returning its node.
+    pub fn add_element(&mut self, element: Box<Element>) -> NodeRef {
+        let child = self.node().add_child(element);
+        self.inlines.push(Inline::Element(child.downgrade()));
+        self.relayout();
+        child
+    }
+
+    pub fn clear(&mut self) {
+        let mut node = self.node();
+        for inline in self.inlines.drain(..) {
+            if let Inline::Element(child) = inline {
+                node.remove_child(&child.upgrade().unwrap());
+            }
+        }
+        self.relayout();
+    }
+
+    pub fn spans(&self) -> Vec<&Span> {
+        self.inlines.iter().filter_map(|inline| match *inline {
+            Inline::Span(ref span) => Some(span),
+            Inline::Element(_) => None,
+        }).collect()
+    }
+
+    /// The current layout, in content coordinates. Inline elements show in
+    /// its text as U+FFFC.
+    pub fn text_layout(&self) -> &TextLayout {
+        &self.layout
+    }
+
+    fn text_spans(&self) -> Vec<TextSpan> {
+        self.inlines.iter().map(|inline| match *inline {
+            Inline::Span(ref span) => {
+                let mut style = SpanStyle::new(self.family.face(span.bold, span.italic),
+                                               span.size.unwrap_or(self.format.size),
+                                               span.color.unwrap_or(self.format.color));
+                style.underline = span.underline;
+                style.strikethrough = span.strikethrough;
+                TextSpan::Text(span.text.clone(), style)
+            }
+            Inline::Element(ref child) => TextSpan::Object(child.upgrade().unwrap().measure()),
+        }).collect()
+    }
+
+    fn relayout(&mut self) {
+        self.layout = TextLayout::with_spans(&self.text_spans(), self.format.clone(), self.layout.max_width());
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+}
+
+impl Element for RichText {
+    fn init(&mut self, node: NodeRef) {
+        info!("[RichText] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[RichText] Measure");
+        self.layout.size()
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[RichText] Layout");
+        // Inline elements may have changed size, so the text is always set again.
+        let size = self.layout.size();
+        self.layout = TextLayout::with_spans(&self.text_spans(), self.format.clone(), container.w);
+        if self.layout.size() != size {
+            node.invalidate_layout();
+        }
+
+        let elements = self.inlines.iter().filter_map(|inline| match *inline {
+            Inline::Element(ref child) => child.upgrade(),
+            Inline::Span(_) => None,
+        });
+        for (child, rect) in elements.zip(self.layout.object_rects()) {
+            child.place(rect.unwrap_or(Rect::zero()));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[RichText] Render");
+        let mut cmd = RenderCommandList::new();
+        let origin = node.content_rect().pos;
+        for run in self.layout.runs() {
+            cmd.add(RenderCommand::Text(origin, run));
+        }
+        for (rect, color) in self.layout.decorations() {
+            cmd.add(RenderCommand::Rect(Rect::pos_size(Point::new(rect.pos.x + origin.x, rect.pos.y + origin.y), rect.size), color));
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Icon(Size);
+
+    impl Element for Icon {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            self.0
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {}
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+    }
+
+    fn rich_text() -> (Tree, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(RichText::new()))).unwrap();
+        (tree, root)
+    }
+
+    #[test]
+    fn spans_pick_faces_sizes_and_colors() {
+        let (_tree, root) = rich_text();
+        let red = Color::argb(1.0, 1.0, 0.0, 0.0);
+        {
+            let mut rich = root.cast_element_mut::<RichText>().unwrap();
+            rich.add_span(Span::new("plain "));
+            rich.add_span(Span::new("bold ").with_bold().with_color(red));
+            rich.add_span(Span::new("big").with_italic().with_size(30.0).with_underline());
+        }
+        let rich = root.cast_element::<RichText>().unwrap();
+        let runs = rich.text_layout().runs();
+        let family = FontFamily::default_family();
+
+        assert_eq!(rich.text_layout().text(), "plain bold big");
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].font.id(), family.regular.id());
+        assert_eq!(runs[1].font.id(), family.bold.id());
+        assert_eq!(runs[1].color, red);
+        assert_eq!(runs[2].font.id(), family.italic.id());
+        assert_eq!(runs[2].size, 30.0);
+        assert_eq!(rich.text_layout().decorations().len(), 1);
+    }
+
+    #[test]
+    fn inline_elements_are_placed_in_the_text_flow() {
+        let (_tree, root) = rich_text();
+        let icon = {
+            let mut rich = root.cast_element_mut::<RichText>().unwrap();
+            rich.add_span(Span::new("Ok "));
+            let icon = rich.add_element(Box::new(Icon(Size::new(12.0, 12.0))));
+            rich.add_span(Span::new(" done"));
+            icon
+        };
+
+        root.layout(Size::new(400.0, 100.0));
+
+        let rich = root.cast_element::<RichText>().unwrap();
+        let line = &rich.text_layout().lines()[0];
+        let x = FontFamily::default_family().regular.measure("Ok ", DEFAULT_FONT_SIZE).w;
+        assert!((icon.rect().pos.x - x).abs() < 0.001);
+        assert_eq!(icon.rect().size.to_tuple(), (12.0, 12.0));
+        assert_eq!(icon.rect().bottom(), line.baseline);
+    }
+
+    #[test]
+    fn clear_removes_spans_and_elements() {
+        let (_tree, root) = rich_text();
+        let mut rich = root.cast_element_mut::<RichText>().unwrap();
+        rich.add_span(Span::new("a"));
+        rich.add_element(Box::new(Icon(Size::new(4.0, 4.0))));
+
+        rich.clear();
+
+        assert!(rich.spans().is_empty());
+        assert_eq!(rich.text_layout().text(), "");
+        assert_eq!(root.children().len(), 0);
+    }
+}
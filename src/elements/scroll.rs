@@ -0,0 +1,684 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use property::*;
+use msg::*;
+use std::cell::Cell;
+use std::f32;
+use std::rc::Rc;
+
+const SCROLL_BAR_THICKNESS: f32 = 12.0;
+const MIN_THUMB_LENGTH: f32 = 16.0;
+const LINE_SIZE: f32 = 16.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScrollBarVisibility {
+    /// No scrolling along the axis; the content gets the viewport size.
+    Disabled,
+    /// The bar is shown when the content is larger than the viewport.
+    Auto,
+    Visible,
+    /// Scrolling is possible, but no bar is shown.
+    Hidden,
+}
+
+/// Raised by content that wants `rect`, in the source node's content
+/// coordinates, scrolled into view by the closest `ScrollViewer` around it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BringIntoView(pub Rect);
+
+impl Message for BringIntoView {}
+
+/// Draws a scroll bar track and thumb. Input is handled by the owning `ScrollViewer`.
+pub struct ScrollBar {
+    orientation: Orientation,
+    offset: f32,
+    extent: f32,
+    viewport: f32,
+}
+
+impl ScrollBar {
+    pub fn new(orientation: Orientation) -> ScrollBar {
+        ScrollBar {
+            orientation: orientation,
+            offset: 0.0,
+            extent: 0.0,
+            viewport: 0.0,
+        }
+    }
+
+    pub fn set_range(&mut self, offset: f32, extent: f32, viewport: f32) {
+        self.offset = offset;
+        self.extent = extent;
+        self.viewport = viewport;
+    }
+
+    fn scrollable(&self) -> f32 {
+        (self.extent - self.viewport).max(0.0)
+    }
+
+    /// Returns the start and length of the thumb along a track of length `track`.
+    pub fn thumb(&self, track: f32) -> (f32, f32) {
+        if self.scrollable() <= 0.0 {
+            return (0.0, track);
+        }
+        let len = (track * self.viewport / self.extent).max(MIN_THUMB_LENGTH).min(track);
+        let start = (track - len) * self.offset / self.scrollable();
+        (start, len)
+    }
+
+    /// The offset change caused by moving the thumb by `distance` along a track of length `track`.
+    pub fn offset_delta(&self, track: f32, distance: f32) -> f32 {
+        let (_, len) = self.thumb(track);
+        if track - len <= 0.0 {
+            0.0
+        } else {
+            distance * self.scrollable() / (track - len)
+        }
+    }
+}
+
+impl Element for ScrollBar {
+    fn init(&mut self, node: NodeRef) {
+
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(SCROLL_BAR_THICKNESS, SCROLL_BAR_THICKNESS)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        let size = node.rect().size;
+        let (start, len) = self.thumb(self.orientation.main(size));
+        let thumb = match self.orientation {
+            Orientation::Horizontal => Rect::pos_size(Point::new(start, 2.0), Size::new(len, size.h - 4.0)),
+            Orientation::Vertical => Rect::pos_size(Point::new(2.0, start), Size::new(size.w - 4.0, len)),
+        };
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Clear(Color::argb(1.0, 0.92, 0.92, 0.92)));
+        cmd.add(RenderCommand::Rect(thumb, Color::argb(1.0, 0.6, 0.6, 0.6)));
+        cmd
+    }
+}
+
+/// Clips the content and places it at the negated scroll offset.
+struct ScrollViewport {
+    offset: Point,
+    extent: Size,
+}
+
+fn scrollable_extent(node: &NodeRef) -> Option<Size> {
+    node.element_mut().as_scrollable().map(|s| s.extent())
+}
+
+impl Element for ScrollViewport {
+    fn init(&mut self, node: NodeRef) {
+
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        node.children().iter().next().map_or(Size::zero(), |c| c.measure())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        for child in node.children().iter() {
+            child.arrange(Rect::pos_size(Point::new(-self.offset.x, -self.offset.y), self.extent));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+}
+
+struct ThumbDrag {
+    orientation: Orientation,
+    start: f32,
+    start_offset: f32,
+}
+
+pub struct ScrollViewer {
+    node: NodeWeakRef,
+    viewport: NodeWeakRef,
+    horizontal_bar: NodeWeakRef,
+    vertical_bar: NodeWeakRef,
+    horizontal_offset: Property<f32>,
+    vertical_offset: Property<f32>,
+    horizontal_visibility: ScrollBarVisibility,
+    vertical_visibility: ScrollBarVisibility,
+    extent: Size,
+    viewport_size: Size,
+    max_offset: Rc<Cell<Point>>,
+    drag: Option<ThumbDrag>,
+    focusable: bool,
+}
+
+impl ScrollViewer {
+    pub fn new() -> ScrollViewer {
+        ScrollViewer::with_visibility(ScrollBarVisibility::Disabled, ScrollBarVisibility::Auto)
+    }
+
+    pub fn with_visibility(horizontal: ScrollBarVisibility, vertical: ScrollBarVisibility) -> ScrollViewer {
+        ScrollViewer {
+            node: NodeWeakRef::empty(),
+            viewport: NodeWeakRef::empty(),
+            horizontal_bar: NodeWeakRef::empty(),
+            vertical_bar: NodeWeakRef::empty(),
+            horizontal_offset: Property::new("HorizontalOffset", AccessType::ReadWrite, Box::new(source::Variable::new(0.0))).unwrap(),
+            vertical_offset: Property::new("VerticalOffset", AccessType::ReadWrite, Box::new(source::Variable::new(0.0))).unwrap(),
+            horizontal_visibility: horizontal,
+            vertical_visibility: vertical,
+            extent: Size::zero(),
+            viewport_size: Size::zero(),
+            // Offsets set before the first layout are kept until it clamps them.
+            max_offset: Rc::new(Cell::new(Point::new(f32::INFINITY, f32::INFINITY))),
+            drag: None,
+            focusable: true,
+        }
+    }
+
+    /// Whether clicking the viewer focuses it for keyboard scrolling; on by default.
+    pub fn with_focusable(mut self, focusable: bool) -> ScrollViewer {
+        self.focusable = focusable;
+        self
+    }
+
+    /// Sets the content, replacing any previous one.
+    pub fn set_content(&mut self, content: Box<Element>) -> NodeRef {
+        self.viewport_node().set_single_child(content)
+    }
+
+    pub fn content(&self) -> Option<NodeRef> {
+        self.viewport_node().children().iter().next().cloned()
+    }
+
+    /// Setting an offset clamps it to the scrollable range and relayouts the viewer.
+    pub fn horizontal_offset(&self) -> &Property<f32> {
+        &self.horizontal_offset
+    }
+
+    pub fn vertical_offset(&self) -> &Property<f32> {
+        &self.vertical_offset
+    }
+
+    /// The size of the scrolled content as of the last layout.
+    pub fn extent(&self) -> Size {
+        self.extent
+    }
+
+    /// The size of the visible area as of the last layout.
+    pub fn viewport_size(&self) -> Size {
+        self.viewport_size
+    }
+
+    pub fn offset(&self) -> Point {
+        Point::new(self.horizontal_offset.get(), self.vertical_offset.get())
+    }
+
+    /// Scrolls to `offset`, clamped to the scrollable range. Returns whether the offset changed.
+    pub fn scroll_to(&mut self, offset: Point) -> bool {
+        let clamped = self.clamp_offset(offset);
+        let old = self.offset();
+        self.store_offset(clamped);
+        clamped != old
+    }
+
+    pub fn scroll_by(&mut self, delta: Point) -> bool {
+        let offset = self.offset() + delta;
+        self.scroll_to(offset)
+    }
+
+    /// Scrolls the least amount needed to make `target`, a descendant of the content, visible.
+    pub fn scroll_into_view(&mut self, target: &NodeRef) -> bool {
+        let rect = target.window_rect();
+        self.scroll_window_rect_into_view(rect)
+    }
+
+    fn scroll_window_rect_into_view(&mut self, rect: Rect) -> bool {
+        let viewport = self.viewport_node().window_rect();
+        let rect = Rect::pos_size(rect.pos - viewport.pos, rect.size);
+        let delta = Point::new(
+            distance_into_view(rect.left(), rect.right(), self.viewport_size.w),
+            distance_into_view(rect.top(), rect.bottom(), self.viewport_size.h));
+        self.scroll_by(delta)
+    }
+
+    fn clamp_offset(&self, offset: Point) -> Point {
+        let max = self.max_offset.get();
+        Point::new(offset.x.max(0.0).min(max.x), offset.y.max(0.0).min(max.y))
+    }
+
+    fn set_extent(&mut self, extent: Size) {
+        self.extent = extent;
+        self.max_offset.set(Point::new((extent.w - self.viewport_size.w).max(0.0),
+                                       (extent.h - self.viewport_size.h).max(0.0)));
+    }
+
+    /// Sets only the offset properties that change, since each set relayouts the viewer.
+    fn store_offset(&self, offset: Point) {
+        if offset.x != self.horizontal_offset.get() {
+            self.horizontal_offset.set(offset.x);
+        }
+        if offset.y != self.vertical_offset.get() {
+            self.vertical_offset.set(offset.y);
+        }
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+
+    fn viewport_node(&self) -> NodeRef {
+        self.viewport.upgrade().unwrap()
+    }
+
+    fn bar(&self, orientation: Orientation) -> NodeRef {
+        match orientation {
+            Orientation::Horizontal => self.horizontal_bar.upgrade().unwrap(),
+            Orientation::Vertical => self.vertical_bar.upgrade().unwrap(),
+        }
+    }
+
+    fn visibility(&self, orientation: Orientation) -> ScrollBarVisibility {
+        match orientation {
+            Orientation::Horizontal => self.horizontal_visibility,
+            Orientation::Vertical => self.vertical_visibility,
+        }
+    }
+
+    /// Returns the bar under `point`, given in content coordinates.
+    fn bar_at(&self, point: Point) -> Option<Orientation> {
+        for &orientation in &[Orientation::Horizontal, Orientation::Vertical] {
+            if self.bar(orientation).rect().contains(point) {
+                return Some(orientation);
+            }
+        }
+        None
+    }
+
+    fn scroll_along(&mut self, orientation: Orientation, delta: f32) -> bool {
+        if self.visibility(orientation) == ScrollBarVisibility::Disabled {
+            return false;
+        }
+        match orientation {
+            Orientation::Horizontal => self.scroll_by(Point::new(delta, 0.0)),
+            Orientation::Vertical => self.scroll_by(Point::new(0.0, delta)),
+        }
+    }
+
+    fn press_bar(&mut self, orientation: Orientation, point: Point) {
+        let bar_node = self.bar(orientation);
+        let rect = bar_node.rect();
+        let track = orientation.main(rect.size);
+        let along = orientation.coord(point) - orientation.coord(rect.pos);
+        let (start, len) = bar_node.cast_element::<ScrollBar>().unwrap().thumb(track);
+        let offset = orientation.coord(self.offset());
+        if along < start {
+            let page = orientation.main(self.viewport_size);
+            self.scroll_along(orientation, -page);
+        } else if along >= start + len {
+            let page = orientation.main(self.viewport_size);
+            self.scroll_along(orientation, page);
+        } else {
+            self.drag = Some(ThumbDrag {
+                orientation: orientation,
+                start: orientation.coord(point),
+                start_offset: offset,
+            });
+        }
+    }
+
+    fn drag_to(&mut self, point: Point) {
+        let (orientation, start, start_offset) = match self.drag {
+            Some(ref drag) => (drag.orientation, drag.start, drag.start_offset),
+            None => return,
+        };
+        let bar_node = self.bar(orientation);
+        let track = orientation.main(bar_node.rect().size);
+        let delta = bar_node.cast_element::<ScrollBar>().unwrap()
+            .offset_delta(track, orientation.coord(point) - start);
+        let offset = self.offset();
+        match orientation {
+            Orientation::Horizontal => self.scroll_to(Point::new(start_offset + delta, offset.y)),
+            Orientation::Vertical => self.scroll_to(Point::new(offset.x, start_offset + delta)),
+        };
+    }
+
+    fn handle_key(&mut self, key: Key) -> bool {
+        let page = self.viewport_size;
+        let extent = self.extent;
+        match key {
+            Key::Up => self.scroll_along(Orientation::Vertical, -LINE_SIZE),
+            Key::Down => self.scroll_along(Orientation::Vertical, LINE_SIZE),
+            Key::Left => self.scroll_along(Orientation::Horizontal, -LINE_SIZE),
+            Key::Right => self.scroll_along(Orientation::Horizontal, LINE_SIZE),
+            Key::PageUp => self.scroll_along(Orientation::Vertical, -page.h),
+            Key::PageDown => self.scroll_along(Orientation::Vertical, page.h),
+            Key::Home => self.scroll_along(Orientation::Vertical, -extent.h),
+            Key::End => self.scroll_along(Orientation::Vertical, extent.h),
+            _ => false,
+        }
+    }
+}
+
+/// How far to scroll so that the span `start..end` lies within `0..len`,
+/// preferring to show the start of spans longer than `len`.
+fn distance_into_view(start: f32, end: f32, len: f32) -> f32 {
+    if start < 0.0 {
+        start
+    } else if end > len {
+        (end - len).min(start)
+    } else {
+        0.0
+    }
+}
+
+/// Clamps an offset property along `orientation` to the scrollable range and
+/// relayouts the viewer when it is set.
+fn observe_offset(property: &Property<f32>, orientation: Orientation, max_offset: &Rc<Cell<Point>>, node: &NodeRef) {
+    let max_offset = max_offset.clone();
+    property.coerce(move |offset| offset.max(0.0).min(orientation.coord(max_offset.get())));
+    let viewer = node.downgrade();
+    property.observe(move |_| if let Some(viewer) = viewer.upgrade() {
+        viewer.invalidate_layout();
+    });
+}
+
+impl Element for ScrollViewer {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[ScrollViewer] Init");
+        self.node = node.downgrade();
+
+        let viewport = node.add_child(Box::new(ScrollViewport {
+            offset: Point::zero(),
+            extent: Size::zero(),
+        }));
+        viewport.set_clip_to_bounds(true);
+        self.viewport = viewport.downgrade();
+
+        self.horizontal_bar = node.add_child(Box::new(ScrollBar::new(Orientation::Horizontal))).downgrade();
+        self.vertical_bar = node.add_child(Box::new(ScrollBar::new(Orientation::Vertical))).downgrade();
+
+        observe_offset(&self.horizontal_offset, Orientation::Horizontal, &self.max_offset, &node);
+        observe_offset(&self.vertical_offset, Orientation::Vertical, &self.max_offset, &node);
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[ScrollViewer] Measure");
+        let desired = self.viewport_node().measure();
+        let w = if self.vertical_visibility == ScrollBarVisibility::Visible { SCROLL_BAR_THICKNESS } else { 0.0 };
+        let h = if self.horizontal_visibility == ScrollBarVisibility::Visible { SCROLL_BAR_THICKNESS } else { 0.0 };
+        Size::new(desired.w + w, desired.h + h)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[ScrollViewer] Layout");
+        let content = self.content();
+        let logical_extent = content.as_ref().and_then(scrollable_extent);
+        let desired = match logical_extent {
+            Some(extent) => extent,
+            None => self.viewport_node().measure(),
+        };
+
+        let mut show_h = self.horizontal_visibility == ScrollBarVisibility::Visible;
+        let mut show_v = self.vertical_visibility == ScrollBarVisibility::Visible;
+        // Showing one bar shrinks the viewport, which may require the other one.
+        for _ in 0..2 {
+            let w = container.w - if show_v { SCROLL_BAR_THICKNESS } else { 0.0 };
+            let h = container.h - if show_h { SCROLL_BAR_THICKNESS } else { 0.0 };
+            if self.horizontal_visibility == ScrollBarVisibility::Auto {
+                show_h = desired.w > w;
+            }
+            if self.vertical_visibility == ScrollBarVisibility::Auto {
+                show_v = desired.h > h;
+            }
+        }
+
+        let viewport_size = Size::new(
+            (container.w - if show_v { SCROLL_BAR_THICKNESS } else { 0.0 }).max(0.0),
+            (container.h - if show_h { SCROLL_BAR_THICKNESS } else { 0.0 }).max(0.0));
+        let extent = Size::new(
+            if self.horizontal_visibility == ScrollBarVisibility::Disabled { viewport_size.w } else { desired.w.max(viewport_size.w) },
+            if self.vertical_visibility == ScrollBarVisibility::Disabled { viewport_size.h } else { desired.h.max(viewport_size.h) });
+        self.viewport_size = viewport_size;
+        self.set_extent(extent);
+
+        let offset = self.clamp_offset(self.offset());
+        self.store_offset(offset);
+
+        let viewport = self.viewport_node();
+        {
+            let mut element = viewport.cast_element_mut::<ScrollViewport>().unwrap();
+            if logical_extent.is_some() {
+                element.offset = Point::zero();
+                element.extent = viewport_size;
+            } else {
+                element.offset = offset;
+                element.extent = extent;
+            }
+        }
+        if let Some(ref content) = content {
+            if let Some(scrollable) = content.element_mut().as_scrollable() {
+                scrollable.set_offset(offset);
+            }
+        }
+        viewport.arrange(Rect::from_size(viewport_size));
+
+        // Content that scrolls itself may refine its extent while laying out.
+        if let Some(extent) = content.as_ref().and_then(scrollable_extent) {
+            self.set_extent(Size::new(extent.w.max(viewport_size.w), extent.h.max(viewport_size.h)));
+        }
+        let extent = self.extent;
+        let clamped = self.clamp_offset(offset);
+        if clamped != offset {
+            self.store_offset(clamped);
+            if let Some(ref content) = content {
+                if let Some(scrollable) = content.element_mut().as_scrollable() {
+                    scrollable.set_offset(clamped);
+                }
+            }
+            viewport.arrange(Rect::from_size(viewport_size));
+        }
+        let offset = clamped;
+
+        let horizontal = self.bar(Orientation::Horizontal);
+        horizontal.cast_element_mut::<ScrollBar>().unwrap().set_range(offset.x, extent.w, viewport_size.w);
+        horizontal.arrange(if show_h {
+            Rect::pos_size(Point::new(0.0, viewport_size.h), Size::new(viewport_size.w, SCROLL_BAR_THICKNESS))
+        } else {
+            Rect::zero()
+        });
+
+        let vertical = self.bar(Orientation::Vertical);
+        vertical.cast_element_mut::<ScrollBar>().unwrap().set_range(offset.y, extent.h, viewport_size.h);
+        vertical.arrange(if show_v {
+            Rect::pos_size(Point::new(viewport_size.w, 0.0), Size::new(SCROLL_BAR_THICKNESS, viewport_size.h))
+        } else {
+            Rect::zero()
+        });
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let content_origin = node.content_rect().pos;
+        match *event {
+            InputEvent::MouseWheel(delta, _) => {
+                let horizontal = self.scroll_along(Orientation::Horizontal, -delta.x);
+                let vertical = self.scroll_along(Orientation::Vertical, -delta.y);
+                horizontal || vertical
+            }
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                let pos = pos - content_origin;
+                match self.bar_at(pos) {
+                    Some(orientation) => {
+                        self.press_bar(orientation, pos);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            InputEvent::MouseMove(pos) if self.drag.is_some() => {
+                self.drag_to(pos - content_origin);
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, _) if self.drag.is_some() => {
+                self.drag = None;
+                true
+            }
+            InputEvent::KeyDown(key, _) => self.handle_key(key),
+            _ => false,
+        }
+    }
+
+    fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+        match message.cast_ref::<BringIntoView>() {
+            Some(&BringIntoView(rect)) => {
+                let rect = source.content_to_window().apply_rect(rect);
+                self.scroll_window_rect_into_view(rect);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        self.focusable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree::testing::SizedElement;
+
+    fn viewer_with_content(content: Size, window: Size) -> (Tree, NodeRef) {
+        let mut tree = Tree::new();
+        let viewer = tree.set_root(Some(Box::new(ScrollViewer::new()))).unwrap();
+        viewer.cast_element_mut::<ScrollViewer>().unwrap().set_content(Box::new(SizedElement::new(content)));
+        viewer.layout(window);
+        (tree, viewer)
+    }
+
+    /// Lays the tree out if, and only if, it asked for it, as the window does.
+    fn relayout(tree: &Tree, window: Size) {
+        if tree.take_invalidation().0 {
+            tree.layout(window);
+        }
+    }
+
+    #[test]
+    fn thumb_is_proportional_to_viewport() {
+        let mut bar = ScrollBar::new(Orientation::Vertical);
+        bar.set_range(150.0, 400.0, 100.0);
+
+        assert_eq!(bar.thumb(200.0), (75.0, 50.0));
+        assert_eq!(bar.offset_delta(200.0, 15.0), 30.0);
+    }
+
+    #[test]
+    fn thumb_has_minimum_length() {
+        let mut bar = ScrollBar::new(Orientation::Vertical);
+        bar.set_range(0.0, 100000.0, 100.0);
+
+        assert_eq!(bar.thumb(200.0).1, MIN_THUMB_LENGTH);
+    }
+
+    #[test]
+    fn vertical_bar_appears_when_content_overflows() {
+        let (_tree, viewer) = viewer_with_content(Size::new(50.0, 1000.0), Size::new(100.0, 200.0));
+        let viewer = viewer.cast_element::<ScrollViewer>().unwrap();
+
+        assert_eq!(viewer.viewport_size(), Size::new(100.0 - SCROLL_BAR_THICKNESS, 200.0));
+        assert_eq!(viewer.extent(), Size::new(100.0 - SCROLL_BAR_THICKNESS, 1000.0));
+    }
+
+    #[test]
+    fn wheel_scrolls_and_clamps() {
+        let (mut tree, viewer) = viewer_with_content(Size::new(50.0, 1000.0), Size::new(100.0, 200.0));
+
+        assert!(tree.dispatch_event(&InputEvent::MouseWheel(Point::new(0.0, -40.0), Point::new(10.0, 10.0))));
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get(), 40.0);
+
+        tree.dispatch_event(&InputEvent::MouseWheel(Point::new(0.0, -5000.0), Point::new(10.0, 10.0)));
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get(), 800.0);
+
+        assert!(!tree.dispatch_event(&InputEvent::MouseWheel(Point::new(0.0, -40.0), Point::new(10.0, 10.0))));
+        assert_eq!(tree.take_invalidation(), (true, true));
+    }
+
+    #[test]
+    fn setting_an_offset_clamps_it_and_moves_the_content() {
+        let (tree, viewer) = viewer_with_content(Size::new(50.0, 1000.0), Size::new(100.0, 200.0));
+        tree.take_invalidation();
+
+        viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().set(300.0);
+        relayout(&tree, Size::new(100.0, 200.0));
+
+        let content = viewer.cast_element::<ScrollViewer>().unwrap().content().unwrap();
+        assert_eq!(content.window_rect().pos, Point::new(0.0, -300.0));
+
+        viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().set(5000.0);
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get(), 800.0);
+        relayout(&tree, Size::new(100.0, 200.0));
+        assert_eq!(content.window_rect().pos, Point::new(0.0, -800.0));
+    }
+
+    #[test]
+    fn dragging_thumb_scrolls() {
+        let (mut tree, viewer) = viewer_with_content(Size::new(50.0, 400.0), Size::new(100.0, 100.0));
+        // The thumb is 25 pixels long at the top of a 100 pixel track; 75 pixels of
+        // track cover 300 pixels of content.
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(95.0, 10.0)));
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(95.0, 40.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, Point::new(95.0, 40.0)));
+
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get(), 120.0);
+    }
+
+    #[test]
+    fn keyboard_scrolls_focused_viewer() {
+        let (mut tree, viewer) = viewer_with_content(Size::new(50.0, 1000.0), Size::new(100.0, 200.0));
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(10.0, 10.0)));
+
+        tree.dispatch_event(&InputEvent::KeyDown(Key::PageDown, Modifiers::none()));
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Down, Modifiers::none()));
+
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get(), 200.0 + LINE_SIZE);
+    }
+
+    #[test]
+    fn scroll_into_view_reveals_descendant() {
+        let mut tree = Tree::new();
+        let viewer = tree.set_root(Some(Box::new(ScrollViewer::new()))).unwrap();
+        let mut content = viewer.cast_element_mut::<ScrollViewer>().unwrap().set_content(Box::new(SizedElement::new(Size::new(50.0, 1000.0))));
+        let target = content.add_child(Box::new(SizedElement::new(Size::zero())));
+        viewer.layout(Size::new(100.0, 200.0));
+        target.arrange(Rect::pos_size(Point::new(0.0, 500.0), Size::new(50.0, 30.0)));
+
+        assert!(viewer.cast_element_mut::<ScrollViewer>().unwrap().scroll_into_view(&target));
+
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get(), 330.0);
+    }
+
+    #[test]
+    fn bring_into_view_messages_scroll_the_viewer() {
+        let mut tree = Tree::new();
+        let viewer = tree.set_root(Some(Box::new(ScrollViewer::new()))).unwrap();
+        let content = viewer.cast_element_mut::<ScrollViewer>().unwrap().set_content(Box::new(SizedElement::new(Size::new(50.0, 1000.0))));
+        viewer.layout(Size::new(100.0, 200.0));
+
+        content.raise(Box::new(BringIntoView(Rect::pos_size(Point::new(0.0, 600.0), Size::new(10.0, 20.0)))));
+        tree.dispatch_messages();
+
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get(), 420.0);
+    }
+}
@@ -0,0 +1,725 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use msg::*;
+use property::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+const TRACK_THICKNESS: f32 = 4.0;
+const THUMB_SIZE: f32 = 14.0;
+const TICK_LENGTH: f32 = 4.0;
+const TICK_SPACING: f32 = 2.0;
+const DEFAULT_LENGTH: f32 = 120.0;
+
+/// Raised by a `Slider` when the user changes its value.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ValueChanged(pub f32);
+
+impl Message for ValueChanged {}
+
+/// Raised by a `RangeSlider` when the user moves either thumb.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RangeChanged(pub f32, pub f32);
+
+impl Message for RangeChanged {}
+
+/// The values a slider can take: `min` to `max`, in multiples of `step`
+/// from `min` unless `step` is zero. Ticks are drawn every `tick_frequency`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SliderRange {
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub tick_frequency: Option<f32>,
+}
+
+impl SliderRange {
+    pub fn new(min: f32, max: f32) -> SliderRange {
+        SliderRange {
+            min: min,
+            max: max.max(min),
+            step: 0.0,
+            tick_frequency: None,
+        }
+    }
+
+    pub fn with_step(mut self, step: f32) -> SliderRange {
+        self.step = step.max(0.0);
+        self
+    }
+
+    pub fn with_ticks(mut self, frequency: f32) -> SliderRange {
+        self.tick_frequency = if frequency > 0.0 { Some(frequency) } else { None };
+        self
+    }
+
+    /// `value` clamped to the range and snapped to the nearest step.
+    pub fn coerce(&self, value: f32) -> f32 {
+        let value = value.max(self.min).min(self.max);
+        if self.step > 0.0 {
+            let snapped = self.min + ((value - self.min) / self.step).round() * self.step;
+            if snapped > self.max { snapped - self.step } else { snapped }
+        } else {
+            value
+        }
+    }
+
+    /// How far `value` is from `min` to `max`, from 0 to 1.
+    pub fn fraction(&self, value: f32) -> f32 {
+        if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).max(0.0).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn value_at(&self, fraction: f32) -> f32 {
+        self.coerce(self.min + fraction * (self.max - self.min))
+    }
+
+    /// The values ticks are drawn at, `min` and `max` included.
+    pub fn ticks(&self) -> Vec<f32> {
+        let frequency = match self.tick_frequency {
+            Some(frequency) => frequency,
+            None => return Vec::new(),
+        };
+        let count = ((self.max - self.min) / frequency).floor() as usize;
+        let mut ticks: Vec<f32> = (0..count + 1).map(|i| self.min + i as f32 * frequency).collect();
+        if ticks.last().map_or(true, |&last| last < self.max) {
+            ticks.push(self.max);
+        }
+        ticks
+    }
+
+    /// The change of the arrow keys.
+    fn small_change(&self) -> f32 {
+        if self.step > 0.0 { self.step } else { (self.max - self.min) / 100.0 }
+    }
+
+    /// The change of Page Up and Page Down, at least one step.
+    fn large_change(&self) -> f32 {
+        ((self.max - self.min) / 10.0).max(self.step)
+    }
+
+    /// The value a key moves `value` to, if it is a key sliders handle.
+    fn key_target(&self, value: f32, key: Key) -> Option<f32> {
+        let target = match key {
+            Key::Left | Key::Down => value - self.small_change(),
+            Key::Right | Key::Up => value + self.small_change(),
+            Key::PageDown => value - self.large_change(),
+            Key::PageUp => value + self.large_change(),
+            Key::Home => self.min,
+            Key::End => self.max,
+            _ => return None,
+        };
+        Some(self.coerce(target))
+    }
+}
+
+/// Geometry and drawing shared by the sliders. The track runs from left to
+/// right, or from bottom to top when vertical, leaving room for half a
+/// thumb at both ends.
+#[derive(Copy, Clone)]
+struct Track {
+    orientation: Orientation,
+    range: SliderRange,
+}
+
+impl Track {
+    fn measure(&self) -> Size {
+        let cross = THUMB_SIZE + if self.range.tick_frequency.is_some() { TICK_SPACING + TICK_LENGTH } else { 0.0 };
+        match self.orientation {
+            Orientation::Horizontal => Size::new(DEFAULT_LENGTH, cross),
+            Orientation::Vertical => Size::new(cross, DEFAULT_LENGTH),
+        }
+    }
+
+    fn length(&self, content: Rect) -> f32 {
+        (self.orientation.main(content.size) - THUMB_SIZE).max(0.0)
+    }
+
+    /// The center of the thumb for `value`.
+    fn point(&self, content: Rect, value: f32) -> Point {
+        let along = self.range.fraction(value) * self.length(content);
+        let half = THUMB_SIZE / 2.0;
+        match self.orientation {
+            Orientation::Horizontal => Point::new(content.pos.x + half + along, content.pos.y + half),
+            Orientation::Vertical => Point::new(content.pos.x + half, content.bottom() - half - along),
+        }
+    }
+
+    fn value_at(&self, content: Rect, pos: Point) -> f32 {
+        let length = self.length(content);
+        if length <= 0.0 {
+            return self.range.min;
+        }
+        let half = THUMB_SIZE / 2.0;
+        let along = match self.orientation {
+            Orientation::Horizontal => pos.x - content.pos.x - half,
+            Orientation::Vertical => content.bottom() - half - pos.y,
+        };
+        self.range.value_at(along / length)
+    }
+
+    fn thumb_rect(&self, content: Rect, value: f32) -> Rect {
+        let center = self.point(content, value);
+        let half = THUMB_SIZE / 2.0;
+        Rect::pos_size(Point::new(center.x - half, center.y - half), Size::new(THUMB_SIZE, THUMB_SIZE))
+    }
+
+    /// A bar across the track between the centers of two thumb positions.
+    fn segment(&self, content: Rect, from: f32, to: f32) -> Rect {
+        let (a, b) = (self.point(content, from), self.point(content, to));
+        let half = TRACK_THICKNESS / 2.0;
+        match self.orientation {
+            Orientation::Horizontal => Rect::pos_size(Point::new(a.x, a.y - half), Size::new(b.x - a.x, TRACK_THICKNESS)),
+            Orientation::Vertical => Rect::pos_size(Point::new(a.x - half, b.y), Size::new(TRACK_THICKNESS, a.y - b.y)),
+        }
+    }
+
+    /// Draws the track with `filled` highlighted, and the ticks.
+    fn render(&self, cmd: &mut RenderCommandList, content: Rect, filled: (f32, f32), enabled: bool) {
+        let round = CornerRadius::uniform(TRACK_THICKNESS / 2.0);
+        let track = self.segment(content, self.range.min, self.range.max);
+        cmd.add(RenderCommand::Border(track, BorderStyle::new(Color::argb(1.0, 0.82, 0.82, 0.82)).with_corner_radius(round)));
+        let fill = if enabled { Color::argb(1.0, 0.2, 0.45, 0.8) } else { Color::argb(1.0, 0.7, 0.7, 0.7) };
+        cmd.add(RenderCommand::Border(self.segment(content, filled.0, filled.1), BorderStyle::new(fill).with_corner_radius(round)));
+
+        let tick_color = Color::argb(1.0, 0.55, 0.55, 0.55);
+        for tick in self.range.ticks() {
+            let center = self.point(content, tick);
+            let offset = THUMB_SIZE / 2.0 + TICK_SPACING;
+            let rect = match self.orientation {
+                Orientation::Horizontal => Rect::pos_size(Point::new(center.x - 0.5, center.y + offset), Size::new(1.0, TICK_LENGTH)),
+                Orientation::Vertical => Rect::pos_size(Point::new(center.x + offset, center.y - 0.5), Size::new(TICK_LENGTH, 1.0)),
+            };
+            cmd.add(RenderCommand::Rect(rect, tick_color));
+        }
+    }
+
+    fn render_thumb(&self, cmd: &mut RenderCommandList, content: Rect, value: f32, hot: bool, enabled: bool, focused: bool) {
+        let rect = self.thumb_rect(content, value);
+        let border = if !enabled {
+            Color::argb(1.0, 0.75, 0.75, 0.75)
+        } else if hot {
+            Color::argb(1.0, 0.15, 0.35, 0.65)
+        } else {
+            Color::argb(1.0, 0.2, 0.45, 0.8)
+        };
+        let width = if focused { 2.0 } else { 1.0 };
+        cmd.add(RenderCommand::Border(rect, BorderStyle::new(Color::argb(1.0, 1.0, 1.0, 1.0))
+            .with_border(Thickness::uniform(width), border)
+            .with_corner_radius(CornerRadius::uniform(THUMB_SIZE / 2.0))));
+    }
+}
+
+fn float_property(name: &str, value: f32) -> Property<f32> {
+    Property::new(name, AccessType::ReadWrite, Box::new(source::Variable::new(value))).unwrap()
+}
+
+/// Repaints `node` each time `property` is set.
+fn repaint_on_set(property: &Property<f32>, node: &NodeRef) {
+    let node = node.downgrade();
+    property.observe(move |_| if let Some(node) = node.upgrade() {
+        node.invalidate_render();
+    });
+}
+
+/// Picks a value from a range by dragging a thumb along a track. Clicking
+/// the track moves the thumb there. The arrow keys change the value by a
+/// step, Page Up and Page Down by a tenth of the range, and Home and End
+/// go to the ends.
+pub struct Slider {
+    node: NodeWeakRef,
+    track: Track,
+    value: Property<f32>,
+    enabled: bool,
+    hovered: bool,
+    dragging: bool,
+}
+
+impl Slider {
+    pub fn new(range: SliderRange) -> Slider {
+        let value = float_property("Value", range.min);
+        value.coerce(move |value| range.coerce(value));
+        Slider {
+            node: NodeWeakRef::empty(),
+            track: Track {
+                orientation: Orientation::Horizontal,
+                range: range,
+            },
+            value: value,
+            enabled: true,
+            hovered: false,
+            dragging: false,
+        }
+    }
+
+    pub fn with_orientation(mut self, orientation: Orientation) -> Slider {
+        self.track.orientation = orientation;
+        self
+    }
+
+    pub fn with_value(self, value: f32) -> Slider {
+        self.value.set(value);
+        self
+    }
+
+    pub fn range(&self) -> SliderRange {
+        self.track.range
+    }
+
+    /// Changes the range, coercing the value into it.
+    pub fn set_range(&mut self, range: SliderRange) {
+        self.track.range = range;
+        self.value.coerce(move |value| range.coerce(value));
+        let value = self.value.get();
+        self.set_value(value);
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+
+    /// The value, coerced into the range when set.
+    pub fn value(&self) -> &Property<f32> {
+        &self.value
+    }
+
+    pub fn set_value(&mut self, value: f32) {
+        self.value.set(value);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.dragging = false;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    /// Sets the value on behalf of the user, raising `ValueChanged` if it changed.
+    fn change(&mut self, node: &NodeRef, value: f32) {
+        let value = self.track.range.coerce(value);
+        if value != self.value.get() {
+            self.set_value(value);
+            node.raise(Box::new(ValueChanged(value)));
+        }
+    }
+}
+
+impl Element for Slider {
+    fn init(&mut self, node: NodeRef) {
+        info!("[Slider] Init");
+        self.node = node.downgrade();
+        repaint_on_set(&self.value, &node);
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[Slider] Measure");
+        self.track.measure()
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[Slider] Layout");
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[Slider] Render");
+        let mut cmd = RenderCommandList::new();
+        let content = node.content_rect();
+        let value = self.track.range.coerce(self.value.get());
+        self.track.render(&mut cmd, content, (self.track.range.min, value), self.enabled);
+        self.track.render_thumb(&mut cmd, content, value, self.hovered || self.dragging, self.enabled, node.has_focus());
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseEnter | InputEvent::MouseLeave => {
+                self.hovered = *event == InputEvent::MouseEnter;
+                node.invalidate_render();
+                false
+            }
+            _ if !self.enabled => false,
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                self.dragging = true;
+                let value = self.track.value_at(node.content_rect(), pos);
+                self.change(&node, value);
+                true
+            }
+            InputEvent::MouseMove(pos) if self.dragging => {
+                let value = self.track.value_at(node.content_rect(), pos);
+                self.change(&node, value);
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, _) if self.dragging => {
+                self.dragging = false;
+                node.invalidate_render();
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) if modifiers.is_empty() => {
+                match self.track.range.key_target(self.value.get(), key) {
+                    Some(value) => {
+                        self.change(&node, value);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Thumb {
+    Lower,
+    Upper,
+}
+
+/// Picks a range of values with two thumbs that can't cross. Clicking the
+/// track moves the closer thumb there; the keys move the thumb last used,
+/// and Tab with Ctrl switches between them.
+pub struct RangeSlider {
+    node: NodeWeakRef,
+    track: Track,
+    lower: Property<f32>,
+    upper: Property<f32>,
+    active: Thumb,
+    enabled: bool,
+    hovered: bool,
+    dragging: bool,
+}
+
+impl RangeSlider {
+    pub fn new(range: SliderRange) -> RangeSlider {
+        let (lower, upper) = (float_property("LowerValue", range.min), float_property("UpperValue", range.max));
+        // Each value is kept on its side of the other, which the cell mirrors.
+        let values = Rc::new(Cell::new((range.min, range.max)));
+        let (upper_bound, lower_bound) = (values.clone(), values.clone());
+        lower.coerce(move |value| range.coerce(value).min(upper_bound.get().1));
+        upper.coerce(move |value| range.coerce(value).max(lower_bound.get().0));
+        let (lower_values, upper_values) = (values.clone(), values);
+        lower.observe(move |&lower| lower_values.set((lower, lower_values.get().1)));
+        upper.observe(move |&upper| upper_values.set((upper_values.get().0, upper)));
+        RangeSlider {
+            node: NodeWeakRef::empty(),
+            track: Track {
+                orientation: Orientation::Horizontal,
+                range: range,
+            },
+            lower: lower,
+            upper: upper,
+            active: Thumb::Lower,
+            enabled: true,
+            hovered: false,
+            dragging: false,
+        }
+    }
+
+    pub fn with_orientation(mut self, orientation: Orientation) -> RangeSlider {
+        self.track.orientation = orientation;
+        self
+    }
+
+    pub fn with_values(mut self, lower: f32, upper: f32) -> RangeSlider {
+        self.set_values(lower, upper);
+        self
+    }
+
+    pub fn range(&self) -> SliderRange {
+        self.track.range
+    }
+
+    /// The lower value, coerced into the range and up to the upper value when set.
+    pub fn lower_value(&self) -> &Property<f32> {
+        &self.lower
+    }
+
+    /// The upper value, coerced into the range and down to the lower value when set.
+    pub fn upper_value(&self) -> &Property<f32> {
+        &self.upper
+    }
+
+    /// Sets both values, coerced into the range and swapped if out of order.
+    pub fn set_values(&mut self, lower: f32, upper: f32) {
+        let (lower, upper) = (lower.min(upper), lower.max(upper));
+        // The value moving away from the other goes first, so neither stops at the other.
+        if self.track.range.coerce(lower) > self.upper.get() {
+            self.upper.set(upper);
+            self.lower.set(lower);
+        } else {
+            self.lower.set(lower);
+            self.upper.set(upper);
+        }
+    }
+
+    /// The thumb the keyboard moves.
+    pub fn active_thumb(&self) -> Thumb {
+        self.active
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.dragging = false;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    fn value(&self, thumb: Thumb) -> f32 {
+        match thumb {
+            Thumb::Lower => self.lower.get(),
+            Thumb::Upper => self.upper.get(),
+        }
+    }
+
+    /// Moves the active thumb on behalf of the user, stopping at the other
+    /// one, and raises `RangeChanged` if it moved.
+    fn change(&mut self, node: &NodeRef, value: f32) {
+        let value = self.track.range.coerce(value);
+        let (lower, upper) = match self.active {
+            Thumb::Lower => (value.min(self.upper.get()), self.upper.get()),
+            Thumb::Upper => (self.lower.get(), value.max(self.lower.get())),
+        };
+        if (lower, upper) != (self.lower.get(), self.upper.get()) {
+            self.set_values(lower, upper);
+            node.raise(Box::new(RangeChanged(lower, upper)));
+        }
+    }
+
+    /// The thumb closer to `value`; the one on the side of `value` when they overlap.
+    fn closer_thumb(&self, value: f32) -> Thumb {
+        let (lower, upper) = (self.lower.get(), self.upper.get());
+        if value > upper || (value - lower).abs() > (upper - value).abs() {
+            Thumb::Upper
+        } else if value < lower || lower < upper {
+            Thumb::Lower
+        } else {
+            self.active
+        }
+    }
+}
+
+impl Element for RangeSlider {
+    fn init(&mut self, node: NodeRef) {
+        info!("[RangeSlider] Init");
+        self.node = node.downgrade();
+        repaint_on_set(&self.lower, &node);
+        repaint_on_set(&self.upper, &node);
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[RangeSlider] Measure");
+        self.track.measure()
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[RangeSlider] Layout");
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[RangeSlider] Render");
+        let mut cmd = RenderCommandList::new();
+        let content = node.content_rect();
+        let (lower, upper) = (self.lower.get(), self.upper.get());
+        self.track.render(&mut cmd, content, (lower, upper), self.enabled);
+        let focused = node.has_focus();
+        // The active thumb is drawn last, on top of the other when they overlap.
+        let thumbs = match self.active {
+            Thumb::Lower => [Thumb::Upper, Thumb::Lower],
+            Thumb::Upper => [Thumb::Lower, Thumb::Upper],
+        };
+        for &thumb in &thumbs {
+            let active = thumb == self.active;
+            let hot = active && (self.hovered || self.dragging);
+            self.track.render_thumb(&mut cmd, content, self.value(thumb), hot, self.enabled, focused && active);
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseEnter | InputEvent::MouseLeave => {
+                self.hovered = *event == InputEvent::MouseEnter;
+                node.invalidate_render();
+                false
+            }
+            _ if !self.enabled => false,
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                let value = self.track.value_at(node.content_rect(), pos);
+                self.active = self.closer_thumb(value);
+                self.dragging = true;
+                self.change(&node, value);
+                node.invalidate_render();
+                true
+            }
+            InputEvent::MouseMove(pos) if self.dragging => {
+                let value = self.track.value_at(node.content_rect(), pos);
+                self.change(&node, value);
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, _) if self.dragging => {
+                self.dragging = false;
+                node.invalidate_render();
+                true
+            }
+            InputEvent::KeyDown(Key::Tab, modifiers) if modifiers.ctrl => {
+                self.active = match self.active {
+                    Thumb::Lower => Thumb::Upper,
+                    Thumb::Upper => Thumb::Lower,
+                };
+                node.invalidate_render();
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) if modifiers.is_empty() => {
+                let value = self.value(self.active);
+                match self.track.range.key_target(value, key) {
+                    Some(value) => {
+                        self.change(&node, value);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slider<E: Element + 'static>(element: E, size: Size) -> (Tree, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(element))).unwrap();
+        root.layout(size);
+        root.focus();
+        (tree, root)
+    }
+
+    fn key(tree: &mut Tree, key: Key) {
+        tree.dispatch_event(&InputEvent::KeyDown(key, Modifiers::none()));
+    }
+
+    fn value(node: &NodeRef) -> f32 {
+        node.cast_element::<Slider>().unwrap().value().get()
+    }
+
+    fn values(node: &NodeRef) -> (f32, f32) {
+        let slider = node.cast_element::<RangeSlider>().unwrap();
+        (slider.lower_value().get(), slider.upper_value().get())
+    }
+
+    #[test]
+    fn range_coerces_to_steps_and_lists_ticks() {
+        let range = SliderRange::new(0.0, 10.0).with_step(3.0).with_ticks(4.0);
+
+        assert_eq!(range.coerce(4.4), 3.0);
+        assert_eq!(range.coerce(4.6), 6.0);
+        assert_eq!(range.coerce(9.9), 9.0);
+        assert_eq!(range.coerce(-5.0), 0.0);
+        assert_eq!(range.ticks(), vec![0.0, 4.0, 8.0, 10.0]);
+        assert_eq!(range.fraction(2.5), 0.25);
+    }
+
+    #[test]
+    fn keys_step_through_the_range() {
+        let (mut tree, node) = slider(Slider::new(SliderRange::new(0.0, 100.0).with_step(5.0)).with_value(50.0), Size::new(200.0, 20.0));
+
+        key(&mut tree, Key::Right);
+        assert_eq!(value(&node), 55.0);
+        key(&mut tree, Key::Down);
+        key(&mut tree, Key::PageDown);
+        assert_eq!(value(&node), 40.0);
+        key(&mut tree, Key::End);
+        assert_eq!(value(&node), 100.0);
+        key(&mut tree, Key::Up);
+        assert_eq!(value(&node), 100.0);
+    }
+
+    #[test]
+    fn clicking_and_dragging_follow_the_pointer() {
+        let (mut tree, node) = slider(Slider::new(SliderRange::new(0.0, 10.0).with_step(1.0)), Size::new(THUMB_SIZE + 100.0, 20.0));
+        let at = |x: f32| Point::new(THUMB_SIZE / 2.0 + x, 5.0);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, at(31.0)));
+        assert_eq!(value(&node), 3.0);
+        tree.dispatch_event(&InputEvent::MouseMove(at(500.0)));
+        assert_eq!(value(&node), 10.0);
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, at(500.0)));
+        tree.dispatch_event(&InputEvent::MouseMove(at(0.0)));
+
+        assert_eq!(value(&node), 10.0);
+    }
+
+    #[test]
+    fn vertical_sliders_grow_upwards() {
+        let (mut tree, node) = slider(Slider::new(SliderRange::new(0.0, 1.0)).with_orientation(Orientation::Vertical), Size::new(20.0, THUMB_SIZE + 100.0));
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(5.0, THUMB_SIZE / 2.0 + 25.0)));
+
+        assert_eq!(value(&node), 0.75);
+        assert_eq!(node.measure().w, THUMB_SIZE);
+    }
+
+    #[test]
+    fn range_slider_moves_the_closer_thumb_without_crossing() {
+        let range = SliderRange::new(0.0, 100.0).with_step(1.0);
+        let (mut tree, node) = slider(RangeSlider::new(range).with_values(20.0, 60.0), Size::new(THUMB_SIZE + 100.0, 20.0));
+        let at = |x: f32| Point::new(THUMB_SIZE / 2.0 + x, 5.0);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, at(50.0)));
+        assert_eq!(values(&node), (20.0, 50.0));
+        tree.dispatch_event(&InputEvent::MouseMove(at(5.0)));
+        assert_eq!(values(&node), (20.0, 20.0));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, at(5.0)));
+
+        key(&mut tree, Key::Right);
+        assert_eq!(values(&node), (20.0, 21.0));
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Tab, Modifiers { shift: false, ctrl: true, alt: false, logo: false }));
+        key(&mut tree, Key::End);
+        assert_eq!(values(&node), (21.0, 21.0));
+        assert_eq!(node.cast_element::<RangeSlider>().unwrap().active_thumb(), Thumb::Lower);
+    }
+
+    #[test]
+    fn setting_the_properties_coerces_and_repaints() {
+        let (tree, node) = slider(Slider::new(SliderRange::new(0.0, 10.0).with_step(1.0)), Size::new(200.0, 20.0));
+        tree.take_invalidation();
+
+        node.cast_element::<Slider>().unwrap().value().set(42.4);
+
+        assert_eq!(value(&node), 10.0);
+        assert!(tree.take_invalidation().1);
+
+        let (tree, node) = slider(RangeSlider::new(SliderRange::new(0.0, 100.0)).with_values(20.0, 60.0), Size::new(200.0, 20.0));
+        tree.take_invalidation();
+
+        node.cast_element::<RangeSlider>().unwrap().lower_value().set(80.0);
+        node.cast_element::<RangeSlider>().unwrap().upper_value().set(-5.0);
+
+        assert_eq!(values(&node), (60.0, 60.0));
+        assert!(tree.take_invalidation().1);
+    }
+}
@@ -0,0 +1,540 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use property::*;
+
+use std::f32;
+
+const SPLITTER_THICKNESS: f32 = 6.0;
+
+/// How a pane claims space along the panel's axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PaneSize {
+    /// A length in pixels, kept when the panel is resized.
+    Fixed(f32),
+    /// A weight; the space left by fixed panes is shared in proportion to the weights.
+    Proportional(f32),
+}
+
+/// Settings of a pane added to a `SplitPanel`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SplitPane {
+    pub size: PaneSize,
+    pub min_size: f32,
+    pub max_size: f32,
+}
+
+impl SplitPane {
+    pub fn new(size: PaneSize) -> SplitPane {
+        SplitPane {
+            size: size,
+            min_size: 0.0,
+            max_size: f32::INFINITY,
+        }
+    }
+
+    pub fn fixed(size: f32) -> SplitPane {
+        SplitPane::new(PaneSize::Fixed(size))
+    }
+
+    pub fn proportional(weight: f32) -> SplitPane {
+        SplitPane::new(PaneSize::Proportional(weight))
+    }
+
+    pub fn with_min_size(mut self, min: f32) -> SplitPane {
+        self.min_size = min;
+        self
+    }
+
+    pub fn with_max_size(mut self, max: f32) -> SplitPane {
+        self.max_size = max;
+        self
+    }
+}
+
+/// The draggable bar between two panes. Input is handled by the owning `SplitPanel`.
+pub struct Splitter;
+
+impl Element for Splitter {
+    fn init(&mut self, node: NodeRef) {
+
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(SPLITTER_THICKNESS, SPLITTER_THICKNESS)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Rect(Rect::from_size(node.rect().size), Color::argb(1.0, 0.75, 0.75, 0.75)));
+        cmd
+    }
+}
+
+struct Pane {
+    node: NodeWeakRef,
+    splitter: Option<NodeWeakRef>,
+    size: Property<PaneSize>,
+    min_size: f32,
+    max_size: f32,
+}
+
+impl Pane {
+    fn settings(&self) -> SplitPane {
+        SplitPane {
+            size: self.size.get(),
+            min_size: self.min_size,
+            max_size: self.max_size,
+        }
+    }
+}
+
+struct SplitterDrag {
+    index: usize,
+    start: f32,
+    sizes: Vec<f32>,
+}
+
+/// Lays out panes along an axis with draggable splitters between them.
+/// The size of each pane is exposed as a `Property<PaneSize>`, so the split
+/// can be saved and restored.
+pub struct SplitPanel {
+    node: NodeWeakRef,
+    orientation: Orientation,
+    panes: Vec<Pane>,
+    sizes: Vec<f32>,
+    drag: Option<SplitterDrag>,
+}
+
+impl SplitPanel {
+    pub fn new(orientation: Orientation) -> SplitPanel {
+        SplitPanel {
+            node: NodeWeakRef::empty(),
+            orientation: orientation,
+            panes: Vec::new(),
+            sizes: Vec::new(),
+            drag: None,
+        }
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Adds a pane after the existing ones, returning its node.
+    pub fn add_pane(&mut self, element: Box<Element>, pane: SplitPane) -> NodeRef {
+        let mut node = self.node();
+        let splitter = if self.panes.is_empty() {
+            None
+        } else {
+            Some(node.add_child(Box::new(Splitter)).downgrade())
+        };
+        let child = node.add_child(element);
+        child.set_clip_to_bounds(true);
+        let size = Property::new("PaneSize", AccessType::ReadWrite, Box::new(source::Variable::new(pane.size))).unwrap();
+        let panel = node.downgrade();
+        size.observe(move |_| if let Some(panel) = panel.upgrade() {
+            panel.invalidate_layout();
+        });
+        self.panes.push(Pane {
+            node: child.downgrade(),
+            splitter: splitter,
+            size: size,
+            min_size: pane.min_size,
+            max_size: pane.max_size,
+        });
+        node.invalidate_layout();
+        child
+    }
+
+    /// Removes the pane at `index` together with the splitter next to it.
+    pub fn remove_pane(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.panes.len() {
+            return Err(format!("No pane at index {}", index));
+        }
+        let mut node = self.node();
+        let pane = self.panes.remove(index);
+        node.remove_child(&pane.node.upgrade().unwrap());
+        let splitter = match pane.splitter {
+            Some(splitter) => Some(splitter),
+            None => self.panes.get_mut(0).and_then(|next| next.splitter.take()),
+        };
+        if let Some(splitter) = splitter {
+            node.remove_child(&splitter.upgrade().unwrap());
+        }
+        self.drag = None;
+        node.invalidate_layout();
+        Ok(())
+    }
+
+    pub fn pane_count(&self) -> usize {
+        self.panes.len()
+    }
+
+    pub fn pane(&self, index: usize) -> NodeRef {
+        self.panes[index].node.upgrade().unwrap()
+    }
+
+    /// The size setting of the pane at `index`. Setting it relayouts the panel.
+    pub fn pane_size(&self, index: usize) -> &Property<PaneSize> {
+        &self.panes[index].size
+    }
+
+    pub fn set_pane_size(&mut self, index: usize, size: PaneSize) {
+        self.panes[index].size.set(size);
+    }
+
+    pub fn set_pane_limits(&mut self, index: usize, min: f32, max: f32) {
+        self.panes[index].min_size = min;
+        self.panes[index].max_size = max;
+        self.node().invalidate_layout();
+    }
+
+    /// The pixel sizes of the panes as of the last layout.
+    pub fn sizes(&self) -> &[f32] {
+        &self.sizes
+    }
+
+    /// Moves the splitter after pane `index` by `delta` pixels, as far as the
+    /// limits of the two panes next to it allow.
+    pub fn move_splitter(&mut self, index: usize, delta: f32) {
+        let sizes = self.sizes.clone();
+        self.resize_from(&sizes, index, delta);
+    }
+
+    fn resize_from(&mut self, sizes: &[f32], index: usize, delta: f32) {
+        if index + 1 >= self.panes.len() || sizes.len() != self.panes.len() {
+            return;
+        }
+        let (before, after) = (&self.panes[index], &self.panes[index + 1]);
+        let low = (before.min_size - sizes[index]).max(sizes[index + 1] - after.max_size);
+        let high = (before.max_size - sizes[index]).min(sizes[index + 1] - after.min_size);
+        let delta = delta.max(low).min(high);
+
+        let mut new_sizes = sizes.to_vec();
+        new_sizes[index] += delta;
+        new_sizes[index + 1] -= delta;
+
+        // Proportional panes get weights that reproduce the new sizes, keeping the total weight.
+        let mut weight = 0.0;
+        let mut space = 0.0;
+        for (pane, &size) in self.panes.iter().zip(new_sizes.iter()) {
+            if let PaneSize::Proportional(w) = pane.size.get() {
+                weight += w;
+                space += size;
+            }
+        }
+        for (pane, &size) in self.panes.iter().zip(new_sizes.iter()) {
+            let value = match pane.size.get() {
+                PaneSize::Fixed(_) => PaneSize::Fixed(size),
+                PaneSize::Proportional(w) if space > 0.0 && weight > 0.0 => PaneSize::Proportional(size * weight / space),
+                other => other,
+            };
+            pane.size.set(value);
+        }
+    }
+
+    /// Returns the index of the splitter under `point`, given in content coordinates.
+    fn splitter_at(&self, point: Point) -> Option<usize> {
+        for (index, pane) in self.panes.iter().enumerate().skip(1) {
+            if let Some(splitter) = pane.splitter.as_ref().and_then(|s| s.upgrade()) {
+                if splitter.rect().contains(point) {
+                    return Some(index - 1);
+                }
+            }
+        }
+        None
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+}
+
+impl Element for SplitPanel {
+    fn init(&mut self, node: NodeRef) {
+        info!("[SplitPanel] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[SplitPanel] Measure");
+        let orientation = self.orientation;
+        let mut main = SPLITTER_THICKNESS * (self.panes.len() as f32 - 1.0).max(0.0);
+        let mut cross: f32 = 0.0;
+        for pane in &self.panes {
+            let desired = pane.node.upgrade().unwrap().measure();
+            let len = match pane.size.get() {
+                PaneSize::Fixed(size) => size,
+                PaneSize::Proportional(_) => orientation.main(desired),
+            };
+            main += len.max(pane.min_size).min(pane.max_size);
+            cross = cross.max(cross_of(orientation, desired));
+        }
+        from_axes(orientation, main, cross)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[SplitPanel] Layout");
+        let orientation = self.orientation;
+        let settings: Vec<SplitPane> = self.panes.iter().map(|p| p.settings()).collect();
+        let splitters = SPLITTER_THICKNESS * (self.panes.len() as f32 - 1.0).max(0.0);
+        let sizes = distribute(&settings, (orientation.main(container) - splitters).max(0.0));
+        let cross = cross_of(orientation, container);
+
+        let mut pos = 0.0;
+        for (pane, &size) in self.panes.iter().zip(sizes.iter()) {
+            if let Some(splitter) = pane.splitter.as_ref().and_then(|s| s.upgrade()) {
+                splitter.arrange(Rect::pos_size(point_from_axes(orientation, pos, 0.0),
+                                                from_axes(orientation, SPLITTER_THICKNESS, cross)));
+                pos += SPLITTER_THICKNESS;
+            }
+            pane.node.upgrade().unwrap().arrange(Rect::pos_size(point_from_axes(orientation, pos, 0.0),
+                                                                from_axes(orientation, size, cross)));
+            pos += size;
+        }
+        self.sizes = sizes;
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let content_origin = node.content_rect().pos;
+        match *event {
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                let pos = pos - content_origin;
+                match self.splitter_at(pos) {
+                    Some(index) => {
+                        self.drag = Some(SplitterDrag {
+                            index: index,
+                            start: self.orientation.coord(pos),
+                            sizes: self.sizes.clone(),
+                        });
+                        true
+                    }
+                    None => false,
+                }
+            }
+            InputEvent::MouseMove(pos) if self.drag.is_some() => {
+                let (index, start, sizes) = {
+                    let drag = self.drag.as_ref().unwrap();
+                    (drag.index, drag.start, drag.sizes.clone())
+                };
+                let delta = self.orientation.coord(pos - content_origin) - start;
+                self.resize_from(&sizes, index, delta);
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, _) if self.drag.is_some() => {
+                self.drag = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn cross_of(orientation: Orientation, size: Size) -> f32 {
+    match orientation {
+        Orientation::Horizontal => size.h,
+        Orientation::Vertical => size.w,
+    }
+}
+
+fn from_axes(orientation: Orientation, main: f32, cross: f32) -> Size {
+    match orientation {
+        Orientation::Horizontal => Size::new(main, cross),
+        Orientation::Vertical => Size::new(cross, main),
+    }
+}
+
+fn point_from_axes(orientation: Orientation, main: f32, cross: f32) -> Point {
+    let size = from_axes(orientation, main, cross);
+    Point::new(size.w, size.h)
+}
+
+/// Splits `available` pixels between panes. Fixed panes take their size;
+/// proportional panes share the rest by weight, and a pane hitting its limit
+/// is frozen at it while the others are recomputed.
+pub fn distribute(panes: &[SplitPane], available: f32) -> Vec<f32> {
+    let mut sizes = vec![0.0; panes.len()];
+    let mut frozen = vec![false; panes.len()];
+    let mut remaining = available;
+    for (i, pane) in panes.iter().enumerate() {
+        if let PaneSize::Fixed(size) = pane.size {
+            sizes[i] = size.max(pane.min_size).min(pane.max_size);
+            frozen[i] = true;
+            remaining -= sizes[i];
+        }
+    }
+
+    loop {
+        let free = remaining - (0..panes.len())
+            .filter(|&i| frozen[i] && is_proportional(&panes[i]))
+            .map(|i| sizes[i])
+            .sum::<f32>();
+        let weight: f32 = (0..panes.len()).filter(|&i| !frozen[i]).map(|i| weight_of(&panes[i])).sum();
+
+        let mut violation = 0.0;
+        for i in (0..panes.len()).filter(|&i| !frozen[i]) {
+            let target = if weight > 0.0 { free.max(0.0) * weight_of(&panes[i]) / weight } else { 0.0 };
+            let clamped = target.max(panes[i].min_size).min(panes[i].max_size);
+            violation += clamped - target;
+            sizes[i] = clamped;
+        }
+        if violation == 0.0 {
+            return sizes;
+        }
+        for i in 0..panes.len() {
+            if frozen[i] {
+                continue;
+            }
+            let min_hit = sizes[i] == panes[i].min_size;
+            let max_hit = sizes[i] == panes[i].max_size;
+            if (violation > 0.0 && min_hit) || (violation < 0.0 && max_hit) {
+                frozen[i] = true;
+            }
+        }
+    }
+}
+
+fn is_proportional(pane: &SplitPane) -> bool {
+    match pane.size {
+        PaneSize::Proportional(_) => true,
+        PaneSize::Fixed(_) => false,
+    }
+}
+
+fn weight_of(pane: &SplitPane) -> f32 {
+    match pane.size {
+        PaneSize::Proportional(w) => w.max(0.0),
+        PaneSize::Fixed(_) => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree::testing::SizedElement;
+
+    fn panel(panes: &[SplitPane]) -> (Tree, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(SplitPanel::new(Orientation::Horizontal)))).unwrap();
+        for pane in panes {
+            root.cast_element_mut::<SplitPanel>().unwrap().add_pane(Box::new(SizedElement::new(Size::new(10.0, 10.0))), *pane);
+        }
+        (tree, root)
+    }
+
+    fn assert_sizes(node: &NodeRef, expected: &[f32]) {
+        let sizes = node.cast_element::<SplitPanel>().unwrap().sizes().to_vec();
+        assert_eq!(sizes.len(), expected.len());
+        for (a, b) in sizes.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 0.001, "{:?} != {:?}", sizes, expected);
+        }
+    }
+
+    fn weight(node: &NodeRef, index: usize) -> f32 {
+        match node.cast_element::<SplitPanel>().unwrap().pane_size(index).get() {
+            PaneSize::Proportional(w) => w,
+            PaneSize::Fixed(_) => panic!("pane {} is fixed", index),
+        }
+    }
+
+    #[test]
+    fn distribute_shares_space_left_by_fixed_panes() {
+        let panes = [SplitPane::fixed(40.0), SplitPane::proportional(1.0), SplitPane::proportional(3.0)];
+
+        assert_eq!(distribute(&panes, 240.0), vec![40.0, 50.0, 150.0]);
+    }
+
+    #[test]
+    fn distribute_freezes_panes_at_their_limits() {
+        let panes = [SplitPane::proportional(1.0).with_max_size(20.0),
+                     SplitPane::proportional(1.0),
+                     SplitPane::proportional(1.0).with_min_size(70.0)];
+
+        assert_eq!(distribute(&panes, 120.0), vec![20.0, 30.0, 70.0]);
+    }
+
+    #[test]
+    fn panes_and_splitters_are_arranged_along_the_axis() {
+        let (_tree, root) = panel(&[SplitPane::fixed(50.0), SplitPane::proportional(1.0)]);
+
+        root.layout(Size::new(206.0, 30.0));
+
+        let panel = root.cast_element::<SplitPanel>().unwrap();
+        assert_eq!(panel.pane(0).rect().to_pos_size_tuple(), ((0.0, 0.0), (50.0, 30.0)));
+        assert_eq!(panel.pane(1).rect().to_pos_size_tuple(), ((56.0, 0.0), (150.0, 30.0)));
+    }
+
+    #[test]
+    fn resize_keeps_fixed_panes_and_scales_proportional_ones() {
+        let (_tree, root) = panel(&[SplitPane::fixed(50.0), SplitPane::proportional(1.0), SplitPane::proportional(1.0)]);
+
+        root.layout(Size::new(162.0, 30.0));
+        assert_sizes(&root, &[50.0, 50.0, 50.0]);
+
+        root.layout(Size::new(262.0, 30.0));
+        assert_sizes(&root, &[50.0, 100.0, 100.0]);
+    }
+
+    #[test]
+    fn dragging_a_splitter_updates_the_pane_sizes() {
+        let (mut tree, root) = panel(&[SplitPane::proportional(1.0), SplitPane::proportional(1.0)]);
+        root.layout(Size::new(106.0, 30.0));
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(52.0, 10.0)));
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(72.0, 10.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, Point::new(72.0, 10.0)));
+        root.layout(Size::new(106.0, 30.0));
+
+        assert_sizes(&root, &[70.0, 30.0]);
+        assert!((weight(&root, 0) - 1.4).abs() < 0.001);
+        assert!((weight(&root, 1) - 0.6).abs() < 0.001);
+    }
+
+    #[test]
+    fn splitter_stops_at_pane_limits() {
+        let (_tree, root) = panel(&[SplitPane::fixed(50.0).with_max_size(60.0), SplitPane::proportional(1.0)]);
+        root.layout(Size::new(106.0, 30.0));
+
+        root.cast_element_mut::<SplitPanel>().unwrap().move_splitter(0, 30.0);
+        root.layout(Size::new(106.0, 30.0));
+
+        assert_sizes(&root, &[60.0, 40.0]);
+        assert_eq!(root.cast_element::<SplitPanel>().unwrap().pane_size(0).get(), PaneSize::Fixed(60.0));
+    }
+
+    #[test]
+    fn remove_pane_removes_its_splitter() {
+        let (_tree, root) = panel(&[SplitPane::fixed(50.0), SplitPane::proportional(1.0)]);
+
+        root.cast_element_mut::<SplitPanel>().unwrap().remove_pane(0).unwrap();
+        root.layout(Size::new(100.0, 30.0));
+
+        assert_eq!(root.children().len(), 1);
+        assert_sizes(&root, &[100.0]);
+        assert!(root.cast_element_mut::<SplitPanel>().unwrap().remove_pane(1).is_err());
+    }
+
+    #[test]
+    fn setting_a_pane_size_relayouts_the_panel() {
+        let (tree, root) = panel(&[SplitPane::fixed(50.0), SplitPane::proportional(1.0)]);
+        root.layout(Size::new(106.0, 30.0));
+        tree.take_invalidation();
+
+        root.cast_element::<SplitPanel>().unwrap().pane_size(0).set(PaneSize::Fixed(20.0));
+
+        assert!(tree.take_invalidation().0);
+        root.layout(Size::new(106.0, 30.0));
+        assert_sizes(&root, &[20.0, 80.0]);
+    }
+}
@@ -0,0 +1,651 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use property::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+const HEADER_HEIGHT: f32 = 24.0;
+const HEADER_WIDTH: f32 = 120.0;
+const CLOSE_SIZE: f32 = 12.0;
+const SCROLL_BUTTON_WIDTH: f32 = 16.0;
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// A tab to add to a `TabControl`. The content is created by `create` the
+/// first time the tab is selected.
+pub struct Tab {
+    title: String,
+    closable: bool,
+    create: Box<Fn() -> Box<Element>>,
+}
+
+impl Tab {
+    pub fn new<S: Into<String>, F: Fn() -> Box<Element> + 'static>(title: S, create: F) -> Tab {
+        Tab {
+            title: title.into(),
+            closable: false,
+            create: Box::new(create),
+        }
+    }
+
+    pub fn with_closable(mut self, closable: bool) -> Tab {
+        self.closable = closable;
+        self
+    }
+}
+
+fn close_rect(size: Size) -> Rect {
+    let margin = (size.h - CLOSE_SIZE) / 2.0;
+    Rect::pos_size(Point::new(size.w - margin - CLOSE_SIZE, margin), Size::new(CLOSE_SIZE, CLOSE_SIZE))
+}
+
+/// Draws one tab header. Input is handled by the owning `TabControl`.
+pub struct TabHeader {
+    title: String,
+    closable: bool,
+    selected: bool,
+}
+
+impl TabHeader {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+}
+
+impl Element for TabHeader {
+    fn init(&mut self, node: NodeRef) {
+
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(HEADER_WIDTH, HEADER_HEIGHT)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        let size = node.rect().size;
+        let background = if self.selected {
+            Color::argb(1.0, 1.0, 1.0, 1.0)
+        } else {
+            Color::argb(1.0, 0.85, 0.85, 0.85)
+        };
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Border(Rect::from_size(size), BorderStyle::new(background)
+            .with_border(Thickness::new(1.0, 1.0, 1.0, if self.selected { 0.0 } else { 1.0 }),
+                         Color::argb(1.0, 0.6, 0.6, 0.6))));
+        if self.closable {
+            cmd.add(RenderCommand::Rect(close_rect(size), Color::argb(1.0, 0.55, 0.55, 0.55)));
+        }
+        cmd
+    }
+}
+
+/// Holds the headers; clips them to the part of the strip not taken by the scroll buttons.
+struct TabStrip;
+
+impl Element for TabStrip {
+    fn init(&mut self, node: NodeRef) {
+
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(HEADER_WIDTH * node.children().len() as f32, HEADER_HEIGHT)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+}
+
+/// Scrolls the header strip by one header when it overflows.
+pub struct TabScrollButton {
+    forward: bool,
+}
+
+impl Element for TabScrollButton {
+    fn init(&mut self, node: NodeRef) {
+
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(SCROLL_BUTTON_WIDTH, HEADER_HEIGHT)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        let size = node.rect().size;
+        let mut cmd = RenderCommandList::new();
+        cmd.add(RenderCommand::Rect(Rect::from_size(size), Color::argb(1.0, 0.8, 0.8, 0.8)));
+        let arrow = Rect::pos_size(Point::new(if self.forward { size.w / 2.0 } else { size.w / 4.0 }, size.h / 2.0 - 4.0),
+                                   Size::new(size.w / 4.0, 8.0));
+        cmd.add(RenderCommand::Rect(arrow, Color::argb(1.0, 0.4, 0.4, 0.4)));
+        cmd
+    }
+}
+
+struct TabEntry {
+    tab: Tab,
+    header: NodeWeakRef,
+    content: Option<NodeRef>,
+}
+
+struct HeaderPress {
+    index: usize,
+    start: f32,
+    dragging: bool,
+}
+
+/// Shows a strip of tab headers and the content of the selected tab.
+/// Headers that don't fit can be scrolled with the wheel or the arrow buttons,
+/// closed with their close button and reordered by dragging.
+pub struct TabControl {
+    node: NodeWeakRef,
+    strip: NodeWeakRef,
+    back_button: NodeWeakRef,
+    forward_button: NodeWeakRef,
+    tabs: Vec<TabEntry>,
+    selected: Property<usize>,
+    shown: Option<NodeRef>,
+    scroll_offset: f32,
+    strip_width: f32,
+    scroll_to_selected: Rc<Cell<bool>>,
+    press: Option<HeaderPress>,
+}
+
+impl TabControl {
+    pub fn new() -> TabControl {
+        TabControl {
+            node: NodeWeakRef::empty(),
+            strip: NodeWeakRef::empty(),
+            back_button: NodeWeakRef::empty(),
+            forward_button: NodeWeakRef::empty(),
+            tabs: Vec::new(),
+            selected: Property::new("SelectedIndex", AccessType::ReadWrite, Box::new(source::Variable::new(0))).unwrap(),
+            shown: None,
+            scroll_offset: 0.0,
+            strip_width: 0.0,
+            scroll_to_selected: Rc::new(Cell::new(false)),
+            press: None,
+        }
+    }
+
+    /// Adds a tab after the existing ones, returning its index.
+    pub fn add_tab(&mut self, tab: Tab) -> usize {
+        let index = self.tabs.len();
+        self.insert_tab(index, tab);
+        index
+    }
+
+    pub fn insert_tab(&mut self, index: usize, tab: Tab) {
+        let header = self.strip_node().add_child(Box::new(TabHeader {
+            title: tab.title.clone(),
+            closable: tab.closable,
+            selected: false,
+        }));
+        self.tabs.insert(index, TabEntry {
+            tab: tab,
+            header: header.downgrade(),
+            content: None,
+        });
+        let selected = self.selected.get();
+        if index <= selected && self.tabs.len() > 1 {
+            self.selected.set(selected + 1);
+        }
+        self.node().invalidate_layout();
+    }
+
+    /// Removes the tab at `index` and its content. The tab after it, or else
+    /// the one before, becomes selected if the closed tab was.
+    pub fn close_tab(&mut self, index: usize) {
+        let entry = self.tabs.remove(index);
+        self.strip_node().remove_child(&entry.header.upgrade().unwrap());
+        if entry.content.is_some() && entry.content == self.shown {
+            self.node().remove_child(entry.content.as_ref().unwrap());
+            self.shown = None;
+        }
+        let selected = self.selected.get();
+        if selected > index || (selected == index && selected == self.tabs.len() && selected > 0) {
+            self.selected.set(selected - 1);
+        }
+        self.press = None;
+        self.node().invalidate_layout();
+    }
+
+    /// Moves the tab at `from` to `to`, keeping the same tab selected.
+    pub fn move_tab(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        let entry = self.tabs.remove(from);
+        self.tabs.insert(to, entry);
+        let selected = self.selected.get();
+        let selected = if selected == from {
+            to
+        } else if from < selected && selected <= to {
+            selected - 1
+        } else if to <= selected && selected < from {
+            selected + 1
+        } else {
+            selected
+        };
+        self.selected.set(selected);
+        self.node().invalidate_layout();
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn title(&self, index: usize) -> &str {
+        &self.tabs[index].tab.title
+    }
+
+    /// The index of the selected tab. Setting it relayouts the control and
+    /// scrolls the headers to the tab.
+    pub fn selected(&self) -> &Property<usize> {
+        &self.selected
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected.set(index);
+    }
+
+    /// The content of the tab at `index`, if it has been created.
+    pub fn content(&self, index: usize) -> Option<NodeRef> {
+        self.tabs[index].content.clone()
+    }
+
+    /// How far the header strip is scrolled.
+    pub fn scroll_offset(&self) -> f32 {
+        self.scroll_offset
+    }
+
+    pub fn scroll_headers_by(&mut self, delta: f32) {
+        self.scroll_offset = self.clamp_scroll(self.scroll_offset + delta);
+        self.node().invalidate_layout();
+    }
+
+    fn clamp_scroll(&self, offset: f32) -> f32 {
+        let max = (HEADER_WIDTH * self.tabs.len() as f32 - self.strip_width).max(0.0);
+        offset.max(0.0).min(max)
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        if self.tabs.is_empty() {
+            None
+        } else {
+            Some(self.selected.get().min(self.tabs.len() - 1))
+        }
+    }
+
+    /// Makes the content of the selected tab the only one attached, creating it if needed.
+    fn show_selected(&mut self) {
+        let wanted = match self.selected_index() {
+            Some(index) => {
+                let entry = &mut self.tabs[index];
+                if entry.content.is_none() {
+                    let content = self.node.upgrade().unwrap().add_child((entry.tab.create)());
+                    entry.content = Some(content);
+                }
+                entry.content.clone()
+            }
+            None => None,
+        };
+        if wanted != self.shown {
+            let mut node = self.node();
+            if let Some(ref shown) = self.shown {
+                node.remove_child(shown);
+            }
+            if let Some(ref wanted) = wanted {
+                if wanted.parrent().is_none() {
+                    node.append_child(wanted.clone());
+                }
+            }
+            self.shown = wanted;
+        }
+    }
+
+    /// Returns the index of the header under `x`, given in strip coordinates.
+    fn header_at(&self, x: f32) -> Option<usize> {
+        if x < 0.0 || x >= self.strip_width {
+            return None;
+        }
+        let index = ((x + self.scroll_offset) / HEADER_WIDTH) as usize;
+        if index < self.tabs.len() { Some(index) } else { None }
+    }
+
+    fn press_header(&mut self, pos: Point) {
+        let index = match self.header_at(pos.x) {
+            Some(index) => index,
+            None => return,
+        };
+        let local = Point::new(pos.x + self.scroll_offset - HEADER_WIDTH * index as f32, pos.y);
+        if self.tabs[index].tab.closable && close_rect(Size::new(HEADER_WIDTH, HEADER_HEIGHT)).contains(local) {
+            self.close_tab(index);
+            return;
+        }
+        self.select(index);
+        self.press = Some(HeaderPress {
+            index: index,
+            start: pos.x,
+            dragging: false,
+        });
+    }
+
+    fn drag_header(&mut self, x: f32) {
+        let (index, start, dragging) = match self.press {
+            Some(ref press) => (press.index, press.start, press.dragging),
+            None => return,
+        };
+        if !dragging && (x - start).abs() < DRAG_THRESHOLD {
+            return;
+        }
+        let target = ((x.max(0.0).min(self.strip_width) + self.scroll_offset) / HEADER_WIDTH) as usize;
+        let target = target.min(self.tabs.len() - 1);
+        if target != index {
+            self.move_tab(index, target);
+        }
+        self.press = Some(HeaderPress {
+            index: target,
+            start: start,
+            dragging: true,
+        });
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+
+    fn strip_node(&self) -> NodeRef {
+        self.strip.upgrade().unwrap()
+    }
+}
+
+impl Element for TabControl {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[TabControl] Init");
+        self.node = node.downgrade();
+
+        let strip = node.add_child(Box::new(TabStrip));
+        strip.set_clip_to_bounds(true);
+        self.strip = strip.downgrade();
+        self.back_button = node.add_child(Box::new(TabScrollButton { forward: false })).downgrade();
+        self.forward_button = node.add_child(Box::new(TabScrollButton { forward: true })).downgrade();
+
+        let control = node.downgrade();
+        let scroll_to_selected = self.scroll_to_selected.clone();
+        self.selected.observe(move |_| if let Some(control) = control.upgrade() {
+            scroll_to_selected.set(true);
+            control.invalidate_layout();
+        });
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[TabControl] Measure");
+        let content = self.shown.as_ref().map_or(Size::zero(), |c| c.measure());
+        Size::new(content.w.max(HEADER_WIDTH), content.h + HEADER_HEIGHT)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[TabControl] Layout");
+        self.show_selected();
+
+        let total = HEADER_WIDTH * self.tabs.len() as f32;
+        let overflow = total > container.w;
+        self.strip_width = if overflow {
+            (container.w - 2.0 * SCROLL_BUTTON_WIDTH).max(0.0)
+        } else {
+            container.w
+        };
+        let selected = self.selected_index();
+        if let (true, Some(index)) = (self.scroll_to_selected.get(), selected) {
+            let start = HEADER_WIDTH * index as f32;
+            if start < self.scroll_offset {
+                self.scroll_offset = start;
+            } else if start + HEADER_WIDTH > self.scroll_offset + self.strip_width {
+                self.scroll_offset = start + HEADER_WIDTH - self.strip_width;
+            }
+        }
+        self.scroll_to_selected.set(false);
+        self.scroll_offset = self.clamp_scroll(self.scroll_offset);
+
+        let strip = self.strip_node();
+        strip.arrange(Rect::pos_size(Point::zero(), Size::new(self.strip_width, HEADER_HEIGHT)));
+        for (index, entry) in self.tabs.iter().enumerate() {
+            let header = entry.header.upgrade().unwrap();
+            header.cast_element_mut::<TabHeader>().unwrap().selected = Some(index) == selected;
+            header.arrange(Rect::pos_size(Point::new(HEADER_WIDTH * index as f32 - self.scroll_offset, 0.0),
+                                          Size::new(HEADER_WIDTH, HEADER_HEIGHT)));
+        }
+
+        let button_size = Size::new(SCROLL_BUTTON_WIDTH, HEADER_HEIGHT);
+        let back = self.back_button.upgrade().unwrap();
+        let forward = self.forward_button.upgrade().unwrap();
+        if overflow {
+            back.arrange(Rect::pos_size(Point::new(self.strip_width, 0.0), button_size));
+            forward.arrange(Rect::pos_size(Point::new(self.strip_width + SCROLL_BUTTON_WIDTH, 0.0), button_size));
+        } else {
+            back.arrange(Rect::zero());
+            forward.arrange(Rect::zero());
+        }
+
+        if let Some(ref content) = self.shown {
+            content.arrange(Rect::pos_size(Point::new(0.0, HEADER_HEIGHT),
+                                           Size::new(container.w, (container.h - HEADER_HEIGHT).max(0.0))));
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let content_origin = node.content_rect().pos;
+        match *event {
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                let pos = pos - content_origin;
+                if pos.y < 0.0 || pos.y >= HEADER_HEIGHT {
+                    return false;
+                }
+                if self.back_button.upgrade().unwrap().rect().contains(pos) {
+                    self.scroll_headers_by(-HEADER_WIDTH);
+                } else if self.forward_button.upgrade().unwrap().rect().contains(pos) {
+                    self.scroll_headers_by(HEADER_WIDTH);
+                } else {
+                    self.press_header(pos);
+                }
+                true
+            }
+            InputEvent::MouseMove(pos) if self.press.is_some() => {
+                self.drag_header((pos - content_origin).x);
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, _) if self.press.is_some() => {
+                self.press = None;
+                true
+            }
+            InputEvent::MouseWheel(delta, pos) => {
+                let pos = pos - content_origin;
+                if pos.y < 0.0 || pos.y >= HEADER_HEIGHT {
+                    return false;
+                }
+                let delta = if delta.x != 0.0 { delta.x } else { delta.y };
+                self.scroll_headers_by(-delta);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Page;
+
+    impl Element for Page {
+        fn init(&mut self, node: NodeRef) {
+
+        }
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+    }
+
+    fn tabs(count: usize, created: &Rc<Cell<usize>>) -> (Tree, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(TabControl::new()))).unwrap();
+        for i in 0..count {
+            let created = created.clone();
+            let tab = Tab::new(format!("Tab {}", i), move || {
+                created.set(created.get() + 1);
+                Box::new(Page) as Box<Element>
+            }).with_closable(true);
+            root.cast_element_mut::<TabControl>().unwrap().add_tab(tab);
+        }
+        root.layout(Size::new(400.0, 300.0));
+        (tree, root)
+    }
+
+    fn selected(node: &NodeRef) -> usize {
+        node.cast_element::<TabControl>().unwrap().selected().get()
+    }
+
+    fn titles(node: &NodeRef) -> Vec<String> {
+        let control = node.cast_element::<TabControl>().unwrap();
+        (0..control.tab_count()).map(|i| control.title(i).to_owned()).collect()
+    }
+
+    #[test]
+    fn content_is_created_when_first_selected() {
+        let created = Rc::new(Cell::new(0));
+        let (_tree, root) = tabs(3, &created);
+
+        assert_eq!(created.get(), 1);
+
+        root.cast_element_mut::<TabControl>().unwrap().select(2);
+        root.layout(Size::new(400.0, 300.0));
+        root.cast_element_mut::<TabControl>().unwrap().select(0);
+        root.layout(Size::new(400.0, 300.0));
+
+        assert_eq!(created.get(), 2);
+        assert!(root.cast_element::<TabControl>().unwrap().content(1).is_none());
+    }
+
+    #[test]
+    fn only_selected_content_is_attached() {
+        let created = Rc::new(Cell::new(0));
+        let (_tree, root) = tabs(2, &created);
+
+        root.cast_element_mut::<TabControl>().unwrap().select(1);
+        root.layout(Size::new(400.0, 300.0));
+
+        let control = root.cast_element::<TabControl>().unwrap();
+        let second = control.content(1).unwrap();
+        assert!(control.content(0).unwrap().parrent().is_none());
+        assert!(second.parrent().unwrap() == root);
+        assert_eq!(second.rect().to_pos_size_tuple(), ((0.0, 24.0), (400.0, 276.0)));
+    }
+
+    #[test]
+    fn clicking_a_header_selects_it() {
+        let created = Rc::new(Cell::new(0));
+        let (mut tree, root) = tabs(3, &created);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(130.0, 10.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, Point::new(130.0, 10.0)));
+
+        assert_eq!(selected(&root), 1);
+    }
+
+    #[test]
+    fn close_button_removes_tab_and_keeps_selection() {
+        let created = Rc::new(Cell::new(0));
+        let (mut tree, root) = tabs(3, &created);
+        root.cast_element_mut::<TabControl>().unwrap().select(2);
+        root.layout(Size::new(400.0, 300.0));
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(110.0, 12.0)));
+        root.layout(Size::new(400.0, 300.0));
+
+        assert_eq!(titles(&root), vec!["Tab 1", "Tab 2"]);
+        assert_eq!(selected(&root), 1);
+    }
+
+    #[test]
+    fn closing_the_last_selected_tab_selects_the_previous_one() {
+        let created = Rc::new(Cell::new(0));
+        let (_tree, root) = tabs(2, &created);
+        root.cast_element_mut::<TabControl>().unwrap().select(1);
+
+        root.cast_element_mut::<TabControl>().unwrap().close_tab(1);
+        root.layout(Size::new(400.0, 300.0));
+
+        assert_eq!(selected(&root), 0);
+        assert!(root.cast_element::<TabControl>().unwrap().content(0).unwrap().parrent().unwrap() == root);
+    }
+
+    #[test]
+    fn dragging_a_header_reorders_tabs() {
+        let created = Rc::new(Cell::new(0));
+        let (mut tree, root) = tabs(3, &created);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(10.0, 10.0)));
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(250.0, 10.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, Point::new(250.0, 10.0)));
+
+        assert_eq!(titles(&root), vec!["Tab 1", "Tab 2", "Tab 0"]);
+        assert_eq!(selected(&root), 2);
+    }
+
+    #[test]
+    fn overflowing_headers_scroll_to_the_selected_tab() {
+        let created = Rc::new(Cell::new(0));
+        let (_tree, root) = tabs(6, &created);
+
+        root.cast_element_mut::<TabControl>().unwrap().select(5);
+        root.layout(Size::new(400.0, 300.0));
+
+        // 6 headers of 120 in a strip of 400 - 2 * 16.
+        assert_eq!(root.cast_element::<TabControl>().unwrap().scroll_offset(), 352.0);
+
+        root.cast_element_mut::<TabControl>().unwrap().scroll_headers_by(-1000.0);
+        assert_eq!(root.cast_element::<TabControl>().unwrap().scroll_offset(), 0.0);
+    }
+
+    #[test]
+    fn setting_the_selected_property_relayouts_and_scrolls() {
+        let created = Rc::new(Cell::new(0));
+        let (tree, root) = tabs(6, &created);
+        tree.take_invalidation();
+
+        root.cast_element::<TabControl>().unwrap().selected().set(5);
+
+        assert!(tree.take_invalidation().0);
+        root.layout(Size::new(400.0, 300.0));
+        assert_eq!(root.cast_element::<TabControl>().unwrap().scroll_offset(), 352.0);
+        assert!(root.cast_element::<TabControl>().unwrap().content(5).is_some());
+    }
+}
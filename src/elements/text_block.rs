@@ -0,0 +1,152 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use text::*;
+use text::layout::*;
+use std::f32;
+
+pub const DEFAULT_FONT_SIZE: f32 = 14.0;
+
+/// Shows read-only text, wrapped to the width it is laid out at.
+///
+/// `measure` has no width to wrap at, so it uses the width of the last
+/// layout. When a new width changes the wrapped height, the block asks for
+/// another layout pass so that its parent can make room for it.
+pub struct TextBlock {
+    node: NodeWeakRef,
+    text: String,
+    format: TextFormat,
+    layout: TextLayout,
+}
+
+impl TextBlock {
+    pub fn new<T: Into<String>>(text: T) -> TextBlock {
+        TextBlock::with_format(text, TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE))
+    }
+
+    pub fn with_format<T: Into<String>>(text: T, format: TextFormat) -> TextBlock {
+        let text = text.into();
+        TextBlock {
+            node: NodeWeakRef::empty(),
+            layout: TextLayout::new(&text, format.clone(), f32::INFINITY),
+            text: text,
+            format: format,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text<T: Into<String>>(&mut self, text: T) {
+        self.text = text.into();
+        self.relayout();
+    }
+
+    pub fn format(&self) -> &TextFormat {
+        &self.format
+    }
+
+    pub fn set_format(&mut self, format: TextFormat) {
+        self.format = format;
+        self.relayout();
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.format.color = color;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    /// The current layout, in content coordinates, for caret placement and hit testing.
+    pub fn text_layout(&self) -> &TextLayout {
+        &self.layout
+    }
+
+    fn relayout(&mut self) {
+        self.layout = TextLayout::new(&self.text, self.format.clone(), self.layout.max_width());
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+}
+
+impl Element for TextBlock {
+    fn init(&mut self, node: NodeRef) {
+        info!("[TextBlock] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[TextBlock] Measure");
+        self.layout.size()
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[TextBlock] Layout");
+        if container.w != self.layout.max_width() {
+            let height = self.layout.size().h;
+            self.layout = TextLayout::new(&self.text, self.format.clone(), container.w);
+            if self.layout.size().h != height {
+                node.invalidate_layout();
+            }
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[TextBlock] Render");
+        let mut cmd = RenderCommandList::new();
+        let origin = node.content_rect().pos;
+        for mut run in self.layout.runs() {
+            run.color = self.format.color;
+            cmd.add(RenderCommand::Text(origin, run));
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_block(text: &str) -> (Tree, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(TextBlock::new(text)))).unwrap();
+        (tree, root)
+    }
+
+    #[test]
+    fn measures_unwrapped_text_before_the_first_layout() {
+        let (_tree, root) = text_block("hello world");
+        let font = Font::default_font();
+
+        assert_eq!(root.measure(), font.measure("hello world", DEFAULT_FONT_SIZE));
+    }
+
+    #[test]
+    fn wrapping_to_a_new_width_requests_another_layout() {
+        let (tree, root) = text_block("hello world");
+        let line_height = Font::default_font().metrics(DEFAULT_FONT_SIZE).line_height();
+        tree.take_invalidation();
+
+        root.layout(Size::new(50.0, 100.0));
+
+        assert_eq!(root.measure().h, 2.0 * line_height);
+        assert_eq!(tree.take_invalidation(), (true, true));
+
+        root.layout(Size::new(50.0, 100.0));
+        assert_eq!(tree.take_invalidation(), (false, false));
+    }
+
+    #[test]
+    fn set_text_invalidates_layout() {
+        let (tree, root) = text_block("a");
+        tree.take_invalidation();
+
+        root.cast_element_mut::<TextBlock>().unwrap().set_text("a\nb");
+
+        assert_eq!(tree.take_invalidation(), (true, true));
+        assert_eq!(root.cast_element::<TextBlock>().unwrap().text_layout().lines().len(), 2);
+    }
+}
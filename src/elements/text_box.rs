@@ -0,0 +1,766 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use msg::*;
+use property::*;
+use clipboard::{self, SharedClipboard};
+use text::*;
+use text::layout::*;
+use elements::text_block::DEFAULT_FONT_SIZE;
+use std::f32;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+
+const MIN_WIDTH: f32 = 120.0;
+pub const BLINK_INTERVAL_MS: u64 = 500;
+pub const CARET_WIDTH: f32 = 1.0;
+
+/// Raised by a `TextBox` when its text is edited, but not when it is set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextChanged;
+
+impl Message for TextChanged {}
+
+/// A change of the text, kept for undo and redo.
+struct Edit {
+    start: usize,
+    removed: String,
+    inserted: String,
+    /// The anchor and caret before the edit.
+    selection: (usize, usize),
+    /// Typing right after typed text joins its edit, up to the next word.
+    typing: bool,
+}
+
+/// A single line text input.
+///
+/// The text is the `Text` property, which relayouts the box when set
+/// directly. Positions of the caret and the selection are byte indices in
+/// the text. Newlines in inserted text become spaces.
+pub struct TextBox {
+    node: NodeWeakRef,
+    text: Property<String>,
+    /// The text the layout was made for.
+    shown: String,
+    format: TextFormat,
+    layout: TextLayout,
+    caret: usize,
+    anchor: usize,
+    scroll: f32,
+    view_width: f32,
+    max_length: Option<usize>,
+    mask: Option<char>,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    clipboard: SharedClipboard,
+    blink_start: Instant,
+    selecting: bool,
+}
+
+impl TextBox {
+    pub fn new() -> TextBox {
+        let format = TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE).with_wrapping(TextWrapping::NoWrap);
+        TextBox {
+            node: NodeWeakRef::empty(),
+            text: Property::new("Text", AccessType::ReadWrite, Box::new(source::Variable::new(String::new()))).unwrap(),
+            shown: String::new(),
+            layout: TextLayout::new("", format.clone(), f32::INFINITY),
+            format: format,
+            caret: 0,
+            anchor: 0,
+            scroll: 0.0,
+            view_width: 0.0,
+            max_length: None,
+            mask: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            clipboard: clipboard::shared(),
+            blink_start: Instant::now(),
+            selecting: false,
+        }
+    }
+
+    pub fn with_text<T: Into<String>>(mut self, text: T) -> TextBox {
+        self.text.set(text.into());
+        self.sync();
+        self.caret = self.shown.len();
+        self.anchor = self.caret;
+        self
+    }
+
+    /// Sets the font, size and color. The text is never wrapped.
+    pub fn with_format(mut self, format: TextFormat) -> TextBox {
+        self.format = format.with_wrapping(TextWrapping::NoWrap);
+        self.relayout();
+        self
+    }
+
+    /// Limits the text to `max_length` characters. Typed and pasted text is cut to fit.
+    pub fn with_max_length(mut self, max_length: usize) -> TextBox {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Shows every character as `mask` and turns off copying and word navigation.
+    pub fn with_password(mut self, mask: char) -> TextBox {
+        self.mask = Some(mask);
+        self.relayout();
+        self
+    }
+
+    pub fn with_clipboard(mut self, clipboard: SharedClipboard) -> TextBox {
+        self.clipboard = clipboard;
+        self
+    }
+
+    pub fn text(&self) -> &Property<String> {
+        &self.text
+    }
+
+    /// Replaces the text, putting the caret at its end and clearing the undo history.
+    pub fn set_text<T: Into<String>>(&mut self, text: T) {
+        self.text.set(text.into());
+        self.sync();
+        self.select(self.shown.len(), self.shown.len());
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    pub fn selection(&self) -> Range<usize> {
+        self.anchor.min(self.caret)..self.anchor.max(self.caret)
+    }
+
+    pub fn selected_text(&self) -> &str {
+        &self.shown[self.selection()]
+    }
+
+    /// Selects from `anchor` to `caret`, which are clamped to the text.
+    pub fn select(&mut self, anchor: usize, caret: usize) {
+        self.sync();
+        self.anchor = clamp_index(&self.shown, anchor);
+        self.caret = clamp_index(&self.shown, caret);
+        self.end_typing();
+        self.caret_moved();
+    }
+
+    pub fn select_all(&mut self) {
+        let len = self.shown.len();
+        self.select(0, len);
+    }
+
+    /// Replaces the selection with `text`.
+    pub fn insert(&mut self, text: &str) {
+        self.sync();
+        let selection = self.selection();
+        self.replace(selection, text, false);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self) {
+        self.sync();
+        if let Some(edit) = self.undo.pop() {
+            let end = edit.start + edit.inserted.len();
+            let text = format!("{}{}{}", &self.shown[..edit.start], edit.removed, &self.shown[end..]);
+            self.set_shown(text);
+            self.anchor = edit.selection.0;
+            self.caret = edit.selection.1;
+            self.redo.push(edit);
+            self.caret_moved();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        self.sync();
+        if let Some(mut edit) = self.redo.pop() {
+            let end = edit.start + edit.removed.len();
+            let text = format!("{}{}{}", &self.shown[..edit.start], edit.inserted, &self.shown[end..]);
+            self.set_shown(text);
+            self.caret = edit.start + edit.inserted.len();
+            self.anchor = self.caret;
+            edit.typing = false;
+            self.undo.push(edit);
+            self.caret_moved();
+        }
+    }
+
+    pub fn copy(&mut self) {
+        if self.mask.is_none() && !self.selection().is_empty() {
+            self.clipboard.borrow_mut().set_text(self.selected_text());
+        }
+    }
+
+    pub fn cut(&mut self) {
+        if self.mask.is_none() && !self.selection().is_empty() {
+            self.copy();
+            self.insert("");
+        }
+    }
+
+    pub fn paste(&mut self) {
+        let text = self.clipboard.borrow().text();
+        if let Some(text) = text {
+            self.insert(&text);
+        }
+    }
+
+    /// Takes a change of the text property made from outside.
+    fn sync(&mut self) {
+        let text = self.text.get();
+        if text != self.shown {
+            self.shown = text;
+            self.caret = clamp_index(&self.shown, self.caret);
+            self.anchor = clamp_index(&self.shown, self.anchor);
+            self.relayout();
+        }
+    }
+
+    fn set_shown(&mut self, text: String) {
+        self.text.set(text.clone());
+        self.shown = text;
+        self.relayout();
+        if let Some(node) = self.node.upgrade() {
+            node.raise(Box::new(TextChanged));
+        }
+    }
+
+    fn relayout(&mut self) {
+        self.layout = TextLayout::new(&self.display_text(), self.format.clone(), f32::INFINITY);
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+
+    fn replace(&mut self, range: Range<usize>, inserted: &str, typing: bool) {
+        let mut inserted: String = inserted.replace("\r\n", " ").chars()
+            .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+            .collect();
+        if let Some(max_length) = self.max_length {
+            let kept = self.shown.chars().count() - self.shown[range.clone()].chars().count();
+            let room = max_length.saturating_sub(kept);
+            if let Some((cut, _)) = inserted.char_indices().nth(room) {
+                inserted.truncate(cut);
+            }
+        }
+        if range.start == range.end && inserted.is_empty() {
+            return;
+        }
+
+        let removed = self.shown[range.clone()].to_string();
+        let joined = match self.undo.last_mut() {
+            Some(last) if typing && last.typing && removed.is_empty() && last.start + last.inserted.len() == range.start => {
+                let word_ends = inserted.starts_with(char::is_whitespace) && !last.inserted.ends_with(char::is_whitespace);
+                if !word_ends {
+                    last.inserted.push_str(&inserted);
+                }
+                !word_ends
+            }
+            _ => false,
+        };
+        if !joined {
+            self.undo.push(Edit {
+                start: range.start,
+                removed: removed,
+                inserted: inserted.clone(),
+                selection: (self.anchor, self.caret),
+                typing: typing,
+            });
+        }
+        self.redo.clear();
+
+        let text = format!("{}{}{}", &self.shown[..range.start], inserted, &self.shown[range.end..]);
+        self.set_shown(text);
+        self.caret = range.start + inserted.len();
+        self.anchor = self.caret;
+        self.caret_moved();
+    }
+
+    /// Deletes the selection, or else the text from the caret to `to`.
+    fn delete_to(&mut self, to: usize) {
+        let selection = self.selection();
+        let range = if !selection.is_empty() {
+            selection
+        } else {
+            self.caret.min(to)..self.caret.max(to)
+        };
+        self.replace(range, "", false);
+    }
+
+    fn move_caret(&mut self, to: usize, extend: bool) {
+        self.caret = to;
+        if !extend {
+            self.anchor = to;
+        }
+        self.end_typing();
+        self.caret_moved();
+    }
+
+    fn end_typing(&mut self) {
+        if let Some(last) = self.undo.last_mut() {
+            last.typing = false;
+        }
+    }
+
+    /// Restarts blinking with the caret shown, and scrolls it into view.
+    fn caret_moved(&mut self) {
+        self.blink_start = Instant::now();
+        self.scroll_to_caret();
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    fn scroll_to_caret(&mut self) {
+        let x = self.layout.caret_rect(self.to_display(self.caret)).pos.x;
+        // Trailing spaces are left out of the layout's size but not of the caret's range.
+        let end = self.layout.caret_rect(self.layout.text().len()).pos.x.max(self.layout.size().w);
+        let max = (end + CARET_WIDTH - self.view_width).max(0.0);
+        if x < self.scroll {
+            self.scroll = x;
+        } else if x + CARET_WIDTH > self.scroll + self.view_width {
+            self.scroll = x + CARET_WIDTH - self.view_width;
+        }
+        self.scroll = self.scroll.max(0.0).min(max);
+    }
+
+    fn display_text(&self) -> String {
+        match self.mask {
+            Some(mask) => self.shown.chars().map(|_| mask).collect(),
+            None => self.shown.clone(),
+        }
+    }
+
+    /// Maps a byte index in the text to one in the displayed text.
+    fn to_display(&self, index: usize) -> usize {
+        match self.mask {
+            Some(mask) => self.shown[..index].chars().count() * mask.len_utf8(),
+            None => index,
+        }
+    }
+
+    fn from_display(&self, index: usize) -> usize {
+        match self.mask {
+            Some(mask) => self.shown.char_indices().nth(index / mask.len_utf8()).map_or(self.shown.len(), |(i, _)| i),
+            None => index,
+        }
+    }
+
+    /// Where the text's top left is drawn, relative to the node.
+    fn text_origin(&self, node: &NodeRef) -> Point {
+        let content = node.content_rect();
+        let top = ((content.size.h - self.layout.size().h) / 2.0).max(0.0);
+        Point::new(content.pos.x - self.scroll, content.pos.y + top)
+    }
+
+    fn index_at(&self, node: &NodeRef, pos: Point) -> usize {
+        self.from_display(self.layout.hit_test(pos - self.text_origin(node)))
+    }
+
+    fn caret_visible(&self, now: Instant) -> bool {
+        caret_blink_on(self.blink_start, now)
+    }
+
+    fn handle_key(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        let extend = modifiers.shift;
+        let words = modifiers.ctrl && self.mask.is_none();
+        let selection = self.selection();
+        match key {
+            Key::Left if !extend && !selection.is_empty() => self.move_caret(selection.start, false),
+            Key::Right if !extend && !selection.is_empty() => self.move_caret(selection.end, false),
+            Key::Left if words => {
+                let to = previous_word_start(&self.shown, self.caret);
+                self.move_caret(to, extend);
+            }
+            Key::Right if words => {
+                let to = next_word_end(&self.shown, self.caret);
+                self.move_caret(to, extend);
+            }
+            Key::Left => {
+                let to = self.from_display(self.layout.caret_left(self.to_display(self.caret)));
+                self.move_caret(to, extend);
+            }
+            Key::Right => {
+                let to = self.from_display(self.layout.caret_right(self.to_display(self.caret)));
+                self.move_caret(to, extend);
+            }
+            Key::Home => self.move_caret(0, extend),
+            Key::End => {
+                let len = self.shown.len();
+                self.move_caret(len, extend);
+            }
+            Key::Back => {
+                let to = if words { previous_word_start(&self.shown, self.caret) } else { previous_grapheme(&self.shown, self.caret) };
+                self.delete_to(to);
+            }
+            Key::Delete => {
+                let to = if words { next_word_end(&self.shown, self.caret) } else { next_grapheme(&self.shown, self.caret) };
+                self.delete_to(to);
+            }
+            Key::Letter('a') if modifiers.ctrl => self.select_all(),
+            Key::Letter('c') if modifiers.ctrl => self.copy(),
+            Key::Letter('x') if modifiers.ctrl => self.cut(),
+            Key::Letter('v') if modifiers.ctrl => self.paste(),
+            Key::Letter('z') if modifiers.ctrl && modifiers.shift => self.redo(),
+            Key::Letter('z') if modifiers.ctrl => self.undo(),
+            Key::Letter('y') if modifiers.ctrl => self.redo(),
+            Key::Insert if modifiers.ctrl => self.copy(),
+            Key::Insert if modifiers.shift => self.paste(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Element for TextBox {
+    fn init(&mut self, node: NodeRef) {
+        info!("[TextBox] Init");
+        self.node = node.downgrade();
+        node.set_layout_properties(LayoutProperties::new().with_padding(Thickness::hv(4.0, 3.0)));
+
+        // The box is borrowed while its property is set, so the new text is
+        // synced by the layout this requests.
+        let text_box = node.downgrade();
+        self.text.observe(move |_| if let Some(text_box) = text_box.upgrade() {
+            text_box.invalidate_layout();
+        });
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[TextBox] Measure");
+        Size::new(MIN_WIDTH, self.layout.size().h)
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[TextBox] Layout");
+        self.sync();
+        self.view_width = container.w;
+        self.scroll_to_caret();
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[TextBox] Render");
+        let mut cmd = RenderCommandList::new();
+        let focused = node.has_focus();
+        let border = if focused { Color::argb(1.0, 0.2, 0.4, 0.8) } else { Color::argb(1.0, 0.6, 0.6, 0.6) };
+        cmd.add(RenderCommand::Border(Rect::from_size(node.rect().size),
+            BorderStyle::new(Color::argb(1.0, 1.0, 1.0, 1.0)).with_border(Thickness::uniform(1.0), border)));
+
+        cmd.add(RenderCommand::PushClip(node.content_rect()));
+        let origin = self.text_origin(&node);
+        let selection = self.to_display(self.selection().start)..self.to_display(self.selection().end);
+        if !selection.is_empty() {
+            let color = if focused { Color::argb(1.0, 0.7, 0.8, 0.97) } else { Color::argb(1.0, 0.85, 0.85, 0.85) };
+            for line in self.layout.lines() {
+                for cluster in line.clusters.iter().filter(|c| c.range.start < selection.end && c.range.end > selection.start) {
+                    let rect = Rect::pos_size(origin + Point::new(cluster.x, line.top), Size::new(cluster.advance, line.height));
+                    cmd.add(RenderCommand::Rect(rect, color));
+                }
+            }
+        }
+        for mut run in self.layout.runs() {
+            run.color = self.format.color;
+            cmd.add(RenderCommand::Text(origin, run));
+        }
+        if focused {
+            let now = Instant::now();
+            if self.caret_visible(now) {
+                let caret = self.layout.caret_rect(self.to_display(self.caret));
+                cmd.add(RenderCommand::Rect(Rect::pos_size(origin + caret.pos, Size::new(CARET_WIDTH, caret.size.h)), self.format.color));
+            }
+            node.invalidate_render_at(next_caret_blink(self.blink_start, now));
+        }
+        cmd.add(RenderCommand::PopClip);
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        self.sync();
+        match *event {
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                let index = self.index_at(&node, pos);
+                self.selecting = true;
+                self.move_caret(index, false);
+                true
+            }
+            InputEvent::MouseMove(pos) if self.selecting => {
+                let index = self.index_at(&node, pos);
+                self.move_caret(index, true);
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, _) if self.selecting => {
+                self.selecting = false;
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) => self.handle_key(key, modifiers),
+            InputEvent::Char(c) if !c.is_control() => {
+                let selection = self.selection();
+                let mut buffer = [0; 4];
+                self.replace(selection, c.encode_utf8(&mut buffer), true);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000000) as u64
+}
+
+/// Whether a caret that started blinking at `start` is shown at `now`.
+pub fn caret_blink_on(start: Instant, now: Instant) -> bool {
+    (millis(now - start) / BLINK_INTERVAL_MS) % 2 == 0
+}
+
+/// When a caret that started blinking at `start` next turns on or off after `now`.
+pub fn next_caret_blink(start: Instant, now: Instant) -> Instant {
+    let blinks = millis(now - start) / BLINK_INTERVAL_MS + 1;
+    start + Duration::from_millis(blinks * BLINK_INTERVAL_MS)
+}
+
+/// The largest char boundary in `text` at or before `index`.
+pub fn clamp_index(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+pub fn previous_grapheme(text: &str, index: usize) -> usize {
+    text.grapheme_indices(true).map(|(i, _)| i).take_while(|&i| i < index).last().unwrap_or(0)
+}
+
+pub fn next_grapheme(text: &str, index: usize) -> usize {
+    text.grapheme_indices(true).map(|(i, _)| i).find(|&i| i > index).unwrap_or(text.len())
+}
+
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(|c| c.is_alphanumeric())
+}
+
+/// The start of the word before `index`, or of the word it is in.
+pub fn previous_word_start(text: &str, index: usize) -> usize {
+    text.split_word_bound_indices()
+        .filter(|&(i, word)| i < index && is_word(word))
+        .map(|(i, _)| i)
+        .last()
+        .unwrap_or(0)
+}
+
+/// The end of the word after `index`, or of the word it is in.
+pub fn next_word_end(text: &str, index: usize) -> usize {
+    text.split_word_bound_indices()
+        .map(|(i, word)| (i + word.len(), word))
+        .find(|&(end, word)| end > index && is_word(word))
+        .map_or(text.len(), |(end, _)| end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clipboard::LocalClipboard;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn text_box(text_box: TextBox) -> (Tree, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(text_box.with_clipboard(Rc::new(RefCell::new(Box::new(LocalClipboard::new()))))))).unwrap();
+        root.layout(Size::new(200.0, 30.0));
+        root.focus();
+        (tree, root)
+    }
+
+    fn key(tree: &mut Tree, key: Key, shift: bool, ctrl: bool) {
+        let modifiers = Modifiers { shift: shift, ctrl: ctrl, alt: false, logo: false };
+        tree.dispatch_event(&InputEvent::KeyDown(key, modifiers));
+    }
+
+    fn type_text(tree: &mut Tree, text: &str) {
+        for c in text.chars() {
+            tree.dispatch_event(&InputEvent::Char(c));
+        }
+    }
+
+    fn text(node: &NodeRef) -> String {
+        node.cast_element::<TextBox>().unwrap().text().get()
+    }
+
+    fn selection(node: &NodeRef) -> Range<usize> {
+        node.cast_element::<TextBox>().unwrap().selection()
+    }
+
+    #[test]
+    fn typing_inserts_at_the_caret_and_replaces_the_selection() {
+        let (mut tree, root) = text_box(TextBox::new());
+
+        type_text(&mut tree, "hello");
+        key(&mut tree, Key::Home, false, false);
+        type_text(&mut tree, "> ");
+        key(&mut tree, Key::End, true, false);
+        type_text(&mut tree, "x");
+
+        assert_eq!(text(&root), "> x");
+        assert_eq!(root.cast_element::<TextBox>().unwrap().caret(), 3);
+    }
+
+    #[test]
+    fn arrows_select_and_ctrl_moves_by_words() {
+        let (mut tree, root) = text_box(TextBox::new().with_text("one two three"));
+
+        key(&mut tree, Key::Left, false, true);
+        assert_eq!(selection(&root), 8..8);
+        key(&mut tree, Key::Left, true, true);
+        assert_eq!(selection(&root), 4..8);
+        key(&mut tree, Key::Left, true, false);
+        assert_eq!(selection(&root), 3..8);
+        key(&mut tree, Key::Right, false, false);
+        assert_eq!(selection(&root), 8..8);
+        key(&mut tree, Key::Right, false, true);
+        assert_eq!(selection(&root), 13..13);
+
+        key(&mut tree, Key::Back, false, true);
+        assert_eq!(text(&root), "one two ");
+        key(&mut tree, Key::Home, false, false);
+        key(&mut tree, Key::Delete, false, false);
+        assert_eq!(text(&root), "ne two ");
+    }
+
+    #[test]
+    fn clipboard_commands() {
+        let (mut tree, root) = text_box(TextBox::new().with_text("copy me"));
+
+        key(&mut tree, Key::Letter('a'), false, true);
+        key(&mut tree, Key::Letter('x'), false, true);
+        assert_eq!(text(&root), "");
+        key(&mut tree, Key::Letter('v'), false, true);
+        key(&mut tree, Key::Letter('v'), false, true);
+
+        assert_eq!(text(&root), "copy mecopy me");
+    }
+
+    #[test]
+    fn undo_groups_typing_by_word() {
+        let (mut tree, root) = text_box(TextBox::new());
+
+        type_text(&mut tree, "one two");
+        key(&mut tree, Key::Back, false, false);
+        key(&mut tree, Key::Letter('z'), false, true);
+        assert_eq!(text(&root), "one two");
+        key(&mut tree, Key::Letter('z'), false, true);
+        assert_eq!(text(&root), "one");
+        key(&mut tree, Key::Letter('z'), false, true);
+        assert_eq!(text(&root), "");
+
+        key(&mut tree, Key::Letter('y'), false, true);
+        key(&mut tree, Key::Letter('z'), true, true);
+        assert_eq!(text(&root), "one two");
+        assert_eq!(selection(&root), 7..7);
+    }
+
+    #[test]
+    fn max_length_cuts_inserted_text() {
+        let (mut tree, root) = text_box(TextBox::new().with_max_length(5));
+
+        type_text(&mut tree, "abc");
+        root.cast_element_mut::<TextBox>().unwrap().insert("defgh\nij");
+        type_text(&mut tree, "z");
+
+        assert_eq!(text(&root), "abcde");
+    }
+
+    #[test]
+    fn setting_the_text_property_relayouts_and_clamps_the_caret() {
+        let (tree, root) = text_box(TextBox::new().with_text("hello world"));
+        tree.take_invalidation();
+
+        root.cast_element::<TextBox>().unwrap().text().set("hi".to_string());
+
+        assert!(tree.take_invalidation().0);
+        root.layout(Size::new(200.0, 30.0));
+        let text_box = root.cast_element::<TextBox>().unwrap();
+        assert_eq!(text_box.layout.text(), "hi");
+        assert_eq!(text_box.caret(), 2);
+    }
+
+    #[test]
+    fn password_mode_masks_text_and_blocks_copying() {
+        let (mut tree, root) = text_box(TextBox::new().with_password('•').with_text("secret"));
+        {
+            let mut text_box = root.cast_element_mut::<TextBox>().unwrap();
+            text_box.clipboard.borrow_mut().set_text("old");
+            text_box.select_all();
+            text_box.copy();
+            assert_eq!(text_box.clipboard.borrow().text(), Some("old".to_string()));
+            assert_eq!(text_box.layout.text(), "••••••");
+        }
+
+        key(&mut tree, Key::Left, false, true);
+        assert_eq!(selection(&root), 0..0);
+        key(&mut tree, Key::Right, false, false);
+        assert_eq!(selection(&root), 1..1);
+    }
+
+    #[test]
+    fn clicking_places_the_caret_and_dragging_selects() {
+        let (mut tree, root) = text_box(TextBox::new().with_text("abcdef"));
+        let origin = root.cast_element::<TextBox>().unwrap().text_origin(&root);
+        let width = Font::default_font().measure("abc", DEFAULT_FONT_SIZE).w;
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, origin + Point::new(width + 0.5, 2.0)));
+        tree.dispatch_event(&InputEvent::MouseMove(origin + Point::new(500.0, 2.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, origin + Point::new(500.0, 2.0)));
+
+        assert_eq!(selection(&root), 3..6);
+        assert_eq!(root.cast_element::<TextBox>().unwrap().selected_text(), "def");
+    }
+
+    #[test]
+    fn long_text_scrolls_to_keep_the_caret_visible() {
+        let (_tree, root) = text_box(TextBox::new().with_text(&"wide ".repeat(20) as &str));
+        let text_box = root.cast_element::<TextBox>().unwrap();
+        let caret = text_box.layout.caret_rect(text_box.caret()).pos.x;
+
+        assert!(text_box.scroll > 0.0);
+        assert!(caret + CARET_WIDTH - text_box.scroll <= root.content_rect().size.w);
+    }
+
+    #[test]
+    fn external_property_changes_are_picked_up() {
+        let (mut tree, root) = text_box(TextBox::new().with_text("abc"));
+        root.cast_element::<TextBox>().unwrap().text().set("x".to_string());
+
+        key(&mut tree, Key::End, false, false);
+        type_text(&mut tree, "y");
+
+        assert_eq!(text(&root), "xy");
+    }
+
+    #[test]
+    fn caret_blinks() {
+        let (_tree, root) = text_box(TextBox::new());
+        let text_box = root.cast_element::<TextBox>().unwrap();
+        let start = text_box.blink_start;
+
+        assert!(text_box.caret_visible(start + Duration::from_millis(100)));
+        assert!(!text_box.caret_visible(start + Duration::from_millis(600)));
+        assert!(text_box.caret_visible(start + Duration::from_millis(1100)));
+    }
+}
@@ -0,0 +1,926 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use clipboard::{self, SharedClipboard};
+use text::*;
+use text::layout::*;
+use text::buffer::TextBuffer;
+use elements::text_block::DEFAULT_FONT_SIZE;
+use elements::text_box::{self, CARET_WIDTH};
+use elements::scroll::BringIntoView;
+use elements::virtualizing::ItemHeights;
+use std::f32;
+use std::ops::Range;
+use std::time::Instant;
+
+const GUTTER_PADDING: f32 = 8.0;
+const TEXT_MARGIN: f32 = 4.0;
+
+struct Edit {
+    start: usize,
+    removed: String,
+    inserted: String,
+}
+
+/// Edits undone and redone together.
+struct EditGroup {
+    edits: Vec<Edit>,
+    /// The anchor and caret before the first edit.
+    selection: (usize, usize),
+    /// Typing right after typed text joins its group, up to the next word.
+    typing: bool,
+}
+
+/// A document line in view, laid out.
+struct VisibleLine {
+    index: usize,
+    start: usize,
+    /// The top of the line relative to the viewport.
+    top: f32,
+    layout: TextLayout,
+}
+
+/// A multi-line text editor for large documents, with line numbers in a
+/// gutter and optional soft wrapping, set with the format's `wrapping`.
+///
+/// The text is kept in a `TextBuffer`, and only the lines in view are laid
+/// out, so the editor is meant to be the content of a `ScrollViewer`. It
+/// keeps the caret in view by raising `BringIntoView`. Positions are byte
+/// offsets in the document.
+pub struct TextEditor {
+    node: NodeWeakRef,
+    buffer: TextBuffer,
+    format: TextFormat,
+    line_numbers: bool,
+    heights: ItemHeights,
+    /// The width lines were wrapped at when their heights were measured.
+    wrap_width: f32,
+    visible: Vec<VisibleLine>,
+    max_line_width: f32,
+    offset: Point,
+    viewport: Size,
+    caret: usize,
+    anchor: usize,
+    /// Where the caret stays horizontally while moving up and down.
+    preferred_x: Option<f32>,
+    undo: Vec<EditGroup>,
+    redo: Vec<EditGroup>,
+    open_groups: usize,
+    clipboard: SharedClipboard,
+    blink_start: Instant,
+    selecting: bool,
+}
+
+impl TextEditor {
+    pub fn new() -> TextEditor {
+        let format = TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE).with_wrapping(TextWrapping::NoWrap);
+        TextEditor {
+            node: NodeWeakRef::empty(),
+            buffer: TextBuffer::new(""),
+            heights: ItemHeights::new(1, format.line_height()),
+            format: format,
+            line_numbers: true,
+            wrap_width: -1.0,
+            visible: Vec::new(),
+            max_line_width: 0.0,
+            offset: Point::zero(),
+            viewport: Size::zero(),
+            caret: 0,
+            anchor: 0,
+            preferred_x: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            open_groups: 0,
+            clipboard: clipboard::shared(),
+            blink_start: Instant::now(),
+            selecting: false,
+        }
+    }
+
+    pub fn with_text<T: Into<String>>(mut self, text: T) -> TextEditor {
+        self.set_text(text);
+        self
+    }
+
+    /// Sets the font, size and color, and whether long lines wrap.
+    pub fn with_format(mut self, format: TextFormat) -> TextEditor {
+        self.format = format;
+        self.reset_heights();
+        self
+    }
+
+    pub fn with_line_numbers(mut self, line_numbers: bool) -> TextEditor {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    pub fn with_clipboard(mut self, clipboard: SharedClipboard) -> TextEditor {
+        self.clipboard = clipboard;
+        self
+    }
+
+    pub fn buffer(&self) -> &TextBuffer {
+        &self.buffer
+    }
+
+    pub fn text(&self) -> String {
+        self.buffer.text()
+    }
+
+    /// Replaces the document, putting the caret at its start and clearing the undo history.
+    pub fn set_text<T: Into<String>>(&mut self, text: T) {
+        self.buffer = TextBuffer::new(text);
+        self.caret = 0;
+        self.anchor = 0;
+        self.undo.clear();
+        self.redo.clear();
+        self.max_line_width = 0.0;
+        self.reset_heights();
+        self.caret_moved();
+    }
+
+    /// The line and the byte offset within it of `offset`.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.buffer.line_of(offset);
+        (line, offset - self.buffer.line_start(line))
+    }
+
+    /// The offset of `column` in `line`, clamped to the line's text.
+    pub fn offset_of(&self, line: usize, column: usize) -> usize {
+        let start = self.buffer.line_start(line);
+        self.clamp(start + column).min(self.buffer.line_end(line))
+    }
+
+    /// The lines laid out in the last layout pass.
+    pub fn visible_lines(&self) -> Range<usize> {
+        match (self.visible.first(), self.visible.last()) {
+            (Some(first), Some(last)) => first.index..last.index + 1,
+            _ => 0..0,
+        }
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    pub fn selection(&self) -> Range<usize> {
+        self.anchor.min(self.caret)..self.anchor.max(self.caret)
+    }
+
+    pub fn selected_text(&self) -> String {
+        self.buffer.slice(self.selection())
+    }
+
+    /// Selects from `anchor` to `caret`, which are clamped to the document.
+    pub fn select(&mut self, anchor: usize, caret: usize) {
+        self.anchor = self.clamp(anchor);
+        self.move_caret(caret, true);
+    }
+
+    pub fn select_all(&mut self) {
+        let len = self.buffer.len();
+        self.select(0, len);
+    }
+
+    /// Inserts `text` at `offset`. The caret and the selection move with the text around them.
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        let offset = self.clamp(offset);
+        self.edit(offset..offset, text, false);
+    }
+
+    pub fn delete(&mut self, range: Range<usize>) {
+        self.replace(range, "");
+    }
+
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let range = self.clamp(range.start)..self.clamp(range.end);
+        self.edit(range, text, false);
+    }
+
+    /// Starts a group of edits that are undone together, until the matching
+    /// `end_undo_group`. Groups can nest; the outermost one counts.
+    pub fn begin_undo_group(&mut self) {
+        if self.open_groups == 0 {
+            self.undo.push(EditGroup {
+                edits: Vec::new(),
+                selection: (self.anchor, self.caret),
+                typing: false,
+            });
+        }
+        self.open_groups += 1;
+    }
+
+    pub fn end_undo_group(&mut self) {
+        self.open_groups -= 1;
+        if self.open_groups == 0 && self.undo.last().map_or(false, |group| group.edits.is_empty()) {
+            self.undo.pop();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.undo.iter().any(|group| !group.edits.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self) {
+        if self.open_groups > 0 {
+            return;
+        }
+        if let Some(group) = self.undo.pop() {
+            for edit in group.edits.iter().rev() {
+                self.apply(edit.start..edit.start + edit.inserted.len(), &edit.removed);
+            }
+            self.anchor = group.selection.0;
+            self.caret = group.selection.1;
+            self.redo.push(group);
+            self.caret_moved();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if self.open_groups > 0 {
+            return;
+        }
+        if let Some(mut group) = self.redo.pop() {
+            for edit in &group.edits {
+                self.apply(edit.start..edit.start + edit.removed.len(), &edit.inserted);
+            }
+            if let Some(edit) = group.edits.last() {
+                self.caret = edit.start + edit.inserted.len();
+                self.anchor = self.caret;
+            }
+            group.typing = false;
+            self.undo.push(group);
+            self.caret_moved();
+        }
+    }
+
+    pub fn copy(&mut self) {
+        if !self.selection().is_empty() {
+            let text = self.selected_text();
+            self.clipboard.borrow_mut().set_text(&text);
+        }
+    }
+
+    pub fn cut(&mut self) {
+        if !self.selection().is_empty() {
+            self.copy();
+            let selection = self.selection();
+            self.edit(selection, "", false);
+        }
+    }
+
+    pub fn paste(&mut self) {
+        let text = self.clipboard.borrow().text();
+        if let Some(text) = text {
+            let selection = self.selection();
+            self.edit(selection, &text, false);
+        }
+    }
+
+    /// The largest char boundary in the document at or before `offset`.
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.buffer.len());
+        while !self.buffer.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    fn reset_heights(&mut self) {
+        self.heights = ItemHeights::new(self.buffer.line_count(), self.format.line_height());
+        self.wrap_width = -1.0;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+
+    /// Changes the buffer, keeping the line heights in step.
+    fn apply(&mut self, range: Range<usize>, text: &str) -> Edit {
+        let first = self.buffer.line_of(range.start);
+        let last = self.buffer.line_of(range.end);
+        let removed = self.buffer.slice(range.clone());
+        self.buffer.replace(range.clone(), text);
+        let end = self.buffer.line_of(range.start + text.len());
+        // A changed line keeps its old height until it is laid out again.
+        if end != last {
+            self.heights.splice(first..last + 1, end - first + 1);
+        }
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+        Edit {
+            start: range.start,
+            removed: removed,
+            inserted: text.to_string(),
+        }
+    }
+
+    fn edit(&mut self, range: Range<usize>, text: &str, typing: bool) {
+        if range.start == range.end && text.is_empty() {
+            return;
+        }
+        let selection = (self.anchor, self.caret);
+        let edit = self.apply(range.clone(), text);
+        let moved = |offset: usize| {
+            if offset >= range.end && offset > range.start {
+                offset - (range.end - range.start) + text.len()
+            } else if offset >= range.start {
+                range.start + text.len()
+            } else {
+                offset
+            }
+        };
+        self.caret = moved(self.caret);
+        self.anchor = moved(self.anchor);
+        self.record(edit, selection, typing);
+        self.redo.clear();
+        self.preferred_x = None;
+        self.caret_moved();
+    }
+
+    fn record(&mut self, edit: Edit, selection: (usize, usize), typing: bool) {
+        if let Some(group) = self.undo.last_mut() {
+            if self.open_groups > 0 {
+                group.edits.push(edit);
+                return;
+            }
+            let joins = typing && group.typing && edit.removed.is_empty() && group.edits.last().map_or(false, |last| {
+                last.start + last.inserted.len() == edit.start
+                    && !(edit.inserted.starts_with(char::is_whitespace) && !last.inserted.ends_with(char::is_whitespace))
+            });
+            if joins {
+                group.edits.last_mut().unwrap().inserted.push_str(&edit.inserted);
+                return;
+            }
+        }
+        self.undo.push(EditGroup {
+            edits: vec![edit],
+            selection: selection,
+            typing: typing,
+        });
+    }
+
+    fn end_typing(&mut self) {
+        if let Some(group) = self.undo.last_mut() {
+            group.typing = false;
+        }
+    }
+
+    fn move_caret(&mut self, to: usize, extend: bool) {
+        self.caret = self.clamp(to);
+        if !extend {
+            self.anchor = self.caret;
+        }
+        self.preferred_x = None;
+        self.end_typing();
+        self.caret_moved();
+    }
+
+    /// Restarts blinking with the caret shown, and asks for it to be scrolled into view.
+    fn caret_moved(&mut self) {
+        self.blink_start = Instant::now();
+        if let Some(node) = self.node.upgrade() {
+            // The gutter covers the start of the text, so room is asked for it too.
+            let caret = self.caret_rect(self.caret);
+            let left = self.text_left();
+            let rect = Rect::pos_size(Point::new(caret.pos.x - left, caret.pos.y), Size::new(caret.size.w + left, caret.size.h));
+            node.raise(Box::new(BringIntoView(rect)));
+            node.invalidate_render();
+        }
+    }
+
+    fn gutter_width(&self) -> f32 {
+        if !self.line_numbers {
+            return 0.0;
+        }
+        let digits = self.buffer.line_count().to_string().len() as f32;
+        digits * self.format.font.measure("0", self.format.size).w + 2.0 * GUTTER_PADDING
+    }
+
+    /// Where the text starts, relative to the viewport when it is not scrolled.
+    fn text_left(&self) -> f32 {
+        self.gutter_width() + TEXT_MARGIN
+    }
+
+    fn wrap_width_for(&self, viewport: Size) -> f32 {
+        match self.format.wrapping {
+            TextWrapping::Wrap => (viewport.w - self.text_left() - CARET_WIDTH).max(1.0),
+            TextWrapping::NoWrap => f32::INFINITY,
+        }
+    }
+
+    fn layout_line(&self, line: usize) -> TextLayout {
+        TextLayout::new(&self.buffer.line(line), self.format.clone(), self.wrap_width.max(1.0))
+    }
+
+    /// The caret at `offset`, relative to the viewport.
+    fn caret_rect(&self, offset: usize) -> Rect {
+        let (line, column) = self.position(offset);
+        let top = self.heights.position(line) - self.offset.y;
+        let rect = match self.visible.iter().find(|visible| visible.index == line) {
+            Some(visible) => visible.layout.caret_rect(column),
+            None => self.layout_line(line).caret_rect(column),
+        };
+        Rect::pos_size(Point::new(self.text_left() - self.offset.x + rect.pos.x, top + rect.pos.y), Size::new(CARET_WIDTH, rect.size.h))
+    }
+
+    /// The offset closest to `pos`, given relative to the viewport. Points
+    /// above or below the lines in view go to the first or last of them.
+    fn offset_at(&self, pos: Point) -> usize {
+        let line = match self.visible.iter().rev().find(|line| line.top <= pos.y).or(self.visible.first()) {
+            Some(line) => line,
+            None => return self.buffer.len(),
+        };
+        let x = pos.x - self.text_left() + self.offset.x;
+        line.start + line.layout.hit_test(Point::new(x, pos.y - line.top))
+    }
+
+    /// The offset one row of text up or down from `from`, at `x`.
+    fn vertical_target(&self, from: usize, down: bool, x: f32) -> usize {
+        let (line, column) = self.position(from);
+        let layout = self.layout_line(line);
+        let row = layout.line_of(column);
+        let rows = layout.lines();
+        if down && row + 1 < rows.len() {
+            self.buffer.line_start(line) + layout.hit_test(Point::new(x, rows[row + 1].top))
+        } else if !down && row > 0 && row < rows.len() {
+            self.buffer.line_start(line) + layout.hit_test(Point::new(x, rows[row - 1].top))
+        } else if down && line + 1 < self.buffer.line_count() {
+            self.buffer.line_start(line + 1) + self.layout_line(line + 1).hit_test(Point::new(x, 0.0))
+        } else if !down && line > 0 {
+            let previous = self.layout_line(line - 1);
+            let top = previous.lines().last().map_or(0.0, |row| row.top);
+            self.buffer.line_start(line - 1) + previous.hit_test(Point::new(x, top))
+        } else if down {
+            self.buffer.len()
+        } else {
+            0
+        }
+    }
+
+    fn move_vertically(&mut self, down: bool, rows: usize, extend: bool) {
+        let x = match self.preferred_x {
+            Some(x) => x,
+            None => {
+                let (line, column) = self.position(self.caret);
+                self.layout_line(line).caret_rect(column).pos.x
+            }
+        };
+        let mut to = self.caret;
+        for _ in 0..rows {
+            to = self.vertical_target(to, down, x);
+        }
+        self.move_caret(to, extend);
+        self.preferred_x = Some(x);
+    }
+
+    /// The next caret stop in reading order, crossing line breaks.
+    fn logical_target(&self, from: usize, forward: bool, words: bool) -> usize {
+        let (line, column) = self.position(from);
+        let start = self.buffer.line_start(line);
+        let text = self.buffer.line(line);
+        if forward {
+            if column >= text.len() {
+                self.buffer.line_start(line + 1)
+            } else if words {
+                start + text_box::next_word_end(&text, column)
+            } else {
+                start + text_box::next_grapheme(&text, column)
+            }
+        } else if column == 0 {
+            if line > 0 { self.buffer.line_end(line - 1) } else { 0 }
+        } else if words {
+            start + text_box::previous_word_start(&text, column)
+        } else {
+            start + text_box::previous_grapheme(&text, column)
+        }
+    }
+
+    /// The caret stop one step to the left or right on screen, crossing line
+    /// breaks at the start or end of a line.
+    fn visual_target(&self, from: usize, right: bool) -> usize {
+        let (line, column) = self.position(from);
+        let layout = self.layout_line(line);
+        let to = if right { layout.caret_right(column) } else { layout.caret_left(column) };
+        if to != column {
+            self.buffer.line_start(line) + to
+        } else {
+            self.logical_target(from, right, false)
+        }
+    }
+
+    /// Deletes the selection, or else the text from the caret to `to`.
+    fn delete_to(&mut self, to: usize) {
+        let selection = self.selection();
+        let range = if !selection.is_empty() {
+            selection
+        } else {
+            self.caret.min(to)..self.caret.max(to)
+        };
+        self.edit(range, "", false);
+    }
+
+    fn page_rows(&self) -> usize {
+        ((self.viewport.h / self.format.line_height()) as usize).max(1)
+    }
+
+    fn handle_key(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        let extend = modifiers.shift;
+        let ctrl = modifiers.ctrl;
+        let selection = self.selection();
+        match key {
+            Key::Left if !extend && !selection.is_empty() => self.move_caret(selection.start, false),
+            Key::Right if !extend && !selection.is_empty() => self.move_caret(selection.end, false),
+            Key::Left | Key::Right => {
+                let right = key == Key::Right;
+                let to = if ctrl { self.logical_target(self.caret, right, true) } else { self.visual_target(self.caret, right) };
+                self.move_caret(to, extend);
+            }
+            Key::Up => self.move_vertically(false, 1, extend),
+            Key::Down => self.move_vertically(true, 1, extend),
+            Key::PageUp => {
+                let rows = self.page_rows();
+                self.move_vertically(false, rows, extend);
+            }
+            Key::PageDown => {
+                let rows = self.page_rows();
+                self.move_vertically(true, rows, extend);
+            }
+            Key::Home => {
+                let to = if ctrl { 0 } else { self.buffer.line_start(self.position(self.caret).0) };
+                self.move_caret(to, extend);
+            }
+            Key::End => {
+                let to = if ctrl { self.buffer.len() } else { self.buffer.line_end(self.position(self.caret).0) };
+                self.move_caret(to, extend);
+            }
+            Key::Back => {
+                let to = self.logical_target(self.caret, false, ctrl);
+                self.delete_to(to);
+            }
+            Key::Delete => {
+                let to = self.logical_target(self.caret, true, ctrl);
+                self.delete_to(to);
+            }
+            Key::Enter if !ctrl && !modifiers.alt => {
+                self.edit(selection, "\n", false);
+            }
+            Key::Letter('a') if ctrl => self.select_all(),
+            Key::Letter('c') if ctrl => self.copy(),
+            Key::Letter('x') if ctrl => self.cut(),
+            Key::Letter('v') if ctrl => self.paste(),
+            Key::Letter('z') if ctrl && extend => self.redo(),
+            Key::Letter('z') if ctrl => self.undo(),
+            Key::Letter('y') if ctrl => self.redo(),
+            Key::Insert if ctrl => self.copy(),
+            Key::Insert if extend => self.paste(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Scrollable for TextEditor {
+    fn extent(&self) -> Size {
+        let width = match self.format.wrapping {
+            TextWrapping::Wrap => self.viewport.w,
+            TextWrapping::NoWrap => self.text_left() + self.max_line_width + CARET_WIDTH + TEXT_MARGIN,
+        };
+        Size::new(width, self.heights.total())
+    }
+
+    fn set_offset(&mut self, offset: Point) {
+        self.offset = offset;
+    }
+}
+
+impl Element for TextEditor {
+    fn init(&mut self, node: NodeRef) {
+        info!("[TextEditor] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[TextEditor] Measure");
+        Size::new(0.0, self.heights.total())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[TextEditor] Layout");
+        self.viewport = container;
+        let wrap_width = self.wrap_width_for(container);
+        if wrap_width != self.wrap_width {
+            self.reset_heights();
+            self.wrap_width = wrap_width;
+        }
+
+        self.visible.clear();
+        let count = self.buffer.line_count();
+        let mut index = self.heights.index_at(self.offset.y.max(0.0));
+        let mut top = self.heights.position(index);
+        while index < count && top < self.offset.y + container.h {
+            let layout = self.layout_line(index);
+            let height = layout.size().h.max(self.format.line_height());
+            self.heights.set(index, height);
+            self.max_line_width = self.max_line_width.max(layout.size().w);
+            self.visible.push(VisibleLine {
+                index: index,
+                start: self.buffer.line_start(index),
+                top: top - self.offset.y,
+                layout: layout,
+            });
+            top += height;
+            index += 1;
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[TextEditor] Render");
+        let mut cmd = RenderCommandList::new();
+        let content = node.content_rect();
+        let gutter = self.gutter_width();
+        let focused = node.has_focus();
+        cmd.add(RenderCommand::Rect(content, Color::argb(1.0, 1.0, 1.0, 1.0)));
+
+        let text_area = Rect::pos_size(Point::new(content.pos.x + gutter, content.pos.y),
+            Size::new((content.size.w - gutter).max(0.0), content.size.h));
+        cmd.add(RenderCommand::PushClip(text_area));
+        let left = content.pos.x + self.text_left() - self.offset.x;
+        let selection = self.selection();
+        let selection_color = if focused { Color::argb(1.0, 0.7, 0.8, 0.97) } else { Color::argb(1.0, 0.85, 0.85, 0.85) };
+        for line in &self.visible {
+            let origin = Point::new(left, content.pos.y + line.top);
+            let end = line.start + line.layout.text().len();
+            if selection.end > line.start && selection.start <= end {
+                let range = selection.start.max(line.start) - line.start..selection.end.min(end) - line.start;
+                for row in line.layout.lines() {
+                    for cluster in row.clusters.iter().filter(|c| c.range.start < range.end && c.range.end > range.start) {
+                        let rect = Rect::pos_size(origin + Point::new(cluster.x, row.top), Size::new(cluster.advance, row.height));
+                        cmd.add(RenderCommand::Rect(rect, selection_color));
+                    }
+                }
+                // A selected line break shows as a space after the line.
+                if selection.end > end && line.index + 1 < self.buffer.line_count() {
+                    if let Some(row) = line.layout.lines().last() {
+                        let rect = Rect::pos_size(origin + Point::new(row.x + row.width, row.top), Size::new(row.height / 3.0, row.height));
+                        cmd.add(RenderCommand::Rect(rect, selection_color));
+                    }
+                }
+            }
+            for mut run in line.layout.runs() {
+                run.color = self.format.color;
+                cmd.add(RenderCommand::Text(origin, run));
+            }
+        }
+        if focused {
+            let now = Instant::now();
+            if text_box::caret_blink_on(self.blink_start, now) {
+                let caret = self.caret_rect(self.caret);
+                cmd.add(RenderCommand::Rect(Rect::pos_size(content.pos + caret.pos, caret.size), self.format.color));
+            }
+            node.invalidate_render_at(text_box::next_caret_blink(self.blink_start, now));
+        }
+        cmd.add(RenderCommand::PopClip);
+
+        if self.line_numbers {
+            cmd.add(RenderCommand::Rect(Rect::pos_size(content.pos, Size::new(gutter, content.size.h)), Color::argb(1.0, 0.95, 0.95, 0.95)));
+            let current = self.position(self.caret).0;
+            let format = TextFormat::new(self.format.font.clone(), self.format.size);
+            for line in &self.visible {
+                let number = TextLayout::new(&(line.index + 1).to_string(), format.clone(), f32::INFINITY);
+                let origin = Point::new(content.pos.x + gutter - GUTTER_PADDING - number.size().w, content.pos.y + line.top);
+                let color = if line.index == current { Color::argb(1.0, 0.2, 0.2, 0.2) } else { Color::argb(1.0, 0.6, 0.6, 0.6) };
+                for mut run in number.runs() {
+                    run.color = color;
+                    cmd.add(RenderCommand::Text(origin, run));
+                }
+            }
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let content = node.content_rect().pos;
+        match *event {
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                let offset = self.offset_at(pos - content);
+                self.selecting = true;
+                self.move_caret(offset, false);
+                true
+            }
+            InputEvent::MouseMove(pos) if self.selecting => {
+                let offset = self.offset_at(pos - content);
+                self.move_caret(offset, true);
+                true
+            }
+            InputEvent::MouseUp(MouseButton::Left, _) if self.selecting => {
+                self.selecting = false;
+                true
+            }
+            InputEvent::KeyDown(key, modifiers) => self.handle_key(key, modifiers),
+            InputEvent::Char(c) if !c.is_control() => {
+                let selection = self.selection();
+                let mut buffer = [0; 4];
+                self.edit(selection, c.encode_utf8(&mut buffer), true);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn as_scrollable(&mut self) -> Option<&mut Scrollable> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::scroll::*;
+
+    fn editor_in_viewer(editor: TextEditor, size: Size) -> (Tree, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let viewer = tree.set_root(Some(Box::new(ScrollViewer::with_visibility(ScrollBarVisibility::Auto, ScrollBarVisibility::Auto)))).unwrap();
+        let editor = viewer.cast_element_mut::<ScrollViewer>().unwrap().set_content(Box::new(editor));
+        viewer.layout(size);
+        editor.focus();
+        (tree, viewer, editor)
+    }
+
+    fn key(tree: &mut Tree, key: Key, shift: bool, ctrl: bool) {
+        let modifiers = Modifiers { shift: shift, ctrl: ctrl, alt: false, logo: false };
+        tree.dispatch_event(&InputEvent::KeyDown(key, modifiers));
+    }
+
+    fn type_text(tree: &mut Tree, text: &str) {
+        for c in text.chars() {
+            tree.dispatch_event(&InputEvent::Char(c));
+        }
+    }
+
+    fn text(editor: &NodeRef) -> String {
+        editor.cast_element::<TextEditor>().unwrap().text()
+    }
+
+    fn caret(editor: &NodeRef) -> (usize, usize) {
+        let editor = editor.cast_element::<TextEditor>().unwrap();
+        editor.position(editor.caret())
+    }
+
+    fn big_document(lines: usize) -> String {
+        (0..lines).map(|i| format!("key{} = value", i)).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn typing_and_line_editing() {
+        let (mut tree, _viewer, editor) = editor_in_viewer(TextEditor::new(), Size::new(300.0, 200.0));
+
+        type_text(&mut tree, "ab");
+        key(&mut tree, Key::Enter, false, false);
+        type_text(&mut tree, "cd");
+        assert_eq!(text(&editor), "ab\ncd");
+        assert_eq!(caret(&editor), (1, 2));
+
+        key(&mut tree, Key::Home, false, false);
+        key(&mut tree, Key::Back, false, false);
+        assert_eq!(text(&editor), "abcd");
+        key(&mut tree, Key::Delete, false, true);
+        assert_eq!(text(&editor), "ab");
+    }
+
+    #[test]
+    fn arrows_move_between_lines_and_select() {
+        let (mut tree, _viewer, editor) = editor_in_viewer(TextEditor::new().with_text("first line is long\nab\nthird line"), Size::new(300.0, 200.0));
+
+        key(&mut tree, Key::End, false, false);
+        key(&mut tree, Key::Down, false, false);
+        assert_eq!(caret(&editor), (1, 2));
+        key(&mut tree, Key::Down, false, false);
+        assert_eq!(caret(&editor), (2, 10));
+        key(&mut tree, Key::Right, false, false);
+        assert_eq!(caret(&editor), (2, 10));
+        key(&mut tree, Key::Home, false, false);
+        key(&mut tree, Key::Left, false, false);
+        assert_eq!(caret(&editor), (1, 2));
+
+        key(&mut tree, Key::Up, true, false);
+        key(&mut tree, Key::Right, true, true);
+        assert_eq!(editor.cast_element::<TextEditor>().unwrap().selected_text(), " line is long\nab");
+        key(&mut tree, Key::End, false, true);
+        assert_eq!(caret(&editor), (2, 10));
+    }
+
+    #[test]
+    fn programmatic_edits_and_undo_groups() {
+        let (mut tree, _viewer, editor) = editor_in_viewer(TextEditor::new().with_text("a = 1\nb = 2"), Size::new(300.0, 200.0));
+        key(&mut tree, Key::End, false, true);
+        type_text(&mut tree, " # two");
+        {
+            let mut editor = editor.cast_element_mut::<TextEditor>().unwrap();
+            editor.begin_undo_group();
+            editor.insert(0, "[main]\n");
+            editor.replace(11..12, "10");
+            editor.end_undo_group();
+            assert_eq!(editor.text(), "[main]\na = 10\nb = 2 # two");
+            assert_eq!(editor.position(editor.caret()), (2, 11));
+        }
+
+        key(&mut tree, Key::Letter('z'), false, true);
+        assert_eq!(text(&editor), "a = 1\nb = 2 # two");
+        key(&mut tree, Key::Letter('z'), false, true);
+        assert_eq!(text(&editor), "a = 1\nb = 2 #");
+        key(&mut tree, Key::Letter('z'), false, true);
+        assert_eq!(text(&editor), "a = 1\nb = 2");
+        assert!(!editor.cast_element::<TextEditor>().unwrap().can_undo());
+
+        key(&mut tree, Key::Letter('y'), false, true);
+        key(&mut tree, Key::Letter('y'), false, true);
+        key(&mut tree, Key::Letter('y'), false, true);
+        assert_eq!(text(&editor), "[main]\na = 10\nb = 2 # two");
+    }
+
+    #[test]
+    fn only_visible_lines_are_laid_out() {
+        let (_tree, viewer, editor) = editor_in_viewer(TextEditor::new().with_text(big_document(100000)), Size::new(300.0, 200.0));
+        let line_height = editor.cast_element::<TextEditor>().unwrap().format.line_height();
+        let visible = editor.cast_element::<TextEditor>().unwrap().visible_lines();
+        assert_eq!(visible.start, 0);
+        assert!(visible.len() <= (200.0 / line_height) as usize + 1);
+
+        viewer.cast_element_mut::<ScrollViewer>().unwrap().vertical_offset().set(line_height * 50000.0);
+        viewer.layout(Size::new(300.0, 200.0));
+
+        let visible = editor.cast_element::<TextEditor>().unwrap().visible_lines();
+        assert_eq!(visible.start, 50000);
+        assert_eq!(viewer.cast_element::<ScrollViewer>().unwrap().extent().h, line_height * 100000.0);
+    }
+
+    #[test]
+    fn caret_is_kept_in_view() {
+        let (mut tree, viewer, editor) = editor_in_viewer(TextEditor::new().with_text(big_document(1000)), Size::new(300.0, 200.0));
+        let line_height = editor.cast_element::<TextEditor>().unwrap().format.line_height();
+
+        key(&mut tree, Key::End, false, true);
+        viewer.layout(Size::new(300.0, 200.0));
+
+        let offset = viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get();
+        let viewport = viewer.cast_element::<ScrollViewer>().unwrap().viewport_size();
+        assert_eq!(offset, line_height * 1000.0 - viewport.h);
+        assert!(editor.cast_element::<TextEditor>().unwrap().visible_lines().end == 1000);
+    }
+
+    #[test]
+    fn soft_wrapped_lines_grow_and_move_by_rows() {
+        let format = TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE).with_wrapping(TextWrapping::Wrap);
+        let long = "word ".repeat(40);
+        let (mut tree, _viewer, editor) = editor_in_viewer(TextEditor::new().with_format(format).with_text(format!("{}\nnext", long)), Size::new(200.0, 300.0));
+        let (rows, line_height) = {
+            let editor = editor.cast_element::<TextEditor>().unwrap();
+            (editor.visible[0].layout.lines().len(), editor.format.line_height())
+        };
+        assert!(rows > 1);
+        assert_eq!(editor.cast_element::<TextEditor>().unwrap().heights.position(1), rows as f32 * line_height);
+
+        key(&mut tree, Key::Down, false, false);
+        assert_eq!(caret(&editor).0, 0);
+        assert!(caret(&editor).1 > 0);
+    }
+
+    #[test]
+    fn clicking_places_the_caret() {
+        let (mut tree, _viewer, editor) = editor_in_viewer(TextEditor::new().with_text("abc\ndef"), Size::new(300.0, 200.0));
+        let (left, line_height) = {
+            let editor = editor.cast_element::<TextEditor>().unwrap();
+            (editor.text_left(), editor.format.line_height())
+        };
+        let width = Font::default_font().measure("de", DEFAULT_FONT_SIZE).w;
+        let pos = Point::new(left + width + 0.5, line_height * 1.5);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, pos));
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(left, 1.0)));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, Point::new(left, 1.0)));
+
+        assert_eq!(editor.cast_element::<TextEditor>().unwrap().selection(), 0..6);
+        assert_eq!(caret(&editor), (0, 0));
+    }
+
+    #[test]
+    fn gutter_fits_the_largest_line_number() {
+        let digit = Font::default_font().measure("0", DEFAULT_FONT_SIZE).w;
+        let small = TextEditor::new().with_text("a\nb");
+        let large = TextEditor::new().with_text(big_document(1000));
+
+        assert_eq!(large.gutter_width() - small.gutter_width(), 3.0 * digit);
+        assert_eq!(TextEditor::new().with_line_numbers(false).text_left(), TEXT_MARGIN);
+    }
+}
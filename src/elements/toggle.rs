@@ -0,0 +1,887 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use msg::*;
+use property::*;
+use text::*;
+use text::layout::*;
+use elements::button::{ButtonState, ButtonStyle, PressTracker, update_label_color};
+use elements::text_block::{TextBlock, DEFAULT_FONT_SIZE};
+use std::cell::Cell;
+use std::f32;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const BOX_SIZE: f32 = 14.0;
+const SWITCH_SIZE: (f32, f32) = (30.0, 16.0);
+const KNOB_INSET: f32 = 2.0;
+const LABEL_SPACING: f32 = 6.0;
+const ANIMATION_MS: u64 = 120;
+const FRAME_MS: u64 = 16;
+
+/// Raised by a `CheckBox`, `RadioButton` or `ToggleSwitch` when the user
+/// changes its value; `None` is the indeterminate state of a check box.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Toggled(pub Option<bool>);
+
+impl Message for Toggled {}
+
+fn accent(state: ButtonState) -> Color {
+    match state {
+        ButtonState::Normal => Color::argb(1.0, 0.2, 0.45, 0.8),
+        ButtonState::Hover => Color::argb(1.0, 0.25, 0.52, 0.9),
+        ButtonState::Pressed => Color::argb(1.0, 0.15, 0.35, 0.65),
+        ButtonState::Disabled => Color::argb(1.0, 0.75, 0.75, 0.75),
+    }
+}
+
+fn bool_property(name: &str, value: bool) -> Property<bool> {
+    Property::new(name, AccessType::ReadWrite, Box::new(source::Variable::new(value))).unwrap()
+}
+
+/// Adds the label as the node's only child, centered vertically.
+fn add_label(node: &mut NodeRef, label: Option<Box<Element>>) {
+    if let Some(label) = label {
+        let label = node.add_child(label);
+        label.set_layout_properties(LayoutProperties::new().with_alignment(Alignment::Start, Alignment::Center));
+    }
+}
+
+fn label(node: &NodeRef) -> Option<NodeRef> {
+    node.children().iter().next().cloned()
+}
+
+/// The size of a glyph followed by the label.
+fn measure_with_label(node: &NodeRef, glyph: Size) -> Size {
+    match label(node) {
+        Some(label) => {
+            let label = label.measure();
+            Size::new(glyph.w + LABEL_SPACING + label.w, glyph.h.max(label.h))
+        }
+        None => glyph,
+    }
+}
+
+fn arrange_label(node: &NodeRef, glyph: Size, container: Size) {
+    if let Some(label) = label(node) {
+        let left = glyph.w + LABEL_SPACING;
+        label.arrange(Rect::pos_size(Point::new(left, 0.0), Size::new((container.w - left).max(0.0), container.h)));
+    }
+}
+
+/// Where the glyph is drawn: at the start, centered vertically.
+fn glyph_rect(node: &NodeRef, glyph: Size) -> Rect {
+    let content = node.content_rect();
+    Rect::pos_size(Point::new(content.pos.x, content.pos.y + ((content.size.h - glyph.h) / 2.0).max(0.0)), glyph)
+}
+
+/// Repaints `node` each time `property` is set.
+fn repaint_on_set<T: Clone>(property: &Property<T>, node: &NodeRef) {
+    let node = node.downgrade();
+    property.observe(move |_| if let Some(node) = node.upgrade() {
+        node.invalidate_render();
+    });
+}
+
+fn focus_ring(rect: Rect, radius: f32, color: Color) -> RenderCommand {
+    let ring = rect.expand(Thickness::uniform(2.0));
+    RenderCommand::Border(ring, BorderStyle::new(Color::transparent())
+        .with_border(Thickness::uniform(1.0), color)
+        .with_corner_radius(CornerRadius::uniform(radius + 2.0)))
+}
+
+pub fn render_mark(cmd: &mut RenderCommandList, rect: Rect, mark: &str, color: Color) {
+    let layout = TextLayout::new(mark, TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE), f32::INFINITY);
+    let size = layout.size();
+    let origin = Point::new(rect.pos.x + (rect.size.w - size.w) / 2.0, rect.pos.y + (rect.size.h - size.h) / 2.0);
+    for mut run in layout.runs() {
+        run.color = color;
+        cmd.add(RenderCommand::Text(origin, run));
+    }
+}
+
+/// A box that is checked, unchecked or, for three-state boxes,
+/// indeterminate, with an optional label. Clicking it or pressing Space
+/// moves to the next state.
+pub struct CheckBox {
+    node: NodeWeakRef,
+    label: Option<Box<Element>>,
+    checked: Property<Option<bool>>,
+    three_state: bool,
+    style: ButtonStyle,
+    enabled: bool,
+    press: PressTracker,
+    label_color: Option<Color>,
+}
+
+impl CheckBox {
+    pub fn new<T: Into<String>>(label: T) -> CheckBox {
+        CheckBox::with_content(Some(Box::new(TextBlock::new(label))))
+    }
+
+    pub fn with_content(label: Option<Box<Element>>) -> CheckBox {
+        CheckBox {
+            node: NodeWeakRef::empty(),
+            label: label,
+            checked: Property::new("IsChecked", AccessType::ReadWrite, Box::new(source::Variable::new(Some(false)))).unwrap(),
+            three_state: false,
+            style: ButtonStyle::new(),
+            enabled: true,
+            press: PressTracker::new(),
+            label_color: None,
+        }
+    }
+
+    pub fn with_checked(self, checked: Option<bool>) -> CheckBox {
+        self.checked.set(checked);
+        self
+    }
+
+    /// Lets the user pick the indeterminate state, after checked.
+    pub fn with_three_state(mut self, three_state: bool) -> CheckBox {
+        self.three_state = three_state;
+        self
+    }
+
+    /// Sets the look of the box frame in each state.
+    pub fn with_style(mut self, style: ButtonStyle) -> CheckBox {
+        self.style = style;
+        self
+    }
+
+    /// The state; `None` is indeterminate.
+    pub fn is_checked(&self) -> &Property<Option<bool>> {
+        &self.checked
+    }
+
+    pub fn set_checked(&mut self, checked: Option<bool>) {
+        self.checked.set(checked);
+    }
+
+    /// Moves to the next state and raises `Toggled`.
+    pub fn toggle(&mut self) {
+        let next = match self.checked.get() {
+            Some(false) => Some(true),
+            Some(true) if self.three_state => None,
+            _ => Some(false),
+        };
+        self.set_checked(next);
+        if let Some(node) = self.node.upgrade() {
+            node.raise(Box::new(Toggled(next)));
+        }
+    }
+
+    pub fn state(&self) -> ButtonState {
+        self.press.state(self.enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.press.cancel();
+        if let Some(node) = self.node.upgrade() {
+            if let Some(label) = label(&node) {
+                update_label_color(&label, enabled, &mut self.label_color, self.style.disabled_text);
+            }
+            node.invalidate_render();
+        }
+    }
+}
+
+impl Element for CheckBox {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[CheckBox] Init");
+        self.node = node.downgrade();
+        add_label(&mut node, self.label.take());
+        repaint_on_set(&self.checked, &node);
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[CheckBox] Measure");
+        measure_with_label(&node, Size::new(BOX_SIZE, BOX_SIZE))
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[CheckBox] Layout");
+        arrange_label(&node, Size::new(BOX_SIZE, BOX_SIZE), container);
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[CheckBox] Render");
+        let mut cmd = RenderCommandList::new();
+        let rect = glyph_rect(&node, Size::new(BOX_SIZE, BOX_SIZE));
+        let state = self.state();
+        let checked = self.checked.get();
+        let style = if checked == Some(false) {
+            self.style.for_state(state)
+        } else {
+            let fill = accent(state);
+            BorderStyle::new(fill).with_border(Thickness::uniform(1.0), fill).with_corner_radius(CornerRadius::uniform(2.0))
+        };
+        cmd.add(RenderCommand::Border(rect, style));
+        let mark = Color::argb(1.0, 1.0, 1.0, 1.0);
+        match checked {
+            Some(true) => render_mark(&mut cmd, rect, "\u{2713}", mark),
+            None => cmd.add(RenderCommand::Rect(rect.inset(Thickness::hv(3.0, 6.0)), mark)),
+            Some(false) => {}
+        }
+        if self.enabled && node.has_focus() {
+            cmd.add(focus_ring(rect, 2.0, self.style.focus));
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let state = self.state();
+        let (handled, clicked) = self.press.handle_event(&node, event, self.enabled);
+        if clicked {
+            self.toggle();
+        }
+        if self.state() != state {
+            node.invalidate_render();
+        }
+        handled
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// One of a set of mutually exclusive options. Checking a radio button
+/// unchecks the others in its group: the radio buttons anywhere in the tree
+/// with the same group name, or, without a name, the unnamed ones sharing
+/// its parent. Arrow keys check the previous or next button of the group.
+pub struct RadioButton {
+    node: NodeWeakRef,
+    label: Option<Box<Element>>,
+    checked: Property<bool>,
+    group: Option<String>,
+    style: ButtonStyle,
+    enabled: bool,
+    press: PressTracker,
+    label_color: Option<Color>,
+}
+
+impl RadioButton {
+    pub fn new<T: Into<String>>(label: T) -> RadioButton {
+        RadioButton::with_content(Some(Box::new(TextBlock::new(label))))
+    }
+
+    pub fn with_content(label: Option<Box<Element>>) -> RadioButton {
+        RadioButton {
+            node: NodeWeakRef::empty(),
+            label: label,
+            checked: bool_property("IsChecked", false),
+            group: None,
+            style: ButtonStyle::new(),
+            enabled: true,
+            press: PressTracker::new(),
+            label_color: None,
+        }
+    }
+
+    pub fn with_group<T: Into<String>>(mut self, group: T) -> RadioButton {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Checks the button initially, without unchecking the others.
+    pub fn with_checked(self, checked: bool) -> RadioButton {
+        self.checked.set(checked);
+        self
+    }
+
+    pub fn with_style(mut self, style: ButtonStyle) -> RadioButton {
+        self.style = style;
+        self
+    }
+
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_ref().map(|group| group.as_str())
+    }
+
+    /// Checking it unchecks the rest of the group.
+    pub fn is_checked(&self) -> &Property<bool> {
+        &self.checked
+    }
+
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked.set(checked);
+    }
+
+    pub fn state(&self) -> ButtonState {
+        self.press.state(self.enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.press.cancel();
+        if let Some(node) = self.node.upgrade() {
+            if let Some(label) = label(&node) {
+                update_label_color(&label, enabled, &mut self.label_color, self.style.disabled_text);
+            }
+            node.invalidate_render();
+        }
+    }
+
+    /// Checks `target`, another button of the group, which unchecks the
+    /// rest. This button is unchecked directly, as its element is borrowed
+    /// while it handles input and so is skipped by the target.
+    fn check_member(&mut self, target: &NodeRef) {
+        self.checked.set(false);
+        target.cast_element::<RadioButton>().unwrap().checked.set(true);
+    }
+
+    fn check(&mut self) {
+        if !self.checked.get() {
+            self.set_checked(true);
+            if let Some(node) = self.node.upgrade() {
+                node.raise(Box::new(Toggled(Some(true))));
+            }
+        }
+    }
+
+    /// Focuses and checks the next enabled button of the group, wrapping around.
+    fn move_in_group(&mut self, node: &NodeRef, forward: bool) -> bool {
+        let members = group_members(node, self.group());
+        let index = match members.iter().position(|member| *member == *node) {
+            Some(index) => index,
+            None => return false,
+        };
+        let len = members.len();
+        for step in 1..len {
+            let next = if forward { (index + step) % len } else { (index + len - step) % len };
+            let member = &members[next];
+            let enabled = member.cast_element::<RadioButton>().map_or(false, |radio| radio.enabled);
+            if enabled {
+                member.focus();
+                let checked = member.cast_element::<RadioButton>().unwrap().checked.get();
+                if !checked {
+                    self.check_member(member);
+                    member.raise(Box::new(Toggled(Some(true))));
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Element for RadioButton {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[RadioButton] Init");
+        self.node = node.downgrade();
+        add_label(&mut node, self.label.take());
+
+        let radio = node.downgrade();
+        let group = self.group.clone();
+        self.checked.observe(move |&checked| if let Some(radio) = radio.upgrade() {
+            if checked {
+                uncheck_others(&radio, group.as_ref().map(|group| group.as_str()));
+            }
+            radio.invalidate_render();
+        });
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[RadioButton] Measure");
+        measure_with_label(&node, Size::new(BOX_SIZE, BOX_SIZE))
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[RadioButton] Layout");
+        arrange_label(&node, Size::new(BOX_SIZE, BOX_SIZE), container);
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[RadioButton] Render");
+        let mut cmd = RenderCommandList::new();
+        let rect = glyph_rect(&node, Size::new(BOX_SIZE, BOX_SIZE));
+        let state = self.state();
+        let round = CornerRadius::uniform(BOX_SIZE / 2.0);
+        if self.checked.get() {
+            cmd.add(RenderCommand::Border(rect, self.style.for_state(state).with_corner_radius(round)));
+            let dot = rect.inset(Thickness::uniform(4.0));
+            cmd.add(RenderCommand::Border(dot, BorderStyle::new(accent(state)).with_corner_radius(CornerRadius::uniform(dot.size.w / 2.0))));
+        } else {
+            cmd.add(RenderCommand::Border(rect, self.style.for_state(state).with_corner_radius(round)));
+        }
+        if self.enabled && node.has_focus() {
+            cmd.add(focus_ring(rect, BOX_SIZE / 2.0, self.style.focus));
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let state = self.state();
+        let handled = match *event {
+            InputEvent::KeyDown(key, modifiers) if self.enabled && modifiers.is_empty() && (key == Key::Up || key == Key::Left) => {
+                self.move_in_group(&node, false)
+            }
+            InputEvent::KeyDown(key, modifiers) if self.enabled && modifiers.is_empty() && (key == Key::Down || key == Key::Right) => {
+                self.move_in_group(&node, true)
+            }
+            _ => {
+                let (handled, clicked) = self.press.handle_event(&node, event, self.enabled);
+                if clicked {
+                    self.check();
+                }
+                handled
+            }
+        };
+        if self.state() != state {
+            node.invalidate_render();
+        }
+        handled
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// The radio buttons of `node`'s group, `node` included, in tree order.
+/// Buttons whose elements are mutably borrowed, like one handling input,
+/// can't be told apart and are left out.
+fn group_members(node: &NodeRef, group: Option<&str>) -> Vec<NodeRef> {
+    let mut members = Vec::new();
+    match group {
+        Some(group) => {
+            let mut root = node.clone();
+            while let Some(parrent) = root.parrent() {
+                root = parrent;
+            }
+            collect_named(&root, node, group, &mut members);
+        }
+        None => {
+            let siblings: Vec<NodeRef> = match node.parrent() {
+                Some(parrent) => parrent.children().iter().cloned().collect(),
+                None => vec![node.clone()],
+            };
+            for sibling in siblings {
+                let unnamed = sibling == *node
+                    || sibling.try_cast_element::<RadioButton>().map_or(false, |radio| radio.group.is_none());
+                if unnamed {
+                    members.push(sibling);
+                }
+            }
+        }
+    }
+    members
+}
+
+fn collect_named(parrent: &NodeRef, node: &NodeRef, group: &str, members: &mut Vec<NodeRef>) {
+    let is_member = *parrent == *node
+        || parrent.try_cast_element::<RadioButton>().map_or(false, |radio| radio.group() == Some(group));
+    if is_member {
+        members.push(parrent.clone());
+    }
+    let children: Vec<NodeRef> = parrent.children().iter().cloned().collect();
+    for child in &children {
+        collect_named(child, node, group, members);
+    }
+}
+
+/// Unchecks the buttons of `node`'s group other than `node`.
+fn uncheck_others(node: &NodeRef, group: Option<&str>) {
+    for member in group_members(node, group) {
+        if member != *node {
+            if let Some(radio) = member.try_cast_element::<RadioButton>() {
+                if radio.checked.get() {
+                    radio.checked.set(false);
+                }
+            }
+        }
+    }
+}
+
+/// An on/off switch whose knob slides to the new side when it is toggled.
+pub struct ToggleSwitch {
+    node: NodeWeakRef,
+    label: Option<Box<Element>>,
+    on: Property<bool>,
+    style: ButtonStyle,
+    enabled: bool,
+    press: PressTracker,
+    label_color: Option<Color>,
+    /// When the knob started moving, and from where.
+    animation: Rc<Cell<Option<(Instant, f32)>>>,
+}
+
+impl ToggleSwitch {
+    pub fn new() -> ToggleSwitch {
+        ToggleSwitch::with_content(None)
+    }
+
+    pub fn with_label<T: Into<String>>(label: T) -> ToggleSwitch {
+        ToggleSwitch::with_content(Some(Box::new(TextBlock::new(label))))
+    }
+
+    pub fn with_content(label: Option<Box<Element>>) -> ToggleSwitch {
+        ToggleSwitch {
+            node: NodeWeakRef::empty(),
+            label: label,
+            on: bool_property("IsOn", false),
+            style: ButtonStyle::new(),
+            enabled: true,
+            press: PressTracker::new(),
+            label_color: None,
+            animation: Rc::new(Cell::new(None)),
+        }
+    }
+
+    pub fn with_on(self, on: bool) -> ToggleSwitch {
+        self.on.set(on);
+        self
+    }
+
+    pub fn with_style(mut self, style: ButtonStyle) -> ToggleSwitch {
+        self.style = style;
+        self
+    }
+
+    /// Changing it slides the knob to the new side.
+    pub fn is_on(&self) -> &Property<bool> {
+        &self.on
+    }
+
+    pub fn set_on(&mut self, on: bool) {
+        self.on.set(on);
+    }
+
+    /// Flips the switch and raises `Toggled`.
+    pub fn toggle(&mut self) {
+        let on = !self.on.get();
+        self.set_on(on);
+        if let Some(node) = self.node.upgrade() {
+            node.raise(Box::new(Toggled(Some(on))));
+        }
+    }
+
+    pub fn state(&self) -> ButtonState {
+        self.press.state(self.enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.press.cancel();
+        if let Some(node) = self.node.upgrade() {
+            if let Some(label) = label(&node) {
+                update_label_color(&label, enabled, &mut self.label_color, self.style.disabled_text);
+            }
+            node.invalidate_render();
+        }
+    }
+
+    fn knob_position(&self, now: Instant) -> f32 {
+        knob_position(self.on.get(), self.animation.get(), now)
+    }
+
+    fn is_animating(&self, now: Instant) -> bool {
+        self.animation.get().map_or(false, |(start, _)| now < start + Duration::from_millis(ANIMATION_MS))
+    }
+}
+
+/// How far the knob of a switch that is `on` is along the track at `now`,
+/// from 0 (off) to 1 (on).
+fn knob_position(on: bool, animation: Option<(Instant, f32)>, now: Instant) -> f32 {
+    let target = if on { 1.0 } else { 0.0 };
+    match animation {
+        Some((start, from)) if now < start + Duration::from_millis(ANIMATION_MS) => {
+            let elapsed = now - start;
+            let t = (elapsed.as_secs() as f32 * 1000.0 + elapsed.subsec_nanos() as f32 / 1000000.0) / ANIMATION_MS as f32;
+            let eased = 1.0 - (1.0 - t) * (1.0 - t);
+            from + (target - from) * eased
+        }
+        _ => target,
+    }
+}
+
+impl Element for ToggleSwitch {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[ToggleSwitch] Init");
+        self.node = node.downgrade();
+        add_label(&mut node, self.label.take());
+
+        // The knob starts sliding from wherever the side it left put it.
+        let switch = node.downgrade();
+        let animation = self.animation.clone();
+        let was_on = Cell::new(self.on.get());
+        self.on.observe(move |&on| if let Some(switch) = switch.upgrade() {
+            if on != was_on.get() {
+                let now = Instant::now();
+                animation.set(Some((now, knob_position(was_on.get(), animation.get(), now))));
+                was_on.set(on);
+            }
+            switch.invalidate_render();
+        });
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[ToggleSwitch] Measure");
+        measure_with_label(&node, Size::from_tuple(SWITCH_SIZE))
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[ToggleSwitch] Layout");
+        arrange_label(&node, Size::from_tuple(SWITCH_SIZE), container);
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[ToggleSwitch] Render");
+        let mut cmd = RenderCommandList::new();
+        let now = Instant::now();
+        let track = glyph_rect(&node, Size::from_tuple(SWITCH_SIZE));
+        let state = self.state();
+        let position = self.knob_position(now);
+        let round = CornerRadius::uniform(track.size.h / 2.0);
+        let off = self.style.for_state(state).with_corner_radius(round);
+        let on = accent(state);
+        // The track blends from the off to the on look as the knob moves.
+        let blend = |a: Color, b: Color| {
+            let (a, b) = (a.to_tuple_argb(), b.to_tuple_argb());
+            Color::argb(a.0 + (b.0 - a.0) * position, a.1 + (b.1 - a.1) * position,
+                        a.2 + (b.2 - a.2) * position, a.3 + (b.3 - a.3) * position)
+        };
+        let style = BorderStyle::new(blend(off.background, on))
+            .with_border(off.thickness, blend(off.top_color, on))
+            .with_corner_radius(round);
+        cmd.add(RenderCommand::Border(track, style));
+
+        let knob_size = track.size.h - 2.0 * KNOB_INSET;
+        let travel = track.size.w - 2.0 * KNOB_INSET - knob_size;
+        let knob = Rect::pos_size(Point::new(track.pos.x + KNOB_INSET + travel * position, track.pos.y + KNOB_INSET), Size::new(knob_size, knob_size));
+        let knob_color = if state == ButtonState::Disabled { Color::argb(1.0, 0.9, 0.9, 0.9) } else { Color::argb(1.0, 1.0, 1.0, 1.0) };
+        cmd.add(RenderCommand::Border(knob, BorderStyle::new(knob_color).with_corner_radius(CornerRadius::uniform(knob_size / 2.0))));
+
+        if self.enabled && node.has_focus() {
+            cmd.add(focus_ring(track, track.size.h / 2.0, self.style.focus));
+        }
+        if self.is_animating(now) {
+            node.invalidate_render_at(now + Duration::from_millis(FRAME_MS));
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        let state = self.state();
+        let (handled, clicked) = self.press.handle_event(&node, event, self.enabled);
+        if clicked {
+            self.toggle();
+        }
+        if self.state() != state {
+            node.invalidate_render();
+        }
+        handled
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stacks its children in rows and counts `Toggled` messages.
+    struct Panel {
+        toggled: Vec<Option<bool>>,
+    }
+
+    impl Element for Panel {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+            for (i, child) in node.children().iter().enumerate() {
+                child.arrange(Rect::pos_size(Point::new(0.0, i as f32 * 20.0), Size::new(100.0, 20.0)));
+            }
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+            match message.cast_ref::<Toggled>() {
+                Some(&Toggled(value)) => {
+                    self.toggled.push(value);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    fn panel(children: Vec<Box<Element>>) -> (Tree, NodeRef, Vec<NodeRef>) {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Panel { toggled: Vec::new() }))).unwrap();
+        let nodes = children.into_iter().map(|child| root.add_child(child)).collect();
+        root.layout(Size::new(100.0, 200.0));
+        (tree, root, nodes)
+    }
+
+    fn click(tree: &mut Tree, node: &NodeRef) {
+        let pos = node.window_rect().pos + Point::new(5.0, 5.0);
+        tree.dispatch_event(&InputEvent::MouseMove(pos));
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, pos));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, pos));
+    }
+
+    fn toggled(root: &NodeRef) -> Vec<Option<bool>> {
+        root.cast_element::<Panel>().unwrap().toggled.clone()
+    }
+
+    fn radio_checked(node: &NodeRef) -> bool {
+        node.cast_element::<RadioButton>().unwrap().is_checked().get()
+    }
+
+    #[test]
+    fn check_box_cycles_through_three_states() {
+        let (mut tree, root, nodes) = panel(vec![Box::new(CheckBox::new("Three").with_three_state(true))]);
+        let check_box = &nodes[0];
+
+        click(&mut tree, check_box);
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Space, Modifiers::none()));
+        tree.dispatch_event(&InputEvent::KeyUp(Key::Space, Modifiers::none()));
+        click(&mut tree, check_box);
+
+        assert_eq!(toggled(&root), vec![Some(true), None, Some(false)]);
+        assert_eq!(check_box.cast_element::<CheckBox>().unwrap().is_checked().get(), Some(false));
+    }
+
+    #[test]
+    fn two_state_check_box_skips_indeterminate() {
+        let (mut tree, _root, nodes) = panel(vec![Box::new(CheckBox::new("Two").with_checked(None))]);
+
+        click(&mut tree, &nodes[0]);
+        assert_eq!(nodes[0].cast_element::<CheckBox>().unwrap().is_checked().get(), Some(false));
+        click(&mut tree, &nodes[0]);
+        click(&mut tree, &nodes[0]);
+        assert_eq!(nodes[0].cast_element::<CheckBox>().unwrap().is_checked().get(), Some(false));
+    }
+
+    #[test]
+    fn disabled_check_box_ignores_clicks() {
+        let (mut tree, root, nodes) = panel(vec![Box::new(CheckBox::new("Off"))]);
+        nodes[0].cast_element_mut::<CheckBox>().unwrap().set_enabled(false);
+
+        click(&mut tree, &nodes[0]);
+
+        assert!(toggled(&root).is_empty());
+        assert_eq!(nodes[0].cast_element::<CheckBox>().unwrap().state(), ButtonState::Disabled);
+        let label = label(&nodes[0]).unwrap();
+        assert_eq!(label.cast_element::<TextBlock>().unwrap().format().color, ButtonStyle::new().disabled_text);
+    }
+
+    #[test]
+    fn radio_buttons_sharing_a_parent_are_exclusive() {
+        let (mut tree, root, nodes) = panel(vec![
+            Box::new(RadioButton::new("A").with_checked(true)),
+            Box::new(RadioButton::new("B")),
+            Box::new(RadioButton::new("C").with_group("other").with_checked(true)),
+        ]);
+
+        click(&mut tree, &nodes[1]);
+        assert!(!radio_checked(&nodes[0]));
+        assert!(radio_checked(&nodes[1]));
+        assert!(radio_checked(&nodes[2]));
+
+        click(&mut tree, &nodes[1]);
+        assert_eq!(toggled(&root), vec![Some(true)]);
+    }
+
+    #[test]
+    fn named_groups_span_the_tree() {
+        let (mut tree, _root, nodes) = panel(vec![
+            Box::new(RadioButton::new("A").with_group("g").with_checked(true)),
+            Box::new(CheckBox::new("Container")),
+        ]);
+        let mut container = nodes[1].clone();
+        let nested = container.add_child(Box::new(RadioButton::new("B").with_group("g")));
+        nested.arrange(Rect::pos_size(Point::new(0.0, 0.0), Size::new(20.0, 20.0)));
+
+        nested.cast_element_mut::<RadioButton>().unwrap().set_checked(true);
+        assert!(!radio_checked(&nodes[0]));
+
+        nodes[0].focus();
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Down, Modifiers::none()));
+        assert!(radio_checked(&nested) && nested.has_focus());
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Down, Modifiers::none()));
+        assert!(radio_checked(&nodes[0]) && !radio_checked(&nested));
+    }
+
+    #[test]
+    fn toggle_switch_animates_its_knob() {
+        let (mut tree, root, nodes) = panel(vec![Box::new(ToggleSwitch::with_label("Wi-Fi"))]);
+
+        click(&mut tree, &nodes[0]);
+
+        let switch = nodes[0].cast_element::<ToggleSwitch>().unwrap();
+        let (start, _) = switch.animation.get().unwrap();
+        assert!(switch.is_on().get());
+        assert_eq!(switch.knob_position(start), 0.0);
+        let halfway = switch.knob_position(start + Duration::from_millis(ANIMATION_MS / 2));
+        assert!(halfway > 0.5 && halfway < 1.0);
+        assert_eq!(switch.knob_position(start + Duration::from_millis(ANIMATION_MS)), 1.0);
+        assert_eq!(toggled(&root), vec![Some(true)]);
+    }
+
+    #[test]
+    fn label_follows_the_glyph() {
+        let (_tree, _root, nodes) = panel(vec![Box::new(ToggleSwitch::with_label("Label"))]);
+        let label = label(&nodes[0]).unwrap();
+
+        assert_eq!(label.rect().pos.x, SWITCH_SIZE.0 + LABEL_SPACING);
+        assert_eq!(nodes[0].measure().w, SWITCH_SIZE.0 + LABEL_SPACING + label.measure().w);
+    }
+
+    #[test]
+    fn setting_the_properties_repaints_and_unchecks_the_group() {
+        let (tree, _root, nodes) = panel(vec![
+            Box::new(RadioButton::new("A").with_checked(true)),
+            Box::new(RadioButton::new("B")),
+            Box::new(CheckBox::new("Check")),
+        ]);
+        tree.take_invalidation();
+
+        nodes[1].cast_element::<RadioButton>().unwrap().is_checked().set(true);
+        assert!(!radio_checked(&nodes[0]) && radio_checked(&nodes[1]));
+        assert!(tree.take_invalidation().1);
+
+        nodes[2].cast_element::<CheckBox>().unwrap().is_checked().set(Some(true));
+        assert!(tree.take_invalidation().1);
+    }
+
+    #[test]
+    fn setting_the_switch_property_animates_the_knob() {
+        let (tree, _root, nodes) = panel(vec![Box::new(ToggleSwitch::new())]);
+        tree.take_invalidation();
+
+        nodes[0].cast_element::<ToggleSwitch>().unwrap().is_on().set(true);
+
+        assert!(tree.take_invalidation().1);
+        let switch = nodes[0].cast_element::<ToggleSwitch>().unwrap();
+        let (start, from) = switch.animation.get().unwrap();
+        assert_eq!(from, 0.0);
+        assert_eq!(switch.knob_position(start + Duration::from_millis(ANIMATION_MS)), 1.0);
+    }
+}
@@ -0,0 +1,950 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+use input::*;
+use msg::*;
+use text::*;
+use text::layout::*;
+use elements::button::ButtonStyle;
+use elements::scroll::{ScrollViewer, ScrollBarVisibility};
+use elements::text_block::{TextBlock, DEFAULT_FONT_SIZE};
+use elements::toggle::render_mark;
+use elements::virtualizing::{ItemSource, VirtualizingList, template};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+const INDENT: f32 = 16.0;
+const EXPANDER_WIDTH: f32 = 16.0;
+const ROW_PADDING: (f32, f32) = (4.0, 2.0);
+
+/// An item of a `TreeView`. Ids identify items in selections and messages,
+/// so they have to be unique within the tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeItem {
+    id: String,
+    label: String,
+    /// `None` until the children of a lazy item are loaded.
+    children: Option<Vec<TreeItem>>,
+    expanded: bool,
+}
+
+impl TreeItem {
+    /// An item without children.
+    pub fn new<I: Into<String>, L: Into<String>>(id: I, label: L) -> TreeItem {
+        TreeItem {
+            id: id.into(),
+            label: label.into(),
+            children: Some(Vec::new()),
+            expanded: false,
+        }
+    }
+
+    pub fn with_children(mut self, children: Vec<TreeItem>) -> TreeItem {
+        self.children = Some(children);
+        self
+    }
+
+    /// Marks the item as having children that the tree view's loader
+    /// provides when it is first expanded.
+    pub fn with_lazy_children(mut self) -> TreeItem {
+        self.children = None;
+        self
+    }
+
+    pub fn with_expanded(mut self, expanded: bool) -> TreeItem {
+        self.expanded = expanded;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The children, or `None` if they are yet to be loaded.
+    pub fn children(&self) -> Option<&[TreeItem]> {
+        self.children.as_ref().map(|children| &children[..])
+    }
+
+    pub fn is_expanded(&self) -> bool {
+        self.expanded && self.is_expandable()
+    }
+
+    /// Whether the item has, or may have, children and shows an expander.
+    pub fn is_expandable(&self) -> bool {
+        self.children.as_ref().map_or(true, |children| !children.is_empty())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SelectionMode {
+    Single,
+    /// Ctrl+click toggles items and Shift+click selects ranges.
+    Multiple,
+}
+
+/// Raised by a `TreeView` when the user changes the selection. Holds the
+/// ids of the selected items, top to bottom.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeSelectionChanged(pub Vec<String>);
+
+impl Message for TreeSelectionChanged {}
+
+/// Raised by a `TreeView` when the user expands (`true`) or collapses an item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemExpanded(pub String, pub bool);
+
+impl Message for ItemExpanded {}
+
+/// Raised by a row when it is clicked.
+struct RowPressed(usize);
+
+impl Message for RowPressed {}
+
+/// Raised by a row when its expander is clicked.
+struct ExpanderPressed(usize);
+
+impl Message for ExpanderPressed {}
+
+/// How clicking or moving to a row changes the selection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Select {
+    Only,
+    Toggle,
+    /// From the anchor, the last item selected alone or toggled.
+    Range,
+    /// Moves the current item but keeps the selection.
+    Move,
+}
+
+fn item_at<'a>(items: &'a [TreeItem], path: &[usize]) -> &'a TreeItem {
+    let item = &items[path[0]];
+    if path.len() == 1 {
+        item
+    } else {
+        item_at(item.children.as_ref().unwrap(), &path[1..])
+    }
+}
+
+fn item_at_mut<'a>(items: &'a mut [TreeItem], path: &[usize]) -> &'a mut TreeItem {
+    let item = &mut items[path[0]];
+    if path.len() == 1 {
+        item
+    } else {
+        item_at_mut(item.children.as_mut().unwrap(), &path[1..])
+    }
+}
+
+/// Finds the path of the item `id` among the loaded items.
+fn find_path(items: &[TreeItem], id: &str, path: &mut Vec<usize>) -> bool {
+    for (i, item) in items.iter().enumerate() {
+        path.push(i);
+        if item.id == id {
+            return true;
+        }
+        if let Some(ref children) = item.children {
+            if find_path(children, id, path) {
+                return true;
+            }
+        }
+        path.pop();
+    }
+    false
+}
+
+/// Appends the paths of the visible items, top to bottom.
+fn flatten(items: &[TreeItem], path: &mut Vec<usize>, rows: &mut Vec<Vec<usize>>) {
+    for (i, item) in items.iter().enumerate() {
+        path.push(i);
+        rows.push(path.clone());
+        if let (true, Some(ref children)) = (item.expanded, item.children.as_ref()) {
+            flatten(children, path, rows);
+        }
+        path.pop();
+    }
+}
+
+/// The items, their visible rows and the selection, shared by a tree view
+/// and its rows.
+struct TreeModel {
+    roots: Vec<TreeItem>,
+    /// The paths of the visible items, top to bottom.
+    rows: Vec<Vec<usize>>,
+    row_ids: HashMap<String, usize>,
+    selected: HashSet<String>,
+    current: Option<String>,
+    anchor: Option<String>,
+    view: NodeWeakRef,
+}
+
+impl ItemSource for TreeModel {
+    fn len(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+impl TreeModel {
+    fn rebuild(&mut self) {
+        let mut rows = Vec::new();
+        flatten(&self.roots, &mut Vec::new(), &mut rows);
+        self.row_ids = rows.iter().enumerate().map(|(row, path)| (item_at(&self.roots, path).id.clone(), row)).collect();
+        self.rows = rows;
+    }
+
+    fn item(&self, row: usize) -> &TreeItem {
+        item_at(&self.roots, &self.rows[row])
+    }
+
+    fn row_of(&self, id: &str) -> Option<usize> {
+        self.row_ids.get(id).cloned()
+    }
+
+    fn current_row(&self) -> Option<usize> {
+        self.current.as_ref().and_then(|id| self.row_of(id))
+    }
+
+    fn parent_row(&self, row: usize) -> Option<usize> {
+        let path = &self.rows[row];
+        if path.len() < 2 {
+            return None;
+        }
+        self.row_of(&item_at(&self.roots, &path[..path.len() - 1]).id)
+    }
+
+    fn selection(&self) -> Vec<String> {
+        (0..self.rows.len()).map(|row| &self.item(row).id).filter(|id| self.selected.contains(*id)).cloned().collect()
+    }
+}
+
+type SharedModel = Rc<RefCell<TreeModel>>;
+
+fn item_format() -> TextFormat {
+    TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE).with_wrapping(TextWrapping::NoWrap)
+}
+
+fn row_height() -> f32 {
+    item_format().line_height() + 2.0 * ROW_PADDING.1
+}
+
+fn expander_rect(depth: usize, height: f32) -> Rect {
+    Rect::pos_size(Point::new(ROW_PADDING.0 + depth as f32 * INDENT, 0.0), Size::new(EXPANDER_WIDTH, height))
+}
+
+/// A visible item: its expander and label, indented by its depth.
+struct TreeRow {
+    model: SharedModel,
+    index: usize,
+}
+
+impl TreeRow {
+    fn depth(&self) -> usize {
+        self.model.borrow().rows.get(self.index).map_or(0, |path| path.len() - 1)
+    }
+}
+
+impl Element for TreeRow {
+    fn init(&mut self, mut node: NodeRef) {
+        node.add_child(Box::new(TextBlock::with_format("", item_format())));
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(0.0, row_height())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        let x = expander_rect(self.depth(), container.h).right();
+        let label = Rect::pos_size(Point::new(x, ROW_PADDING.1), Size::new((container.w - x).max(0.0), container.h - 2.0 * ROW_PADDING.1));
+        for child in node.children().iter() {
+            child.arrange(label);
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        let mut cmd = RenderCommandList::new();
+        let model = self.model.borrow();
+        let path = match model.rows.get(self.index) {
+            Some(path) => path,
+            None => return cmd,
+        };
+        let item = item_at(&model.roots, path);
+        let rect = Rect::from_size(node.rect().size);
+        let focused = model.view.upgrade().map_or(false, |view| view.has_focus());
+        if model.selected.contains(&item.id) {
+            let color = if focused { Color::argb(1.0, 0.8, 0.87, 0.97) } else { Color::argb(1.0, 0.88, 0.88, 0.88) };
+            cmd.add(RenderCommand::Rect(rect, color));
+        }
+        if focused && model.current.as_ref() == Some(&item.id) {
+            cmd.add(RenderCommand::Border(rect, BorderStyle::new(Color::transparent())
+                .with_border(Thickness::uniform(1.0), ButtonStyle::new().focus)));
+        }
+        if item.is_expandable() {
+            let glyph = if item.is_expanded() { "▾" } else { "▸" };
+            render_mark(&mut cmd, expander_rect(path.len() - 1, rect.size.h), glyph, Color::argb(1.0, 0.35, 0.35, 0.35));
+        }
+        cmd
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseDown(MouseButton::Left, pos) => {
+                let on_expander = {
+                    let model = self.model.borrow();
+                    self.index < model.rows.len() && model.item(self.index).is_expandable() &&
+                        expander_rect(self.depth(), node.rect().size.h).contains(pos)
+                };
+                if on_expander {
+                    node.raise(Box::new(ExpanderPressed(self.index)));
+                } else {
+                    node.raise(Box::new(RowPressed(self.index)));
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A hierarchy of items with expanders, each level indented further. Only
+/// the rows in view are realized, so large trees stay cheap. Children of
+/// lazy items come from the loader when they are first expanded.
+///
+/// Up, Down, Home, End, PageUp and PageDown move through the visible items;
+/// Right expands the current item or moves to its first child and Left
+/// collapses it or moves to its parent. Enter toggles the current item and
+/// Space selects it. With multiple selection, Shift extends the selection
+/// from the anchor, Ctrl moves without selecting, Ctrl+Space toggles the
+/// current item and Ctrl+A selects all visible items.
+pub struct TreeView {
+    node: NodeWeakRef,
+    model: SharedModel,
+    loader: Option<Box<Fn(&TreeItem) -> Vec<TreeItem>>>,
+    mode: SelectionMode,
+    list: NodeWeakRef,
+    reveal_pending: bool,
+}
+
+impl TreeView {
+    pub fn new(items: Vec<TreeItem>) -> TreeView {
+        let mut model = TreeModel {
+            roots: items,
+            rows: Vec::new(),
+            row_ids: HashMap::new(),
+            selected: HashSet::new(),
+            current: None,
+            anchor: None,
+            view: NodeWeakRef::empty(),
+        };
+        model.rebuild();
+        TreeView {
+            node: NodeWeakRef::empty(),
+            model: Rc::new(RefCell::new(model)),
+            loader: None,
+            mode: SelectionMode::Single,
+            list: NodeWeakRef::empty(),
+            reveal_pending: false,
+        }
+    }
+
+    /// Sets the callback that loads the children of lazy items. An item
+    /// it returns no children for loses its expander.
+    pub fn with_loader<F: Fn(&TreeItem) -> Vec<TreeItem> + 'static>(mut self, loader: F) -> TreeView {
+        self.loader = Some(Box::new(loader));
+        self
+    }
+
+    pub fn with_selection_mode(mut self, mode: SelectionMode) -> TreeView {
+        self.mode = mode;
+        self
+    }
+
+    pub fn items(&self) -> Vec<TreeItem> {
+        self.model.borrow().roots.clone()
+    }
+
+    /// Replaces the items, clearing the selection.
+    pub fn set_items(&mut self, items: Vec<TreeItem>) {
+        {
+            let mut model = self.model.borrow_mut();
+            model.roots = items;
+            model.selected.clear();
+            model.current = None;
+            model.anchor = None;
+            model.rebuild();
+        }
+        self.refresh();
+    }
+
+    /// The ids of the visible items, top to bottom.
+    pub fn visible_items(&self) -> Vec<String> {
+        let model = self.model.borrow();
+        (0..model.rows.len()).map(|row| model.item(row).id.clone()).collect()
+    }
+
+    pub fn is_expanded(&self, id: &str) -> bool {
+        let model = self.model.borrow();
+        let mut path = Vec::new();
+        find_path(&model.roots, id, &mut path) && item_at(&model.roots, &path).is_expanded()
+    }
+
+    /// Expands a loaded item without raising `ItemExpanded`, loading its
+    /// children if needed.
+    pub fn expand(&mut self, id: &str) {
+        if let Some(path) = self.path_of(id) {
+            self.set_expanded(&path, true);
+        }
+    }
+
+    /// Collapses an item without raising `ItemExpanded`. Selected items
+    /// hidden by it give their selection to it.
+    pub fn collapse(&mut self, id: &str) {
+        if let Some(path) = self.path_of(id) {
+            self.set_expanded(&path, false);
+        }
+    }
+
+    /// The ids of the selected items, top to bottom.
+    pub fn selection(&self) -> Vec<String> {
+        self.model.borrow().selection()
+    }
+
+    /// Selects the visible items among `ids` without raising
+    /// `TreeSelectionChanged`. A single selection keeps only the first.
+    pub fn set_selection(&mut self, ids: Vec<String>) {
+        {
+            let mut model = self.model.borrow_mut();
+            let mut ids: Vec<String> = ids.into_iter().filter(|id| model.row_of(id).is_some()).collect();
+            if self.mode == SelectionMode::Single {
+                ids.truncate(1);
+            }
+            model.current = ids.first().cloned();
+            model.anchor = ids.first().cloned();
+            model.selected = ids.into_iter().collect();
+        }
+        self.reveal_current();
+    }
+
+    /// The item the keyboard acts on.
+    pub fn current(&self) -> Option<String> {
+        self.model.borrow().current.clone()
+    }
+
+    fn path_of(&self, id: &str) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        if find_path(&self.model.borrow().roots, id, &mut path) { Some(path) } else { None }
+    }
+
+    fn viewer(&self) -> Option<NodeRef> {
+        self.node.upgrade().and_then(|node| node.children().iter().next().cloned())
+    }
+
+    /// Rebinds the rows after the visible items changed.
+    fn refresh(&mut self) {
+        if let Some(list) = self.list.upgrade() {
+            list.cast_element_mut::<VirtualizingList>().unwrap().refresh();
+        }
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    /// Scrolls the current item into view on the next layout.
+    fn reveal_current(&mut self) {
+        self.reveal_pending = true;
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+
+    /// Expands or collapses the item at `path`, returning whether it changed.
+    fn set_expanded(&mut self, path: &[usize], expanded: bool) -> bool {
+        let unloaded = {
+            let model = self.model.borrow();
+            let item = item_at(&model.roots, path);
+            if item.is_expanded() == expanded || !item.is_expandable() {
+                return false;
+            }
+            if expanded && item.children.is_none() { Some(item.clone()) } else { None }
+        };
+        if let Some(item) = unloaded {
+            let children = self.loader.as_ref().map_or(Vec::new(), |load| load(&item));
+            item_at_mut(&mut self.model.borrow_mut().roots, path).children = Some(children);
+        }
+        let (row, old_len, new_len) = {
+            let mut model = self.model.borrow_mut();
+            let id = {
+                let item = item_at_mut(&mut model.roots, path);
+                item.expanded = expanded;
+                item.id.clone()
+            };
+            let (row, old_len) = (model.row_of(&id), model.rows.len());
+            if let (false, Some(row)) = (expanded, row) {
+                let hidden: Vec<String> = model.rows[row + 1..].iter()
+                    .take_while(|other| other.starts_with(path))
+                    .map(|other| item_at(&model.roots, other).id.clone())
+                    .collect();
+                let mut was_selected = false;
+                for hidden in &hidden {
+                    was_selected |= model.selected.remove(hidden);
+                    if model.current.as_ref() == Some(hidden) {
+                        model.current = Some(id.clone());
+                    }
+                    if model.anchor.as_ref() == Some(hidden) {
+                        model.anchor = Some(id.clone());
+                    }
+                }
+                if was_selected {
+                    model.selected.insert(id.clone());
+                }
+            }
+            model.rebuild();
+            (row, old_len, model.rows.len())
+        };
+        if let Some(row) = row {
+            self.rows_changed(row, old_len, new_len);
+        }
+        true
+    }
+
+    /// Tells the list that the rows below `row` were shown or hidden, going
+    /// from `old_len` rows to `new_len`, so the other rows keep their nodes
+    /// and heights.
+    fn rows_changed(&mut self, row: usize, old_len: usize, new_len: usize) {
+        if let Some(list) = self.list.upgrade() {
+            let mut list = list.cast_element_mut::<VirtualizingList>().unwrap();
+            if new_len > old_len {
+                list.splice(row + 1..row + 1, new_len - old_len);
+            } else {
+                list.splice(row + 1..row + 1 + old_len - new_len, 0);
+            }
+        }
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_render();
+        }
+    }
+
+    /// Toggles the item in `row` for the user, raising `ItemExpanded`.
+    fn toggle_row(&mut self, node: &NodeRef, row: usize) {
+        let (path, id, expanded) = {
+            let model = self.model.borrow();
+            let item = model.item(row);
+            (model.rows[row].clone(), item.id.clone(), item.is_expanded())
+        };
+        if self.set_expanded(&path, !expanded) && self.is_expanded(&id) != expanded {
+            node.raise(Box::new(ItemExpanded(id, !expanded)));
+        }
+    }
+
+    fn select_at(&mut self, row: usize, how: Select) {
+        let how = if self.mode == SelectionMode::Single { Select::Only } else { how };
+        {
+            let mut model = self.model.borrow_mut();
+            let id = model.item(row).id.clone();
+            match how {
+                Select::Only => {
+                    model.selected.clear();
+                    model.selected.insert(id.clone());
+                    model.anchor = Some(id.clone());
+                }
+                Select::Toggle => {
+                    if !model.selected.remove(&id) {
+                        model.selected.insert(id.clone());
+                    }
+                    model.anchor = Some(id.clone());
+                }
+                Select::Range => {
+                    let anchor = model.anchor.as_ref().and_then(|anchor| model.row_of(anchor)).unwrap_or(row);
+                    let selected = (anchor.min(row)..anchor.max(row) + 1).map(|r| model.item(r).id.clone()).collect();
+                    model.selected = selected;
+                }
+                Select::Move => {}
+            }
+            model.current = Some(id);
+        }
+        self.reveal_current();
+    }
+
+    /// Raises `TreeSelectionChanged` if the selection differs from `before`.
+    fn notify(&self, node: &NodeRef, before: Vec<String>) {
+        let after = self.selection();
+        if after != before {
+            node.raise(Box::new(TreeSelectionChanged(after)));
+        }
+        node.invalidate_render();
+    }
+
+    /// How many rows fit in the viewport.
+    fn page_size(&self) -> usize {
+        let view = self.viewer().map_or(0.0, |viewer| viewer.cast_element::<ScrollViewer>().unwrap().viewport_size().h);
+        ((view / row_height()) as usize).max(1)
+    }
+
+    fn handle_key(&mut self, node: &NodeRef, key: Key, modifiers: Modifiers) -> bool {
+        let (len, current) = {
+            let model = self.model.borrow();
+            (model.rows.len(), model.current_row())
+        };
+        if len == 0 {
+            return false;
+        }
+        let row = current.unwrap_or(0);
+        let moving = if modifiers.shift {
+            Select::Range
+        } else if modifiers.ctrl {
+            Select::Move
+        } else {
+            Select::Only
+        };
+        let before = self.selection();
+        match key {
+            Key::Up => self.select_at(current.map_or(0, |r| r.saturating_sub(1)), moving),
+            Key::Down => self.select_at(current.map_or(0, |r| (r + 1).min(len - 1)), moving),
+            Key::Home => self.select_at(0, moving),
+            Key::End => self.select_at(len - 1, moving),
+            Key::PageUp => self.select_at(row.saturating_sub(self.page_size()), moving),
+            Key::PageDown => self.select_at((row + self.page_size()).min(len - 1), moving),
+            Key::Right => {
+                let (expandable, expanded) = {
+                    let model = self.model.borrow();
+                    (model.item(row).is_expandable(), model.item(row).is_expanded())
+                };
+                if expanded {
+                    self.select_at(row + 1, moving);
+                } else if expandable {
+                    self.toggle_row(node, row);
+                }
+            }
+            Key::Left => {
+                let (expanded, parent) = {
+                    let model = self.model.borrow();
+                    (model.item(row).is_expanded(), model.parent_row(row))
+                };
+                if expanded {
+                    self.toggle_row(node, row);
+                } else if let Some(parent) = parent {
+                    self.select_at(parent, moving);
+                }
+            }
+            Key::Enter => self.toggle_row(node, row),
+            Key::Space => self.select_at(row, if modifiers.ctrl { Select::Toggle } else { Select::Only }),
+            Key::Letter('a') | Key::Letter('A') if modifiers.ctrl && self.mode == SelectionMode::Multiple => {
+                let mut model = self.model.borrow_mut();
+                let all = (0..len).map(|r| model.item(r).id.clone()).collect();
+                model.selected = all;
+            }
+            _ => return false,
+        }
+        self.notify(node, before);
+        true
+    }
+}
+
+impl Element for TreeView {
+    fn init(&mut self, mut node: NodeRef) {
+        info!("[TreeView] Init");
+        self.node = node.downgrade();
+        self.model.borrow_mut().view = node.downgrade();
+        let viewer = node.add_child(Box::new(ScrollViewer::with_visibility(ScrollBarVisibility::Disabled, ScrollBarVisibility::Auto).with_focusable(false)));
+        let model = self.model.clone();
+        let template = template(
+            move || Box::new(TreeRow { model: model.clone(), index: 0 }) as Box<Element>,
+            |node, index| {
+                let model = {
+                    let mut row = node.cast_element_mut::<TreeRow>().unwrap();
+                    row.index = index;
+                    row.model.clone()
+                };
+                // Rows are rebound before the list drops those past a shrunk end.
+                if index >= model.borrow().rows.len() {
+                    return;
+                }
+                let label = model.borrow().item(index).label.clone();
+                node.children()[0].cast_element_mut::<TextBlock>().unwrap().set_text(label);
+                node.invalidate_layout();
+            });
+        let list = viewer.cast_element_mut::<ScrollViewer>().unwrap()
+            .set_content(Box::new(VirtualizingList::new(Box::new(self.model.clone()), template)));
+        self.list = list.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[TreeView] Measure");
+        self.viewer().map_or(Size::zero(), |viewer| viewer.measure())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[TreeView] Layout");
+        let viewer = match self.viewer() {
+            Some(viewer) => viewer,
+            None => return,
+        };
+        viewer.arrange(Rect::from_size(container));
+        let current = self.model.borrow().current_row();
+        if let (true, Some(row)) = (self.reveal_pending, current) {
+            self.reveal_pending = false;
+            let mut viewer = viewer.cast_element_mut::<ScrollViewer>().unwrap();
+            let (top, height) = (row as f32 * row_height(), row_height());
+            let offset = viewer.offset();
+            let view = viewer.viewport_size().h;
+            if top < offset.y {
+                viewer.scroll_to(Point::new(offset.x, top));
+            } else if top + height > offset.y + view {
+                viewer.scroll_to(Point::new(offset.x, top + height - view));
+            }
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        info!("[TreeView] Render");
+        RenderCommandList::new()
+    }
+
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::KeyDown(key, modifiers) => self.handle_key(&node, key, modifiers),
+            _ => false,
+        }
+    }
+
+    fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+        if let Some(&RowPressed(row)) = message.cast_ref::<RowPressed>() {
+            if row < self.model.borrow().rows.len() {
+                let modifiers = node.modifiers();
+                let how = if modifiers.ctrl {
+                    Select::Toggle
+                } else if modifiers.shift {
+                    Select::Range
+                } else {
+                    Select::Only
+                };
+                let before = self.selection();
+                self.select_at(row, how);
+                self.notify(&node, before);
+            }
+            true
+        } else if let Some(&ExpanderPressed(row)) = message.cast_ref::<ExpanderPressed>() {
+            if row < self.model.borrow().rows.len() {
+                let before = self.selection();
+                self.toggle_row(&node, row);
+                self.notify(&node, before);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct Panel {
+        selections: Vec<Vec<String>>,
+        expansions: Vec<(String, bool)>,
+    }
+
+    impl Element for Panel {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+            for child in node.children().iter() {
+                child.arrange(Rect::from_size(Size::new(200.0, 100.0)));
+            }
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+            if let Some(&TreeSelectionChanged(ref ids)) = message.cast_ref::<TreeSelectionChanged>() {
+                self.selections.push(ids.clone());
+                true
+            } else if let Some(&ItemExpanded(ref id, expanded)) = message.cast_ref::<ItemExpanded>() {
+                self.expansions.push((id.clone(), expanded));
+                true
+            } else {
+                false
+            }
+        }
+    }
+
+    fn panel_with(view: TreeView) -> (Tree, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Panel { selections: Vec::new(), expansions: Vec::new() }))).unwrap();
+        let view = root.add_child(Box::new(view));
+        layout(&mut tree);
+        (tree, root, view)
+    }
+
+    fn layout(tree: &mut Tree) {
+        tree.layout(Size::new(200.0, 100.0));
+        tree.layout(Size::new(200.0, 100.0));
+    }
+
+    fn click(tree: &mut Tree, x: f32, row: usize) {
+        let pos = Point::new(x, (row as f32 + 0.5) * row_height());
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, pos));
+        tree.dispatch_event(&InputEvent::MouseUp(MouseButton::Left, pos));
+        layout(tree);
+    }
+
+    fn key(tree: &mut Tree, key: Key, modifiers: Modifiers) {
+        tree.dispatch_event(&InputEvent::KeyDown(key, modifiers));
+        layout(tree);
+    }
+
+    fn visible(view: &NodeRef) -> Vec<String> {
+        view.cast_element::<TreeView>().unwrap().visible_items()
+    }
+
+    fn selection(view: &NodeRef) -> Vec<String> {
+        view.cast_element::<TreeView>().unwrap().selection()
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn project() -> Vec<TreeItem> {
+        vec![
+            TreeItem::new("src", "src").with_children(vec![TreeItem::new("main.rs", "main.rs"), TreeItem::new("lib.rs", "lib.rs")]),
+            TreeItem::new("docs", "docs").with_lazy_children(),
+            TreeItem::new("README", "README.md"),
+        ]
+    }
+
+    #[test]
+    fn arrows_expand_collapse_and_walk_the_hierarchy() {
+        let (mut tree, root, view) = panel_with(TreeView::new(project()));
+
+        click(&mut tree, 50.0, 0);
+        assert!(view.has_focus());
+        key(&mut tree, Key::Right, Modifiers::none());
+        assert_eq!(visible(&view), ids(&["src", "main.rs", "lib.rs", "docs", "README"]));
+        key(&mut tree, Key::Right, Modifiers::none());
+        key(&mut tree, Key::Down, Modifiers::none());
+        assert_eq!(selection(&view), ids(&["lib.rs"]));
+        key(&mut tree, Key::Left, Modifiers::none());
+        assert_eq!(selection(&view), ids(&["src"]));
+        key(&mut tree, Key::Left, Modifiers::none());
+        assert_eq!(visible(&view), ids(&["src", "docs", "README"]));
+        key(&mut tree, Key::End, Modifiers::none());
+
+        let panel = root.cast_element::<Panel>().unwrap();
+        assert_eq!(panel.selections, vec![ids(&["src"]), ids(&["main.rs"]), ids(&["lib.rs"]), ids(&["src"]), ids(&["README"])]);
+        assert_eq!(panel.expansions, vec![("src".to_string(), true), ("src".to_string(), false)]);
+    }
+
+    #[test]
+    fn children_load_lazily_on_first_expand() {
+        let loads = Rc::new(Cell::new(0));
+        let counter = loads.clone();
+        let view = TreeView::new(project()).with_loader(move |item| {
+            counter.set(counter.get() + 1);
+            vec![TreeItem::new(format!("{}/guide", item.id()), "guide.md")]
+        });
+        let (mut tree, _, view) = panel_with(view);
+
+        click(&mut tree, ROW_PADDING.0 + EXPANDER_WIDTH / 2.0, 1);
+        assert_eq!(visible(&view), ids(&["src", "docs", "docs/guide", "README"]));
+        assert!(selection(&view).is_empty());
+        let label = {
+            let view = view.cast_element::<TreeView>().unwrap();
+            let list = view.list.upgrade().unwrap();
+            let row = list.cast_element::<VirtualizingList>().unwrap().node_for(2).unwrap();
+            let label = row.children()[0].clone();
+            label
+        };
+        assert_eq!(label.cast_element::<TextBlock>().unwrap().text(), "guide.md");
+        assert_eq!(label.window_rect().pos.x, ROW_PADDING.0 + INDENT + EXPANDER_WIDTH);
+
+        click(&mut tree, ROW_PADDING.0 + EXPANDER_WIDTH / 2.0, 1);
+        click(&mut tree, ROW_PADDING.0 + EXPANDER_WIDTH / 2.0, 1);
+        assert_eq!(loads.get(), 1);
+        assert!(view.cast_element::<TreeView>().unwrap().is_expanded("docs"));
+
+        let mut empty = TreeView::new(vec![TreeItem::new("empty", "empty").with_lazy_children()]).with_loader(|_| Vec::new());
+        empty.expand("empty");
+        assert!(!empty.is_expanded("empty"));
+        assert!(!empty.items()[0].is_expandable());
+    }
+
+    #[test]
+    fn ctrl_and_shift_extend_a_multiple_selection() {
+        let items = (0..6).map(|i| TreeItem::new(format!("{}", i), format!("Item {}", i))).collect();
+        let (mut tree, root, view) = panel_with(TreeView::new(items).with_selection_mode(SelectionMode::Multiple));
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::none() };
+        let shift = Modifiers { shift: true, ..Modifiers::none() };
+
+        click(&mut tree, 50.0, 0);
+        key(&mut tree, Key::Other, ctrl);
+        click(&mut tree, 50.0, 2);
+        assert_eq!(selection(&view), ids(&["0", "2"]));
+        tree.dispatch_event(&InputEvent::KeyUp(Key::Other, Modifiers::none()));
+        key(&mut tree, Key::Other, shift);
+        click(&mut tree, 50.0, 4);
+        assert_eq!(selection(&view), ids(&["2", "3", "4"]));
+
+        key(&mut tree, Key::Down, shift);
+        assert_eq!(selection(&view), ids(&["2", "3", "4", "5"]));
+        key(&mut tree, Key::Home, ctrl);
+        key(&mut tree, Key::Space, ctrl);
+        assert_eq!(selection(&view), ids(&["0", "2", "3", "4", "5"]));
+        key(&mut tree, Key::Down, Modifiers::none());
+        assert_eq!(selection(&view), ids(&["1"]));
+
+        assert_eq!(root.cast_element::<Panel>().unwrap().selections.len(), 6);
+    }
+
+    #[test]
+    fn only_rows_in_view_are_realized() {
+        let items = (0..1000).map(|i| TreeItem::new(format!("{}", i), format!("Item {}", i))).collect();
+        let (mut tree, _, view) = panel_with(TreeView::new(items));
+        let list = view.cast_element::<TreeView>().unwrap().list.upgrade().unwrap();
+
+        assert!(list.cast_element::<VirtualizingList>().unwrap().realized_count() < 20);
+        view.focus();
+        key(&mut tree, Key::End, Modifiers::none());
+
+        assert_eq!(selection(&view), ids(&["999"]));
+        let list = list.cast_element::<VirtualizingList>().unwrap();
+        assert!(list.node_for(999).is_some());
+        assert!(list.node_for(0).is_none());
+        assert!(list.realized_count() < 20);
+    }
+
+    #[test]
+    fn expanding_keeps_the_rows_around_it() {
+        let (mut tree, _, view) = panel_with(TreeView::new(project()));
+        let list = view.cast_element::<TreeView>().unwrap().list.upgrade().unwrap();
+        let row = |index| list.cast_element::<VirtualizingList>().unwrap().node_for(index).unwrap();
+        let (src, docs) = (row(0), row(1));
+
+        view.cast_element_mut::<TreeView>().unwrap().expand("src");
+        layout(&mut tree);
+
+        assert!(row(0) == src && row(3) == docs);
+        assert_eq!(docs.children()[0].cast_element::<TextBlock>().unwrap().text(), "docs");
+
+        view.cast_element_mut::<TreeView>().unwrap().collapse("src");
+        layout(&mut tree);
+
+        assert!(row(0) == src && row(1) == docs);
+        assert_eq!(visible(&view), ids(&["src", "docs", "README"]));
+    }
+}
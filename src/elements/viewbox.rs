@@ -0,0 +1,132 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+
+/// Lays its single child out at the child's natural size and scales it to
+/// the available space with a render transform, centering the result.
+pub struct Viewbox {
+    node: NodeWeakRef,
+    stretch: Stretch,
+}
+
+impl Viewbox {
+    pub fn new() -> Viewbox {
+        Viewbox::with_stretch(Stretch::Uniform)
+    }
+
+    pub fn with_stretch(stretch: Stretch) -> Viewbox {
+        Viewbox {
+            node: NodeWeakRef::empty(),
+            stretch: stretch,
+        }
+    }
+
+    pub fn stretch(&self) -> Stretch {
+        self.stretch
+    }
+
+    pub fn set_stretch(&mut self, stretch: Stretch) {
+        self.stretch = stretch;
+        self.node().invalidate_layout();
+    }
+
+    /// Sets the element that is scaled, replacing the previous one.
+    pub fn set_content(&mut self, content: Box<Element>) -> NodeRef {
+        self.node().set_single_child(content)
+    }
+
+    pub fn content(&self) -> Option<NodeRef> {
+        self.node().children().iter().next().cloned()
+    }
+
+    fn node(&self) -> NodeRef {
+        self.node.upgrade().unwrap()
+    }
+}
+
+impl Element for Viewbox {
+    fn init(&mut self, node: NodeRef) {
+        info!("[Viewbox] Init");
+        self.node = node.downgrade();
+        node.set_clip_to_bounds(true);
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        info!("[Viewbox] Measure");
+        self.content().map_or(Size::zero(), |c| c.measure())
+    }
+
+    fn layout(&mut self, node: NodeRef, container: Size) {
+        info!("[Viewbox] Layout");
+        let content = match self.content() {
+            Some(content) => content,
+            None => return,
+        };
+        let natural = content.measure();
+        content.arrange(Rect::from_size(natural));
+
+        let (sx, sy) = self.stretch.scale(natural, container);
+        let offset = Point::new((container.w - natural.w * sx) / 2.0, (container.h - natural.h * sy) / 2.0);
+        node.set_render_transform(Transform::new(sx, sy, offset));
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree::testing::SizedElement;
+    use input::*;
+
+    fn viewbox(stretch: Stretch) -> (Tree, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(Viewbox::with_stretch(stretch)))).unwrap();
+        let content = root.cast_element_mut::<Viewbox>().unwrap().set_content(Box::new(SizedElement::new(Size::new(20.0, 10.0))));
+        root.layout(Size::new(100.0, 100.0));
+        (tree, root, content)
+    }
+
+    #[test]
+    fn content_keeps_natural_size_and_is_scaled_to_fit() {
+        let (_tree, root, content) = viewbox(Stretch::Uniform);
+
+        assert_eq!(content.rect().to_pos_size_tuple(), ((0.0, 0.0), (20.0, 10.0)));
+        assert_eq!(root.render_transform(), Transform::new(5.0, 5.0, Point::new(0.0, 25.0)));
+        assert_eq!(content.window_rect().to_pos_size_tuple(), ((0.0, 25.0), (100.0, 50.0)));
+    }
+
+    #[test]
+    fn fill_and_uniform_fill() {
+        let (_tree, _, content) = viewbox(Stretch::Fill);
+        assert_eq!(content.window_rect().to_pos_size_tuple(), ((0.0, 0.0), (100.0, 100.0)));
+
+        let (_tree, _, content) = viewbox(Stretch::UniformToFill);
+        assert_eq!(content.window_rect().to_pos_size_tuple(), ((-50.0, 0.0), (200.0, 100.0)));
+    }
+
+    #[test]
+    fn hit_testing_and_events_use_the_transform() {
+        let (mut tree, root, content) = viewbox(Stretch::Uniform);
+
+        assert!(root.hit_test(Point::new(90.0, 70.0)).unwrap() == content);
+        assert!(root.hit_test(Point::new(90.0, 80.0)).unwrap() == root);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(90.0, 70.0)));
+        assert_eq!(content.cast_element::<SizedElement>().unwrap().pressed_at, Some(Point::new(18.0, 9.0)));
+    }
+
+    #[test]
+    fn zero_scale_hides_the_content_from_events() {
+        let (_tree, root, content) = viewbox(Stretch::Uniform);
+        root.layout(Size::new(0.0, 100.0));
+
+        assert!(!root.render_transform().is_invertible());
+        assert!(root.hit_test(Point::new(0.0, 50.0)).is_none());
+        assert!(!content.handle_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(0.0, 50.0))));
+        assert_eq!(content.cast_element::<SizedElement>().unwrap().pressed_at, None);
+    }
+}
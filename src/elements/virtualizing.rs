@@ -0,0 +1,560 @@
+use primitives::*;
+use render::*;
+use tree::*;
+use layout::*;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::ops::Range;
+
+const DEFAULT_ITEM_HEIGHT: f32 = 20.0;
+
+pub trait ItemSource {
+    fn len(&self) -> usize;
+}
+
+impl ItemSource for usize {
+    fn len(&self) -> usize {
+        *self
+    }
+}
+
+impl<T> ItemSource for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+impl<T: ItemSource> ItemSource for Rc<RefCell<T>> {
+    fn len(&self) -> usize {
+        self.borrow().len()
+    }
+}
+
+/// Creates the nodes that display items. Nodes are reused for other items
+/// as they scroll out of view, so `bind` must fully update a node.
+pub trait ItemTemplate {
+    fn create(&self) -> Box<Element>;
+    fn bind(&self, node: &NodeRef, index: usize);
+}
+
+struct FnTemplate<C, B> {
+    create: C,
+    bind: B,
+}
+
+impl<C, B> ItemTemplate for FnTemplate<C, B>
+    where C: Fn() -> Box<Element>, B: Fn(&NodeRef, usize) {
+    fn create(&self) -> Box<Element> {
+        (self.create)()
+    }
+
+    fn bind(&self, node: &NodeRef, index: usize) {
+        (self.bind)(node, index)
+    }
+}
+
+pub fn template<C, B>(create: C, bind: B) -> Box<ItemTemplate>
+    where C: Fn() -> Box<Element> + 'static, B: Fn(&NodeRef, usize) + 'static {
+    Box::new(FnTemplate {
+        create: create,
+        bind: bind,
+    })
+}
+
+/// Binary indexed tree over `f32`, giving prefix sums in logarithmic time.
+struct Fenwick {
+    tree: Vec<f32>,
+}
+
+impl Fenwick {
+    fn new(len: usize) -> Fenwick {
+        Fenwick {
+            tree: vec![0.0; len + 1],
+        }
+    }
+
+    /// Builds the tree in linear time.
+    fn from_values<I: Iterator<Item = f32>>(values: I) -> Fenwick {
+        let mut tree = vec![0.0];
+        tree.extend(values);
+        for i in 1..tree.len() {
+            let parent = i + (i & i.wrapping_neg());
+            if parent < tree.len() {
+                tree[parent] += tree[i];
+            }
+        }
+        Fenwick {
+            tree: tree,
+        }
+    }
+
+    fn add(&mut self, index: usize, delta: f32) {
+        let mut i = index + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Sum of the values before `index`.
+    fn prefix(&self, index: usize) -> f32 {
+        let mut i = index;
+        let mut sum = 0.0;
+        while i > 0 {
+            sum += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+}
+
+/// Item heights, measured or estimated. Unmeasured items are assumed to have
+/// the average height of the measured ones.
+pub struct ItemHeights {
+    heights: Vec<Option<f32>>,
+    sums: Fenwick,
+    counts: Fenwick,
+    measured_total: f32,
+    measured_count: usize,
+    default_height: f32,
+}
+
+impl ItemHeights {
+    pub fn new(len: usize, default_height: f32) -> ItemHeights {
+        ItemHeights {
+            heights: vec![None; len],
+            sums: Fenwick::new(len),
+            counts: Fenwick::new(len),
+            measured_total: 0.0,
+            measured_count: 0,
+            default_height: default_height,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn estimate(&self) -> f32 {
+        if self.measured_count > 0 {
+            self.measured_total / self.measured_count as f32
+        } else {
+            self.default_height
+        }
+    }
+
+    pub fn set(&mut self, index: usize, height: f32) {
+        match self.heights[index] {
+            Some(old) => {
+                self.sums.add(index, height - old);
+                self.measured_total += height - old;
+            }
+            None => {
+                self.sums.add(index, height);
+                self.counts.add(index, 1.0);
+                self.measured_total += height;
+                self.measured_count += 1;
+            }
+        }
+        self.heights[index] = Some(height);
+    }
+
+    /// Replaces the items in `range` with `count` unmeasured ones, keeping
+    /// the heights of the others.
+    pub fn splice(&mut self, range: Range<usize>, count: usize) {
+        let heights: Vec<Option<f32>> = self.heights.splice(range, vec![None; count]).collect();
+        for height in heights.into_iter().filter_map(|h| h) {
+            self.measured_total -= height;
+            self.measured_count -= 1;
+        }
+        self.sums = Fenwick::from_values(self.heights.iter().map(|h| h.unwrap_or(0.0)));
+        self.counts = Fenwick::from_values(self.heights.iter().map(|h| if h.is_some() { 1.0 } else { 0.0 }));
+    }
+
+    /// The top of item `index`; `position(len())` is the total height.
+    pub fn position(&self, index: usize) -> f32 {
+        let measured = self.counts.prefix(index);
+        self.sums.prefix(index) + (index as f32 - measured) * self.estimate()
+    }
+
+    pub fn total(&self) -> f32 {
+        self.position(self.len())
+    }
+
+    /// The item covering `y`, clamped to the valid range. Requires `len() > 0`.
+    pub fn index_at(&self, y: f32) -> usize {
+        let (mut lo, mut hi) = (0, self.len());
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if self.position(mid) <= y {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+}
+
+/// A vertical list that realizes nodes only for items in or near the viewport.
+/// Meant to be the content of a `ScrollViewer`, which it takes its offset from.
+pub struct VirtualizingList {
+    node: NodeWeakRef,
+    source: Box<ItemSource>,
+    template: Box<ItemTemplate>,
+    heights: ItemHeights,
+    realized: Vec<(usize, NodeRef)>,
+    pool: Vec<NodeRef>,
+    offset: Point,
+    viewport: Size,
+    overscan: f32,
+}
+
+impl VirtualizingList {
+    pub fn new(source: Box<ItemSource>, template: Box<ItemTemplate>) -> VirtualizingList {
+        let len = source.len();
+        VirtualizingList {
+            node: NodeWeakRef::empty(),
+            source: source,
+            template: template,
+            heights: ItemHeights::new(len, DEFAULT_ITEM_HEIGHT),
+            realized: Vec::new(),
+            pool: Vec::new(),
+            offset: Point::zero(),
+            viewport: Size::zero(),
+            overscan: 0.5,
+        }
+    }
+
+    /// How far beyond the viewport items are realized, as a fraction of the viewport height.
+    pub fn with_overscan(mut self, overscan: f32) -> VirtualizingList {
+        self.overscan = overscan;
+        self
+    }
+
+    /// The realized node for item `index`, if any.
+    pub fn node_for(&self, index: usize) -> Option<NodeRef> {
+        self.realized.iter().find(|&&(i, _)| i == index).map(|&(_, ref n)| n.clone())
+    }
+
+    pub fn realized_count(&self) -> usize {
+        self.realized.len()
+    }
+
+    /// The estimated top of item `index`, usable as a scroll offset.
+    pub fn offset_of(&self, index: usize) -> f32 {
+        self.heights.position(index)
+    }
+
+    /// Rebinds the realized nodes, e.g. after the items behind them changed.
+    pub fn refresh(&mut self) {
+        for &(index, ref node) in &self.realized {
+            self.template.bind(node, index);
+        }
+        if let Some(node) = self.node.upgrade() {
+            node.invalidate_layout();
+        }
+    }
+
+    /// Tells the list that the items in `range` were replaced by `count` new
+    /// ones. The heights and nodes of the other items are kept, and those
+    /// after `range` are rebound at their new indices.
+    pub fn splice(&mut self, range: Range<usize>, count: usize) {
+        if let Some(mut node) = self.node.upgrade() {
+            self.splice_items(&mut node, range, count);
+            node.invalidate_layout();
+        } else {
+            self.heights.splice(range, count);
+        }
+    }
+
+    fn splice_items(&mut self, node: &mut NodeRef, range: Range<usize>, count: usize) {
+        self.heights.splice(range.clone(), count);
+        let mut kept = Vec::new();
+        for (index, child) in self.realized.drain(..) {
+            if index < range.start {
+                kept.push((index, child));
+            } else if index >= range.end {
+                let index = index - (range.end - range.start) + count;
+                self.template.bind(&child, index);
+                kept.push((index, child));
+            } else {
+                node.remove_child(&child);
+                self.pool.push(child);
+            }
+        }
+        self.realized = kept;
+    }
+
+    /// Takes length changes not announced through `splice` as items added or
+    /// removed at the end.
+    fn sync_len(&mut self, node: &mut NodeRef) {
+        let (len, old) = (self.source.len(), self.heights.len());
+        if len > old {
+            self.splice_items(node, old..old, len - old);
+        } else if len < old {
+            self.splice_items(node, len..old, 0);
+        }
+    }
+}
+
+impl Scrollable for VirtualizingList {
+    fn extent(&self) -> Size {
+        Size::new(self.viewport.w, self.heights.total())
+    }
+
+    fn set_offset(&mut self, offset: Point) {
+        self.offset = offset;
+    }
+}
+
+impl Element for VirtualizingList {
+    fn init(&mut self, node: NodeRef) {
+        info!("[VirtualizingList] Init");
+        self.node = node.downgrade();
+    }
+
+    fn measure(&self, node: NodeRef) -> Size {
+        Size::new(0.0, self.heights.total())
+    }
+
+    fn layout(&mut self, mut node: NodeRef, container: Size) {
+        info!("[VirtualizingList] Layout");
+        self.viewport = container;
+        self.sync_len(&mut node);
+
+        let margin = container.h * self.overscan;
+        let start = self.offset.y - margin;
+        let end = self.offset.y + container.h + margin;
+        let len = self.heights.len();
+        let first = if len > 0 { self.heights.index_at(start.max(0.0)) } else { 0 };
+
+        // Free the nodes of items that are certainly out of range first, so they
+        // can be reused for the items coming into view.
+        let mut kept = Vec::new();
+        for (index, child) in self.realized.drain(..) {
+            if index >= first && self.heights.position(index) < end {
+                kept.push((index, child));
+            } else {
+                node.remove_child(&child);
+                self.pool.push(child);
+            }
+        }
+
+        let mut y = self.heights.position(first);
+        let mut index = first;
+        while index < len && y < end {
+            let child = match kept.iter().position(|&(i, _)| i == index) {
+                Some(p) => kept.remove(p).1,
+                None => {
+                    let child = match self.pool.pop() {
+                        Some(child) => {
+                            node.append_child(child.clone());
+                            child
+                        }
+                        None => node.add_child(self.template.create()),
+                    };
+                    self.template.bind(&child, index);
+                    child
+                }
+            };
+            let height = child.measure().h;
+            self.heights.set(index, height);
+            child.arrange(Rect::pos_size(Point::new(-self.offset.x, y - self.offset.y), Size::new(container.w, height)));
+            self.realized.push((index, child));
+            y += height;
+            index += 1;
+        }
+
+        for (_, child) in kept {
+            node.remove_child(&child);
+            self.pool.push(child);
+        }
+    }
+
+    fn render(&self, node: NodeRef) -> RenderCommandList {
+        RenderCommandList::new()
+    }
+
+    fn as_scrollable(&mut self) -> Option<&mut Scrollable> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::scroll::*;
+    use std::cell::Cell;
+
+    struct Row {
+        index: usize,
+        height: f32,
+    }
+
+    impl Element for Row {
+        fn init(&mut self, node: NodeRef) {
+
+        }
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::new(100.0, self.height)
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+    }
+
+    fn list_in_viewer(source: Box<ItemSource>, created: Rc<Cell<usize>>) -> (Tree, NodeRef, NodeRef) {
+        let mut tree = Tree::new();
+        let viewer = tree.set_root(Some(Box::new(ScrollViewer::new()))).unwrap();
+        let template = template(
+            move || {
+                created.set(created.get() + 1);
+                Box::new(Row { index: 0, height: 0.0 }) as Box<Element>
+            },
+            |node, index| {
+                let mut row = node.cast_element_mut::<Row>().unwrap();
+                row.index = index;
+                row.height = if index % 2 == 0 { 20.0 } else { 30.0 };
+            });
+        let list = viewer.cast_element_mut::<ScrollViewer>().unwrap()
+            .set_content(Box::new(VirtualizingList::new(source, template)));
+        viewer.layout(Size::new(200.0, 100.0));
+        (tree, viewer, list)
+    }
+
+    #[test]
+    fn heights_estimate_unmeasured_items() {
+        let mut heights = ItemHeights::new(10, 20.0);
+        assert_eq!(heights.total(), 200.0);
+
+        heights.set(0, 10.0);
+        heights.set(1, 30.0);
+
+        assert_eq!(heights.estimate(), 20.0);
+        assert_eq!(heights.position(2), 40.0);
+        assert_eq!(heights.position(3), 60.0);
+
+        heights.set(1, 50.0);
+
+        assert_eq!(heights.estimate(), 30.0);
+        assert_eq!(heights.position(2), 60.0);
+        assert_eq!(heights.total(), 300.0);
+    }
+
+    #[test]
+    fn heights_index_at_finds_covering_item() {
+        let mut heights = ItemHeights::new(4, 10.0);
+        heights.set(1, 30.0);
+        heights.set(0, 10.0);
+        heights.set(2, 10.0);
+        heights.set(3, 10.0);
+
+        assert_eq!(heights.index_at(0.0), 0);
+        assert_eq!(heights.index_at(9.9), 0);
+        assert_eq!(heights.index_at(10.0), 1);
+        assert_eq!(heights.index_at(39.0), 1);
+        assert_eq!(heights.index_at(40.0), 2);
+        assert_eq!(heights.index_at(1000.0), 3);
+    }
+
+    #[test]
+    fn heights_splice_keeps_other_measurements() {
+        let mut heights = ItemHeights::new(4, 10.0);
+        heights.set(0, 10.0);
+        heights.set(1, 30.0);
+        heights.set(3, 20.0);
+
+        heights.splice(1..2, 3);
+
+        assert_eq!(heights.len(), 6);
+        assert_eq!(heights.estimate(), 15.0);
+        assert_eq!(heights.position(5), 10.0 + 4.0 * 15.0);
+        assert_eq!(heights.total(), 10.0 + 4.0 * 15.0 + 20.0);
+    }
+
+    #[test]
+    fn realizes_only_visible_items() {
+        let created = Rc::new(Cell::new(0));
+        let (_tree, _viewer, list) = list_in_viewer(Box::new(100000), created.clone());
+
+        // 100 pixels of viewport plus 50 of overscan, rows alternate 20 and 30 pixels.
+        assert_eq!(list.children().len(), 6);
+        assert_eq!(created.get(), 6);
+        assert!(list.cast_element::<VirtualizingList>().unwrap().extent().h > 2000000.0);
+    }
+
+    #[test]
+    fn scrolling_recycles_nodes() {
+        let created = Rc::new(Cell::new(0));
+        let (_tree, viewer, list) = list_in_viewer(Box::new(100000), created.clone());
+
+        for step in 1..50 {
+            viewer.cast_element_mut::<ScrollViewer>().unwrap().scroll_to(Point::new(0.0, step as f32 * 37.0));
+            viewer.layout(Size::new(200.0, 100.0));
+        }
+
+        assert!(created.get() <= 10, "created {} nodes", created.get());
+        let list = list.cast_element::<VirtualizingList>().unwrap();
+        let offset = viewer.cast_element::<ScrollViewer>().unwrap().vertical_offset().get();
+        let first = list.heights.index_at(offset - 50.0);
+        let row = list.node_for(first + 2).unwrap();
+        assert_eq!(row.cast_element::<Row>().unwrap().index, first + 2);
+    }
+
+    #[test]
+    fn rows_are_stacked_at_their_positions() {
+        let created = Rc::new(Cell::new(0));
+        let (_tree, viewer, list) = list_in_viewer(Box::new(1000), created);
+        viewer.cast_element_mut::<ScrollViewer>().unwrap().scroll_to(Point::new(0.0, 50.0));
+        viewer.layout(Size::new(200.0, 100.0));
+
+        let list = list.cast_element::<VirtualizingList>().unwrap();
+        // Items 0 and 1 take 50 pixels, so item 2 is at the top of the viewport.
+        assert_eq!(list.node_for(2).unwrap().rect().pos, Point::new(0.0, 0.0));
+        assert_eq!(list.node_for(3).unwrap().rect().pos, Point::new(0.0, 20.0));
+    }
+
+    #[test]
+    fn appending_items_keeps_measured_heights() {
+        let created = Rc::new(Cell::new(0));
+        let len = Rc::new(RefCell::new(1000));
+        let (_tree, viewer, list) = list_in_viewer(Box::new(len.clone()), created);
+        let positions = |list: &NodeRef| (0..7).map(|i| list.cast_element::<VirtualizingList>().unwrap().offset_of(i)).collect::<Vec<f32>>();
+        let before = positions(&list);
+        viewer.cast_element_mut::<ScrollViewer>().unwrap().scroll_to(Point::new(0.0, 1000.0));
+        viewer.layout(Size::new(200.0, 100.0));
+        let row = list.cast_element::<VirtualizingList>().unwrap().node_for(40).unwrap();
+
+        *len.borrow_mut() += 10;
+        viewer.layout(Size::new(200.0, 100.0));
+
+        // The rows at the top are out of view, so only their kept heights place them.
+        assert_eq!(positions(&list), before);
+        assert!(list.cast_element::<VirtualizingList>().unwrap().node_for(40).unwrap() == row);
+    }
+
+    #[test]
+    fn splicing_shifts_the_realized_rows() {
+        let created = Rc::new(Cell::new(0));
+        let len = Rc::new(RefCell::new(1000));
+        let (_tree, viewer, list) = list_in_viewer(Box::new(len.clone()), created);
+        let row = list.cast_element::<VirtualizingList>().unwrap().node_for(1).unwrap();
+        let top = list.cast_element::<VirtualizingList>().unwrap().offset_of(1);
+
+        *len.borrow_mut() += 2;
+        list.cast_element_mut::<VirtualizingList>().unwrap().splice(1..1, 2);
+
+        assert!(list.cast_element::<VirtualizingList>().unwrap().node_for(3).unwrap() == row);
+        assert_eq!(row.cast_element::<Row>().unwrap().index, 3);
+        viewer.layout(Size::new(200.0, 100.0));
+        assert_eq!(list.cast_element::<VirtualizingList>().unwrap().offset_of(1), top);
+    }
+}
@@ -0,0 +1,102 @@
+use primitives::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u8),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub fn none() -> Modifiers {
+        Modifiers::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.shift || self.ctrl || self.alt || self.logo)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Letter(char),
+    Digit(u8),
+    F(u8),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Tab,
+    Enter,
+    Space,
+    Escape,
+    Back,
+    Delete,
+    Insert,
+    Other,
+}
+
+/// An input event. Positions are in the coordinates of the node receiving the
+/// event: the tree translates them from window coordinates while routing.
+/// Wheel deltas are in pixels, positive `y` meaning the wheel moved away from the user.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    MouseMove(Point),
+    MouseDown(MouseButton, Point),
+    MouseUp(MouseButton, Point),
+    MouseWheel(Point, Point),
+    KeyDown(Key, Modifiers),
+    KeyUp(Key, Modifiers),
+    Char(char),
+    /// The cursor moved onto the node or one of its descendants. Sent to
+    /// each such node and not bubbled, like `MouseLeave`.
+    MouseEnter,
+    /// Dispatched to the tree, the cursor left the window.
+    MouseLeave,
+}
+
+impl InputEvent {
+    pub fn position(&self) -> Option<Point> {
+        match *self {
+            InputEvent::MouseMove(pos) |
+            InputEvent::MouseDown(_, pos) |
+            InputEvent::MouseUp(_, pos) |
+            InputEvent::MouseWheel(_, pos) => Some(pos),
+            InputEvent::KeyDown(..) | InputEvent::KeyUp(..) | InputEvent::Char(_) |
+            InputEvent::MouseEnter | InputEvent::MouseLeave => None,
+        }
+    }
+
+    pub fn is_mouse(&self) -> bool {
+        self.position().is_some()
+    }
+
+    /// Returns the event with its position moved into a space whose origin is `origin`.
+    pub fn relative_to(&self, origin: Point) -> InputEvent {
+        self.map_position(|pos| pos - origin)
+    }
+
+    /// Returns the event with its position, if any, replaced by `f(position)`.
+    pub fn map_position<F: Fn(Point) -> Point>(&self, f: F) -> InputEvent {
+        match *self {
+            InputEvent::MouseMove(pos) => InputEvent::MouseMove(f(pos)),
+            InputEvent::MouseDown(button, pos) => InputEvent::MouseDown(button, f(pos)),
+            InputEvent::MouseUp(button, pos) => InputEvent::MouseUp(button, f(pos)),
+            InputEvent::MouseWheel(delta, pos) => InputEvent::MouseWheel(delta, f(pos)),
+            other => other,
+        }
+    }
+}
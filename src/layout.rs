@@ -0,0 +1,374 @@
+use primitives::*;
+
+use std::f32;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Alignment {
+    Stretch,
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+impl Orientation {
+    /// The length of `size` along the axis.
+    pub fn main(&self, size: Size) -> f32 {
+        match *self {
+            Orientation::Horizontal => size.w,
+            Orientation::Vertical => size.h,
+        }
+    }
+
+    /// The coordinate of `point` along the axis.
+    pub fn coord(&self, point: Point) -> f32 {
+        match *self {
+            Orientation::Horizontal => point.x,
+            Orientation::Vertical => point.y,
+        }
+    }
+}
+
+/// How content is scaled to the area it is shown in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stretch {
+    /// Keeps the natural size.
+    None,
+    /// Scales each axis to fill the area, distorting the content.
+    Fill,
+    /// Scales uniformly so the whole content fits.
+    Uniform,
+    /// Scales uniformly so the content covers the area, cropping the excess.
+    UniformToFill,
+}
+
+impl Stretch {
+    /// The horizontal and vertical scale that shows `content` in `available`.
+    pub fn scale(&self, content: Size, available: Size) -> (f32, f32) {
+        let sx = if content.w > 0.0 { available.w / content.w } else { 1.0 };
+        let sy = if content.h > 0.0 { available.h / content.h } else { 1.0 };
+        match *self {
+            Stretch::None => (1.0, 1.0),
+            Stretch::Fill => (sx, sy),
+            Stretch::Uniform => (sx.min(sy), sx.min(sy)),
+            Stretch::UniformToFill => (sx.max(sy), sx.max(sy)),
+        }
+    }
+}
+
+/// Implemented by elements that realize only the visible part of their content.
+/// A `ScrollViewer` hosting such an element passes the scroll offset to it
+/// instead of moving it, and takes the extent from it instead of `measure`.
+pub trait Scrollable {
+    fn extent(&self) -> Size;
+    fn set_offset(&mut self, offset: Point);
+}
+
+/// Layout settings every node has. They are applied by the tree around the
+/// element's own `measure` and `layout`, so elements only deal with their content.
+#[derive(Copy, Clone, Debug)]
+pub struct LayoutProperties {
+    pub margin: Thickness,
+    pub padding: Thickness,
+    pub horizontal_alignment: Alignment,
+    pub vertical_alignment: Alignment,
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    pub min_width: f32,
+    pub min_height: f32,
+    pub max_width: f32,
+    pub max_height: f32,
+}
+
+impl Default for LayoutProperties {
+    fn default() -> LayoutProperties {
+        LayoutProperties {
+            margin: Thickness::zero(),
+            padding: Thickness::zero(),
+            horizontal_alignment: Alignment::Stretch,
+            vertical_alignment: Alignment::Stretch,
+            width: None,
+            height: None,
+            min_width: 0.0,
+            min_height: 0.0,
+            max_width: f32::INFINITY,
+            max_height: f32::INFINITY,
+        }
+    }
+}
+
+impl LayoutProperties {
+    pub fn new() -> LayoutProperties {
+        LayoutProperties::default()
+    }
+
+    pub fn with_margin(mut self, margin: Thickness) -> LayoutProperties {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: Thickness) -> LayoutProperties {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_alignment(mut self, horizontal: Alignment, vertical: Alignment) -> LayoutProperties {
+        self.horizontal_alignment = horizontal;
+        self.vertical_alignment = vertical;
+        self
+    }
+
+    pub fn with_width(mut self, width: f32) -> LayoutProperties {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn with_height(mut self, height: f32) -> LayoutProperties {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn with_min_size(mut self, min: Size) -> LayoutProperties {
+        self.min_width = min.w;
+        self.min_height = min.h;
+        self
+    }
+
+    pub fn with_max_size(mut self, max: Size) -> LayoutProperties {
+        self.max_width = max.w;
+        self.max_height = max.h;
+        self
+    }
+
+    pub fn min_size(&self) -> Size {
+        Size::new(self.min_width, self.min_height)
+    }
+
+    pub fn max_size(&self) -> Size {
+        Size::new(self.max_width, self.max_height)
+    }
+
+    /// Turns the size the element wants for its content into the size the
+    /// node wants from its parent, margins included.
+    pub fn measure(&self, content: Size) -> Size {
+        let w = constrain(self.width.unwrap_or(content.w + self.padding.horizontal()), self.min_width, self.max_width);
+        let h = constrain(self.height.unwrap_or(content.h + self.padding.vertical()), self.min_height, self.max_height);
+        Size::new(w + self.margin.horizontal(), h + self.margin.vertical())
+    }
+
+    /// Places the node inside `slot`, given the outer size from `measure`.
+    pub fn arrange(&self, slot: Rect, desired: Size) -> Rect {
+        let available = slot.inset(self.margin);
+        let (x, w) = align_axis(self.horizontal_alignment, available.left(), available.size.w,
+                                desired.w - self.margin.horizontal(), self.width, self.min_width, self.max_width);
+        let (y, h) = align_axis(self.vertical_alignment, available.top(), available.size.h,
+                                desired.h - self.margin.vertical(), self.height, self.min_height, self.max_height);
+        Rect::pos_size(Point::new(x, y), Size::new(w, h))
+    }
+
+    /// The area children are laid out in, relative to the node's own rect.
+    pub fn content_rect(&self, size: Size) -> Rect {
+        let inner = Rect::from_size(size).inset(self.padding);
+        Rect::pos_size(inner.pos, Size::new(inner.size.w.max(0.0), inner.size.h.max(0.0)))
+    }
+}
+
+/// Moves the edges of `rect`, given relative to `origin` in window coordinates,
+/// to the nearest physical pixel. Edges are rounded rather than sizes, so
+/// siblings sharing an edge still share it and no gaps build up between them.
+pub fn snap_to_pixels(rect: Rect, origin: Point, scale_factor: f32) -> Rect {
+    let snap = |v: f32| (v * scale_factor).round() / scale_factor;
+    let left = snap(origin.x + rect.left());
+    let top = snap(origin.y + rect.top());
+    let right = snap(origin.x + rect.right());
+    let bottom = snap(origin.y + rect.bottom());
+    Rect::pos_size(Point::new(left - origin.x, top - origin.y), Size::new(right - left, bottom - top))
+}
+
+/// Where a popup goes next to the rect it is anchored to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Placement {
+    /// Below the anchor with left edges aligned, or above it if there is more room there.
+    Below,
+    /// Right of the anchor with top edges aligned, or left of it if there is more room there.
+    Right,
+}
+
+/// The rect of a popup of `size` placed next to `anchor`, inside `bounds`.
+/// It flips to the other side of the anchor when it doesn't fit, and moves
+/// along the anchor to stay inside.
+pub fn place_popup(anchor: Rect, size: Size, placement: Placement, bounds: Rect) -> Rect {
+    let size = Size::new(size.w.min(bounds.size.w), size.h.min(bounds.size.h));
+    let beside = |before: f32, after: f32, len: f32, min: f32, max: f32| {
+        if after + len <= max || max - after >= before - min {
+            after.min(max - len)
+        } else {
+            (before - len).max(min)
+        }
+    };
+    let along = |start: f32, len: f32, min: f32, max: f32| start.min(max - len).max(min);
+    let pos = match placement {
+        Placement::Below => Point::new(along(anchor.left(), size.w, bounds.left(), bounds.right()),
+                                       beside(anchor.top(), anchor.bottom(), size.h, bounds.top(), bounds.bottom())),
+        Placement::Right => Point::new(beside(anchor.left(), anchor.right(), size.w, bounds.left(), bounds.right()),
+                                       along(anchor.top(), size.h, bounds.top(), bounds.bottom())),
+    };
+    Rect::pos_size(pos, size)
+}
+
+fn constrain(value: f32, min: f32, max: f32) -> f32 {
+    value.min(max).max(min)
+}
+
+fn align_axis(alignment: Alignment, start: f32, available: f32, desired: f32, fixed: Option<f32>, min: f32, max: f32) -> (f32, f32) {
+    let available = available.max(0.0);
+    let len = match alignment {
+        Alignment::Stretch => fixed.unwrap_or(available),
+        _ => fixed.unwrap_or(desired.min(available)),
+    };
+    let len = constrain(len, min, max);
+    let offset = match alignment {
+        Alignment::Start => 0.0,
+        Alignment::End => available - len,
+        Alignment::Center | Alignment::Stretch => (available - len) / 2.0,
+    };
+    (start + offset, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rect(rect: Rect, x: f32, y: f32, w: f32, h: f32) {
+        assert_eq!(rect.to_pos_size_tuple(), ((x, y), (w, h)));
+    }
+
+    #[test]
+    fn measure_adds_padding_and_margin() {
+        let props = LayoutProperties::new()
+            .with_margin(Thickness::hv(5.0, 2.0))
+            .with_padding(Thickness::hv(1.0, 3.0));
+
+        let size = props.measure(Size::new(10.0, 20.0));
+
+        assert_eq!(size.to_tuple(), (22.0, 30.0));
+    }
+
+    #[test]
+    fn measure_prefers_fixed_size_and_clamps() {
+        let props = LayoutProperties::new()
+            .with_width(40.0)
+            .with_min_size(Size::new(0.0, 50.0))
+            .with_max_size(Size::new(30.0, 100.0));
+
+        let size = props.measure(Size::new(10.0, 20.0));
+
+        assert_eq!(size.to_tuple(), (30.0, 50.0));
+    }
+
+    #[test]
+    fn arrange_stretch_fills_slot_minus_margin() {
+        let props = LayoutProperties::new().with_margin(Thickness::hv(4.0, 8.0));
+
+        let rect = props.arrange(Rect::pos_size(Point::new(10.0, 10.0), Size::new(100.0, 50.0)), Size::zero());
+
+        assert_rect(rect, 14.0, 18.0, 92.0, 34.0);
+    }
+
+    #[test]
+    fn arrange_aligns_desired_size() {
+        let slot = Rect::from_size(Size::new(100.0, 100.0));
+        let desired = Size::new(20.0, 30.0);
+
+        let start = LayoutProperties::new().with_alignment(Alignment::Start, Alignment::End);
+        assert_rect(start.arrange(slot, desired), 0.0, 70.0, 20.0, 30.0);
+
+        let center = LayoutProperties::new().with_alignment(Alignment::Center, Alignment::Center);
+        assert_rect(center.arrange(slot, desired), 40.0, 35.0, 20.0, 30.0);
+    }
+
+    #[test]
+    fn arrange_centers_fixed_size_when_stretched() {
+        let props = LayoutProperties::new().with_width(40.0);
+
+        let rect = props.arrange(Rect::from_size(Size::new(100.0, 10.0)), Size::new(40.0, 0.0));
+
+        assert_rect(rect, 30.0, 0.0, 40.0, 10.0);
+    }
+
+    #[test]
+    fn arrange_stretch_respects_max_size() {
+        let props = LayoutProperties::new().with_max_size(Size::new(50.0, 20.0));
+
+        let rect = props.arrange(Rect::from_size(Size::new(100.0, 100.0)), Size::zero());
+
+        assert_rect(rect, 25.0, 40.0, 50.0, 20.0);
+    }
+
+    #[test]
+    fn arrange_never_exceeds_available_without_min_size() {
+        let props = LayoutProperties::new().with_alignment(Alignment::Start, Alignment::Start);
+
+        let rect = props.arrange(Rect::from_size(Size::new(10.0, 10.0)), Size::new(40.0, 5.0));
+
+        assert_rect(rect, 0.0, 0.0, 10.0, 5.0);
+    }
+
+    #[test]
+    fn stretch_scales() {
+        let content = Size::new(20.0, 10.0);
+        let available = Size::new(100.0, 100.0);
+
+        assert_eq!(Stretch::None.scale(content, available), (1.0, 1.0));
+        assert_eq!(Stretch::Fill.scale(content, available), (5.0, 10.0));
+        assert_eq!(Stretch::Uniform.scale(content, available), (5.0, 5.0));
+        assert_eq!(Stretch::UniformToFill.scale(content, available), (10.0, 10.0));
+    }
+
+    #[test]
+    fn snap_to_pixels_rounds_edges_at_the_scale_factor() {
+        let rect = Rect::pos_size(Point::new(0.3, 1.1), Size::new(10.3, 4.3));
+
+        assert_rect(snap_to_pixels(rect, Point::zero(), 1.0), 0.0, 1.0, 11.0, 4.0);
+        assert_rect(snap_to_pixels(rect, Point::zero(), 2.0), 0.5, 1.0, 10.0, 4.5);
+        assert_rect(snap_to_pixels(rect, Point::new(0.25, 0.0), 2.0), 0.25, 1.0, 10.5, 4.5);
+    }
+
+    #[test]
+    fn snapped_siblings_leave_no_gaps() {
+        let width = 100.0 / 3.0;
+        let mut right = 0.0;
+        for i in 0..3 {
+            let rect = Rect::pos_size(Point::new(width * i as f32, 0.0), Size::new(width, 10.0));
+            let snapped = snap_to_pixels(rect, Point::zero(), 1.5);
+            assert_eq!(snapped.left(), right);
+            right = snapped.right();
+        }
+        assert_eq!(right, 100.0);
+    }
+
+    #[test]
+    fn content_rect_insets_padding() {
+        let props = LayoutProperties::new().with_padding(Thickness::new(1.0, 2.0, 3.0, 4.0));
+
+        assert_rect(props.content_rect(Size::new(10.0, 10.0)), 3.0, 2.0, 6.0, 4.0);
+        assert_rect(props.content_rect(Size::new(2.0, 2.0)), 3.0, 2.0, 0.0, 0.0);
+    }
+
+    #[test]
+    fn popups_flip_and_slide_to_stay_inside() {
+        let bounds = Rect::from_size(Size::new(200.0, 100.0));
+        let anchor = Rect::pos_size(Point::new(150.0, 20.0), Size::new(40.0, 10.0));
+
+        assert_rect(place_popup(anchor, Size::new(30.0, 50.0), Placement::Below, bounds), 150.0, 30.0, 30.0, 50.0);
+        assert_rect(place_popup(anchor, Size::new(80.0, 50.0), Placement::Below, bounds), 120.0, 30.0, 80.0, 50.0);
+        let low = Rect::pos_size(Point::new(10.0, 70.0), Size::new(40.0, 10.0));
+        assert_rect(place_popup(low, Size::new(30.0, 50.0), Placement::Below, bounds), 10.0, 20.0, 30.0, 50.0);
+        assert_rect(place_popup(anchor, Size::new(60.0, 90.0), Placement::Right, bounds), 90.0, 10.0, 60.0, 90.0);
+    }
+}
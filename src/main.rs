@@ -7,23 +7,38 @@ extern crate glium;
 #[macro_use]
 extern crate log;
 
+extern crate cassowary;
+extern crate rusttype;
+extern crate xi_unicode;
+extern crate rustybuzz;
+extern crate unicode_bidi;
+extern crate unicode_segmentation;
+extern crate image;
+
 mod primitives;
+mod layout;
 mod tree;
 mod window;
 mod property;
 mod render;
+mod text;
 mod my_logger;
 mod msg;
+mod input;
+mod clipboard;
+mod bitmap;
+mod tooltip;
+mod elements;
 
 use std::any::Any;
 
 use primitives::*;
+use layout::*;
 use render::*;
 use tree::*;
+use elements::border::Border;
 
-struct TestElement {
-    margin: Thickness,
-}
+struct TestElement;
 
 impl Element for TestElement {
     fn init(&mut self, node: NodeRef){
@@ -37,7 +52,6 @@ impl Element for TestElement {
 
     fn layout(&mut self, mut node: NodeRef, container: Size) {
         info!("[TestElement] Layout");
-        node.set_rect(Rect::from_size(container).inset(self.margin));
     }
 
     fn render(&self, node: NodeRef) -> RenderCommandList {
@@ -48,72 +62,12 @@ impl Element for TestElement {
     }
 }
 
-struct TestBorder {
-    margin: Thickness,
-    node: NodeWeakRef,
-    content: Option<NodeWeakRef>,
-}
-
-impl Element for TestBorder {
-    fn init(&mut self, node: NodeRef){
-        info!("[TestBorder] Init");
-        self.node = node.downgrade();
-    }
-
-    fn measure(&self, node: NodeRef) -> Size {
-        info!("[TestBorder] Measure");
-        Size::zero()
-    }
-
-    fn layout(&mut self, mut node: NodeRef, container: Size) {
-        info!("[TestBorder] Layout");
-        let inner = Rect::from_size(container).inset(self.margin);
-        node.set_rect(inner);
-        if let Some(content) = self.content() {
-            content.layout(inner.size);
-        }
-    }
-
-    fn render(&self, node: NodeRef) -> RenderCommandList {
-        info!("[TestBorder] Render");
-        let mut cmd = RenderCommandList::new();
-        cmd.add(RenderCommand::Clear(Color::argb(1.0, 0.0, 1.0, 0.0)));
-        cmd
-    }
-}
-
-impl TestBorder {
-    pub fn new(margin: Thickness) -> TestBorder {
-        TestBorder {
-            margin: margin,
-            node: NodeWeakRef::empty(),
-            content: None,
-        }
-    }
-
-    pub fn set_content(&mut self, content: Box<Element>) -> NodeRef {
-        let content = self.node().add_child(content);
-        self.content = Some(content.downgrade());
-        content
-    }
-
-    fn node(&self) -> NodeRef {
-        self.node.upgrade().unwrap()
-    }
-
-    fn content(&self) -> Option<NodeRef> {
-        self.content.as_ref().map(|c| c.upgrade().unwrap())
-    }
-}
-
 struct TestList {
     node: NodeWeakRef,
     child_items: Vec<NodeWeakRef>,
 }
 
-struct TestListItem {
-    vertical_offset: f32,
-}
+struct TestListItem;
 
 impl TestList {
     pub fn new() -> TestList {
@@ -124,9 +78,7 @@ impl TestList {
     }
 
     pub fn add_item(&mut self, element: Box<Element>) -> NodeRef {
-        let mut item = self.node.upgrade().unwrap().add_child(Box::new(TestListItem {
-            vertical_offset: 0.0,
-        }));
+        let mut item = self.node.upgrade().unwrap().add_child(Box::new(TestListItem));
         self.child_items.push(item.downgrade());
         item.add_child(element)
     }
@@ -145,15 +97,12 @@ impl Element for TestList {
 
     fn layout(&mut self, mut node: NodeRef, container: Size) {
         info!("[TestList] Layout");
-        let rect = Rect::from_size(container);
-        node.set_rect(rect);
         let mut offset_acc = 0.0;
         for item in &self.child_items {
             let item = item.upgrade().unwrap();
             let size = item.measure();
-            item.cast_element_mut::<TestListItem>().unwrap().vertical_offset = offset_acc;
+            item.arrange(Rect::pos_size(Point::new(0.0, offset_acc), Size::new(container.w, size.h)));
             offset_acc += size.h;
-            item.layout(Size::new(container.w, size.h));
         }
     }
 
@@ -181,15 +130,12 @@ impl Element for TestListItem {
 
     fn layout(&mut self, mut node: NodeRef, container: Size) {
         info!("[TestListItem] Layout");
-        let rect = Rect::from_bounds(container.w, self.vertical_offset, 0.0, self.vertical_offset + container.h);
-        node.set_rect(rect);
-        
         let children = node.children();
         if children.len() != 1 {
             panic!("TestListItem: Child count must be equal to 1");
         }
-        info!("[TestListItem] Rect: {:?}", rect);
-        children[0].layout(rect.size);
+        info!("[TestListItem] Rect: {:?}", node.rect());
+        children[0].layout(container);
     }
 
     fn render(&self, node: NodeRef) -> RenderCommandList {
@@ -206,17 +152,22 @@ fn main() {
         .build();
 
     {
-        let mut border = window.set_content(Box::new(TestBorder::new(Thickness::hv(4.0, 8.0))));
-
-        let mut list = border.cast_element_mut::<TestBorder>().unwrap().set_content(Box::new(TestList::new()));
-
-        list.cast_element_mut::<TestList>().unwrap().add_item(Box::new(TestElement {
-            margin: Thickness::hv(6.0, 6.0),
-        }));
+        let border = Border::new()
+            .with_background(Color::argb(1.0, 0.0, 1.0, 0.0))
+            .with_border(Thickness::uniform(2.0), Color::argb(1.0, 0.0, 0.4, 0.0))
+            .with_corner_radius(CornerRadius::uniform(8.0));
+        let mut border = window.set_content(Box::new(border));
+        border.set_layout_properties(LayoutProperties::new()
+            .with_margin(Thickness::hv(4.0, 8.0))
+            .with_padding(Thickness::uniform(4.0)));
+
+        let mut list = border.cast_element_mut::<Border>().unwrap().set_content(Box::new(TestList::new()));
+
+        let item = list.cast_element_mut::<TestList>().unwrap().add_item(Box::new(TestElement));
+        item.set_layout_properties(LayoutProperties::new().with_margin(Thickness::hv(6.0, 6.0)));
         
-        list.cast_element_mut::<TestList>().unwrap().add_item(Box::new(TestElement {
-            margin: Thickness::hv(6.0, 6.0),
-        }));
+        let item = list.cast_element_mut::<TestList>().unwrap().add_item(Box::new(TestElement));
+        item.set_layout_properties(LayoutProperties::new().with_margin(Thickness::hv(6.0, 6.0)));
     }
 
     window.run_loop();
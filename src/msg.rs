@@ -5,6 +5,14 @@ impl Message {
         self.get_type_id()
     }
 
+    pub fn cast_ref<T: Message + Sized>(&self) -> Option<&T> {
+        if self.get_message_type_id() == TypeId::of::<T>() {
+            Some(unsafe { &*(self as *const Message as *const T) })
+        } else {
+            None
+        }
+    }
+
     pub fn cast<T: Message + Sized>(self: Box<Message>) -> Option<T> {
         if self.get_message_type_id() == TypeId::of::<T>() {
             let res = unsafe { *Box::from_raw(Box::into_raw(self) as *mut T) };
@@ -46,6 +54,14 @@ mod tests {
         assert_eq!(casted, Some(Msg1));
     }
 
+    #[test]
+    fn cast_ref_checks_the_type() {
+        let generic : Box<Message> = Box::new(Msg1);
+
+        assert_eq!(generic.cast_ref::<Msg1>(), Some(&Msg1));
+        assert_eq!(generic.cast_ref::<Msg2>(), None);
+    }
+
     #[test]
     fn cast_to_none() {
         let generic : Box<Message> = Box::new(Msg1);
@@ -1,6 +1,6 @@
 use std::ops::{Add, Sub};
  
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -40,7 +40,7 @@ impl Sub for Point {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Size {
     pub w: f32,
     pub h: f32
@@ -64,7 +64,7 @@ impl Size {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Color{
     pub a: f32,
     pub r: f32,
@@ -89,9 +89,17 @@ impl Color {
     pub fn to_tuple_rgb(&self) -> (f32, f32, f32){
         (self.r, self.g, self.b)
     }
+
+    pub fn to_tuple_rgba(&self) -> (f32, f32, f32, f32){
+        (self.r, self.g, self.b, self.a)
+    }
+
+    pub fn transparent() -> Color {
+        Color::argb(0.0, 0.0, 0.0, 0.0)
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Rect {
     pub pos: Point,
     pub size: Size,
@@ -142,6 +150,39 @@ impl Rect {
         self.pos.y
     }
 
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.left() && point.x < self.right() &&
+        point.y >= self.top() && point.y < self.bottom()
+    }
+
+    pub fn intersect(&self, other: Rect) -> Rect {
+        let left = self.left().max(other.left());
+        let top = self.top().max(other.top());
+        let right = self.right().min(other.right()).max(left);
+        let bottom = self.bottom().min(other.bottom()).max(top);
+        Rect::from_bounds(right, top, left, bottom)
+    }
+
+    /// Like `contains`, but excludes the parts cut off by rounding the corners.
+    pub fn contains_rounded(&self, point: Point, radius: CornerRadius) -> bool {
+        if !self.contains(point) {
+            return false;
+        }
+        let (l, t, r, b) = (self.left(), self.top(), self.right(), self.bottom());
+        let cut_off = |radius: f32, center: Point, in_corner: bool| {
+            let d = point - center;
+            in_corner && d.x * d.x + d.y * d.y > radius * radius
+        };
+        let tl = radius.top_left;
+        let tr = radius.top_right;
+        let br = radius.bottom_right;
+        let bl = radius.bottom_left;
+        !(cut_off(tl, Point::new(l + tl, t + tl), point.x < l + tl && point.y < t + tl) ||
+          cut_off(tr, Point::new(r - tr, t + tr), point.x > r - tr && point.y < t + tr) ||
+          cut_off(br, Point::new(r - br, b - br), point.x > r - br && point.y > b - br) ||
+          cut_off(bl, Point::new(l + bl, b - bl), point.x < l + bl && point.y > b - bl))
+    }
+
     pub fn to_pos_size_tuple(&self) -> ((f32, f32), (f32, f32)) {
         (self.pos.to_tuple(), self.size.to_tuple())
     }
@@ -165,7 +206,7 @@ impl Rect {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Thickness {
     pub right: f32,
     pub top: f32,
@@ -187,7 +228,119 @@ impl Thickness {
         Thickness::new(horizontal, vertical, horizontal, vertical)
     }
 
+    pub fn uniform(value: f32) -> Thickness {
+        Thickness::new(value, value, value, value)
+    }
+
+    pub fn zero() -> Thickness {
+        Thickness::uniform(0.0)
+    }
+
+    pub fn horizontal(&self) -> f32 {
+        self.left + self.right
+    }
+
+    pub fn vertical(&self) -> f32 {
+        self.top + self.bottom
+    }
+
     pub fn rect_in(&self, container: Size) -> Rect {
         Rect::from_bounds(container.w - self.right, self.top, self.left, container.h - self.bottom)
     }
-}
\ No newline at end of file
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CornerRadius {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_right: f32,
+    pub bottom_left: f32,
+}
+
+impl CornerRadius {
+    pub fn new(top_left: f32, top_right: f32, bottom_right: f32, bottom_left: f32) -> CornerRadius {
+        CornerRadius {
+            top_left: top_left,
+            top_right: top_right,
+            bottom_right: bottom_right,
+            bottom_left: bottom_left,
+        }
+    }
+
+    pub fn uniform(radius: f32) -> CornerRadius {
+        CornerRadius::new(radius, radius, radius, radius)
+    }
+
+    pub fn zero() -> CornerRadius {
+        CornerRadius::uniform(0.0)
+    }
+
+    /// The radii of the inner edge of a border of the given thickness.
+    pub fn inset(&self, thickness: Thickness) -> CornerRadius {
+        CornerRadius::new(
+            (self.top_left - thickness.left.max(thickness.top)).max(0.0),
+            (self.top_right - thickness.right.max(thickness.top)).max(0.0),
+            (self.bottom_right - thickness.right.max(thickness.bottom)).max(0.0),
+            (self.bottom_left - thickness.left.max(thickness.bottom)).max(0.0))
+    }
+
+    pub fn to_tuple(&self) -> (f32, f32, f32, f32) {
+        (self.top_left, self.top_right, self.bottom_right, self.bottom_left)
+    }
+}
+
+/// A scale followed by a translation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub offset: Point,
+}
+
+impl Transform {
+    pub fn new(scale_x: f32, scale_y: f32, offset: Point) -> Transform {
+        Transform { scale_x: scale_x, scale_y: scale_y, offset: offset }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(1.0, 1.0, Point::zero())
+    }
+
+    pub fn translation(offset: Point) -> Transform {
+        Transform::new(1.0, 1.0, offset)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+
+    pub fn is_translation(&self) -> bool {
+        self.scale_x == 1.0 && self.scale_y == 1.0
+    }
+
+    /// Returns the transform that applies `inner` first and then `self`.
+    pub fn then(&self, inner: Transform) -> Transform {
+        Transform::new(self.scale_x * inner.scale_x, self.scale_y * inner.scale_y, self.apply(inner.offset))
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        Point::new(point.x * self.scale_x + self.offset.x, point.y * self.scale_y + self.offset.y)
+    }
+
+    pub fn apply_rect(&self, rect: Rect) -> Rect {
+        Rect::pos_size(self.apply(rect.pos), Size::new(rect.size.w * self.scale_x, rect.size.h * self.scale_y))
+    }
+
+    /// Whether points can be mapped back; a zero scale collapses everything
+    /// onto a line or a point.
+    pub fn is_invertible(&self) -> bool {
+        self.scale_x != 0.0 && self.scale_y != 0.0
+    }
+
+    pub fn inverse_apply(&self, point: Point) -> Option<Point> {
+        if !self.is_invertible() {
+            return None;
+        }
+        Some(Point::new((point.x - self.offset.x) / self.scale_x, (point.y - self.offset.y) / self.scale_y))
+    }
+}
@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+
 #[derive(Copy, Clone)]
 pub enum AccessType {
     ReadOnly,
@@ -36,19 +38,21 @@ impl AccessType {
     }
 }
 
-pub struct Property<T: Copy> {
+pub struct Property<T: Clone> {
     name: String,
     access_type: AccessType,
     source: Box<ValueSource<T>>,
+    coercion: RefCell<Option<Box<Fn(T) -> T>>>,
+    observers: RefCell<Vec<Box<Fn(&T)>>>,
 }
 
-pub trait ValueSource<T: Copy> {
+pub trait ValueSource<T: Clone> {
     fn access_type(&self) -> AccessType;
     fn get(&self) -> T;
     fn set(&self, value: T);
 }
 
-impl<T: Copy> Property<T> {
+impl<T: Clone> Property<T> {
     pub fn new<S: Into<String>>(name: S, access_type: AccessType, source: Box<ValueSource<T>>) -> Result<Property<T>, String> {
         if !access_type.suits(source.access_type()) {
             Err("Unsiutable value source provided".to_owned())
@@ -56,7 +60,9 @@ impl<T: Copy> Property<T> {
             Ok(Property { 
                 name: name.into(),
                 access_type:access_type,
-                source: source
+                source: source,
+                coercion: RefCell::new(None),
+                observers: RefCell::new(Vec::new()),
             })
         }
     }
@@ -80,36 +86,52 @@ impl<T: Copy> Property<T> {
         if !self.access_type.write_enabled() {
             panic!("Calling set on property with disabled write");
         }
-        self.source.set(value)
+        let value = match *self.coercion.borrow() {
+            Some(ref coerce) => coerce(value),
+            None => value,
+        };
+        self.source.set(value.clone());
+        for observer in self.observers.borrow().iter() {
+            observer(&value);
+        }
+    }
+
+    /// Passes each value set through `coerce` before storing it, such as to
+    /// clamp it to a range. Replaces any previous coercion.
+    pub fn coerce<F: Fn(T) -> T + 'static>(&self, coerce: F) {
+        *self.coercion.borrow_mut() = Some(Box::new(coerce));
+    }
+
+    /// Calls `observer` with the new value each time the property is set.
+    pub fn observe<F: Fn(&T) + 'static>(&self, observer: F) {
+        self.observers.borrow_mut().push(Box::new(observer));
     }
 }
 
 pub mod source {
     use super::*;
 
-    use std::cell::Cell;
-
     pub struct Variable<T> {
-        data: Cell<T>,
+        data: RefCell<T>,
     }
 
-    impl<T> Variable<T> where T: Copy {
+    impl<T> Variable<T> where T: Clone {
         pub fn new(default: T) -> Variable<T> {
             Variable {
-                data: Cell::new(default)
+                data: RefCell::new(default)
             }
         }
     }
 
-    impl<T> ValueSource<T> for Variable<T> where T: Copy {
+    impl<T> ValueSource<T> for Variable<T> where T: Clone {
         fn access_type(&self) -> AccessType { AccessType::ReadWrite }
 
         fn get(&self) -> T {
-            self.data.get()
+            self.data.borrow().clone()
         }
 
         fn set(&self, value: T) {
-            self.data.set(value)
+            *self.data.borrow_mut() = value;
         }
     }
 }
@@ -136,6 +158,48 @@ mod tests {
         assert_eq!(prop.get(), 3);
     }
 
+    #[test]
+    fn string_property() {
+        let prop = Property::new("text", AccessType::ReadWrite, Box::new(source::Variable::new(String::new()))).unwrap();
+
+        prop.set("abc".to_string());
+
+        assert_eq!(prop.get(), "abc");
+    }
+
+    #[test]
+    fn observers_see_each_set() {
+        use std::rc::Rc;
+        use std::cell::Cell;
+
+        let prop = Property::new("foo", AccessType::ReadWrite, Box::new(source::Variable::new(0))).unwrap();
+        let seen = Rc::new(Cell::new(0));
+        let observed = seen.clone();
+        prop.observe(move |value| observed.set(observed.get() + *value));
+
+        prop.set(3);
+        prop.set(4);
+
+        assert_eq!(seen.get(), 7);
+    }
+
+    #[test]
+    fn coercion_applies_before_storing_and_observing() {
+        use std::rc::Rc;
+        use std::cell::Cell;
+
+        let prop = Property::new("foo", AccessType::ReadWrite, Box::new(source::Variable::new(0))).unwrap();
+        let seen = Rc::new(Cell::new(0));
+        let observed = seen.clone();
+        prop.coerce(|value: i32| value.min(10));
+        prop.observe(move |value| observed.set(*value));
+
+        prop.set(30);
+
+        assert_eq!(prop.get(), 10);
+        assert_eq!(seen.get(), 10);
+    }
+
     #[test]
     #[should_panic]
     fn set_read_only_property() {
@@ -1,13 +1,23 @@
 use primitives::*;
+use bitmap::Bitmap;
+use text::*;
+use text::fonts::FontManager;
 use glium;
 use glium::glutin;
-use std::collections::LinkedList;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, LinkedList};
+use std::rc::Rc;
+use std::sync::{Arc, Weak};
 
 pub struct Renderer<'a> {
     surface: &'a mut glium::Frame,
     rendering_context: &'a RenderingContext,
     size: Size,
-    viewport_stack: Vec<Rect>,
+    scale_factor: f32,
+    viewport_stack: Vec<(Transform, Size)>,
+    clip_stack: Vec<Rect>,
+    rounded_clip_stack: Vec<(Rect, CornerRadius, Transform)>,
 }
 
 #[derive(Copy, Clone)]
@@ -17,52 +27,279 @@ struct Vertex {
 
 implement_vertex!(Vertex, position);
 
+#[derive(Copy, Clone)]
+struct GlyphVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+
+implement_vertex!(GlyphVertex, position, tex_coords);
+
 pub struct RenderingContext {
+    display: glium::Display,
     vertex_buffer: glium::VertexBuffer<Vertex>,
     index_buffer: glium::IndexBuffer<u16>,
     program: glium::Program,
+    shape_program: glium::Program,
+    text_program: glium::Program,
+    glyph_cache: RefCell<GlyphCache>,
+    glyph_atlas: glium::texture::Texture2d,
+    image_program: glium::Program,
+    textures: RefCell<HashMap<usize, CachedTexture>>,
+    fonts: Rc<RefCell<FontManager>>,
+}
+
+/// The texture of a bitmap, dropped once the bitmap is.
+struct CachedTexture {
+    bitmap: Weak<Bitmap>,
+    texture: glium::texture::Texture2d,
+    mipmapped: bool,
+}
+
+/// How image pixels are sampled when drawn at another size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SamplingQuality {
+    /// The nearest pixel, keeping pixel art sharp.
+    Nearest,
+    /// Blends the nearest pixels; smooth when enlarging, but aliased when
+    /// shrinking far.
+    Linear,
+    /// Blends prescaled copies of the image, smooth at any size.
+    Mipmapped,
+}
+
+/// How a rounded shape is drawn by `RenderingContext::draw_shape`.
+#[derive(Copy, Clone, PartialEq)]
+pub enum ShapeMode {
+    /// Draws the fill and the border.
+    Paint,
+    /// Covers only the interior, inside the border; used to build clip masks.
+    Interior,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BorderStyle {
+    pub background: Color,
+    pub thickness: Thickness,
+    pub left_color: Color,
+    pub top_color: Color,
+    pub right_color: Color,
+    pub bottom_color: Color,
+    pub corner_radius: CornerRadius,
+}
+
+impl BorderStyle {
+    pub fn new(background: Color) -> BorderStyle {
+        BorderStyle {
+            background: background,
+            thickness: Thickness::zero(),
+            left_color: Color::transparent(),
+            top_color: Color::transparent(),
+            right_color: Color::transparent(),
+            bottom_color: Color::transparent(),
+            corner_radius: CornerRadius::zero(),
+        }
+    }
+
+    pub fn with_border(mut self, thickness: Thickness, color: Color) -> BorderStyle {
+        self.thickness = thickness;
+        self.left_color = color;
+        self.top_color = color;
+        self.right_color = color;
+        self.bottom_color = color;
+        self
+    }
+
+    pub fn with_corner_radius(mut self, radius: CornerRadius) -> BorderStyle {
+        self.corner_radius = radius;
+        self
+    }
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(surface: &'a mut glium::Frame, rendering_context: &'a RenderingContext, size: Size, viewport: Rect) -> Renderer<'a> {
+    /// `size` is in layout units; `scale_factor` is the number of physical pixels per unit.
+    pub fn new(surface: &'a mut glium::Frame, rendering_context: &'a RenderingContext, size: Size, scale_factor: f32, viewport: Rect) -> Renderer<'a> {
         Renderer { 
             surface: surface,
             size: size,
-            viewport_stack: vec![viewport],
+            scale_factor: scale_factor,
+            viewport_stack: vec![(Transform::translation(viewport.pos), viewport.size)],
+            clip_stack: vec![Rect::from_size(size)],
+            rounded_clip_stack: Vec::new(),
             rendering_context: rendering_context,
         }
     }
 
     fn to_relative(&self, rect: Rect) ->((f32, f32), (f32, f32)) {
-        let ((x, y), (w, h)) = self.viewport().transform_to_outer(rect).to_pos_size_tuple();
+        self.to_ndc(self.to_window(rect))
+    }
+
+    /// Maps a rect relative to the current viewport to window coordinates.
+    fn to_window(&self, rect: Rect) -> Rect {
+        self.transform().apply_rect(rect)
+    }
+
+    fn to_ndc(&self, rect: Rect) -> ((f32, f32), (f32, f32)) {
+        let ((x, y), (w, h)) = rect.to_pos_size_tuple();
         ((x / self.size.w * 2.0 - 1.0, 1.0 - y / self.size.h * 2.0), (w / self.size.w * 2.0, h / self.size.h * 2.0))
     }
 
-    fn viewport(&self) -> Rect {
-        self.viewport_stack.last().unwrap().clone()
+    fn transform(&self) -> Transform {
+        self.viewport_stack.last().unwrap().0
     }
 
-    /*pub fn sub_renderer(&'a mut self, rect: Rect) -> Renderer<'a> {
-        Renderer::new(self.surface, self.rendering_context, self.size, self.viewport.transform_to_outer(rect))
-    }*/
+    fn viewport_size(&self) -> Size {
+        self.viewport_stack.last().unwrap().1
+    }
+
+    /// The number of physical pixels per unit of the current viewport.
+    fn pixel_scale(transform: Transform, scale_factor: f32) -> f32 {
+        scale_factor * (transform.scale_x.abs() + transform.scale_y.abs()) / 2.0
+    }
 
     pub fn push_rect(&mut self, rect: Rect) {
-        let transformed = self.viewport().transform_to_outer(rect);
-        self.viewport_stack.push(transformed);
+        let transform = self.transform().then(Transform::translation(rect.pos));
+        self.viewport_stack.push((transform, rect.size));
     }
 
     pub fn pop_rect(&mut self) {
         self.viewport_stack.pop();
     }
 
+    /// Scales and moves everything drawn until the matching `pop_transform`.
+    /// Nothing drawn under a transform with a zero scale is visible.
+    pub fn push_transform(&mut self, transform: Transform) {
+        let size = if transform.is_invertible() {
+            let size = self.viewport_size();
+            Size::new(size.w / transform.scale_x, size.h / transform.scale_y)
+        } else {
+            Size::zero()
+        };
+        let transform = self.transform().then(transform);
+        self.viewport_stack.push((transform, size));
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.viewport_stack.pop();
+    }
+
+    fn clip(&self) -> Rect {
+        self.clip_stack.last().unwrap().clone()
+    }
+
+    /// Restricts drawing to `rect`, given relative to the current viewport,
+    /// until the matching `pop_clip`. Clips nest by intersection.
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = self.clip().intersect(self.to_window(rect));
+        self.clip_stack.push(clip);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+
+    /// Restricts drawing to the rounded rect, given relative to the current
+    /// viewport, until the matching `pop_rounded_clip`. The shape is kept in
+    /// the stencil buffer, whose value is the number of rounded clips a pixel
+    /// is inside of; drawing only passes where that equals the clip depth.
+    pub fn push_rounded_clip(&mut self, rect: Rect, radius: CornerRadius) {
+        let transform = self.transform();
+        self.stencil_shape(rect, radius, transform, glium::StencilOperation::Increment);
+        self.rounded_clip_stack.push((rect, radius, transform));
+        self.push_clip(rect);
+    }
+
+    pub fn pop_rounded_clip(&mut self) {
+        self.pop_clip();
+        if let Some((rect, radius, transform)) = self.rounded_clip_stack.last().cloned() {
+            self.stencil_shape(rect, radius, transform, glium::StencilOperation::Decrement);
+        }
+        self.rounded_clip_stack.pop();
+    }
+
+    fn stencil_shape(&mut self, rect: Rect, radius: CornerRadius, transform: Transform, operation: glium::StencilOperation) {
+        let mut params = self.draw_parameters();
+        params.color_mask = (false, false, false, false);
+        params.stencil.depth_pass_operation_clockwise = operation;
+        params.stencil.depth_pass_operation_counter_clockwise = operation;
+        let style = BorderStyle::new(Color::transparent()).with_corner_radius(radius);
+        let (pos, size) = self.to_ndc(transform.apply_rect(rect));
+        let pixel_scale = Renderer::pixel_scale(transform, self.scale_factor);
+        self.rendering_context.draw_shape(self.surface, pos, size, rect.size, pixel_scale, &style, ShapeMode::Interior, &params);
+    }
+
+    fn scissor(&self) -> glium::Rect {
+        let clip = self.clip();
+        let scale = self.scale_factor;
+        glium::Rect {
+            left: (clip.left().max(0.0) * scale).round() as u32,
+            bottom: ((self.size.h - clip.bottom()).max(0.0) * scale).round() as u32,
+            width: (clip.size.w.max(0.0) * scale).round() as u32,
+            height: (clip.size.h.max(0.0) * scale).round() as u32,
+        }
+    }
+
+    fn draw_parameters(&self) -> glium::DrawParameters<'static> {
+        use glium::{StencilTest, StencilOperation};
+
+        let depth = self.rounded_clip_stack.len() as i32;
+        let mut params = glium::DrawParameters {
+            scissor: Some(self.scissor()),
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+        params.stencil.test_clockwise = StencilTest::IfEqual { mask: 0xff };
+        params.stencil.test_counter_clockwise = StencilTest::IfEqual { mask: 0xff };
+        params.stencil.reference_value_clockwise = depth;
+        params.stencil.reference_value_counter_clockwise = depth;
+        params.stencil.depth_pass_operation_clockwise = StencilOperation::Keep;
+        params.stencil.depth_pass_operation_counter_clockwise = StencilOperation::Keep;
+        params
+    }
+
     pub fn clear(&mut self, color: Color) {
-        let size = self.viewport().size.clone();
+        let size = self.viewport_size();
         self.rect(Rect::from_size(size), color);
     }
 
     pub fn rect(&mut self, rect: Rect, color: Color) {
         let (pos, size) = self.to_relative(rect);
-        self.rendering_context.draw_rect(self.surface, pos, size, color);
+        let params = self.draw_parameters();
+        self.rendering_context.draw_rect(self.surface, pos, size, color, &params);
+    }
+
+    pub fn border(&mut self, rect: Rect, style: &BorderStyle) {
+        let (pos, size) = self.to_relative(rect);
+        let params = self.draw_parameters();
+        let pixel_scale = Renderer::pixel_scale(self.transform(), self.scale_factor);
+        self.rendering_context.draw_shape(self.surface, pos, size, rect.size, pixel_scale, style, ShapeMode::Paint, &params);
+    }
+
+    /// Draws `run` with the start of its baseline at `origin`. Glyphs are
+    /// rasterized for the physical pixel size and horizontal subpixel
+    /// position they end up at.
+    pub fn text(&mut self, origin: Point, run: &TextRun) {
+        let transform = self.transform();
+        let scale = self.scale_factor;
+        let pixel_scale = Renderer::pixel_scale(transform, scale);
+        let origin = transform.apply(origin);
+        let quads = self.rendering_context.glyph_cache.borrow_mut()
+            .quads(run, Point::new(origin.x * scale, origin.y * scale), pixel_scale);
+        let quads: Vec<((f32, f32), (f32, f32), Rect)> = quads.iter().map(|quad| {
+            let rect = Rect::pos_size(Point::new(quad.rect.pos.x / scale, quad.rect.pos.y / scale),
+                                      Size::new(quad.rect.size.w / scale, quad.rect.size.h / scale));
+            let (pos, size) = self.to_ndc(rect);
+            (pos, size, quad.uv)
+        }).collect();
+        let params = self.draw_parameters();
+        self.rendering_context.draw_glyphs(self.surface, &quads, run.color, &params);
+    }
+
+    /// Draws `bitmap` scaled to `rect`.
+    pub fn image(&mut self, rect: Rect, bitmap: &Arc<Bitmap>, quality: SamplingQuality) {
+        let (pos, size) = self.to_relative(rect);
+        let params = self.draw_parameters();
+        self.rendering_context.draw_image(self.surface, pos, size, bitmap, quality, &params);
     }
 
     pub fn execute(&mut self, commands: RenderCommandList) {
@@ -70,13 +307,18 @@ impl<'a> Renderer<'a> {
             match cmd {
                 RenderCommand::Clear(color) => self.clear(color),
                 RenderCommand::Rect(rect, color) => self.rect(rect, color),
+                RenderCommand::Border(rect, style) => self.border(rect, &style),
+                RenderCommand::Text(origin, run) => self.text(origin, &run),
+                RenderCommand::Image(rect, bitmap, quality) => self.image(rect, &bitmap, quality),
+                RenderCommand::PushClip(rect) => self.push_clip(rect),
+                RenderCommand::PopClip => self.pop_clip(),
             }
         }
     }
 }
 
 impl RenderingContext {
-    pub fn new(display: &glium::Display) -> RenderingContext {
+    pub fn new(display: &glium::Display, fonts: Rc<RefCell<FontManager>>) -> RenderingContext {
         let vertex_buffer = {        
             glium::VertexBuffer::new(display,
                 &[
@@ -160,14 +402,159 @@ impl RenderingContext {
             },*/
         ).unwrap();
 
+        let shape_program = program!(display,
+            140 => {
+                vertex: "
+                    #version 140
+                    uniform vec2 pos;
+                    uniform vec2 size;
+                    uniform vec2 rect_size;
+                    in vec2 position;
+                    out vec2 vLocal;
+                    void main() {
+                        gl_Position = vec4(position.x * size.x + pos.x, position.y * size.y + pos.y, 0.0, 1.0);
+                        vLocal = vec2(position.x, -position.y) * rect_size;
+                    }
+                ",
+
+                fragment: "
+                    #version 140
+                    uniform vec2 rect_size;
+                    uniform vec4 radii;
+                    uniform vec4 widths;
+                    uniform vec4 fill;
+                    uniform vec4 left_color;
+                    uniform vec4 top_color;
+                    uniform vec4 right_color;
+                    uniform vec4 bottom_color;
+                    uniform int interior_only;
+                    uniform float pixel_scale;
+                    in vec2 vLocal;
+                    out vec4 f_color;
+
+                    // Signed distance to a rounded box; radii are top-left, top-right,
+                    // bottom-right, bottom-left.
+                    float rounded_box(vec2 p, vec2 origin, vec2 size, vec4 r) {
+                        vec2 q = p - origin - size * 0.5;
+                        float radius = q.x < 0.0 ? (q.y < 0.0 ? r.x : r.w) : (q.y < 0.0 ? r.y : r.z);
+                        vec2 d = abs(q) - size * 0.5 + radius;
+                        return min(max(d.x, d.y), 0.0) + length(max(d, 0.0)) - radius;
+                    }
+
+                    void main() {
+                        vec2 inner_origin = widths.xy;
+                        vec2 inner_size = rect_size - widths.xy - widths.zw;
+                        vec4 inner_radii = max(radii - vec4(max(widths.x, widths.y), max(widths.z, widths.y),
+                                                            max(widths.z, widths.w), max(widths.x, widths.w)), 0.0);
+                        float inner = rounded_box(vLocal, inner_origin, inner_size, inner_radii);
+
+                        if (interior_only != 0) {
+                            if (inner > 0.0) {
+                                discard;
+                            }
+                            f_color = vec4(0.0);
+                            return;
+                        }
+
+                        float outer = rounded_box(vLocal, vec2(0.0), rect_size, radii);
+
+                        // The side a border pixel belongs to is the one it is
+                        // relatively closest to, which splits corners diagonally.
+                        vec4 dist = vec4(vLocal.x, vLocal.y, rect_size.x - vLocal.x, rect_size.y - vLocal.y)
+                                  / max(widths, vec4(0.0001));
+                        vec4 border = left_color;
+                        float best = dist.x;
+                        if (dist.y < best) { best = dist.y; border = top_color; }
+                        if (dist.z < best) { best = dist.z; border = right_color; }
+                        if (dist.w < best) { best = dist.w; border = bottom_color; }
+
+                        // Edges are antialiased over one physical pixel.
+                        float inside = clamp(0.5 - inner * pixel_scale, 0.0, 1.0);
+                        vec4 color = mix(border, fill, inside);
+                        f_color = vec4(color.rgb, color.a * clamp(0.5 - outer * pixel_scale, 0.0, 1.0));
+                    }
+                "
+            }
+        ).unwrap();
+
+        let text_program = program!(display,
+            140 => {
+                vertex: "
+                    #version 140
+                    in vec2 position;
+                    in vec2 tex_coords;
+                    out vec2 vTexCoords;
+                    void main() {
+                        gl_Position = vec4(position, 0.0, 1.0);
+                        vTexCoords = tex_coords;
+                    }
+                ",
+
+                fragment: "
+                    #version 140
+                    uniform sampler2D atlas;
+                    uniform vec4 color;
+                    in vec2 vTexCoords;
+                    out vec4 f_color;
+                    void main() {
+                        f_color = vec4(color.rgb, color.a * texture(atlas, vTexCoords).r);
+                    }
+                "
+            }
+        ).unwrap();
+
+        let image_program = program!(display,
+            140 => {
+                vertex: "
+                    #version 140
+                    uniform vec2 pos;
+                    uniform vec2 size;
+                    in vec2 position;
+                    out vec2 vTexCoords;
+                    void main() {
+                        gl_Position = vec4(position.x * size.x + pos.x, position.y * size.y + pos.y, 0.0, 1.0);
+                        vTexCoords = vec2(position.x, -position.y);
+                    }
+                ",
+
+                fragment: "
+                    #version 140
+                    uniform sampler2D image;
+                    in vec2 vTexCoords;
+                    out vec4 f_color;
+                    void main() {
+                        f_color = texture(image, vTexCoords);
+                    }
+                "
+            }
+        ).unwrap();
+
+        let glyph_atlas = glium::texture::Texture2d::empty_with_format(display,
+            glium::texture::UncompressedFloatFormat::U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            ATLAS_SIZE, ATLAS_SIZE).unwrap();
+
         RenderingContext {
+            display: display.clone(),
             vertex_buffer: vertex_buffer,
             index_buffer: index_buffer,
             program: program,
+            shape_program: shape_program,
+            text_program: text_program,
+            glyph_cache: RefCell::new(GlyphCache::new(ATLAS_SIZE, ATLAS_SIZE)),
+            glyph_atlas: glyph_atlas,
+            image_program: image_program,
+            textures: RefCell::new(HashMap::new()),
+            fonts: fonts,
         }
     }
 
-    pub fn draw_rect(&self, surface: &mut glium::Frame, pos: (f32, f32), size: (f32, f32), color: Color) {
+    /// The fonts of the windows that share this context.
+    pub fn fonts(&self) -> Rc<RefCell<FontManager>> {
+        self.fonts.clone()
+    }
+
+    pub fn draw_rect(&self, surface: &mut glium::Frame, pos: (f32, f32), size: (f32, f32), color: Color, params: &glium::DrawParameters) {
         use glium::Surface;
 
         info!("[Renderer] Drawing rect with pos: {:?} size: {:?}", &pos, &size);
@@ -178,13 +565,151 @@ impl RenderingContext {
             color: color.to_tuple_rgb()
         };
 
-        surface.draw(&self.vertex_buffer, &self.index_buffer, &self.program, &uniforms, &Default::default()).unwrap();
+        surface.draw(&self.vertex_buffer, &self.index_buffer, &self.program, &uniforms, params).unwrap();
+    }
+
+    /// Draws a rounded rect with a border. `pos` and `size` place the quad in
+    /// device coordinates, `rect_size` is its size in layout units, of which
+    /// there are `pixel_scale` physical pixels each.
+    pub fn draw_shape(&self, surface: &mut glium::Frame, pos: (f32, f32), size: (f32, f32), rect_size: Size, pixel_scale: f32,
+                      style: &BorderStyle, mode: ShapeMode, params: &glium::DrawParameters) {
+        use glium::Surface;
+
+        let t = style.thickness;
+        let uniforms = uniform! {
+            pos: pos,
+            size: size,
+            rect_size: rect_size.to_tuple(),
+            radii: style.corner_radius.to_tuple(),
+            widths: (t.left, t.top, t.right, t.bottom),
+            fill: style.background.to_tuple_rgba(),
+            left_color: style.left_color.to_tuple_rgba(),
+            top_color: style.top_color.to_tuple_rgba(),
+            right_color: style.right_color.to_tuple_rgba(),
+            bottom_color: style.bottom_color.to_tuple_rgba(),
+            interior_only: if mode == ShapeMode::Interior { 1 } else { 0 },
+            pixel_scale: pixel_scale,
+        };
+
+        surface.draw(&self.vertex_buffer, &self.index_buffer, &self.shape_program, &uniforms, params).unwrap();
+    }
+
+    /// Draws glyphs from the atlas, each given as its position and size in
+    /// device coordinates and its area of the atlas. Glyphs rasterized since
+    /// the last call are copied to the atlas first.
+    pub fn draw_glyphs(&self, surface: &mut glium::Frame, quads: &[((f32, f32), (f32, f32), Rect)], color: Color,
+                       params: &glium::DrawParameters) {
+        use glium::Surface;
+        use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+        for upload in self.glyph_cache.borrow_mut().take_uploads() {
+            let rect = glium::Rect { left: upload.x, bottom: upload.y, width: upload.width, height: upload.height };
+            self.glyph_atlas.write(rect, glium::texture::RawImage2d {
+                data: Cow::Owned(upload.data),
+                width: upload.width,
+                height: upload.height,
+                format: glium::texture::ClientFormat::U8,
+            });
+        }
+
+        if quads.is_empty() {
+            return;
+        }
+
+        // Atlas rows are used top to bottom, the way bitmaps were uploaded,
+        // so texture coordinates need no flipping.
+        let mut vertices = Vec::with_capacity(quads.len() * 6);
+        for &((x, y), (w, h), uv) in quads {
+            let corner = |dx: f32, dy: f32| GlyphVertex {
+                position: [x + w * dx, y - h * dy],
+                tex_coords: [uv.pos.x + uv.size.w * dx, uv.pos.y + uv.size.h * dy],
+            };
+            vertices.extend_from_slice(&[corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0),
+                                         corner(0.0, 0.0), corner(1.0, 1.0), corner(0.0, 1.0)]);
+        }
+        let vertex_buffer = glium::VertexBuffer::new(&self.display, &vertices).unwrap();
+
+        let uniforms = uniform! {
+            atlas: self.glyph_atlas.sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
+            color: color.to_tuple_rgba(),
+        };
+
+        surface.draw(&vertex_buffer, glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                     &self.text_program, &uniforms, params).unwrap();
+    }
+
+    /// Draws `bitmap` into the quad at `pos` and `size` in device
+    /// coordinates. Its texture is made on first use and kept until the
+    /// bitmap is dropped.
+    pub fn draw_image(&self, surface: &mut glium::Frame, pos: (f32, f32), size: (f32, f32), bitmap: &Arc<Bitmap>,
+                      quality: SamplingQuality, params: &glium::DrawParameters) {
+        use glium::Surface;
+        use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d};
+        use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+        let mipmapped = quality == SamplingQuality::Mipmapped;
+        let mut textures = self.textures.borrow_mut();
+        if textures.get(&bitmap.id()).map_or(true, |cached| mipmapped && !cached.mipmapped) {
+            let dropped: Vec<usize> = textures.iter()
+                .filter(|&(_, cached)| cached.bitmap.upgrade().is_none())
+                .map(|(&id, _)| id)
+                .collect();
+            for id in dropped {
+                textures.remove(&id);
+            }
+            // Rows go top to bottom, the way they are stored, so that the
+            // top of the quad samples the first row.
+            let raw = RawImage2d {
+                data: Cow::Borrowed(bitmap.pixels()),
+                width: bitmap.width(),
+                height: bitmap.height(),
+                format: ClientFormat::U8U8U8U8,
+            };
+            let mipmaps = if mipmapped { MipmapsOption::AutoGeneratedMipmaps } else { MipmapsOption::NoMipmap };
+            let texture = match Texture2d::with_mipmaps(&self.display, raw, mipmaps) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    info!("[Renderer] Can't make a texture of a {}x{} bitmap: {:?}", bitmap.width(), bitmap.height(), e);
+                    return;
+                }
+            };
+            textures.insert(bitmap.id(), CachedTexture {
+                bitmap: Arc::downgrade(bitmap),
+                texture: texture,
+                mipmapped: mipmapped,
+            });
+        }
+
+        let (magnify, minify) = match quality {
+            SamplingQuality::Nearest => (MagnifySamplerFilter::Nearest, MinifySamplerFilter::Nearest),
+            SamplingQuality::Linear => (MagnifySamplerFilter::Linear, MinifySamplerFilter::Linear),
+            SamplingQuality::Mipmapped => (MagnifySamplerFilter::Linear, MinifySamplerFilter::LinearMipmapLinear),
+        };
+        let uniforms = uniform! {
+            pos: pos,
+            size: size,
+            image: textures[&bitmap.id()].texture.sampled()
+                .magnify_filter(magnify)
+                .minify_filter(minify),
+        };
+
+        surface.draw(&self.vertex_buffer, &self.index_buffer, &self.image_program, &uniforms, params).unwrap();
     }
 }
 
 pub enum RenderCommand {
     Clear(Color),
     Rect(Rect, Color),
+    Border(Rect, BorderStyle),
+    /// Glyphs with the start of their baseline at the point.
+    Text(Point, TextRun),
+    /// A bitmap scaled to the rect.
+    Image(Rect, Arc<Bitmap>, SamplingQuality),
+    /// Restricts the following commands to the rect until the matching `PopClip`.
+    PushClip(Rect),
+    PopClip,
 }
 
 pub struct RenderCommandList {
@@ -0,0 +1,361 @@
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Source {
+    Original,
+    Added,
+}
+
+/// A span of one of the buffer's two backing strings.
+#[derive(Copy, Clone, Debug)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize,
+    newlines: usize,
+}
+
+/// Text stored as a piece table, for documents too large to copy on every
+/// edit. The loaded text is never changed; inserted text is appended to a
+/// second string, and the document is the sequence of pieces of both.
+///
+/// Offsets are byte offsets in the document, and must lie on char
+/// boundaries. Lines end with `\n`; a `\r` before it belongs to the line
+/// break as well.
+pub struct TextBuffer {
+    original: String,
+    added: String,
+    /// The offsets of the newlines in `original` and `added`.
+    original_breaks: Vec<usize>,
+    added_breaks: Vec<usize>,
+    pieces: Vec<Piece>,
+    len: usize,
+    newlines: usize,
+}
+
+impl TextBuffer {
+    pub fn new<T: Into<String>>(text: T) -> TextBuffer {
+        let original = text.into();
+        let original_breaks = breaks(&original, 0);
+        let mut buffer = TextBuffer {
+            pieces: Vec::new(),
+            len: original.len(),
+            newlines: original_breaks.len(),
+            original: original,
+            added: String::new(),
+            original_breaks: original_breaks,
+            added_breaks: Vec::new(),
+        };
+        if buffer.len > 0 {
+            buffer.pieces.push(Piece {
+                source: Source::Original,
+                start: 0,
+                len: buffer.len,
+                newlines: buffer.newlines,
+            });
+        }
+        buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of lines; an empty buffer has one empty line.
+    pub fn line_count(&self) -> usize {
+        self.newlines + 1
+    }
+
+    pub fn text(&self) -> String {
+        self.slice(0..self.len)
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let mut text = String::with_capacity(range.end - range.start);
+        let mut offset = 0;
+        for piece in &self.pieces {
+            let end = offset + piece.len;
+            if end > range.start && offset < range.end {
+                let from = piece.start + range.start.max(offset) - offset;
+                let to = piece.start + range.end.min(end) - offset;
+                text.push_str(&self.source(piece.source)[from..to]);
+            }
+            if end >= range.end {
+                break;
+            }
+            offset = end;
+        }
+        text
+    }
+
+    /// The byte at `offset`, if it is inside the buffer.
+    pub fn byte(&self, offset: usize) -> Option<u8> {
+        let (index, at) = self.piece_at(offset)?;
+        let piece = self.pieces[index];
+        Some(self.source(piece.source).as_bytes()[piece.start + at])
+    }
+
+    pub fn is_char_boundary(&self, offset: usize) -> bool {
+        match self.byte(offset) {
+            Some(byte) => (byte as i8) >= -0x40,
+            None => offset == self.len,
+        }
+    }
+
+    /// The offset where `line` starts. Lines past the end start at the end.
+    pub fn line_start(&self, line: usize) -> usize {
+        if line == 0 {
+            return 0;
+        }
+        let mut newlines = 0;
+        let mut offset = 0;
+        for piece in &self.pieces {
+            if newlines + piece.newlines >= line {
+                let breaks = self.breaks_in(piece);
+                return offset + breaks[line - newlines - 1] - piece.start + 1;
+            }
+            newlines += piece.newlines;
+            offset += piece.len;
+        }
+        self.len
+    }
+
+    /// The offset where the text of `line` ends, before its line break.
+    pub fn line_end(&self, line: usize) -> usize {
+        if line + 1 >= self.line_count() {
+            return self.len;
+        }
+        let end = self.line_start(line + 1) - 1;
+        if end > self.line_start(line) && self.byte(end - 1) == Some(b'\r') {
+            end - 1
+        } else {
+            end
+        }
+    }
+
+    /// The text of `line`, without its line break.
+    pub fn line(&self, line: usize) -> String {
+        self.slice(self.line_start(line)..self.line_end(line))
+    }
+
+    /// The line `offset` is on. Offsets inside a line break belong to the
+    /// line it ends.
+    pub fn line_of(&self, offset: usize) -> usize {
+        let mut newlines = 0;
+        let mut start = 0;
+        for piece in &self.pieces {
+            if offset < start + piece.len {
+                let end = piece.start + offset - start;
+                let breaks = self.source_breaks(piece.source);
+                return newlines + lower_bound(breaks, end) - lower_bound(breaks, piece.start);
+            }
+            newlines += piece.newlines;
+            start += piece.len;
+        }
+        self.newlines
+    }
+
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        assert!(offset <= self.len, "insert past the end of the buffer");
+        if text.is_empty() {
+            return;
+        }
+        let start = self.added.len();
+        self.added.push_str(text);
+        let new_breaks = breaks(text, start);
+        let newlines = new_breaks.len();
+        self.added_breaks.extend(new_breaks);
+        self.len += text.len();
+        self.newlines += newlines;
+
+        let piece = Piece {
+            source: Source::Added,
+            start: start,
+            len: text.len(),
+            newlines: newlines,
+        };
+        let mut piece_start = 0;
+        for index in 0..self.pieces.len() {
+            let current = self.pieces[index];
+            let piece_end = piece_start + current.len;
+            if offset == piece_end && current.source == Source::Added && current.start + current.len == start {
+                // Typing appends to the piece of the previous keystroke.
+                let current = &mut self.pieces[index];
+                current.len += piece.len;
+                current.newlines += piece.newlines;
+                return;
+            }
+            if offset == piece_start {
+                self.pieces.insert(index, piece);
+                return;
+            }
+            if offset < piece_end {
+                let (left, right) = self.split(current, offset - piece_start);
+                self.pieces.splice(index..index + 1, vec![left, piece, right]);
+                return;
+            }
+            piece_start = piece_end;
+        }
+        self.pieces.push(piece);
+    }
+
+    pub fn delete(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.len, "delete out of the buffer's range");
+        if range.start == range.end {
+            return;
+        }
+        let mut pieces = Vec::with_capacity(self.pieces.len() + 1);
+        let mut offset = 0;
+        for &piece in &self.pieces {
+            let end = offset + piece.len;
+            if end <= range.start || offset >= range.end {
+                pieces.push(piece);
+            } else {
+                if offset < range.start {
+                    pieces.push(self.split(piece, range.start - offset).0);
+                }
+                if end > range.end {
+                    pieces.push(self.split(piece, range.end - offset).1);
+                }
+                self.newlines -= self.count_breaks(piece.source, piece.start + range.start.max(offset) - offset, piece.start + range.end.min(end) - offset);
+            }
+            offset = end;
+        }
+        self.pieces = pieces;
+        self.len -= range.end - range.start;
+    }
+
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let start = range.start;
+        self.delete(range);
+        self.insert(start, text);
+    }
+
+    fn source(&self, source: Source) -> &str {
+        match source {
+            Source::Original => &self.original,
+            Source::Added => &self.added,
+        }
+    }
+
+    fn source_breaks(&self, source: Source) -> &[usize] {
+        match source {
+            Source::Original => &self.original_breaks,
+            Source::Added => &self.added_breaks,
+        }
+    }
+
+    /// The newline offsets within `piece`, in its source's offsets.
+    fn breaks_in(&self, piece: &Piece) -> &[usize] {
+        let breaks = self.source_breaks(piece.source);
+        &breaks[lower_bound(breaks, piece.start)..lower_bound(breaks, piece.start + piece.len)]
+    }
+
+    fn count_breaks(&self, source: Source, start: usize, end: usize) -> usize {
+        let breaks = self.source_breaks(source);
+        lower_bound(breaks, end) - lower_bound(breaks, start)
+    }
+
+    /// Splits `piece` `at` bytes from its start.
+    fn split(&self, piece: Piece, at: usize) -> (Piece, Piece) {
+        let newlines = self.count_breaks(piece.source, piece.start, piece.start + at);
+        (Piece { len: at, newlines: newlines, ..piece },
+         Piece { start: piece.start + at, len: piece.len - at, newlines: piece.newlines - newlines, ..piece })
+    }
+
+    /// The piece holding `offset` and the offset within it.
+    fn piece_at(&self, offset: usize) -> Option<(usize, usize)> {
+        let mut start = 0;
+        for (index, piece) in self.pieces.iter().enumerate() {
+            if offset < start + piece.len {
+                return Some((index, offset - start));
+            }
+            start += piece.len;
+        }
+        None
+    }
+}
+
+fn breaks(text: &str, base: usize) -> Vec<usize> {
+    text.bytes().enumerate().filter(|&(_, b)| b == b'\n').map(|(i, _)| base + i).collect()
+}
+
+/// The number of items in sorted `values` less than `value`.
+fn lower_bound(values: &[usize], value: usize) -> usize {
+    match values.binary_search(&value) {
+        Ok(i) | Err(i) => i,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(buffer: &TextBuffer) -> Vec<String> {
+        (0..buffer.line_count()).map(|line| buffer.line(line)).collect()
+    }
+
+    #[test]
+    fn lines_of_loaded_text() {
+        let buffer = TextBuffer::new("one\r\ntwo\n\nthree");
+
+        assert_eq!(buffer.line_count(), 4);
+        assert_eq!(lines(&buffer), vec!["one", "two", "", "three"]);
+        assert_eq!(buffer.line_start(1), 5);
+        assert_eq!(buffer.line_end(0), 3);
+        assert_eq!(buffer.line_of(4), 0);
+        assert_eq!(buffer.line_of(5), 1);
+        assert_eq!(buffer.line_of(buffer.len()), 3);
+        assert_eq!(TextBuffer::new("").line_count(), 1);
+    }
+
+    #[test]
+    fn edits_split_and_join_pieces() {
+        let mut buffer = TextBuffer::new("hello\nworld");
+
+        buffer.insert(5, ",");
+        buffer.insert(6, " there");
+        buffer.insert(0, "> ");
+        buffer.delete(2..9);
+        buffer.replace(7..8, "\n\n");
+
+        assert_eq!(buffer.text(), "> there\n\nworld");
+        assert_eq!(lines(&buffer), vec!["> there", "", "world"]);
+        assert_eq!(buffer.slice(2..5), "the");
+        assert_eq!(buffer.pieces.len(), 4);
+    }
+
+    #[test]
+    fn matches_a_string_after_many_edits() {
+        let mut buffer = TextBuffer::new("a\nbc\ndef\n");
+        let mut model = String::from("a\nbc\ndef\n");
+        let mut seed = 7usize;
+        for step in 0..300 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345) % (1 << 31);
+            let boundary = |i: usize| (0..i + 1).rev().find(|&i| model.is_char_boundary(i)).unwrap();
+            let at = boundary(seed % (model.len() + 1));
+            if step % 3 == 2 {
+                let end = boundary((at + seed % 4).min(model.len()));
+                buffer.delete(at..end);
+                model.replace_range(at..end, "");
+            } else {
+                let text = ["x", "\n", "yz\n", "é"][seed % 4];
+                buffer.insert(at, text);
+                model.insert_str(at, text);
+            }
+        }
+
+        assert_eq!(buffer.text(), model);
+        assert_eq!(lines(&buffer), model.split('\n').collect::<Vec<_>>());
+        for offset in 0..model.len() + 1 {
+            assert_eq!(buffer.is_char_boundary(offset), model.is_char_boundary(offset));
+            if model.is_char_boundary(offset) {
+                assert_eq!(buffer.line_of(offset), model[..offset].matches('\n').count());
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use text::*;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+thread_local! {
+    static SHARED: Rc<RefCell<FontManager>> = Rc::new(RefCell::new(FontManager::with_default_fonts()));
+}
+
+/// The fonts an application can use, indexed by family, weight and style.
+///
+/// A request for a face resolves like in CSS: the closest style first, then
+/// the closest weight. Characters missing from a face are looked up in the
+/// fallback families, in order, such as an emoji or a CJK family.
+pub struct FontManager {
+    faces: Vec<Rc<Font>>,
+    fallbacks: Vec<String>,
+}
+
+impl FontManager {
+    pub fn new() -> FontManager {
+        FontManager {
+            faces: Vec::new(),
+            fallbacks: Vec::new(),
+        }
+    }
+
+    /// A manager with the faces of the bundled DejaVu Sans.
+    pub fn with_default_fonts() -> FontManager {
+        let mut fonts = FontManager::new();
+        let family = FontFamily::default_family();
+        for face in vec![family.regular, family.bold, family.italic, family.bold_italic] {
+            fonts.add_font(face);
+        }
+        fonts
+    }
+
+    /// The manager of the current thread, which windows share unless they
+    /// are built with another one. Text formats take their fallback fonts
+    /// from it.
+    pub fn shared() -> Rc<RefCell<FontManager>> {
+        SHARED.with(|fonts| fonts.clone())
+    }
+
+    pub fn add_font(&mut self, font: Rc<Font>) {
+        if !self.faces.iter().any(|face| Rc::ptr_eq(face, &font)) {
+            self.faces.push(font);
+        }
+    }
+
+    /// Loads a TrueType or OpenType font and adds it under its family name.
+    pub fn load_bytes(&mut self, data: Vec<u8>) -> Result<Rc<Font>, String> {
+        let font = Rc::new(Font::from_bytes(data)?);
+        self.add_font(font.clone());
+        Ok(font)
+    }
+
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Rc<Font>, String> {
+        let font = Rc::new(Font::from_file(path)?);
+        self.add_font(font.clone());
+        Ok(font)
+    }
+
+    /// The names of the families of all faces, sorted.
+    pub fn families(&self) -> Vec<&str> {
+        let mut families: Vec<&str> = self.faces.iter().map(|face| face.family()).collect();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    /// The faces of a family, ordered from the best to the worst match for
+    /// `weight` and `style`. Family names are compared ignoring case.
+    pub fn matches(&self, family: &str, weight: u16, style: FontStyle) -> Vec<Rc<Font>> {
+        let mut faces: Vec<Rc<Font>> = self.faces.iter()
+            .filter(|face| face.family().eq_ignore_ascii_case(family))
+            .cloned()
+            .collect();
+        faces.sort_by_key(|face| (style_rank(style, face.style()), weight_rank(weight, face.weight())));
+        faces
+    }
+
+    /// The face of a family that best matches `weight` and `style`.
+    pub fn resolve(&self, family: &str, weight: u16, style: FontStyle) -> Option<Rc<Font>> {
+        self.matches(family, weight, style).into_iter().next()
+    }
+
+    /// Like `resolve`, but falls back to the first fallback family, and then
+    /// to the bundled font.
+    pub fn font(&self, family: &str, weight: u16, style: FontStyle) -> Rc<Font> {
+        self.resolve(family, weight, style)
+            .or_else(|| self.fallbacks.first().and_then(|fallback| self.resolve(fallback, weight, style)))
+            .unwrap_or_else(Font::default_font)
+    }
+
+    /// The regular, bold, italic and bold italic faces of a family.
+    pub fn family(&self, name: &str) -> Option<FontFamily> {
+        let face = |weight, style| self.resolve(name, weight, style);
+        Some(FontFamily {
+            regular: face(WEIGHT_NORMAL, FontStyle::Normal)?,
+            bold: face(WEIGHT_BOLD, FontStyle::Normal)?,
+            italic: face(WEIGHT_NORMAL, FontStyle::Italic)?,
+            bold_italic: face(WEIGHT_BOLD, FontStyle::Italic)?,
+        })
+    }
+
+    pub fn fallbacks(&self) -> &[String] {
+        &self.fallbacks
+    }
+
+    /// Sets the families searched, in order, for characters a face lacks.
+    pub fn set_fallbacks(&mut self, families: Vec<String>) {
+        self.fallbacks = families;
+    }
+
+    /// The faces of all fallback families, each family's ordered by how
+    /// well they match `weight` and `style`.
+    pub fn fallback_chain(&self, weight: u16, style: FontStyle) -> Vec<Rc<Font>> {
+        self.fallbacks.iter()
+            .flat_map(|family| self.matches(family, weight, style))
+            .collect()
+    }
+
+    /// `font` if it has a glyph for `c`, or else the first face in its
+    /// fallback chain that has one. Missing glyphs are shown with `font`.
+    pub fn font_for(&self, font: &Rc<Font>, c: char) -> Rc<Font> {
+        if font.has_glyph(c) {
+            return font.clone();
+        }
+        self.fallback_chain(font.weight(), font.style())
+            .into_iter()
+            .find(|fallback| fallback.has_glyph(c))
+            .unwrap_or_else(|| font.clone())
+    }
+}
+
+/// How far a face's style is from the requested one; italic and oblique
+/// stand in for each other before an upright face is used.
+fn style_rank(requested: FontStyle, style: FontStyle) -> u8 {
+    use self::FontStyle::*;
+    match (requested, style) {
+        (a, b) if a == b => 0,
+        (Normal, Italic) | (_, Normal) => 2,
+        _ => 1,
+    }
+}
+
+/// How far a weight is from the requested one, following the CSS font
+/// matching rules: from 400 to 500 the weights up to 500 come first, then
+/// lighter ones. Lighter requests look lighter first, bolder ones bolder.
+fn weight_rank(requested: u16, weight: u16) -> (u8, u16) {
+    let distance = if weight > requested { weight - requested } else { requested - weight };
+    let group = if weight == requested {
+        0
+    } else if requested >= 400 && requested <= 500 {
+        if weight > requested && weight <= 500 { 1 } else if weight < requested { 2 } else { 3 }
+    } else if (requested < 400) == (weight < requested) {
+        1
+    } else {
+        2
+    };
+    (group, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_the_closest_style_and_weight() {
+        let fonts = FontManager::with_default_fonts();
+        let family = FontFamily::default_family();
+
+        assert_eq!(fonts.families(), vec!["DejaVu Sans"]);
+        assert_eq!(fonts.resolve("dejavu sans", WEIGHT_NORMAL, FontStyle::Normal).unwrap().id(), family.regular.id());
+        assert_eq!(fonts.resolve("DejaVu Sans", 600, FontStyle::Normal).unwrap().id(), family.bold.id());
+        assert_eq!(fonts.resolve("DejaVu Sans", 500, FontStyle::Normal).unwrap().id(), family.regular.id());
+        assert_eq!(fonts.resolve("DejaVu Sans", 300, FontStyle::Normal).unwrap().id(), family.regular.id());
+        assert_eq!(fonts.resolve("DejaVu Sans", 900, FontStyle::Italic).unwrap().id(), family.bold_italic.id());
+        assert!(fonts.resolve("Missing", WEIGHT_NORMAL, FontStyle::Normal).is_none());
+        assert_eq!(fonts.font("Missing", WEIGHT_NORMAL, FontStyle::Normal).id(), Font::default_font().id());
+
+        let resolved = fonts.family("DejaVu Sans").unwrap();
+        assert_eq!(resolved.bold_italic.id(), family.bold_italic.id());
+    }
+
+    #[test]
+    fn weight_matching_follows_css() {
+        let order = |requested, weights: &[u16]| {
+            let mut weights = weights.to_vec();
+            weights.sort_by_key(|&weight| weight_rank(requested, weight));
+            weights
+        };
+        let weights = [100, 300, 400, 500, 600, 900];
+
+        assert_eq!(order(400, &weights), vec![400, 500, 300, 100, 600, 900]);
+        assert_eq!(order(500, &weights), vec![500, 400, 300, 100, 600, 900]);
+        assert_eq!(order(200, &weights), vec![100, 300, 400, 500, 600, 900]);
+        assert_eq!(order(700, &weights), vec![900, 600, 500, 400, 300, 100]);
+    }
+
+    #[test]
+    fn missing_glyphs_come_from_fallback_families() {
+        let mut fonts = FontManager::with_default_fonts();
+        let family = FontFamily::default_family();
+        let arabic = '\u{0633}';
+        assert!(!family.italic.has_glyph(arabic));
+
+        assert_eq!(fonts.font_for(&family.italic, arabic).id(), family.italic.id());
+
+        fonts.set_fallbacks(vec!["DejaVu Sans".to_string()]);
+        assert_eq!(fonts.font_for(&family.italic, arabic).id(), family.regular.id());
+        assert_eq!(fonts.font_for(&family.italic, 'a').id(), family.italic.id());
+    }
+
+    #[test]
+    fn loading_reads_family_weight_and_style() {
+        let mut fonts = FontManager::new();
+        let font = fonts.load_file("assets/DejaVuSans-Bold.ttf").unwrap();
+
+        assert_eq!(font.family(), "DejaVu Sans");
+        assert_eq!(font.weight(), WEIGHT_BOLD);
+        assert_eq!(font.style(), FontStyle::Normal);
+        assert!(fonts.load_bytes(vec![0; 16]).is_err());
+        assert_eq!(fonts.families(), vec!["DejaVu Sans"]);
+    }
+}
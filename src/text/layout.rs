@@ -0,0 +1,1260 @@
+use primitives::*;
+use text::*;
+use text::fonts::FontManager;
+use std::f32;
+use std::ops::Range;
+use std::rc::Rc;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_segmentation::UnicodeSegmentation;
+use xi_unicode::LineBreakIterator;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+    Right,
+    /// Stretches the spaces of wrapped lines to fill the width. The last
+    /// line of a paragraph is left aligned.
+    Justify,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextWrapping {
+    NoWrap,
+    Wrap,
+}
+
+/// What happens to text that doesn't fit the width or the line count.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextTrimming {
+    None,
+    /// Ends the last visible line with "…".
+    Ellipsis,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineHeight {
+    /// The font's ascent, descent and line gap.
+    Normal,
+    /// A multiple of the normal line height.
+    Scale(f32),
+    Fixed(f32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Clone)]
+pub struct TextFormat {
+    pub font: Rc<Font>,
+    pub size: f32,
+    pub color: Color,
+    pub alignment: TextAlignment,
+    pub wrapping: TextWrapping,
+    pub trimming: TextTrimming,
+    pub max_lines: Option<usize>,
+    pub line_height: LineHeight,
+    /// The base direction of paragraphs; when unset it comes from the
+    /// first strong character of each paragraph.
+    pub direction: Option<TextDirection>,
+    /// Fonts tried in order for characters the text's fonts lack.
+    pub fallbacks: Vec<Rc<Font>>,
+}
+
+impl TextFormat {
+    /// A format with the fallback chain of the shared font manager.
+    pub fn new(font: Rc<Font>, size: f32) -> TextFormat {
+        let fallbacks = FontManager::shared().borrow().fallback_chain(font.weight(), font.style());
+        TextFormat {
+            font: font,
+            size: size,
+            color: Color::argb(1.0, 0.0, 0.0, 0.0),
+            alignment: TextAlignment::Left,
+            wrapping: TextWrapping::Wrap,
+            trimming: TextTrimming::None,
+            max_lines: None,
+            line_height: LineHeight::Normal,
+            direction: None,
+            fallbacks: fallbacks,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> TextFormat {
+        self.color = color;
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: TextAlignment) -> TextFormat {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn with_wrapping(mut self, wrapping: TextWrapping) -> TextFormat {
+        self.wrapping = wrapping;
+        self
+    }
+
+    pub fn with_trimming(mut self, trimming: TextTrimming) -> TextFormat {
+        self.trimming = trimming;
+        self
+    }
+
+    pub fn with_max_lines(mut self, max_lines: usize) -> TextFormat {
+        self.max_lines = Some(max_lines);
+        self
+    }
+
+    pub fn with_line_height(mut self, line_height: LineHeight) -> TextFormat {
+        self.line_height = line_height;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: TextDirection) -> TextFormat {
+        self.direction = Some(direction);
+        self
+    }
+
+    pub fn with_fallbacks(mut self, fallbacks: Vec<Rc<Font>>) -> TextFormat {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// The height of one line of text in this format.
+    pub fn line_height(&self) -> f32 {
+        let normal = self.font.metrics(self.size).line_height();
+        match self.line_height {
+            LineHeight::Normal => normal,
+            LineHeight::Scale(scale) => normal * scale,
+            LineHeight::Fixed(height) => height,
+        }
+    }
+}
+
+/// The look of a span of text in a layout.
+#[derive(Clone)]
+pub struct SpanStyle {
+    pub font: Rc<Font>,
+    pub size: f32,
+    pub color: Color,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl SpanStyle {
+    pub fn new(font: Rc<Font>, size: f32, color: Color) -> SpanStyle {
+        SpanStyle {
+            font: font,
+            size: size,
+            color: color,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+}
+
+/// A piece of the content of a layout.
+#[derive(Clone)]
+pub enum TextSpan {
+    Text(String, SpanStyle),
+    /// Room for an inline element, standing on the baseline. It takes the
+    /// place of one U+FFFC character in the layout's text.
+    Object(Size),
+}
+
+/// Stands for an inline object in the text of a layout.
+pub const OBJECT_REPLACEMENT: char = '\u{FFFC}';
+
+/// A glyph placed on a line. `x` is relative to the layout.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineGlyph {
+    /// Byte index in the text of the cluster the glyph belongs to.
+    pub index: usize,
+    pub id: u16,
+    pub x: f32,
+    /// Vertical displacement from the baseline, as for marks; down is positive.
+    pub dy: f32,
+    pub advance: f32,
+    /// Index of the glyph's style in `TextLayout::styles`.
+    pub style: usize,
+    /// For inline objects, their index among the layout's objects.
+    pub object: Option<usize>,
+    /// Index of the glyph's cluster in `TextLine::clusters`.
+    cluster: usize,
+}
+
+/// Characters shaped together, such as a ligature or a letter with its marks.
+/// The caret can only stop inside a cluster between grapheme clusters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineCluster {
+    /// The bytes of the text the cluster shows; empty for an ellipsis.
+    pub range: Range<usize>,
+    pub x: f32,
+    pub advance: f32,
+    pub rtl: bool,
+    pub style: usize,
+    pub object: Option<usize>,
+    space: bool,
+    /// Part of the white space the line was wrapped at.
+    trailing: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct TextLine {
+    /// The bytes of the text on the line, including trailing spaces and the line break.
+    pub range: Range<usize>,
+    pub top: f32,
+    pub height: f32,
+    pub baseline: f32,
+    /// Where the line starts after alignment.
+    pub x: f32,
+    /// The width of the line without trailing spaces.
+    pub width: f32,
+    /// Glyphs in visual order, left to right.
+    pub glyphs: Vec<LineGlyph>,
+    /// Clusters in visual order, left to right. Line breaks aren't included.
+    pub clusters: Vec<LineCluster>,
+    /// Whether text was cut from the end of the line.
+    pub trimmed: bool,
+    /// The direction of the line's paragraph.
+    pub rtl: bool,
+    /// The last caret position on the line: before its line break or the
+    /// space it was wrapped at.
+    caret_end: usize,
+}
+
+/// Text broken into lines at a maximum width, following the Unicode line
+/// breaking rules, with every glyph positioned for drawing, caret placement
+/// and hit testing. The text can mix styles and hold inline objects.
+///
+/// Text is shaped in runs of one style and direction, and lines are
+/// reordered for display with the Unicode bidirectional algorithm. Carets
+/// are byte indices in the text, always at grapheme boundaries.
+pub struct TextLayout {
+    text: String,
+    format: TextFormat,
+    styles: Vec<SpanStyle>,
+    objects: Vec<Size>,
+    max_width: f32,
+    lines: Vec<TextLine>,
+    size: Size,
+}
+
+/// A cluster in logical order, before it is placed on a line.
+#[derive(Clone)]
+struct Cluster {
+    range: Range<usize>,
+    style: usize,
+    object: Option<usize>,
+    level: u8,
+    /// Glyph ids with positions relative to the cluster's left edge and baseline.
+    glyphs: Vec<(u16, Point)>,
+    advance: f32,
+    space: bool,
+    /// Line breaks and other control characters, which aren't drawn.
+    control: bool,
+    /// Pen position if the whole paragraph were set on one line.
+    pen: f32,
+}
+
+/// Vertical extent of a line's content around the baseline.
+#[derive(Copy, Clone)]
+struct LineMetrics {
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+impl TextLayout {
+    /// Lays `text` out in the format's font, size and color; `max_width` may be infinite.
+    pub fn new(text: &str, format: TextFormat, max_width: f32) -> TextLayout {
+        let style = SpanStyle::new(format.font.clone(), format.size, format.color);
+        TextLayout::with_spans(&[TextSpan::Text(text.to_string(), style)], format, max_width)
+    }
+
+    /// Lays out styled spans. The format's font and size only apply to
+    /// empty layouts; its paragraph settings apply to all lines.
+    pub fn with_spans(spans: &[TextSpan], format: TextFormat, max_width: f32) -> TextLayout {
+        let mut text = String::new();
+        let mut styles: Vec<SpanStyle> = Vec::new();
+        let mut objects = Vec::new();
+        let mut pieces = Vec::new();
+        for span in spans {
+            let start = text.len();
+            match *span {
+                TextSpan::Text(ref content, ref style) => {
+                    styles.push(style.clone());
+                    text.push_str(content);
+                    pieces.push((start..text.len(), styles.len() - 1, None));
+                }
+                TextSpan::Object(size) => {
+                    if styles.is_empty() {
+                        styles.push(SpanStyle::new(format.font.clone(), format.size, format.color));
+                    }
+                    objects.push(size);
+                    text.push(OBJECT_REPLACEMENT);
+                    pieces.push((start..text.len(), styles.len() - 1, Some(objects.len() - 1)));
+                }
+            }
+        }
+
+        let base_level = format.direction.map(|direction| match direction {
+            TextDirection::LeftToRight => Level::ltr(),
+            TextDirection::RightToLeft => Level::rtl(),
+        });
+        let bidi = BidiInfo::new(&text, base_level);
+        let mut clusters = Vec::new();
+        for (range, style, object) in pieces {
+            match object {
+                Some(object) => clusters.push(Cluster {
+                    level: bidi.levels[range.start].number(),
+                    range: range,
+                    style: style,
+                    object: Some(object),
+                    glyphs: Vec::new(),
+                    advance: objects[object].w,
+                    space: false,
+                    control: false,
+                    pen: 0.0,
+                }),
+                None => {
+                    for (range, style) in TextLayout::itemize(&text, range, style, &mut styles, &format.fallbacks) {
+                        TextLayout::shape(&mut clusters, &text, range, style, &styles[style], &bidi.levels);
+                    }
+                }
+            }
+        }
+        let mut pen = 0.0;
+        for cluster in clusters.iter_mut() {
+            cluster.pen = pen;
+            pen = if text[cluster.range.clone()].ends_with('\n') { 0.0 } else { pen + cluster.advance };
+        }
+
+        let breaks = TextLayout::break_lines(&text, &clusters, &format, max_width);
+        let truncated = format.max_lines.map_or(false, |max| breaks.len() > max);
+        let mut lines = Vec::with_capacity(breaks.len());
+        let mut top = 0.0;
+        for (i, &(start, end, hard)) in breaks.iter().take(format.max_lines.unwrap_or(breaks.len())).enumerate() {
+            let last = i + 1 == breaks.len() || (truncated && Some(i + 1) == format.max_lines);
+            let line_clusters = &clusters[start..end];
+            let previous = start.checked_sub(1).and_then(|i| clusters.get(i));
+            let metrics = TextLayout::line_metrics(line_clusters, &styles, &objects, &format, previous);
+            let height = match format.line_height {
+                LineHeight::Normal => metrics.ascent - metrics.descent + metrics.line_gap,
+                LineHeight::Scale(scale) => (metrics.ascent - metrics.descent + metrics.line_gap) * scale,
+                LineHeight::Fixed(height) => height,
+            };
+            let baseline = top + (height - (metrics.ascent - metrics.descent)) / 2.0 + metrics.ascent;
+            let position = if end < clusters.len() { clusters[end].range.start } else { text.len() };
+            let rtl = bidi.paragraphs.iter()
+                .find(|p| p.range.start <= position && position <= p.range.end)
+                .map_or(format.direction == Some(TextDirection::RightToLeft), |p| p.level.is_rtl());
+            let range = line_clusters.first().map_or(position, |c| c.range.start)..position;
+
+            let mut line_clusters = line_clusters.to_vec();
+            let caret_end = TextLayout::caret_end(&text, &line_clusters, hard || last, position);
+            let mut trimmed = false;
+            let line_width = TextLayout::visible_width(&line_clusters);
+            if format.trimming == TextTrimming::Ellipsis && (line_width > max_width || (truncated && last)) {
+                TextLayout::ellipsize(&mut line_clusters, &styles, max_width, if rtl { 1 } else { 0 });
+                trimmed = true;
+            }
+            let mut line = TextLayout::build_line(&line_clusters, rtl, !hard && !last, top, height, baseline);
+            line.range = range;
+            line.caret_end = if trimmed { line_clusters.last().map_or(caret_end, |c| c.range.start) } else { caret_end };
+            line.trimmed = trimmed;
+            top += height;
+            lines.push(line);
+        }
+
+        let width = lines.iter().fold(0.0f32, |w, line| w.max(line.width));
+        let mut layout = TextLayout {
+            text: text,
+            format: format,
+            styles: styles,
+            objects: objects,
+            max_width: max_width,
+            size: Size::new(width, top),
+            lines: lines,
+        };
+        layout.align(breaks);
+        layout
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn format(&self) -> &TextFormat {
+        &self.format
+    }
+
+    /// The styles of the spans, followed by copies of them with fallback fonts.
+    pub fn styles(&self) -> &[SpanStyle] {
+        &self.styles
+    }
+
+    pub fn max_width(&self) -> f32 {
+        self.max_width
+    }
+
+    /// The width of the longest line and the height of all lines.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn lines(&self) -> &[TextLine] {
+        &self.lines
+    }
+
+    /// Whether some of the text was left out because of `max_lines` or trimming.
+    pub fn is_trimmed(&self) -> bool {
+        self.lines.last().map_or(false, |line| line.trimmed || line.range.end < self.text.len())
+    }
+
+    /// The glyphs of all lines, grouped into runs of one style, with the
+    /// layout's top left as the runs' origin.
+    pub fn runs(&self) -> Vec<TextRun> {
+        let mut runs: Vec<(usize, TextRun)> = Vec::new();
+        for line in &self.lines {
+            for g in line.glyphs.iter().filter(|g| g.object.is_none()) {
+                let glyph = Glyph { id: g.id, pos: Point::new(g.x, line.baseline + g.dy) };
+                match runs.last_mut() {
+                    Some(&mut (style, ref mut run)) if style == g.style => {
+                        run.glyphs.push(glyph);
+                        continue;
+                    }
+                    _ => {}
+                }
+                let style = &self.styles[g.style];
+                runs.push((g.style, TextRun {
+                    font: style.font.clone(),
+                    size: style.size,
+                    color: style.color,
+                    glyphs: vec![glyph],
+                }));
+            }
+        }
+        runs.into_iter().map(|(_, run)| run).collect()
+    }
+
+    /// Underline and strikethrough rects with their colors.
+    pub fn decorations(&self) -> Vec<(Rect, Color)> {
+        let mut decorations: Vec<(Rect, Color)> = Vec::new();
+        for line in &self.lines {
+            for c in line.clusters.iter().filter(|c| c.object.is_none() && !c.trailing) {
+                let style = &self.styles[c.style];
+                let thickness = (style.size / 14.0).max(1.0);
+                let ascent = style.font.metrics(style.size).ascent;
+                let mut add = |y: f32| {
+                    let rect = Rect::pos_size(Point::new(c.x, y), Size::new(c.advance, thickness));
+                    // Continue the previous stroke if this cluster is next to it.
+                    if let Some(&mut (ref mut last, color)) = decorations.last_mut() {
+                        if color == style.color && last.pos.y == y && last.size.h == thickness && (last.right() - c.x).abs() < 0.01 {
+                            last.size.w += c.advance;
+                            return;
+                        }
+                    }
+                    decorations.push((rect, style.color));
+                };
+                if style.underline {
+                    add(line.baseline + thickness);
+                }
+                if style.strikethrough {
+                    add(line.baseline - ascent * 0.3);
+                }
+            }
+        }
+        decorations
+    }
+
+    /// Where each inline object goes, relative to the layout, or `None` for
+    /// objects left out by trimming.
+    pub fn object_rects(&self) -> Vec<Option<Rect>> {
+        let mut rects = vec![None; self.objects.len()];
+        for line in &self.lines {
+            for c in &line.clusters {
+                if let Some(object) = c.object {
+                    let size = self.objects[object];
+                    rects[object] = Some(Rect::pos_size(Point::new(c.x, line.baseline - size.h), size));
+                }
+            }
+        }
+        rects
+    }
+
+    /// The line the caret at byte `index` is on. A caret at a wrap goes to the
+    /// start of the next line.
+    pub fn line_of(&self, index: usize) -> usize {
+        self.lines.iter().rposition(|line| line.range.start <= index).unwrap_or(0)
+    }
+
+    /// A zero width rect spanning the line where the caret at byte `index`
+    /// is drawn. Indices past the visible text go to its end.
+    pub fn caret_rect(&self, index: usize) -> Rect {
+        let line = match self.lines.get(self.line_of(index)) {
+            Some(line) => line,
+            None => return Rect::pos_size(Point::zero(), Size::new(0.0, self.format.line_height())),
+        };
+        let x = self.caret_x(line, index.min(line.caret_end));
+        Rect::pos_size(Point::new(x, line.top), Size::new(0.0, line.height))
+    }
+
+    /// The caret position closest to `point`, given relative to the layout.
+    pub fn hit_test(&self, point: Point) -> usize {
+        let line = match self.lines.iter().find(|line| point.y < line.top + line.height).or(self.lines.last()) {
+            Some(line) => line,
+            None => return 0,
+        };
+        let cluster = match line.clusters.iter().find(|c| point.x < c.x + c.advance).or(line.clusters.last()) {
+            Some(cluster) => cluster,
+            None => return line.caret_end,
+        };
+        if cluster.range.start == cluster.range.end {
+            return cluster.range.start;
+        }
+
+        let stops = self.grapheme_stops(cluster.range.clone());
+        let along = if cluster.advance > 0.0 { ((point.x - cluster.x) / cluster.advance).max(0.0).min(1.0) } else { 0.0 };
+        let along = if cluster.rtl { 1.0 - along } else { along };
+        let stop = (along * (stops.len() - 1) as f32).round() as usize;
+        stops[stop].min(line.caret_end)
+    }
+
+    /// The caret position after `index` in the text, skipping whole grapheme clusters.
+    pub fn next_caret(&self, index: usize) -> usize {
+        self.text.grapheme_indices(true)
+            .map(|(i, _)| i)
+            .chain(Some(self.text.len()))
+            .find(|&i| i > index)
+            .unwrap_or(self.text.len())
+    }
+
+    /// The caret position before `index` in the text.
+    pub fn previous_caret(&self, index: usize) -> usize {
+        self.text.grapheme_indices(true)
+            .map(|(i, _)| i)
+            .take_while(|&i| i < index)
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// The caret position one step to the left on screen, which in right to
+    /// left text is the next one in the text.
+    pub fn caret_left(&self, index: usize) -> usize {
+        self.move_visually(index, false)
+    }
+
+    /// The caret position one step to the right on screen.
+    pub fn caret_right(&self, index: usize) -> usize {
+        self.move_visually(index, true)
+    }
+
+    fn move_visually(&self, index: usize, right: bool) -> usize {
+        let n = self.line_of(index);
+        let line = match self.lines.get(n) {
+            Some(line) => line,
+            None => return index,
+        };
+        let index = index.min(line.caret_end);
+        let x = self.caret_x(line, index);
+        let stops = self.grapheme_stops(line.range.start..line.caret_end);
+        let next = stops.into_iter()
+            .map(|i| (i, self.caret_x(line, i)))
+            .filter(|&(_, stop_x)| if right { stop_x > x + 0.01 } else { stop_x < x - 0.01 })
+            .fold(None, |best: Option<(usize, f32)>, (i, stop_x)| match best {
+                Some((_, best_x)) if (best_x - x).abs() <= (stop_x - x).abs() => best,
+                _ => Some((i, stop_x)),
+            });
+        // Past the edge of the line the caret goes on to the next line in the
+        // paragraph's direction.
+        match next {
+            Some((i, _)) => i,
+            None if right != line.rtl => self.lines.get(n + 1).map_or(index, |next| next.range.start),
+            None if n > 0 => self.lines[n - 1].caret_end,
+            None => index,
+        }
+    }
+
+    /// Where the caret at `index` goes on `line`. Inside a cluster of several
+    /// grapheme clusters, like a ligature, the advance is split evenly.
+    fn caret_x(&self, line: &TextLine, index: usize) -> f32 {
+        if let Some(cluster) = line.clusters.iter().find(|c| c.range.start <= index && index < c.range.end) {
+            let stops = self.grapheme_stops(cluster.range.clone());
+            let before = stops.iter().filter(|&&i| i < index).count() as f32;
+            let along = before / (stops.len() - 1) as f32;
+            return cluster.x + cluster.advance * if cluster.rtl { 1.0 - along } else { along };
+        }
+        // At the end of the text on the line: after the last cluster before it.
+        let previous = line.clusters.iter()
+            .filter(|c| c.range.end <= index && c.range.start < c.range.end)
+            .max_by_key(|c| c.range.start);
+        match previous {
+            Some(c) if c.rtl => c.x,
+            Some(c) => c.x + c.advance,
+            None if line.rtl => line.x + line.width,
+            None => line.x,
+        }
+    }
+
+    /// Grapheme boundaries within `range`, both ends included.
+    fn grapheme_stops(&self, range: Range<usize>) -> Vec<usize> {
+        let mut stops: Vec<usize> = self.text[range.clone()].grapheme_indices(true)
+            .map(|(i, _)| range.start + i)
+            .collect();
+        stops.push(range.end);
+        stops
+    }
+
+    /// Splits `range` of the text where its font changes: grapheme clusters
+    /// the style's font has no glyph for go to the first fallback font that
+    /// has one, in a copy of the style added to `styles`. White space stays
+    /// with the text before it.
+    fn itemize(text: &str, range: Range<usize>, style: usize, styles: &mut Vec<SpanStyle>, fallbacks: &[Rc<Font>]) -> Vec<(Range<usize>, usize)> {
+        let mut items: Vec<(Range<usize>, usize)> = Vec::new();
+        let mut derived: Vec<usize> = Vec::new();
+        for (i, grapheme) in text[range.clone()].grapheme_indices(true) {
+            let start = range.start + i;
+            let c = grapheme.chars().next().unwrap();
+            let item_style = if c.is_whitespace() || c.is_control() {
+                items.last().map_or(style, |item| item.1)
+            } else if styles[style].font.has_glyph(c) {
+                style
+            } else {
+                match fallbacks.iter().find(|fallback| fallback.has_glyph(c)) {
+                    Some(fallback) => match derived.iter().cloned().find(|&d| Rc::ptr_eq(&styles[d].font, fallback)) {
+                        Some(d) => d,
+                        None => {
+                            let mut copy = styles[style].clone();
+                            copy.font = fallback.clone();
+                            styles.push(copy);
+                            derived.push(styles.len() - 1);
+                            styles.len() - 1
+                        }
+                    },
+                    None => style,
+                }
+            };
+            match items.last_mut() {
+                Some(item) if item.1 == item_style => {
+                    item.0.end = start + grapheme.len();
+                    continue;
+                }
+                _ => {}
+            }
+            items.push((start..start + grapheme.len(), item_style));
+        }
+        items
+    }
+
+    /// Shapes `range` of the text in `style`, splitting it where the bidi
+    /// level changes, and appends its clusters in logical order.
+    fn shape(clusters: &mut Vec<Cluster>, text: &str, range: Range<usize>, style_index: usize, style: &SpanStyle, levels: &[Level]) {
+        let mut start = range.start;
+        while start < range.end {
+            let c = text[start..].chars().next().unwrap();
+            if c.is_control() {
+                let end = start + c.len_utf8();
+                clusters.push(Cluster {
+                    range: start..end,
+                    style: style_index,
+                    object: None,
+                    level: levels[start].number(),
+                    glyphs: Vec::new(),
+                    advance: 0.0,
+                    space: true,
+                    control: true,
+                    pen: 0.0,
+                });
+                start = end;
+                continue;
+            }
+
+            let level = levels[start];
+            let end = text[start..range.end].char_indices()
+                .find(|&(i, c)| c.is_control() || levels[start + i] != level)
+                .map_or(range.end, |(i, _)| start + i);
+            let run = &text[start..end];
+            let shaped = style.font.shape(run, style.size, level.is_rtl());
+
+            let mut starts: Vec<usize> = shaped.iter().map(|g| g.cluster).collect();
+            starts.sort();
+            starts.dedup();
+            for (n, &cluster_start) in starts.iter().enumerate() {
+                let cluster_end = starts.get(n + 1).cloned().unwrap_or(run.len());
+                let mut x = 0.0;
+                let glyphs: Vec<(u16, Point)> = shaped.iter()
+                    .filter(|g| g.cluster == cluster_start)
+                    .map(|g| {
+                        let glyph = (g.id, Point::new(x + g.offset.x, g.offset.y));
+                        x += g.advance;
+                        glyph
+                    })
+                    .collect();
+                clusters.push(Cluster {
+                    range: start + cluster_start..start + cluster_end,
+                    style: style_index,
+                    object: None,
+                    level: level.number(),
+                    glyphs: glyphs,
+                    advance: x,
+                    space: run[cluster_start..cluster_end].chars().all(|c| c.is_whitespace()),
+                    control: false,
+                    pen: 0.0,
+                });
+            }
+            start = end;
+        }
+    }
+
+    /// The tallest ascent and descent on the line. Empty lines take the
+    /// metrics of the cluster before them, or of the format.
+    fn line_metrics(clusters: &[Cluster], styles: &[SpanStyle], objects: &[Size], format: &TextFormat, previous: Option<&Cluster>) -> LineMetrics {
+        let style_metrics = |style: &SpanStyle| {
+            let m = style.font.metrics(style.size);
+            LineMetrics { ascent: m.ascent, descent: m.descent, line_gap: m.line_gap }
+        };
+        let fallback = match clusters.first().or(previous) {
+            Some(c) => style_metrics(&styles[c.style]),
+            None => {
+                let m = format.font.metrics(format.size);
+                LineMetrics { ascent: m.ascent, descent: m.descent, line_gap: m.line_gap }
+            }
+        };
+        clusters.iter().fold(fallback, |acc, c| {
+            let m = match c.object {
+                Some(object) => LineMetrics { ascent: objects[object].h, descent: 0.0, line_gap: 0.0 },
+                None => style_metrics(&styles[c.style]),
+            };
+            LineMetrics {
+                ascent: acc.ascent.max(m.ascent),
+                descent: acc.descent.min(m.descent),
+                line_gap: acc.line_gap.max(m.line_gap),
+            }
+        })
+    }
+
+    /// Width of the clusters without trailing white space.
+    fn visible_width(clusters: &[Cluster]) -> f32 {
+        match clusters.iter().rposition(|c| !c.space) {
+            Some(last) => clusters[last].pen + clusters[last].advance - clusters[0].pen,
+            None => 0.0,
+        }
+    }
+
+    /// Breaks the text into lines given as cluster ranges and whether they
+    /// end in a mandatory break.
+    fn break_lines(text: &str, clusters: &[Cluster], format: &TextFormat, max_width: f32) -> Vec<(usize, usize, bool)> {
+        let wrap = format.wrapping == TextWrapping::Wrap;
+        // Breaks inside a cluster, as in a ligature, aren't taken.
+        let mut opportunities: Vec<(usize, bool)> = LineBreakIterator::new(text)
+            .filter(|&(index, _)| index < text.len())
+            .filter_map(|(index, hard)| clusters.binary_search_by_key(&index, |c| c.range.start).ok().map(|n| (n, hard)))
+            .collect();
+        opportunities.push((clusters.len(), true));
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut last_break = None;
+        for (end, hard) in opportunities {
+            if wrap && TextLayout::visible_width(&clusters[start..end]) > max_width {
+                if let Some(at) = last_break {
+                    lines.push((start, at, false));
+                    start = at;
+                }
+                // A word wider than the line is broken between clusters.
+                while TextLayout::visible_width(&clusters[start..end]) > max_width {
+                    let fits = (start + 1..end)
+                        .take_while(|&i| TextLayout::visible_width(&clusters[start..i + 1]) <= max_width)
+                        .last()
+                        .map_or(start + 1, |i| i + 1);
+                    lines.push((start, fits, false));
+                    start = fits;
+                }
+            }
+            if hard {
+                lines.push((start, end, true));
+                start = end;
+                last_break = None;
+            } else {
+                last_break = Some(end);
+            }
+        }
+        // Text ending with a line break has an empty line after it for the caret.
+        if text.ends_with('\n') {
+            lines.push((clusters.len(), clusters.len(), true));
+        }
+        lines
+    }
+
+    /// The last caret position on a line ending at byte `end`.
+    fn caret_end(text: &str, clusters: &[Cluster], hard: bool, end: usize) -> usize {
+        match clusters.last() {
+            Some(last) if last.control || (!hard && last.space) => {
+                // "\r\n" ends a line as a whole.
+                match clusters.len() {
+                    n if n >= 2 && &text[last.range.clone()] == "\n" && &text[clusters[n - 2].range.clone()] == "\r" => {
+                        clusters[n - 2].range.start
+                    }
+                    _ => last.range.start,
+                }
+            }
+            _ => end,
+        }
+    }
+
+    /// Places the clusters of a line, given in logical order, left to right.
+    /// Runs are reversed by bidi level (rule L2), with trailing white space
+    /// put at the paragraph's level first (rule L1).
+    fn build_line(clusters: &[Cluster], rtl: bool, wrapped: bool, top: f32, height: f32, baseline: f32) -> TextLine {
+        let base = if rtl { 1 } else { 0 };
+        let trailing_from = clusters.iter().rposition(|c| !c.space).map_or(0, |i| i + 1);
+        let levels: Vec<u8> = clusters.iter().enumerate()
+            .map(|(i, c)| if i >= trailing_from { base } else { c.level })
+            .collect();
+
+        let mut order: Vec<usize> = (0..clusters.len()).filter(|&i| !clusters[i].control).collect();
+        let highest = levels.iter().cloned().max().unwrap_or(0);
+        let lowest_odd = levels.iter().cloned().filter(|l| l % 2 == 1).min().unwrap_or(highest + 1);
+        let mut level = highest;
+        while level >= lowest_odd && level > 0 {
+            let mut i = 0;
+            while i < order.len() {
+                if levels[order[i]] < level {
+                    i += 1;
+                    continue;
+                }
+                let start = i;
+                while i < order.len() && levels[order[i]] >= level {
+                    i += 1;
+                }
+                order[start..i].reverse();
+            }
+            level -= 1;
+        }
+
+        let mut x = 0.0;
+        let mut glyphs = Vec::new();
+        let mut line_clusters = Vec::with_capacity(order.len());
+        for &i in &order {
+            let c = &clusters[i];
+            for &(id, pos) in &c.glyphs {
+                glyphs.push(LineGlyph {
+                    index: c.range.start,
+                    id: id,
+                    x: x + pos.x,
+                    dy: pos.y,
+                    advance: 0.0,
+                    style: c.style,
+                    object: None,
+                    cluster: line_clusters.len(),
+                });
+            }
+            if let Some(object) = c.object {
+                glyphs.push(LineGlyph {
+                    index: c.range.start,
+                    id: 0,
+                    x: x,
+                    dy: 0.0,
+                    advance: c.advance,
+                    style: c.style,
+                    object: Some(object),
+                    cluster: line_clusters.len(),
+                });
+            }
+            line_clusters.push(LineCluster {
+                range: c.range.clone(),
+                x: x,
+                advance: c.advance,
+                rtl: levels[i] % 2 == 1,
+                style: c.style,
+                object: c.object,
+                space: c.space,
+                trailing: wrapped && i >= trailing_from,
+            });
+            x += c.advance;
+        }
+        // A glyph's advance runs to the next glyph of its cluster or the cluster's end.
+        for n in 0..glyphs.len() {
+            if glyphs[n].object.is_some() {
+                continue;
+            }
+            let cluster = &line_clusters[glyphs[n].cluster];
+            let next = glyphs.get(n + 1)
+                .filter(|g| g.cluster == glyphs[n].cluster)
+                .map_or(cluster.x + cluster.advance, |g| g.x.max(glyphs[n].x));
+            glyphs[n].advance = next - glyphs[n].x;
+        }
+
+        TextLine {
+            range: 0..0,
+            top: top,
+            height: height,
+            baseline: baseline,
+            x: 0.0,
+            width: TextLayout::visible_width(clusters),
+            glyphs: glyphs,
+            clusters: line_clusters,
+            trimmed: false,
+            rtl: rtl,
+            caret_end: 0,
+        }
+    }
+
+    /// Cuts the logical end of a line so that it fits `max_width` with an
+    /// ellipsis after it, in the style of the text before it.
+    fn ellipsize(clusters: &mut Vec<Cluster>, styles: &[SpanStyle], max_width: f32, level: u8) {
+        let style_index = clusters.iter().rev().find(|c| c.object.is_none()).map_or(0, |c| c.style);
+        let style = &styles[style_index];
+        let font = &style.font;
+        let ellipsis = if font.has_glyph('…') { "…" } else { "..." };
+        let shaped = font.shape(ellipsis, style.size, false);
+        let advance = shaped.iter().fold(0.0, |w, g| w + g.advance);
+        let room = max_width - advance;
+
+        let origin = clusters.first().map_or(0.0, |c| c.pen);
+        let mut keep = clusters.iter()
+            .take_while(|c| c.pen - origin + c.advance <= room)
+            .count();
+        while keep > 0 && clusters[keep - 1].space {
+            keep -= 1;
+        }
+        let cut = clusters.get(keep).map_or(clusters.last().map_or(0, |c| c.range.end), |c| c.range.start);
+        clusters.truncate(keep);
+
+        let mut x = 0.0;
+        let glyphs = shaped.iter().map(|g| {
+            let glyph = (g.id, Point::new(x + g.offset.x, g.offset.y));
+            x += g.advance;
+            glyph
+        }).collect();
+        let pen = clusters.last().map_or(origin, |c| c.pen + c.advance);
+        clusters.push(Cluster {
+            range: cut..cut,
+            style: style_index,
+            object: None,
+            level: level,
+            glyphs: glyphs,
+            advance: advance,
+            space: false,
+            control: false,
+            pen: pen,
+        });
+    }
+
+    fn align(&mut self, breaks: Vec<(usize, usize, bool)>) {
+        let width = if self.max_width.is_finite() { self.max_width } else { self.size.w };
+        let alignment = self.format.alignment;
+        for (line, &(_, _, hard)) in self.lines.iter_mut().zip(breaks.iter()) {
+            let extra = (width - line.width).max(0.0);
+            let offset = match alignment {
+                TextAlignment::Left | TextAlignment::Justify => 0.0,
+                TextAlignment::Center => extra / 2.0,
+                TextAlignment::Right => extra,
+            };
+            line.x = offset;
+
+            let justify = alignment == TextAlignment::Justify && !hard && !line.trimmed && self.max_width.is_finite();
+            let gaps = line.clusters.iter().filter(|c| c.space && !c.trailing).count();
+            let stretch = if justify && gaps > 0 { extra / gaps as f32 } else { 0.0 };
+
+            let mut shift = offset;
+            let mut shifts = Vec::with_capacity(line.clusters.len());
+            for cluster in line.clusters.iter_mut() {
+                cluster.x += shift;
+                shifts.push(shift);
+                if cluster.space && !cluster.trailing {
+                    shift += stretch;
+                    cluster.advance += stretch;
+                }
+            }
+            for glyph in line.glyphs.iter_mut() {
+                glyph.x += shifts[glyph.cluster];
+                if line.clusters[glyph.cluster].space && !line.clusters[glyph.cluster].trailing {
+                    glyph.advance += stretch;
+                }
+            }
+            if stretch > 0.0 {
+                line.width = width;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format() -> TextFormat {
+        TextFormat::new(Font::default_font(), 10.0)
+    }
+
+    fn width(text: &str) -> f32 {
+        Font::default_font().measure(text, 10.0).w
+    }
+
+    fn line_texts(layout: &TextLayout) -> Vec<&str> {
+        layout.lines().iter().map(|line| &layout.text()[line.range.clone()]).collect()
+    }
+
+    #[test]
+    fn wraps_at_break_opportunities() {
+        let max = width("hello world") + 1.0;
+        let layout = TextLayout::new("hello world again", format(), max);
+
+        assert_eq!(line_texts(&layout), vec!["hello world ", "again"]);
+        assert_eq!(layout.lines()[0].width, width("hello world"));
+        assert_eq!(layout.size().h, 2.0 * format().font.metrics(10.0).line_height());
+    }
+
+    #[test]
+    fn mandatory_breaks_and_long_words() {
+        let layout = TextLayout::new("ab\ncd\n", format(), f32::INFINITY);
+        assert_eq!(line_texts(&layout), vec!["ab\n", "cd\n", ""]);
+
+        let layout = TextLayout::new("abcdefgh", format(), width("abc") + 0.5);
+        assert_eq!(line_texts(&layout), vec!["abc", "def", "gh"]);
+
+        let layout = TextLayout::new("hello world", format().with_wrapping(TextWrapping::NoWrap), 10.0);
+        assert_eq!(layout.lines().len(), 1);
+    }
+
+    #[test]
+    fn alignment_offsets_lines() {
+        let max = 200.0;
+        let right = TextLayout::new("hi", format().with_alignment(TextAlignment::Right), max);
+        let center = TextLayout::new("hi", format().with_alignment(TextAlignment::Center), max);
+
+        assert_eq!(right.lines()[0].x, max - width("hi"));
+        assert_eq!(right.lines()[0].glyphs[0].x, max - width("hi"));
+        assert_eq!(center.lines()[0].x, (max - width("hi")) / 2.0);
+    }
+
+    #[test]
+    fn justify_stretches_all_but_the_last_line() {
+        let max = width("aa bb cc") + 5.0;
+        let layout = TextLayout::new("aa bb cc dd", format().with_alignment(TextAlignment::Justify), max);
+        let lines = layout.lines();
+
+        assert_eq!(lines.len(), 2);
+        let last = lines[0].glyphs.iter().filter(|g| g.id != format().font.glyph_id(' ')).last().unwrap();
+        assert!((last.x + last.advance - max).abs() < 0.01);
+        assert_eq!(lines[1].glyphs[0].x, 0.0);
+        assert_eq!(lines[1].width, width("dd"));
+    }
+
+    #[test]
+    fn max_lines_with_ellipsis() {
+        let max = width("one two") + 1.0;
+        let format = format().with_max_lines(1).with_trimming(TextTrimming::Ellipsis);
+        let layout = TextLayout::new("one two three", format, max);
+        let line = &layout.lines()[0];
+
+        assert_eq!(layout.lines().len(), 1);
+        assert!(layout.is_trimmed());
+        assert!(line.trimmed);
+        assert!(line.width <= max);
+        assert_eq!(line.glyphs.last().unwrap().id, Font::default_font().glyph_id('…'));
+    }
+
+    #[test]
+    fn nowrap_ellipsis_fits_width() {
+        let format = format().with_wrapping(TextWrapping::NoWrap).with_trimming(TextTrimming::Ellipsis);
+        let layout = TextLayout::new("a long single line", format, 40.0);
+
+        assert!(layout.lines()[0].width <= 40.0);
+        assert!(layout.is_trimmed());
+
+        let format = self::format().with_wrapping(TextWrapping::NoWrap).with_trimming(TextTrimming::Ellipsis);
+        assert!(!TextLayout::new("ok", format, 40.0).is_trimmed());
+    }
+
+    #[test]
+    fn line_height_control() {
+        let normal = format().font.metrics(10.0).line_height();
+        let fixed = TextLayout::new("a\nb", format().with_line_height(LineHeight::Fixed(30.0)), f32::INFINITY);
+        let scaled = TextLayout::new("a", format().with_line_height(LineHeight::Scale(2.0)), f32::INFINITY);
+
+        assert_eq!(fixed.lines()[1].top, 30.0);
+        assert_eq!(fixed.size().h, 60.0);
+        assert_eq!(scaled.size().h, normal * 2.0);
+        assert!(fixed.lines()[0].baseline > 15.0);
+    }
+
+    #[test]
+    fn caret_rects_and_hit_testing() {
+        let max = width("ab cd") + 1.0;
+        let layout = TextLayout::new("ab cd ef\nx", format(), max);
+        let line_height = format().font.metrics(10.0).line_height();
+
+        assert_eq!(layout.caret_rect(0).pos.to_tuple(), (0.0, 0.0));
+        assert_eq!(layout.caret_rect(2).pos.x, width("ab"));
+        assert_eq!(layout.caret_rect(6).pos.to_tuple(), (0.0, line_height));
+        assert!((layout.caret_rect(8).pos.x - width("ef")).abs() < 0.001);
+        assert!((layout.caret_rect(100).pos.x - width("x")).abs() < 0.001);
+        assert_eq!(layout.caret_rect(100).pos.y, 2.0 * line_height);
+
+        assert_eq!(layout.hit_test(Point::new(1.0, 1.0)), 0);
+        assert_eq!(layout.hit_test(Point::new(width("ab") - 1.0, 1.0)), 2);
+        assert_eq!(layout.hit_test(Point::new(500.0, 1.0)), 5);
+        assert_eq!(layout.hit_test(Point::new(500.0, line_height + 1.0)), 8);
+        assert_eq!(layout.hit_test(Point::new(500.0, 500.0)), 10);
+    }
+
+    #[test]
+    fn run_places_glyphs_on_baselines() {
+        let layout = TextLayout::new("a\nb", format(), f32::INFINITY);
+        let runs = layout.runs();
+
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].glyphs.len(), 2);
+        assert_eq!(runs[0].glyphs[0].pos.y, layout.lines()[0].baseline);
+        assert_eq!(runs[0].glyphs[1].pos.y, layout.lines()[1].baseline);
+    }
+
+    fn style(size: f32) -> SpanStyle {
+        SpanStyle::new(Font::default_font(), size, Color::argb(1.0, 0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn mixed_sizes_share_the_tallest_baseline() {
+        let spans = vec![
+            TextSpan::Text("small ".to_string(), style(10.0)),
+            TextSpan::Text("big".to_string(), style(20.0)),
+        ];
+        let layout = TextLayout::with_spans(&spans, format(), f32::INFINITY);
+        let big = Font::default_font().metrics(20.0);
+        let runs = layout.runs();
+
+        assert_eq!(layout.text(), "small big");
+        assert_eq!(layout.size().h, big.line_height());
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].size, 10.0);
+        assert_eq!(runs[0].glyphs[0].pos.y, runs[1].glyphs[0].pos.y);
+        assert!((runs[1].glyphs[0].pos.x - width("small ")).abs() < 0.001);
+    }
+
+    #[test]
+    fn decorations_cover_decorated_spans() {
+        let mut underlined = style(10.0);
+        underlined.underline = true;
+        let mut struck = style(10.0);
+        struck.strikethrough = true;
+        let spans = vec![
+            TextSpan::Text("ab ".to_string(), underlined),
+            TextSpan::Text("cd".to_string(), struck),
+        ];
+        let layout = TextLayout::with_spans(&spans, format(), f32::INFINITY);
+        let decorations = layout.decorations();
+        let baseline = layout.lines()[0].baseline;
+
+        assert_eq!(decorations.len(), 2);
+        assert!((decorations[0].0.size.w - width("ab ")).abs() < 0.001);
+        assert!(decorations[0].0.pos.y > baseline);
+        assert!(decorations[1].0.pos.y < baseline);
+        assert!((decorations[1].0.pos.x - width("ab ")).abs() < 0.001);
+    }
+
+    #[test]
+    fn inline_objects_flow_with_the_text() {
+        let spans = vec![
+            TextSpan::Text("ab ".to_string(), style(10.0)),
+            TextSpan::Object(Size::new(16.0, 30.0)),
+            TextSpan::Text(" cd".to_string(), style(10.0)),
+        ];
+        let layout = TextLayout::with_spans(&spans, format(), width("ab ") + 20.0);
+        let rects = layout.object_rects();
+        let first = &layout.lines()[0];
+
+        assert_eq!(layout.text(), "ab \u{FFFC} cd");
+        assert_eq!(layout.lines().len(), 2);
+        assert_eq!(first.baseline, 30.0 + (first.height - (30.0 - format().font.metrics(10.0).descent)) / 2.0);
+        let rect = rects[0].unwrap();
+        assert!((rect.pos.x - width("ab ")).abs() < 0.001);
+        assert_eq!(rect.bottom(), first.baseline);
+        assert_eq!(layout.runs().iter().map(|run| run.glyphs.len()).sum::<usize>(), 6);
+    }
+
+    #[test]
+    fn right_to_left_runs_are_reordered() {
+        let layout = TextLayout::new("abc אבג", format(), f32::INFINITY);
+        let line = &layout.lines()[0];
+        let x_of = |index: usize| line.glyphs.iter().find(|g| g.index == index).unwrap().x;
+
+        assert!(!line.rtl);
+        assert!(x_of(0) < x_of(2));
+        assert!(x_of(3) < x_of(8));
+        assert!(x_of(8) < x_of(6));
+        assert!(x_of(6) < x_of(4));
+        assert!(line.clusters[4].rtl);
+
+        let layout = TextLayout::new("אבג abc", format(), f32::INFINITY);
+        assert!(layout.lines()[0].rtl);
+        assert!(layout.lines()[0].clusters[0].range.start > 6);
+    }
+
+    #[test]
+    fn combining_marks_share_a_cluster() {
+        let layout = TextLayout::new("x\u{0301}b", format(), f32::INFINITY);
+        let line = &layout.lines()[0];
+
+        assert_eq!(line.clusters.len(), 2);
+        assert_eq!(line.clusters[0].range, 0..3);
+        assert_eq!(line.glyphs.iter().filter(|g| g.index == 0).count(), 2);
+        assert_eq!(layout.next_caret(0), 3);
+        assert_eq!(layout.previous_caret(3), 0);
+        assert_eq!(layout.caret_rect(3).pos.x, line.clusters[1].x);
+    }
+
+    #[test]
+    fn right_to_left_carets_and_hit_testing() {
+        let layout = TextLayout::new("אב", format(), f32::INFINITY);
+        let width = layout.size().w;
+
+        assert!((layout.caret_rect(0).pos.x - width).abs() < 0.001);
+        assert_eq!(layout.caret_rect(4).pos.x, 0.0);
+        assert_eq!(layout.hit_test(Point::new(width - 1.0, 1.0)), 0);
+        assert_eq!(layout.hit_test(Point::new(1.0, 1.0)), 4);
+    }
+
+    #[test]
+    fn visual_and_logical_caret_movement() {
+        // Shown as "ab בא", with the logical end of the text between the space and "ב".
+        let layout = TextLayout::new("ab אב", format(), f32::INFINITY);
+        let mut right = vec![0];
+        for _ in 0..6 {
+            let last = *right.last().unwrap();
+            right.push(layout.caret_right(last));
+        }
+        let mut left = vec![3];
+        for _ in 0..6 {
+            let last = *left.last().unwrap();
+            left.push(layout.caret_left(last));
+        }
+
+        assert_eq!(right, vec![0, 1, 2, 7, 5, 3, 3]);
+        assert_eq!(left, vec![3, 5, 7, 2, 1, 0, 0]);
+        assert_eq!(layout.next_caret(3), 5);
+        assert_eq!(layout.previous_caret(7), 5);
+
+        let layout = TextLayout::new("ab\ncd", format(), f32::INFINITY);
+        assert_eq!(layout.caret_right(2), 3);
+        assert_eq!(layout.caret_left(3), 2);
+    }
+
+    #[test]
+    fn missing_glyphs_use_fallback_fonts() {
+        let family = FontFamily::default_family();
+        let format = TextFormat::new(family.italic.clone(), 10.0).with_fallbacks(vec![family.regular.clone()]);
+        let layout = TextLayout::new("ab \u{0633}\u{0644}", format, f32::INFINITY);
+        let runs = layout.runs();
+
+        assert_eq!(layout.styles().len(), 2);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].font.id(), family.italic.id());
+        assert_eq!(runs[0].glyphs.len(), 3);
+        assert_eq!(runs[1].font.id(), family.regular.id());
+    }
+}
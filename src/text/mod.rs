@@ -0,0 +1,623 @@
+use primitives::*;
+use rusttype;
+use rusttype::{GlyphId, Scale};
+use rustybuzz;
+use rustybuzz::ttf_parser;
+use std::rc::Rc;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+pub mod buffer;
+pub mod fonts;
+pub mod layout;
+
+const DEFAULT_FONT: &'static [u8] = include_bytes!("../../assets/DejaVuSans.ttf");
+const DEFAULT_BOLD_FONT: &'static [u8] = include_bytes!("../../assets/DejaVuSans-Bold.ttf");
+const DEFAULT_ITALIC_FONT: &'static [u8] = include_bytes!("../../assets/DejaVuSans-Oblique.ttf");
+const DEFAULT_BOLD_ITALIC_FONT: &'static [u8] = include_bytes!("../../assets/DejaVuSans-BoldOblique.ttf");
+
+pub const WEIGHT_NORMAL: u16 = 400;
+pub const WEIGHT_BOLD: u16 = 700;
+
+/// Side of the square glyph atlas texture, in pixels.
+pub const ATLAS_SIZE: u32 = 1024;
+
+/// Number of horizontal subpixel positions a glyph is rasterized at.
+const SUBPIXEL_STEPS: f32 = 4.0;
+
+thread_local! {
+    static NEXT_FONT_ID: Cell<usize> = Cell::new(0);
+    static DEFAULT: Rc<Font> = Rc::new(Font::from_bytes(DEFAULT_FONT.to_vec()).unwrap());
+    static DEFAULT_FAMILY: FontFamily = FontFamily {
+        regular: Font::default_font(),
+        bold: Rc::new(Font::from_bytes(DEFAULT_BOLD_FONT.to_vec()).unwrap()),
+        italic: Rc::new(Font::from_bytes(DEFAULT_ITALIC_FONT.to_vec()).unwrap()),
+        bold_italic: Rc::new(Font::from_bytes(DEFAULT_BOLD_ITALIC_FONT.to_vec()).unwrap()),
+    };
+}
+
+pub struct Font {
+    id: usize,
+    /// The font file, for shaping.
+    data: Vec<u8>,
+    font: rusttype::Font<'static>,
+    family: String,
+    weight: u16,
+    style: FontStyle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    /// A slanted version of the upright face.
+    Oblique,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    /// Distance from the baseline to the lowest descender; negative.
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl FontMetrics {
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, String> {
+        let font = rusttype::Font::try_from_vec(data.clone()).ok_or("Unsupported font data".to_string())?;
+        let (family, weight, style) = {
+            let face = ttf_parser::Face::parse(&data, 0).map_err(|e| format!("Unsupported font data: {}", e))?;
+            let name = |id| face.names().into_iter().filter(|name| name.name_id == id).filter_map(|name| name.to_string()).next();
+            let family = name(ttf_parser::name_id::TYPOGRAPHIC_FAMILY)
+                .or_else(|| name(ttf_parser::name_id::FAMILY))
+                .unwrap_or_default();
+            let style = match face.style() {
+                ttf_parser::Style::Italic => FontStyle::Italic,
+                ttf_parser::Style::Oblique => FontStyle::Oblique,
+                ttf_parser::Style::Normal if face.italic_angle() != 0.0 => FontStyle::Oblique,
+                ttf_parser::Style::Normal => FontStyle::Normal,
+            };
+            (family, face.weight().to_number(), style)
+        };
+        let id = NEXT_FONT_ID.with(|next| {
+            let id = next.get();
+            next.set(id + 1);
+            id
+        });
+        Ok(Font {
+            id: id,
+            data: data,
+            font: font,
+            family: family,
+            weight: weight,
+            style: style,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Font, String> {
+        let path = path.as_ref();
+        let mut data = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|e| format!("Can't read font {}: {}", path.display(), e))?;
+        Font::from_bytes(data)
+    }
+
+    /// The font bundled with the library, DejaVu Sans.
+    pub fn default_font() -> Rc<Font> {
+        DEFAULT.with(|font| font.clone())
+    }
+
+    /// Identifies the font in glyph caches.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The family name from the font's name table, such as "DejaVu Sans".
+    pub fn family(&self) -> &str {
+        &self.family
+    }
+
+    /// The weight class, from 100 (thin) to 900 (black).
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn style(&self) -> FontStyle {
+        self.style
+    }
+
+    pub fn metrics(&self, size: f32) -> FontMetrics {
+        let v = self.font.v_metrics(Scale::uniform(size));
+        FontMetrics {
+            ascent: v.ascent,
+            descent: v.descent,
+            line_gap: v.line_gap,
+        }
+    }
+
+    pub fn glyph_id(&self, c: char) -> u16 {
+        self.font.glyph(c).id().0
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.glyph_id(c) != 0
+    }
+
+    pub fn advance(&self, glyph: u16, size: f32) -> f32 {
+        self.font.glyph(GlyphId(glyph)).scaled(Scale::uniform(size)).h_metrics().advance_width
+    }
+
+    /// Adjustment of the advance between two glyphs that follow each other.
+    pub fn kerning(&self, size: f32, first: u16, second: u16) -> f32 {
+        self.font.pair_kerning(Scale::uniform(size), GlyphId(first), GlyphId(second))
+    }
+
+    /// Shapes `text` as a single run in one direction: characters are mapped
+    /// to glyphs with ligatures, kerning and marks applied. Glyphs come in
+    /// visual order, so right-to-left text comes reversed.
+    pub fn shape(&self, text: &str, size: f32, rtl: bool) -> Vec<ShapedGlyph> {
+        let face = match rustybuzz::Face::from_slice(&self.data, 0) {
+            Some(face) => face,
+            None => return Vec::new(),
+        };
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(if rtl { rustybuzz::Direction::RightToLeft } else { rustybuzz::Direction::LeftToRight });
+        let output = rustybuzz::shape(&face, &[], buffer);
+
+        // Sizes follow rusttype, which scales the height from ascent to descent to `size`.
+        let v = self.font.v_metrics_unscaled();
+        let scale = size / (v.ascent - v.descent);
+        output.glyph_infos().iter().zip(output.glyph_positions()).map(|(info, pos)| ShapedGlyph {
+            id: info.glyph_id as u16,
+            cluster: info.cluster as usize,
+            advance: pos.x_advance as f32 * scale,
+            offset: Point::new(pos.x_offset as f32 * scale, -pos.y_offset as f32 * scale),
+        }).collect()
+    }
+
+    /// Lays `text` out left to right on a single line with the pen starting
+    /// at the origin on the baseline.
+    pub fn glyphs(&self, text: &str, size: f32) -> Vec<Glyph> {
+        let mut x = 0.0;
+        self.shape(text, size, false).iter().map(|g| {
+            let glyph = Glyph { id: g.id, pos: Point::new(x + g.offset.x, g.offset.y) };
+            x += g.advance;
+            glyph
+        }).collect()
+    }
+
+    /// The size of `text` set on a single line: its advance width and the line height.
+    pub fn measure(&self, text: &str, size: f32) -> Size {
+        let width = self.shape(text, size, false).iter().fold(0.0, |w, g| w + g.advance);
+        Size::new(width, self.metrics(size).line_height())
+    }
+
+    /// Renders a glyph with its pen at `(offset, 0)`, returning the coverage
+    /// bitmap and where its top left pixel is relative to the pen.
+    fn rasterize(&self, glyph: u16, size: f32, offset: f32) -> Option<(u32, u32, (i32, i32), Vec<u8>)> {
+        let glyph = self.font.glyph(GlyphId(glyph))
+            .scaled(Scale::uniform(size))
+            .positioned(rusttype::point(offset, 0.0));
+        glyph.pixel_bounding_box().map(|bb| {
+            let (width, height) = (bb.width() as u32, bb.height() as u32);
+            let mut data = vec![0u8; (width * height) as usize];
+            glyph.draw(|x, y, coverage| {
+                data[(y * width + x) as usize] = (coverage * 255.0).round() as u8;
+            });
+            (width, height, (bb.min.x, bb.min.y), data)
+        })
+    }
+}
+
+/// The regular, bold, italic and bold italic faces of a typeface.
+#[derive(Clone)]
+pub struct FontFamily {
+    pub regular: Rc<Font>,
+    pub bold: Rc<Font>,
+    pub italic: Rc<Font>,
+    pub bold_italic: Rc<Font>,
+}
+
+impl FontFamily {
+    /// The family bundled with the library, DejaVu Sans.
+    pub fn default_family() -> FontFamily {
+        DEFAULT_FAMILY.with(|family| family.clone())
+    }
+
+    pub fn face(&self, bold: bool, italic: bool) -> Rc<Font> {
+        match (bold, italic) {
+            (false, false) => self.regular.clone(),
+            (true, false) => self.bold.clone(),
+            (false, true) => self.italic.clone(),
+            (true, true) => self.bold_italic.clone(),
+        }
+    }
+}
+
+/// A glyph produced by shaping, sized in pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShapedGlyph {
+    pub id: u16,
+    /// Byte index in the shaped text of the first character of the glyph's
+    /// cluster: the characters that are shaped together, like a ligature or
+    /// a base with its marks.
+    pub cluster: usize,
+    pub advance: f32,
+    /// Displacement from the pen position; y grows downwards.
+    pub offset: Point,
+}
+
+/// A glyph and its pen position, relative to the start of the baseline.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Glyph {
+    pub id: u16,
+    pub pos: Point,
+}
+
+/// Positioned glyphs drawn with one font, size and color.
+#[derive(Clone)]
+pub struct TextRun {
+    pub font: Rc<Font>,
+    pub size: f32,
+    pub color: Color,
+    pub glyphs: Vec<Glyph>,
+}
+
+impl TextRun {
+    /// Sets `text` on a single line.
+    pub fn new(font: Rc<Font>, size: f32, color: Color, text: &str) -> TextRun {
+        let glyphs = font.glyphs(text, size);
+        TextRun {
+            font: font,
+            size: size,
+            color: color,
+            glyphs: glyphs,
+        }
+    }
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+/// Packs rectangles into rows ("shelves") of a fixed size area. Each
+/// rectangle goes to the shortest shelf it fits in, and a new shelf is opened
+/// below the last one when none does.
+pub struct AtlasAllocator {
+    width: u32,
+    height: u32,
+    shelves: Vec<Shelf>,
+}
+
+impl AtlasAllocator {
+    pub fn new(width: u32, height: u32) -> AtlasAllocator {
+        AtlasAllocator {
+            width: width,
+            height: height,
+            shelves: Vec::new(),
+        }
+    }
+
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.width {
+            return None;
+        }
+
+        // The shortest shelf the rect fits in, unless it's so much taller that
+        // the space would be wasted; then a new shelf is tried first.
+        let width_limit = self.width;
+        let best = self.shelves.iter().enumerate()
+            .filter(|&(_, shelf)| shelf.height >= height && shelf.x + width <= width_limit)
+            .min_by_key(|&(_, shelf)| shelf.height)
+            .map(|(i, _)| i);
+        let snug = best.map_or(false, |i| self.shelves[i].height <= height + height / 2 + 1);
+
+        if !snug {
+            let y = self.shelves.last().map_or(0, |last| last.y + last.height);
+            if y + height <= self.height {
+                self.shelves.push(Shelf { y: y, height: height, x: width });
+                return Some((0, y));
+            }
+        }
+
+        best.map(|i| {
+            let shelf = &mut self.shelves[i];
+            let pos = (shelf.x, shelf.y);
+            shelf.x += width;
+            pos
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    glyph: u16,
+    /// In 1/64 pixels.
+    size: u32,
+    subpixel: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct CachedGlyph {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    /// The bitmap's top left relative to the pen, which is at the subpixel offset.
+    offset: (i32, i32),
+}
+
+/// A bitmap to copy into the atlas texture; rows go top to bottom.
+pub struct GlyphUpload {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Where to draw a glyph, in physical pixels, and its area of the atlas in
+/// texture coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlyphQuad {
+    pub rect: Rect,
+    pub uv: Rect,
+}
+
+/// Rasterized glyphs kept in a texture atlas, keyed by font, glyph, pixel
+/// size and horizontal subpixel offset. Bitmaps of newly added glyphs wait
+/// in `take_uploads` until the renderer copies them to the texture. When the
+/// atlas is full it is emptied and refilled with the glyphs in use.
+pub struct GlyphCache {
+    allocator: AtlasAllocator,
+    size: (u32, u32),
+    glyphs: HashMap<GlyphKey, Option<CachedGlyph>>,
+    uploads: Vec<GlyphUpload>,
+}
+
+impl GlyphCache {
+    pub fn new(width: u32, height: u32) -> GlyphCache {
+        GlyphCache {
+            allocator: AtlasAllocator::new(width, height),
+            size: (width, height),
+            glyphs: HashMap::new(),
+            uploads: Vec::new(),
+        }
+    }
+
+    /// Places the glyphs of `run` with the start of its baseline at `origin`.
+    /// `origin` and the returned rects are in physical pixels, of which there
+    /// are `scale` per unit of the run's size and glyph positions. Glyphs
+    /// without pixels, like spaces, produce no quad.
+    pub fn quads(&mut self, run: &TextRun, origin: Point, scale: f32) -> Vec<GlyphQuad> {
+        match self.try_quads(run, origin, scale) {
+            Ok(quads) => quads,
+            // The atlas was emptied to make room: place the run again so that
+            // every quad refers to the new contents.
+            Err(_) => self.try_quads(run, origin, scale).unwrap_or_else(|quads| quads),
+        }
+    }
+
+    fn try_quads(&mut self, run: &TextRun, origin: Point, scale: f32) -> Result<Vec<GlyphQuad>, Vec<GlyphQuad>> {
+        let size = run.size * scale;
+        let mut quads = Vec::with_capacity(run.glyphs.len());
+        for glyph in &run.glyphs {
+            let pen = Point::new(origin.x + glyph.pos.x * scale, origin.y + glyph.pos.y * scale);
+            match self.place(&run.font, glyph.id, size, pen) {
+                Ok(Some(quad)) => quads.push(quad),
+                Ok(None) => {}
+                Err(()) => return Err(quads),
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Bitmaps added since the last call, to be copied to the atlas texture.
+    pub fn take_uploads(&mut self) -> Vec<GlyphUpload> {
+        ::std::mem::replace(&mut self.uploads, Vec::new())
+    }
+
+    fn place(&mut self, font: &Font, glyph: u16, size: f32, pen: Point) -> Result<Option<GlyphQuad>, ()> {
+        let (base_x, fraction) = (pen.x.floor(), pen.x - pen.x.floor());
+        let subpixel = (fraction * SUBPIXEL_STEPS).round();
+        let (base_x, subpixel) = if subpixel >= SUBPIXEL_STEPS { (base_x + 1.0, 0.0) } else { (base_x, subpixel) };
+        let key = GlyphKey {
+            font: font.id(),
+            glyph: glyph,
+            size: (size * 64.0).round() as u32,
+            subpixel: subpixel as u8,
+        };
+
+        let cached = match self.glyphs.get(&key) {
+            Some(cached) => *cached,
+            None => {
+                let cached = match self.add(font, glyph, size, subpixel / SUBPIXEL_STEPS) {
+                    Ok(cached) => cached,
+                    Err(()) => {
+                        self.clear();
+                        return Err(());
+                    }
+                };
+                self.glyphs.insert(key, cached);
+                cached
+            }
+        };
+
+        Ok(cached.map(|g| {
+            let (atlas_w, atlas_h) = (self.size.0 as f32, self.size.1 as f32);
+            GlyphQuad {
+                rect: Rect::pos_size(Point::new(base_x + g.offset.0 as f32, pen.y.round() + g.offset.1 as f32),
+                                     Size::new(g.width as f32, g.height as f32)),
+                uv: Rect::pos_size(Point::new(g.x as f32 / atlas_w, g.y as f32 / atlas_h),
+                                   Size::new(g.width as f32 / atlas_w, g.height as f32 / atlas_h)),
+            }
+        }))
+    }
+
+    /// Rasterizes a glyph into the atlas. Fails when there's no room left.
+    fn add(&mut self, font: &Font, glyph: u16, size: f32, offset: f32) -> Result<Option<CachedGlyph>, ()> {
+        let (width, height, bitmap_offset, data) = match font.rasterize(glyph, size, offset) {
+            Some(bitmap) => bitmap,
+            None => return Ok(None),
+        };
+        // One pixel of padding keeps neighbours from bleeding in when sampling.
+        let (x, y) = self.allocator.allocate(width + 1, height + 1).ok_or(())?;
+        self.uploads.push(GlyphUpload { x: x, y: y, width: width, height: height, data: data });
+        Ok(Some(CachedGlyph {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+            offset: bitmap_offset,
+        }))
+    }
+
+    fn clear(&mut self) {
+        self.allocator.clear();
+        self.glyphs.clear();
+        self.uploads.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> Rc<Font> {
+        Font::default_font()
+    }
+
+    fn black() -> Color {
+        Color::argb(1.0, 0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn invalid_font_data_is_rejected() {
+        assert!(Font::from_bytes(vec![1, 2, 3]).is_err());
+        assert!(Font::from_file("does/not/exist.ttf").is_err());
+    }
+
+    #[test]
+    fn measure_sums_advances_and_kerning() {
+        let font = font();
+        let (a, v) = (font.glyph_id('A'), font.glyph_id('V'));
+        let unkerned = font.advance(a, 20.0) + font.advance(v, 20.0);
+
+        let size = font.measure("AV", 20.0);
+
+        assert!(size.w < unkerned - 0.5);
+        assert!((font.measure("A", 20.0).w - font.advance(a, 20.0)).abs() < 0.01);
+        assert_eq!(size.h, font.metrics(20.0).line_height());
+        assert_eq!(font.measure("", 20.0).w, 0.0);
+    }
+
+    #[test]
+    fn shaping_joins_arabic_and_positions_marks() {
+        let font = font();
+
+        // Each letter of a joined Arabic word takes a contextual form.
+        let word = "\u{0633}\u{0644}\u{0627}\u{0645}";
+        let shaped = font.shape(word, 20.0, true);
+        let isolated: Vec<u16> = word.chars().map(|c| font.glyph_id(c)).collect();
+        assert!(shaped.len() >= 3);
+        assert!(shaped.iter().any(|g| !isolated.contains(&g.id)));
+        // Right to left runs come in visual order: the first character last.
+        assert_eq!(shaped.last().unwrap().cluster, 0);
+
+        // A combining mark joins the cluster of its base and takes no room.
+        let shaped = font.shape("x\u{0301}b", 20.0, false);
+        assert_eq!(shaped[1].cluster, shaped[0].cluster);
+        assert_eq!(shaped[1].advance, 0.0);
+        assert_eq!(shaped.last().unwrap().cluster, 3);
+    }
+
+    #[test]
+    fn measure_scales_with_size() {
+        let font = font();
+        let small = font.measure("Hello", 10.0);
+        let large = font.measure("Hello", 20.0);
+
+        assert!((large.w - small.w * 2.0).abs() < 0.01);
+        assert!((large.h - small.h * 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn allocator_fills_shelves_then_opens_new_ones() {
+        let mut allocator = AtlasAllocator::new(20, 20);
+
+        assert_eq!(allocator.allocate(8, 10), Some((0, 0)));
+        assert_eq!(allocator.allocate(8, 9), Some((8, 0)));
+        assert_eq!(allocator.allocate(8, 10), Some((0, 10)));
+        assert_eq!(allocator.allocate(8, 10), Some((8, 10)));
+        assert_eq!(allocator.allocate(8, 10), None);
+        assert_eq!(allocator.allocate(30, 1), None);
+
+        allocator.clear();
+        assert_eq!(allocator.allocate(8, 10), Some((0, 0)));
+    }
+
+    #[test]
+    fn glyphs_are_rasterized_once_per_subpixel_position() {
+        let mut cache = GlyphCache::new(ATLAS_SIZE, ATLAS_SIZE);
+        let run = TextRun::new(font(), 16.0, black(), "aa a");
+
+        let quads = cache.quads(&run, Point::zero(), 1.0);
+        let uploads = cache.take_uploads().len();
+        let again = cache.quads(&run, Point::zero(), 1.0);
+
+        assert_eq!(quads.len(), 3);
+        assert!(uploads >= 1 && uploads <= 3);
+        assert!(quads == again);
+        assert!(cache.take_uploads().is_empty());
+
+        cache.quads(&run, Point::new(0.25, 0.0), 1.0);
+        assert!(!cache.take_uploads().is_empty());
+    }
+
+    #[test]
+    fn quads_are_snapped_to_whole_pixels() {
+        let mut cache = GlyphCache::new(ATLAS_SIZE, ATLAS_SIZE);
+        let run = TextRun::new(font(), 13.0, black(), "Text");
+
+        for quad in cache.quads(&run, Point::new(10.3, 20.6), 2.0) {
+            assert_eq!(quad.rect.pos.x, quad.rect.pos.x.round());
+            assert_eq!(quad.rect.pos.y, quad.rect.pos.y.round());
+            assert!(quad.rect.pos.y < 21.0);
+        }
+    }
+
+    #[test]
+    fn full_atlas_is_emptied_and_refilled() {
+        let mut cache = GlyphCache::new(40, 40);
+        cache.quads(&TextRun::new(font(), 16.0, black(), "abcdefgh"), Point::zero(), 1.0);
+        cache.take_uploads();
+
+        let run = TextRun::new(font(), 16.0, black(), "ijkl");
+        let quads = cache.quads(&run, Point::zero(), 1.0);
+        let uploads = cache.take_uploads();
+
+        assert_eq!(quads.len(), 4);
+        assert_eq!(uploads.len(), 4);
+        for quad in &quads {
+            assert!(quad.uv.right() <= 1.0 && quad.uv.bottom() <= 1.0);
+        }
+    }
+}
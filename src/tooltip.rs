@@ -0,0 +1,258 @@
+use primitives::*;
+use tree::*;
+use layout::*;
+use text::*;
+use text::layout::*;
+use elements::border::Border;
+use elements::text_block::{TextBlock, DEFAULT_FONT_SIZE};
+use std::f32;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_DELAY_MS: u64 = 500;
+/// How far below the cursor tooltips go, to clear the cursor's arrow.
+const CURSOR_HEIGHT: f32 = 20.0;
+/// The widest tooltip content can be; text wraps there.
+const MAX_TEXT_WIDTH: f32 = 320.0;
+
+/// A tooltip a node carries, set with `NodeRef::set_tooltip`. It is shown
+/// in a popup below the cursor once the cursor rests on the node for the
+/// tooltip's delay.
+#[derive(Clone)]
+pub struct ToolTip {
+    content: Rc<Fn() -> Box<Element>>,
+    delay: Duration,
+}
+
+impl ToolTip {
+    pub fn text<T: Into<String>>(text: T) -> ToolTip {
+        let text = text.into();
+        ToolTip::new(move || {
+            let format = TextFormat::new(Font::default_font(), DEFAULT_FONT_SIZE).with_color(Color::argb(1.0, 0.15, 0.15, 0.15));
+            Box::new(TextBlock::with_format(text.clone(), format)) as Box<Element>
+        })
+    }
+
+    /// A tooltip showing an element made by `content` each time it is shown.
+    pub fn new<F: Fn() -> Box<Element> + 'static>(content: F) -> ToolTip {
+        ToolTip {
+            content: Rc::new(content),
+            delay: Duration::from_millis(DEFAULT_DELAY_MS),
+        }
+    }
+
+    /// Sets how long the cursor has to rest on the node before the tooltip shows.
+    pub fn with_delay(mut self, delay: Duration) -> ToolTip {
+        self.delay = delay;
+        self
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+}
+
+/// Shows the tooltip of the node under the cursor. The tree feeds it the
+/// cursor moves and input, and fires it from `Tree::fire_timers`.
+pub struct ToolTips {
+    /// The node whose tooltip is pending or shown.
+    owner: NodeWeakRef,
+    popup: NodeWeakRef,
+    due: Option<Instant>,
+    cursor: Point,
+}
+
+impl ToolTips {
+    pub fn new() -> ToolTips {
+        ToolTips {
+            owner: NodeWeakRef::empty(),
+            popup: NodeWeakRef::empty(),
+            due: None,
+            cursor: Point::zero(),
+        }
+    }
+
+    /// The popup of the shown tooltip.
+    pub fn popup(&self) -> Option<NodeRef> {
+        self.popup.upgrade().and_then(|popup| if popup.is_popup() { Some(popup) } else { None })
+    }
+
+    /// When the pending tooltip is to be shown.
+    pub fn due(&self) -> Option<Instant> {
+        self.due
+    }
+
+    /// Follows the cursor, at `cursor` in window coordinates, onto `owner`,
+    /// the node carrying the tooltip it is over. Moving onto another node
+    /// hides the tooltip; resting on one shows its tooltip after its delay.
+    pub fn cursor_moved(&mut self, owner: Option<NodeRef>, cursor: Point, now: Instant) {
+        self.cursor = cursor;
+        if owner.is_some() && owner == self.owner.upgrade() {
+            if self.due.is_some() {
+                self.due = owner.and_then(|o| o.tooltip()).map(|t| now + t.delay());
+            }
+            return;
+        }
+        self.dismiss();
+        self.due = owner.as_ref().and_then(|o| o.tooltip()).map(|t| now + t.delay());
+        self.owner = owner.map_or(NodeWeakRef::empty(), |o| o.downgrade());
+    }
+
+    /// Hides the tooltip and forgets its owner, once the cursor left the window.
+    pub fn cursor_left(&mut self) {
+        self.dismiss();
+        self.owner = NodeWeakRef::empty();
+    }
+
+    /// Shows the pending tooltip if it is due at `now`.
+    pub fn fire(&mut self, now: Instant) {
+        match self.due {
+            Some(due) if due <= now => self.due = None,
+            _ => return,
+        }
+        let owner = match self.owner.upgrade() {
+            Some(owner) => owner,
+            None => return,
+        };
+        let tooltip = match owner.tooltip() {
+            Some(tooltip) => tooltip,
+            None => return,
+        };
+        let anchor = Rect::pos_size(self.cursor, Size::new(0.0, CURSOR_HEIGHT));
+        let frame = Border::new()
+            .with_background(Color::argb(1.0, 1.0, 1.0, 0.92))
+            .with_border(Thickness::uniform(1.0), Color::argb(1.0, 0.6, 0.6, 0.6))
+            .with_corner_radius(CornerRadius::uniform(2.0));
+        let popup = owner.open_popup(Box::new(frame), anchor, Placement::Below);
+        let content = {
+            let mut frame = popup.cast_element_mut::<Border>().unwrap();
+            frame.set_padding(Thickness::hv(6.0, 3.0));
+            frame.set_content((tooltip.content)())
+        };
+        content.set_layout_properties(content.layout_properties().with_max_size(Size::new(MAX_TEXT_WIDTH, f32::INFINITY)));
+        self.popup = popup.downgrade();
+    }
+
+    /// Hides the tooltip, which stays hidden until the cursor moves onto
+    /// another node.
+    pub fn dismiss(&mut self) {
+        if let Some(popup) = self.popup() {
+            popup.close_popup();
+        }
+        self.popup = NodeWeakRef::empty();
+        self.due = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::*;
+    use input::*;
+
+    struct Panel;
+
+    impl Element for Panel {
+        fn init(&mut self, node: NodeRef) {}
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+            for (i, child) in node.children().iter().enumerate() {
+                child.arrange(Rect::pos_size(Point::new(0.0, i as f32 * 50.0), Size::new(container.w, 50.0)));
+            }
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+    }
+
+    const WINDOW: Size = Size { w: 400.0, h: 300.0 };
+
+    fn panel() -> Tree {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Panel))).unwrap();
+        let first = root.add_child(Box::new(Panel));
+        let second = root.add_child(Box::new(Panel));
+        first.set_tooltip(Some(ToolTip::text("First").with_delay(Duration::from_millis(100))));
+        second.set_tooltip(Some(ToolTip::text("Second")));
+        tree.layout(WINDOW);
+        tree
+    }
+
+    fn hover(tree: &mut Tree, pos: Point) -> Instant {
+        tree.dispatch_event(&InputEvent::MouseMove(pos));
+        tree.next_timer_time().unwrap()
+    }
+
+    fn fire(tree: &mut Tree, at: Instant) {
+        tree.fire_timers(at);
+        tree.layout(WINDOW);
+    }
+
+    #[test]
+    fn shows_after_the_delay_below_the_cursor() {
+        let mut tree = panel();
+
+        let due = hover(&mut tree, Point::new(20.0, 10.0));
+        fire(&mut tree, due - Duration::from_millis(1));
+        assert!(tree.popups().is_empty());
+        fire(&mut tree, due);
+        let popup = tree.popups()[0].clone();
+        assert_eq!(popup.window_rect().pos, Point::new(20.0, 10.0 + CURSOR_HEIGHT));
+        let text = popup.children()[0].clone();
+        assert_eq!(text.cast_element::<TextBlock>().unwrap().text(), "First");
+
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(30.0, 12.0)));
+        assert_eq!(tree.popups().len(), 1);
+        let due = hover(&mut tree, Point::new(395.0, 95.0));
+        assert!(tree.popups().is_empty());
+        fire(&mut tree, due);
+        let rect = tree.popups()[0].window_rect();
+        assert_eq!(rect.right(), WINDOW.w);
+        assert_eq!(rect.pos.y, 95.0 + CURSOR_HEIGHT);
+    }
+
+    #[test]
+    fn flips_above_the_cursor_at_the_bottom() {
+        let mut tree = panel();
+        tree.root().unwrap().set_tooltip(Some(ToolTip::text("Root")));
+
+        let due = hover(&mut tree, Point::new(50.0, 290.0));
+        fire(&mut tree, due);
+        let rect = tree.popups()[0].window_rect();
+        assert_eq!(rect.bottom(), 290.0);
+    }
+
+    #[test]
+    fn key_presses_and_leaving_hide_it() {
+        let mut tree = panel();
+
+        let due = hover(&mut tree, Point::new(20.0, 10.0));
+        fire(&mut tree, due);
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Letter('a'), Modifiers::none()));
+        assert!(tree.popups().is_empty());
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(25.0, 10.0)));
+        assert_eq!(tree.next_timer_time(), None);
+
+        let due = hover(&mut tree, Point::new(20.0, 60.0));
+        fire(&mut tree, due);
+        assert_eq!(tree.popups().len(), 1);
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(20.0, 250.0)));
+        assert!(tree.popups().is_empty());
+        assert_eq!(tree.next_timer_time(), None);
+
+        let due = hover(&mut tree, Point::new(20.0, 10.0));
+        tree.dispatch_event(&InputEvent::MouseLeave);
+        fire(&mut tree, due);
+        assert!(tree.popups().is_empty());
+        let due = hover(&mut tree, Point::new(20.0, 60.0));
+        fire(&mut tree, due);
+        tree.dispatch_event(&InputEvent::MouseLeave);
+        assert!(tree.popups().is_empty());
+        assert_eq!(tree.next_timer_time(), None);
+    }
+}
@@ -2,14 +2,55 @@ use std::rc::{Rc, Weak};
 use std::cell::{RefCell, Ref, RefMut};
 use std::cmp::Eq;
 use primitives::*;
+use layout::*;
 use render::*;
+use input::*;
+use msg::*;
+use tooltip::{ToolTip, ToolTips};
 use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 pub trait Element: Any {
     fn init(&mut self, node: NodeRef);
+    /// Returns the size wanted for the element's content. Padding, margin and
+    /// size limits from the node's `LayoutProperties` are added by the tree.
     fn measure(&self, node: NodeRef) -> Size;
+    /// Lays out the children inside `container`, the node's content size.
+    /// The node's own rect has already been set by the tree.
     fn layout(&mut self, node: NodeRef, container: Size);
     fn render(&self, node: NodeRef) -> RenderCommandList;
+
+    /// Handles an input event routed to the node, with positions relative to
+    /// the node's rect. Returning `false` lets the event bubble to the parent.
+    fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+        false
+    }
+
+    /// Handles a message raised by `source`, a descendant of the node.
+    /// Returning `false` lets the message bubble to the parent.
+    fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+        false
+    }
+
+    /// Called when a timer started with `NodeRef::start_timer` is due.
+    fn handle_timer(&mut self, node: NodeRef, timer: TimerId) {}
+
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn as_scrollable(&mut self) -> Option<&mut Scrollable> {
+        None
+    }
+
+    /// A rounded rect, relative to the node's rect, that children are clipped to.
+    fn children_clip(&self, node: NodeRef) -> Option<(Rect, CornerRadius)> {
+        None
+    }
 }
 
 impl Element {
@@ -47,11 +88,71 @@ type NodeBox = Rc<RefCell<Node>>;
 type NodeWeakBox = Weak<RefCell<Node>>;
 type ElementBox = Rc<RefCell<Box<Element + 'static>>>;
 type ElementWeakBox = Weak<RefCell<Box<Element + 'static>>>;
+type TreeStateBox = Rc<RefCell<TreeState>>;
+type TreeStateWeakBox = Weak<RefCell<TreeState>>;
 
 struct Node {
     pub parrent: Option<NodeWeakRef>,
     pub children: Vec<NodeRef>,
     pub rect: Rect,
+    pub layout: LayoutProperties,
+    pub clip_to_bounds: bool,
+    pub render_transform: Transform,
+    pub attached: HashMap<TypeId, Box<Any>>,
+    pub tree: TreeStateWeakBox,
+}
+
+#[derive(Default)]
+struct TreeState {
+    focused: Option<NodeWeakRef>,
+    captured: Option<NodeWeakRef>,
+    hovered: Option<NodeWeakRef>,
+    messages: Vec<(NodeWeakRef, Box<Message>)>,
+    layout_requested: bool,
+    render_requested: bool,
+    render_at: Option<Instant>,
+    layout_rounding: Option<f32>,
+    popups: Vec<Popup>,
+    timers: Vec<Timer>,
+    next_timer: usize,
+    modifiers: Modifiers,
+    waker: Waker,
+}
+
+/// Lets another thread request a layout and render of a tree, e.g. once
+/// work it did for an element is done. Got from `NodeRef::waker`.
+#[derive(Clone, Default)]
+pub struct Waker {
+    requested: Arc<AtomicBool>,
+    wakeup: Arc<Mutex<Option<Box<Fn() + Send>>>>,
+}
+
+impl Waker {
+    pub fn wake(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        if let Some(ref wakeup) = *self.wakeup.lock().unwrap() {
+            wakeup();
+        }
+    }
+}
+
+/// Identifies a timer started with `NodeRef::start_timer`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId(usize);
+
+struct Timer {
+    id: TimerId,
+    node: NodeWeakRef,
+    at: Instant,
+}
+
+/// A node shown over the rest of the tree, outside of its parent's bounds.
+struct Popup {
+    node: NodeRef,
+    owner: NodeWeakRef,
+    /// The rect the popup is placed next to, in window coordinates.
+    anchor: Rect,
+    placement: Placement,
 }
 
 pub struct NodeRef {
@@ -66,6 +167,8 @@ pub struct NodeWeakRef {
 
 pub struct Tree {
     root: Option<NodeRef>,
+    state: TreeStateBox,
+    tooltips: ToolTips,
 }
 
 impl NodeRef {
@@ -76,12 +179,17 @@ impl NodeRef {
         }
     }
 
-    fn new_node(element: Box<Element + 'static>, parrent: Option<NodeWeakRef>) -> NodeRef {
+    fn new_node(element: Box<Element + 'static>, parrent: Option<NodeWeakRef>, tree: TreeStateWeakBox) -> NodeRef {
         NodeRef::new(
             Rc::new(RefCell::new(Node {
                 parrent: parrent,
                 children: Vec::new(),
-                rect: Rect::zero()
+                rect: Rect::zero(),
+                layout: LayoutProperties::default(),
+                clip_to_bounds: false,
+                render_transform: Transform::identity(),
+                attached: HashMap::new(),
+                tree: tree,
             })),
             Rc::new(RefCell::new(element)))
     }
@@ -95,12 +203,35 @@ impl NodeRef {
     }
 
     pub fn add_child(&mut self, child: Box<Element + 'static>) -> NodeRef {
-        let node = NodeRef::new_node(child, Some(self.downgrade()));
+        let tree = self.node.borrow().tree.clone();
+        let node = NodeRef::new_node(child, Some(self.downgrade()), tree);
         self.node.borrow_mut().children.push(node.clone());
         node.init();
         node
     }
 
+    pub fn remove_child(&mut self, child: &NodeRef) {
+        self.node.borrow_mut().children.retain(|c| c != child);
+        child.node.borrow_mut().parrent = None;
+    }
+
+    /// Replaces all children with a node for `child`, returning it. Meant for
+    /// elements that host a single content element.
+    pub fn set_single_child(&mut self, child: Box<Element + 'static>) -> NodeRef {
+        let old: Vec<NodeRef> = self.children().iter().cloned().collect();
+        for node in &old {
+            self.remove_child(node);
+        }
+        self.invalidate_layout();
+        self.add_child(child)
+    }
+
+    /// Attaches a node previously detached with `remove_child`.
+    pub fn append_child(&mut self, child: NodeRef) {
+        child.node.borrow_mut().parrent = Some(self.downgrade());
+        self.node.borrow_mut().children.push(child);
+    }
+
     pub fn parrent(&self) -> Option<NodeRef> {
         self.node.borrow().parrent.as_ref().map(|w| w.upgrade().unwrap())
     }
@@ -113,6 +244,315 @@ impl NodeRef {
         self.node.borrow_mut().rect = rect;
     }
 
+    pub fn layout_properties(&self) -> LayoutProperties {
+        self.node.borrow().layout
+    }
+
+    pub fn set_layout_properties(&self, properties: LayoutProperties) {
+        self.node.borrow_mut().layout = properties;
+    }
+
+    /// The node's rect in window coordinates.
+    pub fn window_rect(&self) -> Rect {
+        self.parrent_to_window().apply_rect(self.rect())
+    }
+
+    /// Maps the node's content coordinates, where its children are laid out, to window coordinates.
+    pub fn content_to_window(&self) -> Transform {
+        let origin = self.rect().pos + self.content_rect().pos;
+        self.parrent_to_window()
+            .then(Transform::translation(origin))
+            .then(self.render_transform())
+    }
+
+    fn parrent_to_window(&self) -> Transform {
+        match self.parrent() {
+            Some(parrent) => parrent.content_to_window(),
+            None => Transform::identity(),
+        }
+    }
+
+    pub fn render_transform(&self) -> Transform {
+        self.node.borrow().render_transform
+    }
+
+    /// Sets a transform applied to the children when drawing and hit testing.
+    /// It doesn't affect layout: children are laid out untransformed and then
+    /// scaled and moved within the content area.
+    pub fn set_render_transform(&self, transform: Transform) {
+        self.node.borrow_mut().render_transform = transform;
+        self.invalidate_render();
+    }
+
+    pub fn clip_to_bounds(&self) -> bool {
+        self.node.borrow().clip_to_bounds
+    }
+
+    /// When set, children are not drawn or hit outside of the node's rect.
+    pub fn set_clip_to_bounds(&self, clip: bool) {
+        self.node.borrow_mut().clip_to_bounds = clip;
+    }
+
+    /// Returns the deepest node under `point`, given in the parent's content coordinates.
+    pub fn hit_test(&self, point: Point) -> Option<NodeRef> {
+        let rect = self.rect();
+        let inside = rect.contains(point);
+        if !inside && self.clip_to_bounds() {
+            return None;
+        }
+        let children_clip = self.element.borrow().children_clip(self.clone());
+        if let Some((clip, radius)) = children_clip {
+            if !clip.contains_rounded(point - rect.pos, radius) {
+                return if inside { Some(self.clone()) } else { None };
+            }
+        }
+        // Children collapsed by a zero scale can't be hit.
+        let local = match self.render_transform().inverse_apply(point - rect.pos - self.content_rect().pos) {
+            Some(local) => local,
+            None => return if inside { Some(self.clone()) } else { None },
+        };
+        for child in self.children().iter().rev() {
+            if let Some(hit) = child.hit_test(local) {
+                return Some(hit);
+            }
+        }
+        if inside { Some(self.clone()) } else { None }
+    }
+
+    fn tree_state(&self) -> Option<TreeStateBox> {
+        self.node.borrow().tree.upgrade()
+    }
+
+    /// Requests a new layout and render pass of the tree.
+    pub fn invalidate_layout(&self) {
+        if let Some(state) = self.tree_state() {
+            let mut state = state.borrow_mut();
+            state.layout_requested = true;
+            state.render_requested = true;
+        }
+    }
+
+    pub fn invalidate_render(&self) {
+        if let Some(state) = self.tree_state() {
+            state.borrow_mut().render_requested = true;
+        }
+    }
+
+    /// Requests a render pass at `time`, for animations such as a blinking caret.
+    pub fn invalidate_render_at(&self, time: Instant) {
+        if let Some(state) = self.tree_state() {
+            let mut state = state.borrow_mut();
+            if state.render_at.map_or(true, |at| time < at) {
+                state.render_at = Some(time);
+            }
+        }
+    }
+
+    pub fn focus(&self) {
+        if let Some(state) = self.tree_state() {
+            let mut state = state.borrow_mut();
+            state.focused = Some(self.downgrade());
+            state.render_requested = true;
+        }
+    }
+
+    /// Whether the cursor is over the node or one of its descendants.
+    pub fn is_hovered(&self) -> bool {
+        let mut node = self.tree_state().and_then(|s| s.borrow().hovered.as_ref().and_then(|h| h.upgrade()));
+        while let Some(n) = node {
+            if n == *self {
+                return true;
+            }
+            node = n.parrent();
+        }
+        false
+    }
+
+    /// Queues a message to bubble up from the node's parent. Messages are
+    /// delivered by `Tree::dispatch_messages`, after the event being handled.
+    pub fn raise(&self, message: Box<Message>) {
+        if let Some(state) = self.tree_state() {
+            state.borrow_mut().messages.push((self.downgrade(), message));
+        }
+    }
+
+    /// Opens `element` in a popup over the whole tree, placed next to `anchor`
+    /// in window coordinates and kept inside the window. The popup isn't
+    /// clipped by the node's ancestors. Events and messages bubbling out of
+    /// it go on to the node. A mouse press outside of the popup and of
+    /// `anchor` closes it.
+    pub fn open_popup(&self, element: Box<Element + 'static>, anchor: Rect, placement: Placement) -> NodeRef {
+        let tree = self.node.borrow().tree.clone();
+        let popup = NodeRef::new_node(element, None, tree);
+        if let Some(state) = self.tree_state() {
+            let mut state = state.borrow_mut();
+            state.popups.push(Popup {
+                node: popup.clone(),
+                owner: self.downgrade(),
+                anchor: anchor,
+                placement: placement,
+            });
+            state.layout_requested = true;
+            state.render_requested = true;
+        }
+        popup.init();
+        popup
+    }
+
+    /// Closes the popup the node is the root of, and the popups opened from
+    /// inside it. Focus inside them goes back to the node that opened it.
+    pub fn close_popup(&self) {
+        let state = match self.tree_state() {
+            Some(state) => state,
+            None => return,
+        };
+        let owner = self.popup_owner();
+        let focused = state.borrow().focused.as_ref().and_then(|f| f.upgrade());
+        let focus_inside = focused.map_or(false, |f| f.is_within(self));
+        let closing: Vec<bool> = state.borrow().popups.iter().map(|p| p.node.is_within(self)).collect();
+        let closed: Vec<Popup> = {
+            let mut state = state.borrow_mut();
+            let popups = mem::replace(&mut state.popups, Vec::new());
+            let (closed, open): (Vec<_>, Vec<_>) = popups.into_iter().zip(closing).partition(|&(_, closing)| closing);
+            state.popups = open.into_iter().map(|(p, _)| p).collect();
+            state.render_requested = true;
+            closed.into_iter().map(|(p, _)| p).collect()
+        };
+        if focus_inside && !closed.is_empty() {
+            if let Some(owner) = owner {
+                owner.focus();
+            }
+        }
+    }
+
+    /// Calls the element's `handle_timer` once, when `delay` has passed.
+    /// Timers are fired by `Tree::fire_timers`, which the window calls from
+    /// its event loop.
+    pub fn start_timer(&self, delay: Duration) -> TimerId {
+        let state = match self.tree_state() {
+            Some(state) => state,
+            None => return TimerId(0),
+        };
+        let mut state = state.borrow_mut();
+        state.next_timer += 1;
+        let id = TimerId(state.next_timer);
+        state.timers.push(Timer {
+            id: id,
+            node: self.downgrade(),
+            at: Instant::now() + delay,
+        });
+        id
+    }
+
+    pub fn cancel_timer(&self, timer: TimerId) {
+        if let Some(state) = self.tree_state() {
+            state.borrow_mut().timers.retain(|t| t.id != timer);
+        }
+    }
+
+    /// Sets the tooltip shown when the cursor rests on the node or its
+    /// descendants without tooltips of their own.
+    pub fn set_tooltip(&self, tooltip: Option<ToolTip>) {
+        self.set_attached(tooltip);
+    }
+
+    pub fn tooltip(&self) -> Option<ToolTip> {
+        self.attached::<Option<ToolTip>>().and_then(|tooltip| tooltip)
+    }
+
+    /// The nearest node on the bubbling chain that carries a tooltip.
+    fn tooltip_owner(&self) -> Option<NodeRef> {
+        let mut node = Some(self.clone());
+        while let Some(n) = node {
+            if n.tooltip().is_some() {
+                return Some(n);
+            }
+            node = n.bubble_parrent();
+        }
+        None
+    }
+
+    /// Whether the node is the root of an open popup.
+    pub fn is_popup(&self) -> bool {
+        self.tree_state().map_or(false, |s| s.borrow().popups.iter().any(|p| p.node == *self))
+    }
+
+    /// The node that opened the popup this node is the root of.
+    fn popup_owner(&self) -> Option<NodeRef> {
+        self.tree_state().and_then(|s| s.borrow().popups.iter()
+            .find(|p| p.node == *self)
+            .and_then(|p| p.owner.upgrade()))
+    }
+
+    /// The parent, or for the root of a popup the node that opened it. Events
+    /// and messages bubble along this chain.
+    fn bubble_parrent(&self) -> Option<NodeRef> {
+        self.parrent().or_else(|| self.popup_owner())
+    }
+
+    /// Whether `ancestor` is the node or on its bubbling chain.
+    fn is_within(&self, ancestor: &NodeRef) -> bool {
+        let mut node = Some(self.clone());
+        while let Some(n) = node {
+            if n == *ancestor {
+                return true;
+            }
+            node = n.bubble_parrent();
+        }
+        false
+    }
+
+    pub fn has_focus(&self) -> bool {
+        self.tree_state()
+            .and_then(|s| s.borrow().focused.as_ref().and_then(|f| f.upgrade()))
+            .map_or(false, |f| f == *self)
+    }
+
+    /// The modifier keys held as of the last key event, for handling clicks
+    /// with modifiers.
+    pub fn modifiers(&self) -> Modifiers {
+        self.tree_state().map_or(Modifiers::none(), |s| s.borrow().modifiers)
+    }
+
+    /// A handle other threads can wake the tree the node is in with.
+    pub fn waker(&self) -> Option<Waker> {
+        self.tree_state().map(|s| s.borrow().waker.clone())
+    }
+
+    /// The focused node of the tree the node is in.
+    pub fn focused(&self) -> Option<NodeRef> {
+        self.tree_state().and_then(|s| s.borrow().focused.as_ref().and_then(|f| f.upgrade()))
+    }
+
+    /// Delivers an event given in window coordinates to the node's element.
+    pub fn handle_event(&self, event: &InputEvent) -> bool {
+        let to_window = self.parrent_to_window();
+        if event.is_mouse() && !to_window.is_invertible() {
+            return false;
+        }
+        let origin = self.rect().pos;
+        let event = event.map_position(|pos| to_window.inverse_apply(pos).unwrap() - origin);
+        self.element.borrow_mut().handle_event(self.clone(), &event)
+    }
+
+    /// The rect children are laid out in, relative to the node's own rect.
+    pub fn content_rect(&self) -> Rect {
+        let node = self.node.borrow();
+        node.layout.content_rect(node.rect.size)
+    }
+
+    /// Attaches a value to the node, replacing any previous value of the same type.
+    /// Containers use this to store per-child settings on the child nodes.
+    pub fn set_attached<T: Any>(&self, value: T) {
+        self.node.borrow_mut().attached.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    pub fn attached<T: Any + Clone>(&self) -> Option<T> {
+        self.node.borrow().attached.get(&TypeId::of::<T>())
+            .and_then(|a| a.downcast_ref::<T>())
+            .cloned()
+    }
+
     pub fn element(&self) -> Ref<Element + 'static> {
         Ref::map(self.element.borrow(), |b| &**b)
     }
@@ -130,6 +570,20 @@ impl NodeRef {
         }
     }
 
+    /// Like `cast_element`, but `None` as well while the element is mutably
+    /// borrowed, such as while it handles an event.
+    pub fn try_cast_element<T: Any>(&self) -> Option<Ref<T>> {
+        let element = match self.element.try_borrow() {
+            Ok(element) => Ref::map(element, |b| &**b),
+            Err(_) => return None,
+        };
+        if element.can_cast::<T>() {
+            Some(Ref::map(element, |e| e.cast_element_ref::<T>().unwrap()))
+        } else {
+            None
+        }
+    }
+
     pub fn cast_element_mut<T: Any>(&self) -> Option<RefMut<T>> {
         let element = RefMut::map(self.element.borrow_mut(), |b| &mut **b);
         if element.can_cast::<T>() {
@@ -144,22 +598,79 @@ impl NodeRef {
     }
 
     pub fn render(&self, renderer: &mut Renderer) {
-        renderer.push_rect(self.node.borrow().rect);
+        renderer.push_rect(self.rect());
         renderer.execute(self.element.borrow().render(self.clone()));
 
-        for child in self.children().iter() {
-            child.render(renderer);
+        let clip = self.clip_to_bounds();
+        if clip {
+            renderer.push_clip(Rect::from_size(self.rect().size));
+        }
+        let children_clip = self.element.borrow().children_clip(self.clone());
+        if let Some((rect, radius)) = children_clip {
+            renderer.push_rounded_clip(rect, radius);
+        }
+        renderer.push_rect(self.content_rect());
+        let transform = self.render_transform();
+        // A zero scale leaves nothing of the children to draw.
+        if transform.is_invertible() {
+            if !transform.is_identity() {
+                renderer.push_transform(transform);
+            }
+            for child in self.children().iter() {
+                child.render(renderer);
+            }
+            if !transform.is_identity() {
+                renderer.pop_transform();
+            }
+        }
+        renderer.pop_rect();
+        if children_clip.is_some() {
+            renderer.pop_rounded_clip();
+        }
+        if clip {
+            renderer.pop_clip();
         }
 
         renderer.pop_rect();
     }
 
     pub fn layout(&self, container: Size) {
-        self.element.borrow_mut().layout(self.clone(), container);
+        self.arrange(Rect::from_size(container));
+    }
+
+    /// Lays the node out into `slot`, given in the parent's content coordinates.
+    /// Margin, alignment and size limits are applied here; the element itself
+    /// is laid out into the area left after padding.
+    pub fn arrange(&self, slot: Rect) {
+        let properties = self.layout_properties();
+        let desired = match (properties.horizontal_alignment, properties.vertical_alignment) {
+            (Alignment::Stretch, Alignment::Stretch) => Size::zero(),
+            _ => self.measure(),
+        };
+        self.place(properties.arrange(slot, desired));
+    }
+
+    /// Lays the node out with exactly `rect` as its rect, given in the parent's
+    /// content coordinates, for containers that compute the rect themselves.
+    pub fn place(&self, rect: Rect) {
+        let mut rect = rect;
+        let rounding = self.tree_state().and_then(|s| s.borrow().layout_rounding);
+        if let Some(scale_factor) = rounding {
+            // Under a scaling render transform pixels don't line up with layout units.
+            let to_window = self.parrent_to_window();
+            if to_window.is_translation() {
+                rect = snap_to_pixels(rect, to_window.offset, scale_factor);
+            }
+        }
+        self.set_rect(rect);
+        let content = self.content_rect();
+        self.element.borrow_mut().layout(self.clone(), content.size);
     }
 
+    /// The size the node wants from its parent, margins included.
     pub fn measure(&self) -> Size {
-        self.element.borrow().measure(self.clone())
+        let content = self.element.borrow().measure(self.clone());
+        self.layout_properties().measure(content)
     }
 }
 
@@ -208,7 +719,11 @@ impl Clone for NodeWeakRef {
 
 impl Tree {
     pub fn new() -> Tree {
-        Tree { root: None }
+        Tree {
+            root: None,
+            state: Rc::new(RefCell::new(TreeState::default())),
+            tooltips: ToolTips::new(),
+        }
     }
 
     pub fn root(&self) -> Option<NodeRef> {
@@ -216,17 +731,333 @@ impl Tree {
     }
 
     pub fn set_root(&mut self, element: Option<Box<Element>>) -> Option<NodeRef> {
-        self.root = element.map(|e| NodeRef::new_node(e, None));
+        let tree = Rc::downgrade(&self.state);
+        self.root = element.map(|e| NodeRef::new_node(e, None, tree));
         if let Some(root) = self.root.as_ref() {
             root.init();
         }
         self.root.clone()
     }
+
+    /// Makes layout snap node edges to physical pixels, given the number of
+    /// pixels per layout unit, or turns snapping off with `None`.
+    pub fn set_layout_rounding(&mut self, scale_factor: Option<f32>) {
+        let mut state = self.state.borrow_mut();
+        state.layout_rounding = scale_factor;
+        state.layout_requested = true;
+    }
+
+    pub fn layout_rounding(&self) -> Option<f32> {
+        self.state.borrow().layout_rounding
+    }
+
+    /// Forgets the held modifier keys when the window loses focus, since
+    /// their releases go to another window.
+    pub fn focus_lost(&mut self) {
+        self.state.borrow_mut().modifiers = Modifiers::none();
+    }
+
+    /// The open popups, from the bottom to the top one.
+    pub fn popups(&self) -> Vec<NodeRef> {
+        self.state.borrow().popups.iter().map(|p| p.node.clone()).collect()
+    }
+
+    /// Lays the root out to fill `size`, then the popups at their anchors.
+    pub fn layout(&self, size: Size) {
+        if let Some(root) = self.root() {
+            root.layout(size);
+        }
+        let popups: Vec<(NodeRef, Rect, Placement)> = self.state.borrow().popups.iter()
+            .map(|p| (p.node.clone(), p.anchor, p.placement))
+            .collect();
+        for (popup, anchor, placement) in popups {
+            let rect = place_popup(anchor, popup.measure(), placement, Rect::from_size(size));
+            popup.arrange(rect);
+        }
+    }
+
+    pub fn focused(&self) -> Option<NodeRef> {
+        self.state.borrow().focused.as_ref().and_then(|f| f.upgrade())
+    }
+
+    /// The deepest node under the cursor.
+    pub fn hovered(&self) -> Option<NodeRef> {
+        self.state.borrow().hovered.as_ref().and_then(|h| h.upgrade())
+    }
+
+    /// Sets what wakes the event loop up when a `Waker` of the tree is
+    /// woken. It is called on the waking thread.
+    pub fn set_wakeup<F: Fn() + Send + 'static>(&mut self, wakeup: F) {
+        *self.state.borrow().waker.wakeup.lock().unwrap() = Some(Box::new(wakeup));
+    }
+
+    /// Returns and clears the pending (layout, render) requests. A render
+    /// requested for a time that has come counts as pending, and a `Waker`
+    /// woken since the last call requests both.
+    pub fn take_invalidation(&self) -> (bool, bool) {
+        let mut state = self.state.borrow_mut();
+        if state.waker.requested.swap(false, Ordering::SeqCst) {
+            state.layout_requested = true;
+            state.render_requested = true;
+        }
+        if state.render_at.map_or(false, |at| at <= Instant::now()) {
+            state.render_at = None;
+            state.render_requested = true;
+        }
+        let requests = (state.layout_requested, state.render_requested);
+        state.layout_requested = false;
+        state.render_requested = false;
+        requests
+    }
+
+    /// When the earliest render requested with `invalidate_render_at` is due.
+    pub fn next_render_time(&self) -> Option<Instant> {
+        self.state.borrow().render_at
+    }
+
+    /// When the earliest timer, or the tooltip waiting to be shown, is due.
+    pub fn next_timer_time(&self) -> Option<Instant> {
+        let timers = self.state.borrow().timers.iter().map(|t| t.at).min();
+        match (timers, self.tooltips.due()) {
+            (Some(timer), Some(tooltip)) => Some(timer.min(tooltip)),
+            (timer, tooltip) => timer.or(tooltip),
+        }
+    }
+
+    /// Fires the timers due at `now`, and shows the tooltip if it is due.
+    pub fn fire_timers(&mut self, now: Instant) {
+        let due: Vec<Timer> = {
+            let mut state = self.state.borrow_mut();
+            let timers = mem::replace(&mut state.timers, Vec::new());
+            let (due, pending): (Vec<_>, Vec<_>) = timers.into_iter().partition(|t| t.at <= now);
+            state.timers = pending;
+            due
+        };
+        for timer in due {
+            if let Some(node) = timer.node.upgrade() {
+                node.element_mut().handle_timer(node.clone(), timer.id);
+            }
+        }
+        self.tooltips.fire(now);
+        self.dispatch_messages();
+    }
+
+    /// Takes a layout request made during layout, leaving render requests pending.
+    pub fn take_layout_request(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        let requested = state.layout_requested;
+        state.layout_requested = false;
+        requested
+    }
+
+    /// Routes an event given in window coordinates and returns whether it was handled.
+    /// Mouse events go to the node under the cursor, or to the node that handled
+    /// the last mouse press until the button is released. Keyboard events go to
+    /// the focused node. Either way the event bubbles up until a node handles it.
+    pub fn dispatch_event(&mut self, event: &InputEvent) -> bool {
+        let root = match self.root() {
+            Some(root) => root,
+            None => return false,
+        };
+
+        match *event {
+            InputEvent::MouseMove(pos) => {
+                let hovered = self.hit_test(&root, pos);
+                let owner = hovered.as_ref().and_then(|h| h.tooltip_owner());
+                self.update_hover(hovered);
+                self.tooltips.cursor_moved(owner, pos, Instant::now());
+            }
+            InputEvent::MouseLeave => {
+                self.update_hover(None);
+                self.tooltips.cursor_left();
+            }
+            InputEvent::MouseDown(..) | InputEvent::MouseWheel(..) => self.tooltips.dismiss(),
+            InputEvent::KeyDown(_, modifiers) => {
+                self.tooltips.dismiss();
+                self.state.borrow_mut().modifiers = modifiers;
+            }
+            InputEvent::KeyUp(_, modifiers) => self.state.borrow_mut().modifiers = modifiers,
+            _ => {}
+        }
+
+        let captured = self.state.borrow().captured.as_ref().and_then(|c| c.upgrade());
+        let target = match *event {
+            InputEvent::MouseDown(_, pos) | InputEvent::MouseWheel(_, pos) => self.hit_test(&root, pos),
+            InputEvent::MouseMove(pos) | InputEvent::MouseUp(_, pos) => captured.or_else(|| self.hit_test(&root, pos)),
+            InputEvent::KeyDown(..) | InputEvent::KeyUp(..) | InputEvent::Char(_) => self.focused().or(Some(root)),
+            InputEvent::MouseEnter | InputEvent::MouseLeave => None,
+        };
+        if let InputEvent::MouseDown(_, pos) = *event {
+            self.dismiss_popups(target.as_ref(), pos);
+        }
+        let target = match target {
+            Some(target) => target,
+            None => return false,
+        };
+
+        if let InputEvent::MouseDown(..) = *event {
+            let mut node = Some(target.clone());
+            while let Some(n) = node {
+                if n.element().focusable() {
+                    n.focus();
+                    break;
+                }
+                node = n.bubble_parrent();
+            }
+        }
+
+        let handled_by = Tree::bubble(target, event);
+
+        match *event {
+            InputEvent::MouseDown(..) => self.state.borrow_mut().captured = handled_by.as_ref().map(|n| n.downgrade()),
+            InputEvent::MouseUp(..) => self.state.borrow_mut().captured = None,
+            _ => {}
+        }
+
+        self.dispatch_messages();
+        handled_by.is_some()
+    }
+
+    /// Delivers the messages raised so far, each bubbling from the parent of
+    /// the node that raised it until a node handles it.
+    pub fn dispatch_messages(&mut self) {
+        loop {
+            let messages = mem::replace(&mut self.state.borrow_mut().messages, Vec::new());
+            if messages.is_empty() {
+                break;
+            }
+            for (source, message) in messages {
+                let source = match source.upgrade() {
+                    Some(source) => source,
+                    None => continue,
+                };
+                let mut node = source.bubble_parrent();
+                while let Some(n) = node {
+                    if n.element_mut().handle_message(n.clone(), source.clone(), &*message) {
+                        break;
+                    }
+                    node = n.bubble_parrent();
+                }
+            }
+        }
+    }
+
+    /// Sends `MouseLeave` to the nodes the cursor left, deepest first, and
+    /// `MouseEnter` to the ones it entered, outermost first.
+    fn update_hover(&mut self, hovered: Option<NodeRef>) {
+        let previous = self.hovered();
+        if previous == hovered {
+            return;
+        }
+        self.state.borrow_mut().hovered = hovered.as_ref().map(|h| h.downgrade());
+
+        let ancestors = |node: Option<NodeRef>| {
+            let mut chain = Vec::new();
+            let mut node = node;
+            while let Some(n) = node {
+                node = n.parrent();
+                chain.push(n);
+            }
+            chain
+        };
+        let left = ancestors(previous);
+        let entered = ancestors(hovered);
+        for node in left.iter().filter(|n| !entered.contains(n)) {
+            node.handle_event(&InputEvent::MouseLeave);
+        }
+        for node in entered.iter().rev().filter(|n| !left.contains(n)) {
+            node.handle_event(&InputEvent::MouseEnter);
+        }
+    }
+
+    /// The deepest node under `pos`, in the top popup containing it or else under the root.
+    fn hit_test(&self, root: &NodeRef, pos: Point) -> Option<NodeRef> {
+        self.popups().iter().rev()
+            .filter_map(|popup| popup.hit_test(pos))
+            .next()
+            .or_else(|| root.hit_test(pos))
+    }
+
+    /// Closes the popups a mouse press at `pos` on `target` is outside of.
+    fn dismiss_popups(&mut self, target: Option<&NodeRef>, pos: Point) {
+        let dismissed: Vec<NodeRef> = self.state.borrow().popups.iter()
+            .filter(|p| !p.anchor.contains(pos) && !target.map_or(false, |t| t.is_within(&p.node)))
+            .map(|p| p.node.clone())
+            .collect();
+        for popup in dismissed {
+            popup.close_popup();
+        }
+    }
+
+    fn bubble(target: NodeRef, event: &InputEvent) -> Option<NodeRef> {
+        let mut node = Some(target);
+        while let Some(n) = node {
+            if n.handle_event(event) {
+                return Some(n);
+            }
+            node = n.bubble_parrent();
+        }
+        None
+    }
+}
+
+/// Elements shared by the tests of the tree and of the elements.
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+
+    /// A leaf measuring as a fixed size. It records the container it was
+    /// laid out in and where it was last pressed.
+    pub struct SizedElement {
+        pub size: Size,
+        pub container: Option<Size>,
+        pub pressed_at: Option<Point>,
+    }
+
+    impl SizedElement {
+        pub fn new(size: Size) -> SizedElement {
+            SizedElement {
+                size: size,
+                container: None,
+                pressed_at: None,
+            }
+        }
+    }
+
+    impl Element for SizedElement {
+        fn init(&mut self, node: NodeRef) {
+
+        }
+
+        fn measure(&self, node: NodeRef) -> Size {
+            self.size
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+            self.container = Some(container);
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+            match *event {
+                InputEvent::MouseDown(_, pos) => {
+                    self.pressed_at = Some(pos);
+                    true
+                }
+                _ => false,
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::testing::SizedElement;
+    use layout::*;
 
     #[derive(Copy, Clone, Eq, PartialEq)]
     struct StubElement;
@@ -249,6 +1080,94 @@ mod tests {
         }
     }
 
+    #[derive(Default)]
+    struct Recorder {
+        crossings: Vec<InputEvent>,
+        messages: usize,
+        handles_messages: bool,
+        timers: Vec<TimerId>,
+    }
+
+    struct Ping;
+
+    impl Message for Ping {}
+
+    impl Element for Recorder {
+        fn init(&mut self, node: NodeRef) {
+
+        }
+
+        fn measure(&self, node: NodeRef) -> Size {
+            Size::zero()
+        }
+
+        fn layout(&mut self, node: NodeRef, container: Size) {
+
+        }
+
+        fn render(&self, node: NodeRef) -> RenderCommandList {
+            RenderCommandList::new()
+        }
+
+        fn handle_event(&mut self, node: NodeRef, event: &InputEvent) -> bool {
+            if let InputEvent::MouseEnter | InputEvent::MouseLeave = *event {
+                self.crossings.push(*event);
+            }
+            false
+        }
+
+        fn handle_message(&mut self, node: NodeRef, source: NodeRef, message: &Message) -> bool {
+            if message.cast_ref::<Ping>().is_some() {
+                self.messages += 1;
+            }
+            self.handles_messages
+        }
+
+        fn handle_timer(&mut self, node: NodeRef, timer: TimerId) {
+            self.timers.push(timer);
+        }
+    }
+
+    fn crossings(node: &NodeRef) -> Vec<InputEvent> {
+        node.cast_element::<Recorder>().unwrap().crossings.clone()
+    }
+
+    #[test]
+    fn wakers_request_layout_from_other_threads() {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(StubElement))).unwrap();
+        let (sender, woken) = ::std::sync::mpsc::channel();
+        tree.set_wakeup(move || sender.send(()).unwrap());
+        tree.take_invalidation();
+
+        let waker = root.waker().unwrap();
+        ::std::thread::spawn(move || waker.wake()).join().unwrap();
+
+        woken.recv().unwrap();
+        assert_eq!(tree.take_invalidation(), (true, true));
+        assert_eq!(tree.take_invalidation(), (false, false));
+    }
+
+    #[test]
+    fn timers_fire_once_when_due() {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(Recorder::default()))).unwrap();
+        let start = Instant::now();
+        let late = root.start_timer(Duration::from_millis(50));
+        let soon = root.start_timer(Duration::from_millis(10));
+        let cancelled = root.start_timer(Duration::from_millis(10));
+        root.cancel_timer(cancelled);
+
+        tree.fire_timers(start);
+        assert!(tree.next_timer_time().unwrap() >= start + Duration::from_millis(10));
+        tree.fire_timers(start + Duration::from_millis(30));
+        assert_eq!(root.cast_element::<Recorder>().unwrap().timers, vec![soon]);
+        tree.fire_timers(start + Duration::from_millis(100));
+        tree.fire_timers(start + Duration::from_millis(200));
+        assert_eq!(root.cast_element::<Recorder>().unwrap().timers, vec![soon, late]);
+        assert_eq!(tree.next_timer_time(), None);
+    }
+
     #[test]
     fn new_tree_has_empty_root() {
         let tree = Tree::new();
@@ -305,4 +1224,147 @@ mod tests {
             assert!(child1.cast_element::<StubElement>().is_some());
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn set_single_child_replaces_children() {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(StubElement))).unwrap();
+        let old = root.add_child(Box::new(StubElement));
+        root.add_child(Box::new(StubElement));
+
+        let new = root.set_single_child(Box::new(StubElement));
+
+        assert_eq!(root.children().len(), 1);
+        assert!(root.children()[0] == new);
+        assert!(old.parrent().is_none());
+    }
+
+    #[test]
+    fn attached_value_roundtrip() {
+        let mut tree = Tree::new();
+        tree.set_root(Some(Box::new(StubElement)));
+        let root = tree.root().unwrap();
+
+        assert_eq!(root.attached::<u32>(), None);
+
+        root.set_attached(3u32);
+        root.set_attached(5i64);
+
+        assert_eq!(root.attached::<u32>(), Some(3));
+        assert_eq!(root.attached::<i64>(), Some(5));
+
+        root.set_attached(4u32);
+
+        assert_eq!(root.attached::<u32>(), Some(4));
+    }
+
+    #[test]
+    fn measure_and_arrange_apply_layout_properties() {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(SizedElement::new(Size::new(10.0, 20.0))))).unwrap();
+        root.set_layout_properties(LayoutProperties::new()
+            .with_margin(Thickness::uniform(1.0))
+            .with_padding(Thickness::uniform(2.0))
+            .with_alignment(Alignment::Start, Alignment::Center));
+
+        assert_eq!(root.measure().to_tuple(), (16.0, 26.0));
+
+        root.arrange(Rect::pos_size(Point::new(5.0, 5.0), Size::new(100.0, 100.0)));
+
+        assert_eq!(root.rect().to_pos_size_tuple(), ((6.0, 43.0), (14.0, 24.0)));
+        assert_eq!(root.content_rect().to_pos_size_tuple(), ((2.0, 2.0), (10.0, 20.0)));
+        assert_eq!(root.cast_element::<SizedElement>().unwrap().container.unwrap().to_tuple(), (10.0, 20.0));
+    }
+
+    #[test]
+    fn layout_rounding_snaps_rects_in_window_coordinates() {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(StubElement))).unwrap();
+        root.set_layout_properties(LayoutProperties::new().with_padding(Thickness::uniform(0.3)));
+        let child = root.add_child(Box::new(SizedElement::new(Size::zero())));
+        tree.set_layout_rounding(Some(2.0));
+
+        root.layout(Size::new(10.2, 10.2));
+        child.arrange(Rect::pos_size(Point::new(1.0, 1.0), Size::new(3.3, 3.3)));
+
+        assert_eq!(root.rect().to_pos_size_tuple(), ((0.0, 0.0), (10.0, 10.0)));
+        assert_eq!(child.window_rect().to_pos_size_tuple(), ((1.5, 1.5), (3.0, 3.0)));
+        assert_eq!(child.cast_element::<SizedElement>().unwrap().container.unwrap().to_tuple(), (3.0, 3.0));
+    }
+
+    #[test]
+    fn mouse_moves_send_enter_and_leave() {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Recorder::default()))).unwrap();
+        let child = root.add_child(Box::new(Recorder::default()));
+        root.place(Rect::from_size(Size::new(100.0, 100.0)));
+        child.place(Rect::pos_size(Point::new(10.0, 10.0), Size::new(20.0, 20.0)));
+
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(5.0, 5.0)));
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(15.0, 15.0)));
+
+        assert!(child.is_hovered() && root.is_hovered());
+        assert!(tree.hovered().unwrap() == child);
+
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(50.0, 50.0)));
+
+        assert_eq!(crossings(&root), vec![InputEvent::MouseEnter]);
+        assert_eq!(crossings(&child), vec![InputEvent::MouseEnter, InputEvent::MouseLeave]);
+        assert!(!child.is_hovered());
+    }
+
+    #[test]
+    fn raised_messages_bubble_until_handled() {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Recorder::default()))).unwrap();
+        let mut middle = root.add_child(Box::new(Recorder { handles_messages: true, ..Recorder::default() }));
+        let leaf = middle.add_child(Box::new(Recorder::default()));
+
+        leaf.raise(Box::new(Ping));
+        leaf.raise(Box::new(Ping));
+        assert_eq!(middle.cast_element::<Recorder>().unwrap().messages, 0);
+
+        tree.dispatch_messages();
+
+        assert_eq!(leaf.cast_element::<Recorder>().unwrap().messages, 0);
+        assert_eq!(middle.cast_element::<Recorder>().unwrap().messages, 2);
+        assert_eq!(root.cast_element::<Recorder>().unwrap().messages, 0);
+    }
+
+    #[test]
+    fn popups_are_hit_first_and_bubble_to_their_owner() {
+        let mut tree = Tree::new();
+        let mut root = tree.set_root(Some(Box::new(Recorder { handles_messages: true, ..Recorder::default() }))).unwrap();
+        let owner = root.add_child(Box::new(Recorder::default()));
+        let anchor = Rect::pos_size(Point::new(10.0, 10.0), Size::new(20.0, 20.0));
+        let popup = owner.open_popup(Box::new(SizedElement::new(Size::new(40.0, 30.0))), anchor, Placement::Below);
+        tree.layout(Size::new(100.0, 100.0));
+
+        assert_eq!(popup.window_rect().to_pos_size_tuple(), ((10.0, 30.0), (40.0, 30.0)));
+        tree.dispatch_event(&InputEvent::MouseMove(Point::new(20.0, 40.0)));
+        assert!(tree.hovered().unwrap() == popup);
+        popup.raise(Box::new(Ping));
+        tree.dispatch_messages();
+        assert_eq!(owner.cast_element::<Recorder>().unwrap().messages, 1);
+        assert_eq!(root.cast_element::<Recorder>().unwrap().messages, 1);
+
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(20.0, 40.0)));
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(15.0, 15.0)));
+        assert!(popup.is_popup());
+        tree.dispatch_event(&InputEvent::MouseDown(MouseButton::Left, Point::new(90.0, 90.0)));
+        assert!(tree.popups().is_empty());
+    }
+
+    #[test]
+    fn losing_focus_forgets_held_modifiers() {
+        let mut tree = Tree::new();
+        let root = tree.set_root(Some(Box::new(Recorder::default()))).unwrap();
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::none() };
+
+        tree.dispatch_event(&InputEvent::KeyDown(Key::Other, ctrl));
+        assert_eq!(root.modifiers(), ctrl);
+
+        tree.focus_lost();
+        assert_eq!(root.modifiers(), Modifiers::none());
+    }
+}
@@ -4,30 +4,53 @@ use glium::glutin;
 use primitives::*;
 use tree::*; 
 use render::*;
+use input::*;
+use text::fonts::FontManager;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::Instant;
+
+const MAX_LAYOUT_PASSES: usize = 3;
 
 pub struct Window {
     display: glium::Display,
     rendering_context: RenderingContext,
     tree: Tree,
     size: Size,
+    scale_factor: f32,
     title: String,
+    cursor: Point,
+    modifiers: Modifiers,
 }
 
 pub struct WindowBuilder {
     size: Size,
     title: String,
+    layout_rounding: bool,
     content: Option<Box<Element>>,
+    fonts: Rc<RefCell<FontManager>>,
 }
 
 impl Window {
-    fn new(display: glium::Display, size: Size, title: String) -> Window {
-        let rendering_context = RenderingContext::new(&display);
+    fn new(display: glium::Display, size: Size, title: String, fonts: Rc<RefCell<FontManager>>) -> Window {
+        let rendering_context = RenderingContext::new(&display, fonts);
+        let (scale_factor, size) = {
+            let window = display.get_window().unwrap();
+            let scale_factor = window.hidpi_factor();
+            let size = window.get_inner_size_pixels()
+                .map_or(size, |(w, h)| Size::new(w as f32 / scale_factor, h as f32 / scale_factor));
+            (scale_factor, size)
+        };
         Window { 
             display: display,
             rendering_context: rendering_context,
             tree: Tree::new(),
             size: size,
+            scale_factor: scale_factor,
             title: title,
+            cursor: Point::zero(),
+            modifiers: Modifiers::none(),
         }
     }
 
@@ -35,14 +58,28 @@ impl Window {
         self.tree.set_root(Some(element)).unwrap()
     }
 
+    pub fn fonts(&self) -> Rc<RefCell<FontManager>> {
+        self.rendering_context.fonts()
+    }
+
+    /// Turns snapping of node rects to physical pixels on or off.
+    pub fn set_layout_rounding(&mut self, enabled: bool) {
+        let scale_factor = self.scale_factor;
+        self.tree.set_layout_rounding(if enabled { Some(scale_factor) } else { None });
+    }
+
     pub fn run_loop(&mut self) {
         use glium::glutin::Event::*;
 
         let proxy = self.display.get_window().unwrap().create_window_proxy();
+        let wakeup_proxy = proxy.clone();
+        self.tree.set_wakeup(move || wakeup_proxy.wakeup_event_loop());
 
         self.layout_content();
         self.render();
-        
+        let mut wakeup_at = None;
+        self.schedule_wakeup(&proxy, &mut wakeup_at);
+
         'main: loop {
             let (mut render, mut layout, mut wakeup_scheduled) = (false, false, false);
             let mut events_recieved = 0u32;
@@ -64,7 +101,15 @@ impl Window {
                         }
                     },
                     Resized(w, h) => {
-                        self.size = Size::new(w as f32, h as f32);
+                        // The scale factor changes when the window moves to a monitor with another DPI.
+                        let scale_factor = self.display.get_window().map_or(self.scale_factor, |w| w.hidpi_factor());
+                        if scale_factor != self.scale_factor {
+                            self.scale_factor = scale_factor;
+                            if self.tree.layout_rounding().is_some() {
+                                self.tree.set_layout_rounding(Some(scale_factor));
+                            }
+                        }
+                        self.size = Size::new(w as f32 / scale_factor, h as f32 / scale_factor);
                         render = true;
                         layout = true;
                         if !wakeup_scheduled {
@@ -72,37 +117,180 @@ impl Window {
                             proxy.wakeup_event_loop();
                         }      
                     }
-                    _ => {},
+                    Focused(false) => {
+                        // Keys released in another window never send their key up here.
+                        self.modifiers = Modifiers::none();
+                        self.tree.focus_lost();
+                    }
+                    other => {
+                        if let Some(input) = translate_event(other, self.scale_factor, &mut self.cursor, &mut self.modifiers) {
+                            self.tree.dispatch_event(&input);
+                            if !wakeup_scheduled {
+                                wakeup_scheduled = true;
+                                proxy.wakeup_event_loop();
+                            }
+                        }
+                    }
                 }
             }
 
             info!("[Window] Events recieved: {}", events_recieved);
 
+            self.tree.fire_timers(Instant::now());
+            let (layout_requested, render_requested) = self.tree.take_invalidation();
+            layout = layout || layout_requested;
+            render = render || render_requested;
+
             if layout {
                 self.layout_content();
             }
             if render {
                 self.render();
             }
+            self.schedule_wakeup(&proxy, &mut wakeup_at);
         }
     }
 
+    /// Wakes the event loop up when a render requested for a later time or a
+    /// timer is due. `scheduled` is the time of the last wakeup scheduled.
+    fn schedule_wakeup(&self, proxy: &glutin::WindowProxy, scheduled: &mut Option<Instant>) {
+        let at = match (self.tree.next_render_time(), self.tree.next_timer_time()) {
+            (Some(render), Some(timer)) => render.min(timer),
+            (Some(at), None) | (None, Some(at)) => at,
+            (None, None) => return,
+        };
+        let now = Instant::now();
+        if scheduled.map_or(false, |scheduled| scheduled <= at && scheduled > now) {
+            return;
+        }
+        *scheduled = Some(at);
+        let proxy = proxy.clone();
+        thread::spawn(move || {
+            let now = Instant::now();
+            if at > now {
+                thread::sleep(at - now);
+            }
+            proxy.wakeup_event_loop();
+        });
+    }
+
     fn render(&self) {
         use glium::Surface;
         info!("[Window] Render");
         if let Some(root) = self.tree.root() {
             let mut suface = self.display.draw();
-            suface.clear_color(1.0, 1.0, 1.0, 1.0);
-            root.render(&mut Renderer::new(&mut suface, &self.rendering_context, self.size, Rect::from_size(self.size)));
+            suface.clear_color_and_stencil((1.0, 1.0, 1.0, 1.0), 0);
+            {
+                let mut renderer = Renderer::new(&mut suface, &self.rendering_context, self.size, self.scale_factor, Rect::from_size(self.size));
+                root.render(&mut renderer);
+                for popup in self.tree.popups() {
+                    popup.render(&mut renderer);
+                }
+            }
             suface.finish().unwrap();
         }
     }
 
     fn layout_content(&self) {
         info!("[Window] Layout");
-        if let Some(mut root) = self.tree.root() {
-            root.layout(self.size);
+        // Elements whose measured size depends on the width they get, like
+        // wrapped text, ask for another pass once they know it.
+        for _ in 0..MAX_LAYOUT_PASSES {
+            self.tree.layout(self.size);
+            if !self.tree.take_layout_request() {
+                break;
+            }
+        }
+    }
+}
+
+/// Translates a glutin event, tracking the cursor position and the held
+/// modifier keys that glutin reports separately. Positions in physical
+/// pixels are converted to layout units.
+fn translate_event(event: glutin::Event, scale_factor: f32, cursor: &mut Point, modifiers: &mut Modifiers) -> Option<InputEvent> {
+    use glium::glutin::Event::*;
+    use glium::glutin::{ElementState, MouseScrollDelta, VirtualKeyCode};
+
+    const LINE_HEIGHT: f32 = 20.0;
+
+    match event {
+        MouseMoved(x, y) => {
+            *cursor = Point::new(x as f32 / scale_factor, y as f32 / scale_factor);
+            Some(InputEvent::MouseMove(*cursor))
+        }
+        MouseInput(state, button) => {
+            let button = match button {
+                glutin::MouseButton::Left => MouseButton::Left,
+                glutin::MouseButton::Right => MouseButton::Right,
+                glutin::MouseButton::Middle => MouseButton::Middle,
+                glutin::MouseButton::Other(n) => MouseButton::Other(n),
+            };
+            match state {
+                ElementState::Pressed => Some(InputEvent::MouseDown(button, *cursor)),
+                ElementState::Released => Some(InputEvent::MouseUp(button, *cursor)),
+            }
+        }
+        MouseWheel(delta, _) => {
+            let delta = match delta {
+                MouseScrollDelta::LineDelta(x, y) => Point::new(x * LINE_HEIGHT, y * LINE_HEIGHT),
+                MouseScrollDelta::PixelDelta(x, y) => Point::new(x / scale_factor, y / scale_factor),
+            };
+            Some(InputEvent::MouseWheel(delta, *cursor))
+        }
+        KeyboardInput(state, _, Some(code)) => {
+            let pressed = state == ElementState::Pressed;
+            match code {
+                VirtualKeyCode::LShift | VirtualKeyCode::RShift => modifiers.shift = pressed,
+                VirtualKeyCode::LControl | VirtualKeyCode::RControl => modifiers.ctrl = pressed,
+                VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => modifiers.alt = pressed,
+                VirtualKeyCode::LWin | VirtualKeyCode::RWin => modifiers.logo = pressed,
+                _ => {}
+            }
+            let key = translate_key(code);
+            if pressed {
+                Some(InputEvent::KeyDown(key, *modifiers))
+            } else {
+                Some(InputEvent::KeyUp(key, *modifiers))
+            }
         }
+        MouseLeft => Some(InputEvent::MouseLeave),
+        ReceivedCharacter(c) if !c.is_control() => Some(InputEvent::Char(c)),
+        _ => None,
+    }
+}
+
+fn translate_key(code: glutin::VirtualKeyCode) -> Key {
+    use glium::glutin::VirtualKeyCode::*;
+
+    match code {
+        A => Key::Letter('a'), B => Key::Letter('b'), C => Key::Letter('c'), D => Key::Letter('d'),
+        E => Key::Letter('e'), F => Key::Letter('f'), G => Key::Letter('g'), H => Key::Letter('h'),
+        I => Key::Letter('i'), J => Key::Letter('j'), K => Key::Letter('k'), L => Key::Letter('l'),
+        M => Key::Letter('m'), N => Key::Letter('n'), O => Key::Letter('o'), P => Key::Letter('p'),
+        Q => Key::Letter('q'), R => Key::Letter('r'), S => Key::Letter('s'), T => Key::Letter('t'),
+        U => Key::Letter('u'), V => Key::Letter('v'), W => Key::Letter('w'), X => Key::Letter('x'),
+        Y => Key::Letter('y'), Z => Key::Letter('z'),
+        Key0 => Key::Digit(0), Key1 => Key::Digit(1), Key2 => Key::Digit(2), Key3 => Key::Digit(3),
+        Key4 => Key::Digit(4), Key5 => Key::Digit(5), Key6 => Key::Digit(6), Key7 => Key::Digit(7),
+        Key8 => Key::Digit(8), Key9 => Key::Digit(9),
+        F1 => Key::F(1), F2 => Key::F(2), F3 => Key::F(3), F4 => Key::F(4), F5 => Key::F(5), F6 => Key::F(6),
+        F7 => Key::F(7), F8 => Key::F(8), F9 => Key::F(9), F10 => Key::F(10), F11 => Key::F(11), F12 => Key::F(12),
+        Left => Key::Left,
+        Right => Key::Right,
+        Up => Key::Up,
+        Down => Key::Down,
+        Home => Key::Home,
+        End => Key::End,
+        PageUp => Key::PageUp,
+        PageDown => Key::PageDown,
+        Tab => Key::Tab,
+        Return | NumpadEnter => Key::Enter,
+        Space => Key::Space,
+        Escape => Key::Escape,
+        Back => Key::Back,
+        Delete => Key::Delete,
+        Insert => Key::Insert,
+        _ => Key::Other,
     }
 }
 
@@ -111,7 +299,9 @@ impl WindowBuilder {
         WindowBuilder { 
             size: Size::new(600.0, 400.0),
             title: String::new(),
+            layout_rounding: true,
             content: None,
+            fonts: FontManager::shared(),
         }
     }
 
@@ -123,9 +313,11 @@ impl WindowBuilder {
             .with_dimensions(self.size.w as u32, self.size.h as u32)
             .with_title(self.title.clone())
             .with_vsync()
+            .with_stencil_buffer(8)
             .build_glium()
-            .map(|display| Window::new(display, self.size, self.title.clone()))
+            .map(|display| Window::new(display, self.size, self.title.clone(), self.fonts.clone()))
             .unwrap();
+        window.set_layout_rounding(self.layout_rounding);
         if let Some(content) = self.content {
             window.set_content(content);
         }
@@ -143,6 +335,18 @@ impl WindowBuilder {
         self
     }
 
+    /// Snaps node rects to physical pixels; on by default.
+    pub fn with_layout_rounding(mut self, enabled: bool) -> WindowBuilder {
+        self.layout_rounding = enabled;
+        self
+    }
+
+    /// Uses another font manager than the one shared by the windows of the thread.
+    pub fn with_fonts(mut self, fonts: Rc<RefCell<FontManager>>) -> WindowBuilder {
+        self.fonts = fonts;
+        self
+    }
+
     pub fn with_content<T: Element + 'static>(mut self, content: T) -> WindowBuilder {
         self.content = Some(Box::new(content));
         self